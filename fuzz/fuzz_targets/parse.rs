@@ -0,0 +1,12 @@
+#![no_main]
+
+use late_format::{FormatOptions, Limits, Template};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    let options = FormatOptions::new().limits(Limits::untrusted());
+    if let Ok(template) = Template::parse_with(source, &options) {
+        assert_eq!(source, template.to_string());
+        assert!(template.placeholders().count() <= 1024);
+    }
+});
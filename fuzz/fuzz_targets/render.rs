@@ -0,0 +1,12 @@
+#![no_main]
+
+use std::collections::HashMap;
+use late_format::{FormatOptions, LateFormat, Limits};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (&str, Vec<(String, String)>)| {
+    let (source, arguments) = input;
+    let arguments: HashMap<String, String> = arguments.into_iter().collect();
+    let options = FormatOptions::new().limits(Limits::untrusted());
+    source.late_format_with(arguments, &options);
+});
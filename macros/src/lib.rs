@@ -0,0 +1,259 @@
+//! Procedural macros for the `late_format` crate.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, Ident, LitStr, Token, Visibility};
+
+/// Generates a struct with one `String` field per parameter of a template
+/// literal, and a `render(self)` method rendering the template with the
+/// fields, so that filling in a template is checked by the compiler.
+///
+/// The template is parsed when compiling, failing with the parse error, and
+/// parsed again once at run time, on first render. A parameter name that is
+/// not an identifier is converted into one, as in `user_name` for `user.name`
+/// and `type_` for `type`. Names of resolver namespaces, as in `{env:HOME}`,
+/// have no field.
+///
+/// The generated struct also has:
+///
+/// - `SOURCE`, the template source.
+/// - `template()`, returning the parsed `&'static Template`.
+/// - `into_arguments(self)`, returning the map of arguments.
+///
+/// ```
+/// late_format_macros::template_args! {
+///     /// The arguments of the greeting message.
+///     #[derive(Clone, Debug, Default)]
+///     pub struct Greeting = "Hello, {user.name}! You have {count} {count | pluralize:message,messages}.";
+/// }
+/// let greeting = Greeting { user_name: "x".into(), count: "2".into() };
+/// assert_eq!("Hello, x! You have 2 messages.", greeting.render());
+/// ```
+#[proc_macro]
+pub fn template_args(input: TokenStream) -> TokenStream {
+    let TemplateArgs { attributes, visibility, name, source } = parse_macro_input!(input as TemplateArgs);
+    let template = match late_format::Template::parse(&source.value()) {
+        Ok(template) => template,
+        Err(error) => return syn::Error::new(source.span(), format!("invalid template: {error}")).to_compile_error().into(),
+    };
+    let parameters: Vec<&String> = template.parameters().iter().filter(|p| !p.contains(':')).collect();
+    let mut fields: Vec<Ident> = vec![];
+    for parameter in &parameters {
+        let field = Ident::new(&field_name(parameter), Span::call_site());
+        if let Some(i) = fields.iter().position(|f| *f == field) {
+            let message = format!("parameters `{}` and `{parameter}` have the same field `{field}`", parameters[i]);
+            return syn::Error::new(source.span(), message).to_compile_error().into();
+        }
+        fields.push(field);
+    }
+    quote! {
+        #(#attributes)*
+        #visibility struct #name {
+            #(pub #fields: ::std::string::String,)*
+        }
+
+        impl #name {
+            /// The source of the template.
+            pub const SOURCE: &'static str = #source;
+
+            /// The parsed template.
+            pub fn template() -> &'static ::late_format::Template {
+                ::late_format::static_template!(Self::SOURCE)
+            }
+
+            /// The arguments of the template, by parameter name.
+            pub fn into_arguments(self) -> ::std::collections::HashMap<::std::string::String, ::std::string::String> {
+                let mut arguments = ::std::collections::HashMap::new();
+                #(arguments.insert(::std::string::String::from(#parameters), self.#fields);)*
+                arguments
+            }
+
+            /// Renders the template with the fields.
+            pub fn render(self) -> ::std::string::String {
+                Self::template().render(&self.into_arguments())
+            }
+        }
+    }.into()
+}
+
+/// Implements `late_format::LateError` and `Display` for an error type with
+/// a template per variant, given by `#[late_error("...")]`, whose parameters
+/// are fields: `{path}` for a named field, and `{0}` for the first field of
+/// a tuple variant. The template of a struct is given on the struct.
+///
+/// The fields used by the template are displayed when the error is, so that
+/// only they need to implement `Display`. The templates are parsed when
+/// compiling, failing on a parse error or on a parameter that is not a field.
+///
+/// ```
+/// #[derive(Debug, late_format_macros::LateError)]
+/// enum ConfigError {
+///     #[late_error("failed to open {path}: {source}")]
+///     Open { path: String, source: std::io::Error },
+///     #[late_error("unknown key `{0}` at line {1}")]
+///     UnknownKey(String, usize),
+///     #[late_error("empty configuration")]
+///     Empty,
+/// }
+/// impl std::error::Error for ConfigError {}
+/// let error = ConfigError::UnknownKey("colour".into(), 3);
+/// assert_eq!("unknown key `colour` at line 3", error.to_string());
+/// ```
+#[proc_macro_derive(LateError, attributes(late_error))]
+pub fn late_error(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    late_error_impl(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn late_error_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let arms: Vec<Arm> = match &input.data {
+        Data::Struct(data) => vec![Arm::new(quote!(Self), name, &input.attrs, &data.fields)?],
+        Data::Enum(data) => data.variants.iter().map(|variant| {
+            let ident = &variant.ident;
+            Arm::new(quote!(Self::#ident), ident, &variant.attrs, &variant.fields)
+        }).collect::<syn::Result<_>>()?,
+        Data::Union(_) => return Err(syn::Error::new(name.span(), "`LateError` cannot be derived for unions")),
+    };
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let any = arms.iter().map(|arm| &arm.any);
+    let sources = arms.iter().map(|arm| &arm.source);
+    let bindings = arms.iter().map(|arm| &arm.binding);
+    let inserts = arms.iter().map(|arm| &arm.inserts);
+    Ok(quote! {
+        impl #impl_generics ::late_format::LateError for #name #type_generics #where_clause {
+            fn template(&self) -> &'static ::late_format::Template {
+                match self {
+                    #(#any => ::late_format::static_template!(#sources),)*
+                }
+            }
+
+            fn arguments(&self) -> ::std::collections::HashMap<::std::string::String, ::std::string::String> {
+                let mut arguments = ::std::collections::HashMap::new();
+                match self {
+                    #(#bindings => { #(#inserts)* },)*
+                }
+                arguments
+            }
+        }
+
+        impl #impl_generics ::std::fmt::Display for #name #type_generics #where_clause {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                let arguments = ::late_format::LateError::arguments(self);
+                ::std::fmt::Display::fmt(&::late_format::LateDisplay::new(::late_format::LateError::template(self), &arguments), f)
+            }
+        }
+    })
+}
+
+/// The match arms of a variant in the `LateError` implementation.
+struct Arm {
+    /// The pattern matching the variant.
+    any: proc_macro2::TokenStream,
+    source: LitStr,
+    /// The pattern binding the fields used by the template.
+    binding: proc_macro2::TokenStream,
+    /// The statements inserting the fields into `arguments`.
+    inserts: Vec<proc_macro2::TokenStream>,
+}
+
+impl Arm {
+    fn new(path: proc_macro2::TokenStream, name: &Ident, attributes: &[Attribute], fields: &Fields) -> syn::Result<Self> {
+        let attribute = attributes.iter().find(|a| a.path().is_ident("late_error"))
+            .ok_or_else(|| syn::Error::new(name.span(), format!("missing `#[late_error(\"...\")]` on `{name}`")))?;
+        let source: LitStr = attribute.parse_args()?;
+        let template = late_format::Template::parse(&source.value())
+            .map_err(|error| syn::Error::new(source.span(), format!("invalid template: {error}")))?;
+        let parameters: Vec<&String> = template.parameters().iter().filter(|p| !p.contains(':')).collect();
+        let mut inserts = vec![];
+        let (any, binding) = match fields {
+            Fields::Named(named) => {
+                let mut bound = vec![];
+                for parameter in &parameters {
+                    let field = named.named.iter().filter_map(|f| f.ident.as_ref()).find(|f| f == parameter)
+                        .ok_or_else(|| not_a_field(&source, parameter, name))?;
+                    let variable = format_ident!("__{}", field);
+                    bound.push(quote!(#field: #variable));
+                    inserts.push(quote!(arguments.insert(::std::string::String::from(#parameter), ::std::string::ToString::to_string(#variable));));
+                }
+                (quote!(#path { .. }), quote!(#path { #(#bound,)* .. }))
+            },
+            Fields::Unnamed(unnamed) => {
+                let mut bound: Vec<proc_macro2::TokenStream> = vec![quote!(_); unnamed.unnamed.len()];
+                for parameter in &parameters {
+                    let index = parameter.parse::<usize>().ok().filter(|i| *i < bound.len())
+                        .ok_or_else(|| not_a_field(&source, parameter, name))?;
+                    let variable = format_ident!("__{}", index);
+                    bound[index] = quote!(#variable);
+                    inserts.push(quote!(arguments.insert(::std::string::String::from(#parameter), ::std::string::ToString::to_string(#variable));));
+                }
+                (quote!(#path(..)), quote!(#path(#(#bound),*)))
+            },
+            Fields::Unit => {
+                if let Some(parameter) = parameters.first() {
+                    return Err(not_a_field(&source, parameter, name));
+                }
+                (path.clone(), path)
+            },
+        };
+        Ok(Self { any, source, binding, inserts })
+    }
+}
+
+fn not_a_field(source: &LitStr, parameter: &str, name: &Ident) -> syn::Error {
+    syn::Error::new(source.span(), format!("parameter `{parameter}` is not a field of `{name}`"))
+}
+
+/// The input of [`template_args!`]: `#[attributes] visibility struct Name = "source";`.
+struct TemplateArgs {
+    attributes: Vec<Attribute>,
+    visibility: Visibility,
+    name: Ident,
+    source: LitStr,
+}
+
+impl Parse for TemplateArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attributes = input.call(Attribute::parse_outer)?;
+        let visibility = input.parse()?;
+        input.parse::<Token![struct]>()?;
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let source = input.parse()?;
+        if !input.is_empty() {
+            input.parse::<Token![;]>()?;
+        }
+        Ok(Self { attributes, visibility, name, source })
+    }
+}
+
+/// Converts a parameter name into a field name, replacing the characters
+/// that cannot be part of an identifier by `_`, and appending `_` to
+/// keywords.
+fn field_name(parameter: &str) -> String {
+    let mut name: String = parameter.chars().map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' }).collect();
+    if name.starts_with(|ch: char| ch.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    if syn::parse_str::<Ident>(&name).is_err() {
+        name.push('_');
+    }
+    name
+}
+
+#[cfg(test)]
+mod test {
+    use super::field_name;
+
+    #[test]
+    fn field_names() {
+        assert_eq!("user_name", field_name("user.name"));
+        assert_eq!("_0", field_name("0"));
+        assert_eq!("items_2_id", field_name("items.2.id"));
+        assert_eq!("type_", field_name("type"));
+        assert_eq!("self_", field_name("self"));
+        assert_eq!("a_b", field_name("a-b"));
+    }
+}
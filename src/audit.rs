@@ -0,0 +1,196 @@
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// The resolution of a parameter when rendering, reported to the observer of
+/// [`FormatOptions::observer`](crate::FormatOptions::observer).
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use late_format::{FormatOptions, Layers, Template};
+/// use maplit::hashmap;
+/// let log = Arc::new(Mutex::new(vec![]));
+/// let sink = log.clone();
+/// let options = FormatOptions::new().observer(move |r| {
+///     sink.lock().unwrap().push(format!("{} {:?} {:?}", r.name(), r.layer(), r.value_len()));
+/// });
+/// let layers = Layers::new().layer("defaults", hashmap!{"app".to_owned() => "demo".to_owned()});
+/// Template::parse("{app}{user}").unwrap().render_arguments(&layers, &options).unwrap();
+/// assert_eq!(["app Some(\"defaults\") Some(4)", "user None None"], &log.lock().unwrap()[..]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Resolution<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) layer: Option<&'a str>,
+    pub(crate) len: Option<usize>,
+}
+
+impl Resolution<'_> {
+    /// The name of the parameter.
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// The source supplying the argument: the name of the [`Layers`](crate::Layers)
+    /// layer, or the namespace of a [`Resolver`](crate::Resolver). `None` for
+    /// arguments from other sources, and for missing arguments.
+    pub fn layer(&self) -> Option<&str> {
+        self.layer
+    }
+
+    /// The length in bytes of the text of the argument, or `None` if it is missing.
+    pub fn value_len(&self) -> Option<usize> {
+        self.len
+    }
+
+    /// Whether the argument was found.
+    pub fn is_hit(&self) -> bool {
+        self.len.is_some()
+    }
+}
+
+/// A rendered output with the provenance of each placeholder substituted
+/// in it, as returned by [`Template::render_traced`](crate::Template::render_traced).
+///
+/// The `Display` implementation writes one line per placeholder, such as
+/// `{user} = "x" from arguments | html -> "x"`, for debugging layered setups.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trace {
+    pub(crate) output: String,
+    pub(crate) placeholders: Vec<TracedPlaceholder>,
+}
+
+/// A placeholder substituted in a [`Trace`]. A placeholder in a `{for}`
+/// block is traced once per item.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TracedPlaceholder {
+    pub(crate) name: String,
+    /// The index of the parameter, unless the name is a loop variable.
+    pub(crate) parameter: Option<usize>,
+    pub(crate) source: Range<usize>,
+    pub(crate) output: Range<usize>,
+    pub(crate) value: Option<String>,
+    pub(crate) layer: Option<String>,
+    pub(crate) filters: Vec<String>,
+}
+
+impl Trace {
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    pub fn into_output(self) -> String {
+        self.output
+    }
+
+    /// The substituted placeholders, in output order.
+    pub fn placeholders(&self) -> &[TracedPlaceholder] {
+        &self.placeholders
+    }
+}
+
+impl TracedPlaceholder {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The byte range of the placeholder in the template source.
+    pub fn source(&self) -> Range<usize> {
+        self.source.clone()
+    }
+
+    /// The byte range of the output produced by the placeholder.
+    pub fn output(&self) -> Range<usize> {
+        self.output.clone()
+    }
+
+    /// The text of the argument before any spec or filter, masked if it is
+    /// sensitive, or `None` if it is missing.
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+
+    /// The source supplying the argument, as described in [`Resolution::layer`].
+    pub fn layer(&self) -> Option<&str> {
+        self.layer.as_deref()
+    }
+
+    /// The names of the filters applied to the argument, in order.
+    pub fn filters(&self) -> &[String] {
+        &self.filters
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, p) in self.placeholders.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{{{}}} = ", p.name)?;
+            match &p.value {
+                Some(value) => write!(f, "{value:?}")?,
+                None => f.write_str("missing")?,
+            }
+            if let Some(layer) = &p.layer {
+                write!(f, " from {layer}")?;
+            }
+            for filter in &p.filters {
+                write!(f, " | {filter}")?;
+            }
+            write!(f, " -> {:?}", &self.output[p.output.clone()])?;
+        }
+        Ok(())
+    }
+}
+
+/// The observer of [`FormatOptions`](crate::FormatOptions).
+#[derive(Clone)]
+pub(crate) struct Observer(pub Arc<dyn Fn(&Resolution<'_>) + Send + Sync>);
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Observer")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use crate::{FormatOptions, Layers, Masking, Template, Value};
+    use maplit::hashmap;
+
+    #[test]
+    fn tracing() {
+        let arguments = hashmap!{"user".to_owned() => "<x>".to_owned(), "token".to_owned() => "abcdef".to_owned()};
+        let defaults = hashmap!{"app".to_owned() => Value::from(vec!["a", "b"])};
+        let layers = Layers::new().layer("arguments", arguments).layer("defaults", defaults);
+        let options = FormatOptions::new().sensitive("token").masking(Masking::Dots);
+        let template = Template::parse("{user | html}@{for a in app}{a}{end} {token} {nope}").unwrap();
+        let trace = template.render_traced(&layers, &options).unwrap();
+        assert_eq!("&lt;x&gt;@ab ••• None", trace.output());
+        assert_eq!(
+            "{user} = \"<x>\" from arguments | html -> \"&lt;x&gt;\"\n{a} = \"a\" -> \"a\"\n{a} = \"b\" -> \"b\"\n\
+            {token} = \"•••\" from arguments -> \"•••\"\n{nope} = missing -> \"None\"",
+            trace.to_string(),
+        );
+        assert_eq!((28..31, 11..12), (trace.placeholders()[1].source(), trace.placeholders()[2].output()));
+    }
+
+    #[test]
+    fn observing() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let sink = log.clone();
+        let options = FormatOptions::new()
+            .alias("login", "user")
+            .resolver("ns", |key: &str| (key == "a").then(|| Value::from("xy")))
+            .observer(move |r| sink.lock().unwrap().push((r.name().to_owned(), r.layer().map(str::to_owned), r.value_len(), r.is_hit())));
+        let template = Template::parse_with("{login}{ns:a}{ns:b}{for x in login}{x}{end}", &options).unwrap();
+        assert_eq!("abcxyNoneabc", template.render_with(&hashmap!{"user".into() => "abc".into()}, &options).unwrap());
+        assert_eq!(vec![
+            ("login".to_owned(), None, Some(3), true),
+            ("ns:a".to_owned(), Some("ns".to_owned()), Some(2), true),
+            ("ns:b".to_owned(), None, None, false),
+        ], *log.lock().unwrap());
+    }
+}
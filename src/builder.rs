@@ -0,0 +1,110 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+use crate::{FormatOptions, Missing, ParseError, Template};
+
+/// Parses templates with options attached to them, which the methods
+/// rendering without options, such as [`Template::render`], then use.
+/// Call sites stay minimal, and the behavior travels with the template.
+///
+/// ```
+/// use late_format::{Missing, Template};
+/// use maplit::hashmap;
+/// let template = Template::builder().missing(Missing::Marker).locale("fr").parse("{ratio | percent} {user}").unwrap();
+/// assert_eq!("50\u{202f}% <missing:user>", template.render(&hashmap!{"ratio".into() => "0.5".into()}));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TemplateBuilder {
+    options: FormatOptions,
+}
+
+impl TemplateBuilder {
+    /// Replaces the options set so far.
+    pub fn options(mut self, value: FormatOptions) -> Self {
+        self.options = value;
+        self
+    }
+
+    /// Selects how parameters missing from the arguments are rendered,
+    /// as [`FormatOptions::missing`] does.
+    pub fn missing(mut self, value: Missing) -> Self {
+        self.options = self.options.missing(value);
+        self
+    }
+
+    /// Strips the common leading indentation of the source, as
+    /// [`FormatOptions::dedent`] does.
+    pub fn dedent(mut self, value: bool) -> Self {
+        self.options = self.options.dedent(value);
+        self
+    }
+
+    /// Sets the locale of the arguments, as [`FormatOptions::locale`] does.
+    pub fn locale(mut self, value: impl Into<String>) -> Self {
+        self.options = self.options.locale(value);
+        self
+    }
+
+    /// Parses a template with the options, as [`Template::parse_with`] does,
+    /// and attaches them to it.
+    pub fn parse(&self, source: &str) -> Result<Template, ParseError> {
+        let mut template = Template::parse_with(source, &self.options)?;
+        template.options = Some(Arc::new(self.options.clone()));
+        Ok(template)
+    }
+}
+
+impl Template {
+    /// Starts parsing a template with attached options, as described in
+    /// [`TemplateBuilder`].
+    pub fn builder() -> TemplateBuilder {
+        TemplateBuilder::default()
+    }
+
+    /// The options attached to the template by [`Template::builder`].
+    /// Templates derived from it, such as [`Template::minified`], keep them.
+    pub fn options(&self) -> Option<&FormatOptions> {
+        self.options.as_deref()
+    }
+
+    /// The attached options, or the default ones.
+    pub(crate) fn attached_options(&self) -> Cow<'_, FormatOptions> {
+        match &self.options {
+            Some(options) => Cow::Borrowed(options),
+            None => Cow::Owned(FormatOptions::default()),
+        }
+    }
+
+    /// The template with the options attached to `other`.
+    pub(crate) fn with_options_of(mut self, other: &Template) -> Self {
+        self.options.clone_from(&other.options);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FormatOptions, LateDisplay, Missing, Template};
+    use maplit::hashmap;
+    use std::fmt::Write;
+
+    #[test]
+    fn attached_options() {
+        let template = Template::builder().dedent(true).missing(Missing::Error).parse("\n    {a}, {b}!\n    ").unwrap();
+        let arguments = hashmap!{"a".into() => "x".into()};
+        assert_eq!("x, {b}!\n", template.render(&arguments));
+        assert_eq!("x, None!\n", template.render_with(&arguments, &FormatOptions::new()).unwrap());
+        assert!(template.check_arguments(&arguments).is_err());
+        assert_eq!(Some(Missing::Error), template.options().map(|options| options.missing));
+        assert_eq!(template, Template::parse("{a}, {b}!\n").unwrap());
+        let renamed = template.rename_parameter("b", "c").minified();
+        assert!(renamed.options().is_some() && Template::parse("{a}").unwrap().options().is_none());
+        let mut r = String::new();
+        assert!(write!(r, "{}", LateDisplay::new(&template, &arguments)).is_err());
+        // every way of rendering uses the attached options.
+        for (missing, expected) in [(Missing::Marker, "x <missing:b>!"), (Missing::Error, "x {b}!")] {
+            let template = Template::builder().missing(missing).parse("{a} {b}!").unwrap();
+            let segments: String = template.render_segments(&arguments).concat();
+            assert_eq!([expected; 3], [template.render(&arguments), segments, template.bind(&arguments).render()]);
+        }
+    }
+}
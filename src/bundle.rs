@@ -0,0 +1,318 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use crate::{Arguments, FormatOptions, ParseError, RenderError, Template};
+
+/// Templates by locale and key, for localizing the messages of an
+/// application.
+///
+/// A message missing from a locale such as `pt-BR` is looked up in the
+/// locales obtained by removing its last subtags, such as `pt`, and then
+/// in the fallback locale of the bundle and its own subtags. Locales are
+/// compared case-insensitively, with `_` equivalent to `-`.
+///
+/// Messages may be keyed by a key and a context, as a [`MessageKey`], the
+/// way gettext disambiguates the same source text with `msgctxt`.
+///
+/// ```
+/// use late_format::MessageBundle;
+/// use maplit::hashmap;
+/// let mut bundle = MessageBundle::new("en");
+/// bundle.add("en", "greeting", "Hello, {name}!").unwrap();
+/// bundle.add("en", "farewell", "Bye, {name}.").unwrap();
+/// bundle.add("pt", "greeting", "Olá, {name}!").unwrap();
+/// let arguments = hashmap!{"name".to_owned() => "x".to_owned()};
+/// assert_eq!("Olá, x!", bundle.render("pt-BR", "greeting", &arguments).unwrap());
+/// assert_eq!("Bye, x.", bundle.render("pt-BR", "farewell", &arguments).unwrap());
+/// ```
+#[derive(Clone, Debug)]
+pub struct MessageBundle {
+    fallback: String,
+    options: FormatOptions,
+    locales: HashMap<String, Locale>,
+}
+
+/// The messages of a locale, with the options rendering them.
+#[derive(Clone, Debug)]
+struct Locale {
+    options: FormatOptions,
+    /// The messages by [`MessageKey::stored`].
+    messages: HashMap<String, Template>,
+}
+
+/// The key of a message in a [`MessageBundle`], with an optional context
+/// telling apart the uses of the same key, as in `("Open", "menu")` and
+/// `("Open", "status")`.
+///
+/// A message looked up with a context that no locale of the fallback chain
+/// has is looked up without the context.
+///
+/// ```
+/// use late_format::MessageBundle;
+/// use maplit::hashmap;
+/// let mut bundle = MessageBundle::new("en");
+/// bundle.add("fr", "Open", "Ouvrir").unwrap();
+/// bundle.add("fr", ("Open", "status"), "Ouvert").unwrap();
+/// let arguments = hashmap!{"n".to_owned() => "1".to_owned()};
+/// assert_eq!("Ouvert", bundle.render("fr", ("Open", "status"), &arguments).unwrap());
+/// assert_eq!("Ouvrir", bundle.render("fr", ("Open", "menu"), &arguments).unwrap());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MessageKey<'a> {
+    key: Cow<'a, str>,
+    context: Option<Cow<'a, str>>,
+}
+
+/// An error produced by [`MessageBundle::render`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BundleError {
+    /// No locale of the fallback chain has a message with this key.
+    MissingMessage(String),
+    Render(RenderError),
+}
+
+impl MessageBundle {
+    /// Creates an empty bundle falling back to the given locale.
+    pub fn new(fallback: impl Into<String>) -> Self {
+        Self { fallback: normalize(&fallback.into()), options: FormatOptions::default(), locales: HashMap::new() }
+    }
+
+    /// Sets the options used to parse and render the messages. Each locale
+    /// renders with these options and its own [`FormatOptions::locale`].
+    pub fn options(mut self, value: FormatOptions) -> Self {
+        for (tag, locale) in &mut self.locales {
+            locale.options = value.clone().locale(tag.clone());
+        }
+        self.options = value;
+        self
+    }
+
+    /// Adds a message, replacing the message of the same locale, key and
+    /// context.
+    pub fn insert<'k>(&mut self, locale: &str, key: impl Into<MessageKey<'k>>, template: Template) {
+        let tag = normalize(locale);
+        let options = &self.options;
+        let locale = self.locales.entry(tag.clone()).or_insert_with(|| {
+            Locale { options: options.clone().locale(tag), messages: HashMap::new() }
+        });
+        locale.messages.insert(key.into().stored().into_owned(), template);
+    }
+
+    /// Parses and adds a message, with the options of the bundle.
+    pub fn add<'k>(&mut self, locale: &str, key: impl Into<MessageKey<'k>>, source: &str) -> Result<(), ParseError> {
+        let template = Template::parse_with(source, &self.options)?;
+        self.insert(locale, key, template);
+        Ok(())
+    }
+
+    /// Looks up a message through the fallback chain of a locale.
+    pub fn get<'k>(&self, locale: &str, key: impl Into<MessageKey<'k>>) -> Option<&Template> {
+        self.find(locale, &key.into()).map(|(_, template)| template)
+    }
+
+    /// The locales that have messages, in no particular order.
+    pub fn locales(&self) -> impl Iterator<Item = &str> {
+        self.locales.keys().map(String::as_str)
+    }
+
+    /// Renders a message looked up through the fallback chain of a locale,
+    /// under the plural rules of the locale the message is found in.
+    pub fn render<'k>(&self, locale: &str, key: impl Into<MessageKey<'k>>, arguments: &(impl Arguments + ?Sized)) -> Result<String, BundleError> {
+        let key = key.into();
+        let (locale, template) = self.find(locale, &key).ok_or_else(|| BundleError::MissingMessage(key.key.into_owned()))?;
+        template.render_arguments(arguments, &locale.options).map_err(BundleError::Render)
+    }
+
+    /// Negotiates the locale to render with from an `Accept-Language` header,
+    /// as described in [`negotiate_locale`], returning the fallback locale
+    /// if no locale of the bundle matches.
+    ///
+    /// ```
+    /// use late_format::MessageBundle;
+    /// let mut bundle = MessageBundle::new("en");
+    /// bundle.add("en", "greeting", "Hello!").unwrap();
+    /// bundle.add("pt", "greeting", "Olá!").unwrap();
+    /// assert_eq!("pt", bundle.negotiate("fr-CH, pt-BR;q=0.9, en;q=0.8"));
+    /// assert_eq!("en", bundle.negotiate("de"));
+    /// ```
+    pub fn negotiate(&self, accept_language: &str) -> &str {
+        negotiate_locale(accept_language, self.locales()).unwrap_or(&self.fallback)
+    }
+
+    /// Looks up a message through the fallback chain of a locale, and then
+    /// without its context through the chain again.
+    fn find(&self, locale: &str, key: &MessageKey<'_>) -> Option<(&Locale, &Template)> {
+        let requested = normalize(locale);
+        let stored = key.stored();
+        let keys = std::iter::once(&*stored).chain(key.context.is_some().then_some(&*key.key));
+        let found = keys.flat_map(|key| chain(&requested).chain(chain(&self.fallback)).map(move |tag| (tag, key))).find_map(|(tag, key)| {
+            let locale = self.locales.get(tag)?;
+            Some((locale, locale.messages.get(key)?))
+        });
+        found
+    }
+}
+
+impl MessageKey<'_> {
+    /// The key of the message, as the source text with gettext.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+
+    /// The key the message is stored by: the context and the key separated by
+    /// the EOT character, as in the `.mo` files of gettext, or the key alone.
+    fn stored(&self) -> Cow<'_, str> {
+        match &self.context {
+            Some(context) => Cow::Owned(format!("{context}\u{4}{}", self.key)),
+            None => Cow::Borrowed(&self.key),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for MessageKey<'a> {
+    fn from(key: &'a str) -> Self {
+        Self { key: Cow::Borrowed(key), context: None }
+    }
+}
+
+impl From<String> for MessageKey<'_> {
+    fn from(key: String) -> Self {
+        Self { key: Cow::Owned(key), context: None }
+    }
+}
+
+/// A key and its context.
+impl<'a> From<(&'a str, &'a str)> for MessageKey<'a> {
+    fn from((key, context): (&'a str, &'a str)) -> Self {
+        Self { key: Cow::Borrowed(key), context: Some(Cow::Borrowed(context)) }
+    }
+}
+
+/// A key and its context.
+impl From<(String, String)> for MessageKey<'_> {
+    fn from((key, context): (String, String)) -> Self {
+        Self { key: Cow::Owned(key), context: Some(Cow::Owned(context)) }
+    }
+}
+
+/// Selects the best of the `available` locales for a priority list of
+/// language ranges, such as the value of an `Accept-Language` header, with
+/// the lookup scheme of RFC 4647.
+///
+/// Ranges are tried by decreasing quality value, in the order given for
+/// equal values, ignoring ranges of quality zero and `*`. Each range is then
+/// shortened by its last subtags, so that `pt-BR` matches `pt`, but not the
+/// reverse. Locales are compared case-insensitively, with `_` equivalent to `-`.
+///
+/// ```
+/// use late_format::negotiate_locale;
+/// assert_eq!(Some("de"), negotiate_locale("de-CH;q=0.8, fr;q=0, ja", ["fr", "de", "en-US"]));
+/// assert_eq!(None, negotiate_locale("en", ["en-US"]));
+/// ```
+pub fn negotiate_locale<'a>(priority_list: &str, available: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let available: Vec<_> = available.into_iter().map(|locale| (normalize(locale), locale)).collect();
+    let mut ranges: Vec<(f32, String)> = priority_list.split(',').filter_map(|item| {
+        let mut parts = item.split(';');
+        let range = parts.next()?.trim();
+        let quality = parts
+            .find_map(|p| p.trim().strip_prefix("q=")?.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        (!range.is_empty() && range != "*" && quality > 0.0).then(|| (quality, normalize(range)))
+    }).collect();
+    ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+    ranges.iter().find_map(|(_, range)| {
+        let mut range = range.as_str();
+        loop {
+            if let Some((_, locale)) = available.iter().find(|(tag, _)| tag == range) {
+                return Some(*locale);
+            }
+            range = &range[..range.rfind('-')?];
+            // a single-letter subtag, as in `-x-`, introduces the subtags removed.
+            if range.len() >= 2 && range.as_bytes()[range.len() - 2] == b'-' {
+                range = &range[..range.len() - 2];
+            }
+        }
+    })
+}
+
+/// Lowercases a locale and replaces `_` by `-`.
+fn normalize(locale: &str) -> String {
+    locale.to_ascii_lowercase().replace('_', "-")
+}
+
+/// A locale followed by the locales obtained by removing its last subtags,
+/// as in `zh-hant-tw`, `zh-hant` and `zh`.
+fn chain(locale: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(locale), |tag| tag.rfind('-').map(|i| &tag[..i]))
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingMessage(key) => write!(f, "missing message `{key}`"),
+            Self::Render(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for BundleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Render(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FormatOptions, Missing, Template};
+    use super::{negotiate_locale, BundleError, MessageBundle};
+    use maplit::hashmap;
+    use std::collections::HashMap;
+
+    #[test]
+    fn fallbacks() {
+        let mut bundle = MessageBundle::new("en-US").options(FormatOptions::new().missing(Missing::Error));
+        bundle.add("en", "files", "{plural n}{one}1 file{other}{n} files{end}").unwrap();
+        bundle.add("ru", "files", "{plural n}{one}{n} файл{few}{n} файла{other}{n} файлов{end}").unwrap();
+        bundle.insert("zh_Hant", "files", Template::parse("{n} 個檔案").unwrap());
+        let arguments = hashmap!{"n".to_owned() => "3".to_owned()};
+        assert_eq!("3 файла", bundle.render("RU-ru", "files", &arguments).unwrap());
+        assert_eq!("3 files", bundle.render("de", "files", &arguments).unwrap());
+        assert_eq!("3 個檔案", bundle.render("zh-Hant-TW", "files", &arguments).unwrap());
+        assert_eq!(Err(BundleError::MissingMessage("nope".into())), bundle.render("en", "nope", &arguments));
+        assert!(matches!(bundle.render("en", "files", &hashmap!{"m".to_owned() => "1".to_owned()}), Err(BundleError::Render(_))));
+        let mut locales: Vec<_> = bundle.locales().collect();
+        locales.sort();
+        assert_eq!(["en", "ru", "zh-hant"], &locales[..]);
+    }
+
+    #[test]
+    fn contexts() {
+        let mut bundle = MessageBundle::new("en");
+        bundle.add("en", "Open", "Open").unwrap();
+        bundle.add("de", "Open", "Öffnen").unwrap();
+        bundle.add("de-AT", "Open", "Aufmachen").unwrap();
+        bundle.add("de", ("Open", "status"), "Geöffnet").unwrap();
+        let arguments: HashMap<String, String> = hashmap!{};
+        assert_eq!("Geöffnet", bundle.render("de-AT", ("Open", "status"), &arguments).unwrap());
+        assert_eq!("Aufmachen", bundle.render("de-AT", ("Open", "menu"), &arguments).unwrap());
+        assert_eq!("Open", bundle.render("fr", ("Open".to_owned(), "status".to_owned()), &arguments).unwrap());
+        assert_eq!(Err(BundleError::MissingMessage("Save".into())), bundle.render("de", ("Save", "menu"), &arguments));
+    }
+
+    #[test]
+    fn negotiation() {
+        let available = ["en", "zh-Hant-CN", "pt_BR"];
+        assert_eq!(Some("zh-Hant-CN"), negotiate_locale("zh-hant-cn-x-private", available));
+        assert_eq!(Some("pt_BR"), negotiate_locale("en;q=0.5, PT-br;q=0.7", available));
+        assert_eq!(Some("en"), negotiate_locale("*, pt;q=0.9, en-GB;q=0.2", available));
+        assert_eq!(None, negotiate_locale("", available));
+    }
+}
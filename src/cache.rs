@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use crate::{FormatOptions, RenderError, Template};
+use crate::render::Renderer;
+use crate::template::walk;
+use crate::value::ValueRef;
+
+/// A template with its last arguments and output, which re-renders only the
+/// parts of the output depending on the arguments that changed, for labels
+/// updating a counter at a high frequency.
+///
+/// The output is split at the segments of the top level of the template: a
+/// block is rendered again as a whole if any of its parameters changes.
+///
+/// ```
+/// use late_format::{RenderCache, Template};
+/// use maplit::hashmap;
+/// let mut cache = RenderCache::new(Template::parse("{user}: {count} unread").unwrap());
+/// assert_eq!("x: 1 unread", cache.render(&hashmap!{"user".into() => "x".into(), "count".into() => "1".into()}).unwrap());
+/// assert_eq!("x: 42 unread", cache.render(&hashmap!{"user".into() => "x".into(), "count".into() => "42".into()}).unwrap());
+/// ```
+#[derive(Clone, Debug)]
+pub struct RenderCache {
+    template: Template,
+    options: FormatOptions,
+    /// The arguments of the last render, by parameter index, if any.
+    values: Option<Vec<Option<String>>>,
+    output: String,
+    /// The range of the output of each segment of the top level.
+    spans: Vec<Range<usize>>,
+    /// The parameters each segment of the top level depends on.
+    dependencies: Vec<Vec<usize>>,
+    /// The number of segments rendered by the last render.
+    rendered: usize,
+}
+
+impl RenderCache {
+    /// Creates a cache rendering with the options attached to the template,
+    /// or the default ones.
+    pub fn new(template: Template) -> Self {
+        let options = template.attached_options().into_owned();
+        Self::with_options(template, options)
+    }
+
+    pub fn with_options(template: Template, options: FormatOptions) -> Self {
+        let dependencies = template.segments.iter().map(|segment| {
+            let mut parameters = vec![];
+            walk(std::slice::from_ref(segment), &mut |s| parameters.extend(s.parameters()));
+            parameters
+        }).collect();
+        Self { template, options, values: None, output: String::new(), spans: vec![], dependencies, rendered: 0 }
+    }
+
+    /// Renders the template, reusing the output of the segments whose
+    /// arguments did not change since the last render. On failure, the next
+    /// render renders every segment again.
+    pub fn render(&mut self, arguments: &HashMap<String, String>) -> Result<&str, RenderError> {
+        let values: Vec<Option<String>> = self.template.resolve_map(arguments, &self.options)
+            .into_iter()
+            .map(|value| value.map(String::from))
+            .collect();
+        let result = self.update(&values);
+        self.values = result.is_ok().then_some(values);
+        if result.is_err() {
+            self.output.clear();
+        }
+        result?;
+        Ok(&self.output)
+    }
+
+    fn update(&mut self, values: &[Option<String>]) -> Result<(), RenderError> {
+        let changed: Vec<bool> = match &self.values {
+            Some(last) => last.iter().zip(values).map(|(a, b)| a != b).collect(),
+            None => {
+                self.output.clear();
+                self.spans = vec![0..0; self.template.segments.len()];
+                vec![true; values.len()]
+            },
+        };
+        let full = self.values.is_none();
+        let mut renderer = Renderer::new(&self.template, &self.options);
+        let value = |index: usize| values[index].as_deref().map(ValueRef::Str);
+        self.rendered = 0;
+        // the change of length of the output before the current segment.
+        let mut shift = 0isize;
+        for (i, segment) in self.template.segments.iter().enumerate() {
+            let span = &mut self.spans[i];
+            *span = span.start.wrapping_add_signed(shift)..span.end.wrapping_add_signed(shift);
+            if !full && !self.dependencies[i].iter().any(|p| changed[*p]) {
+                continue;
+            }
+            let mut text = String::new();
+            renderer.render_segment(segment, value, &mut text)?;
+            self.output.replace_range(span.clone(), &text);
+            shift += text.len() as isize - span.len() as isize;
+            *span = span.start..span.start + text.len();
+            self.rendered += 1;
+        }
+        Ok(())
+    }
+
+    /// The output of the last render, which is empty if it failed.
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    pub fn template(&self) -> &Template {
+        &self.template
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FormatOptions, Missing, Template};
+    use super::RenderCache;
+    use maplit::hashmap;
+
+    #[test]
+    fn incremental() {
+        let template = Template::parse("{a} {for x in xs}[{x}]{end} {n:>{w}} {if flag}on{else}off{end}").unwrap();
+        let mut cache = RenderCache::new(template.clone());
+        let mut arguments = hashmap!{"a".into() => "é".into(), "xs".into() => "1".into(), "n".into() => "7".into(), "w".into() => "3".into()};
+        assert_eq!(template.render(&arguments), cache.render(&arguments).unwrap());
+        assert_eq!(7, cache.rendered);
+        for (name, value, rendered) in [("n", "12", 1), ("w", "1", 1), ("a", "abc", 1), ("flag", "1", 1), ("xs", "", 1), ("zzz", "", 0)] {
+            arguments.insert(name.into(), value.into());
+            assert_eq!(template.render(&arguments), cache.render(&arguments).unwrap());
+            assert_eq!(rendered, cache.rendered);
+        }
+        let mut cache = RenderCache::with_options(template, FormatOptions::new().missing(Missing::Error));
+        assert!(cache.render(&hashmap!{}).is_err());
+        assert_eq!("abc [] 12 on", cache.render(&arguments).unwrap());
+        assert_eq!(7, cache.rendered);
+    }
+}
@@ -0,0 +1,94 @@
+/// Writes text in title case, where the first letter of each word is in
+/// title case and the other letters in lowercase, or, if `sentence` is true,
+/// in sentence case, where only the first letter of each sentence is
+/// capitalized. Case mapping follows Unicode, with the rules of Turkish and
+/// Azerbaijani for the dotted and dotless i and of Dutch for the `ij` digraph.
+pub(crate) fn capitalize(text: &str, locale: &str, sentence: bool) -> String {
+    let locale = locale.to_ascii_lowercase();
+    let language = locale.split(['-', '_']).next().unwrap_or_default();
+    let turkic = matches!(language, "tr" | "az");
+    let mut r = String::with_capacity(text.len());
+    // whether the next letter starts a word or sentence.
+    let mut start = true;
+    let mut previous = None::<char>;
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        let in_word = ch.is_alphanumeric()
+            || (matches!(ch, '\'' | '\u{2019}') && previous.is_some_and(char::is_alphabetic) && chars.peek().is_some_and(|c| c.is_alphabetic()));
+        if in_word {
+            if start && ch.is_alphabetic() {
+                r.push_str(&title(ch, turkic));
+                if language == "nl" && ch.eq_ignore_ascii_case(&'i') && chars.peek().is_some_and(|c| c.eq_ignore_ascii_case(&'j')) {
+                    chars.next();
+                    r.push('J');
+                }
+            } else {
+                r.push_str(&lower(ch, turkic));
+            }
+            // a word or sentence starting with a digit is not capitalized.
+            start = false;
+        } else {
+            start |= !sentence || matches!(ch, '.' | '!' | '?');
+            r.push(ch);
+        }
+        previous = Some(ch);
+    }
+    final_sigma(&r)
+}
+
+/// The title case mapping of a character, which differs from its uppercase
+/// mapping for `ß` and the digraphs such as `ǆ`.
+fn title(ch: char, turkic: bool) -> String {
+    match ch {
+        'i' if turkic => "İ".to_owned(),
+        'ß' => "Ss".to_owned(),
+        '\u{1c4}'..='\u{1c6}' => '\u{1c5}'.to_string(),
+        '\u{1c7}'..='\u{1c9}' => '\u{1c8}'.to_string(),
+        '\u{1ca}'..='\u{1cc}' => '\u{1cb}'.to_string(),
+        '\u{1f1}'..='\u{1f3}' => '\u{1f2}'.to_string(),
+        _ => ch.to_uppercase().collect(),
+    }
+}
+
+fn lower(ch: char, turkic: bool) -> String {
+    match ch {
+        'I' if turkic => "ı".to_owned(),
+        'İ' if turkic => "i".to_owned(),
+        _ => ch.to_lowercase().collect(),
+    }
+}
+
+/// Replaces the `σ` ending a word by `ς`, which lowercasing characters one
+/// at a time does not do.
+fn final_sigma(text: &str) -> String {
+    let mut r = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut previous = None::<char>;
+    while let Some(ch) = chars.next() {
+        let last = chars.peek().is_none_or(|c| !c.is_alphabetic());
+        if ch == 'σ' && last && previous.is_some_and(char::is_alphabetic) {
+            r.push('ς');
+        } else {
+            r.push(ch);
+        }
+        previous = Some(ch);
+    }
+    r
+}
+
+#[cfg(test)]
+mod test {
+    use super::capitalize;
+
+    #[test]
+    fn capitalization() {
+        assert_eq!("The Óld Man's Street, 3rd Ed.", capitalize("the ÓLD man's STREET, 3rd ed.", "en", false));
+        assert_eq!("Ssüß Straße", capitalize("ßÜß STRAßE", "de", false));
+        assert_eq!("İstanbul Ve Izmir, Iğdır", capitalize("istanbul VE ızmir, IĞDIR", "tr", false));
+        assert_eq!("Istanbul", capitalize("ISTANBUL", "en-US", false));
+        assert_eq!("IJsselmeer, IJs", capitalize("ijsselmeer, IJS", "nl", false));
+        assert_eq!("ǅungla", capitalize("ǆungla", "hr", false));
+        assert_eq!("Οδυσσέας", capitalize("ΟΔΥΣΣΈΑΣ", "el", false));
+        assert_eq!("Hello world. It works! Yes", capitalize("hello WORLD. it works! yes", "en", true));
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// The source of random numbers of [`FormatOptions`](crate::FormatOptions),
+/// for the `{choose}` blocks.
+#[derive(Clone)]
+pub(crate) struct Random(pub Arc<dyn Fn() -> u64 + Send + Sync>);
+
+impl Random {
+    /// The SplitMix64 sequence of a seed, shared by the clones.
+    pub fn seeded(seed: u64) -> Self {
+        let state = AtomicU64::new(seed);
+        Self(Arc::new(move || splitmix(&state)))
+    }
+}
+
+impl fmt::Debug for Random {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Random")
+    }
+}
+
+/// Advances a SplitMix64 state, returning the next number of its sequence.
+pub(crate) fn splitmix(state: &AtomicU64) -> u64 {
+    let mut z = state.fetch_add(0x9e3779b97f4a7c15, Ordering::Relaxed).wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// A random number, from the randomly keyed hasher of the standard library.
+pub(crate) fn entropy() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish()
+}
+
+/// The index of the variant that a random number selects, each variant
+/// being selected in proportion to its weight, or `None` if every weight is
+/// zero.
+pub(crate) fn pick(weights: impl Iterator<Item = u32> + Clone, random: u64) -> Option<usize> {
+    let total: u64 = weights.clone().map(u64::from).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut target = random % total;
+    weights.enumerate().find_map(|(i, weight)| {
+        if target < u64::from(weight) {
+            return Some(i);
+        }
+        target -= u64::from(weight);
+        None
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{pick, Random};
+
+    #[test]
+    fn picking() {
+        let weights = [60, 0, 40];
+        assert_eq!([Some(0), Some(0), Some(2), Some(0)], [0, 59, 60, 100].map(|r| pick(weights.into_iter(), r)));
+        assert_eq!(None, pick([0, 0].into_iter(), 7));
+        let random = Random::seeded(1);
+        let counts = (0..1000).fold([0; 3], |mut counts, _| {
+            counts[pick(weights.into_iter(), (random.0)()).unwrap()] += 1;
+            counts
+        });
+        assert!(counts[1] == 0 && (550..650).contains(&counts[0]), "{counts:?}");
+        let (a, b) = (Random::seeded(5), Random::seeded(5));
+        assert_eq!([(a.0)(), (a.0)()], [(b.0)(), (b.0)()]);
+    }
+}
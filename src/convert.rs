@@ -0,0 +1,591 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use crate::{escape_literal, ParseError, ParseErrorKind, Template};
+use crate::filters::Filter;
+use crate::parser::{form_end, is_name_char, KEYWORDS};
+use crate::template::Segment;
+
+impl Template {
+    /// Converts a `format!`-style or Python `str.format`-style string into
+    /// a template, which helps migrating an existing message catalog.
+    ///
+    /// - `{name}` and `{0}` become placeholders of the same name.
+    /// - `{}` becomes a placeholder named after its position, as in `{0}` and `{1}`.
+    /// - `{{` and `}}` become literal curly braces.
+    ///
+    /// Format specs and conversions, such as `{name:>8}` and `{name!r}`, and
+    /// names that are keywords of this syntax, such as `{raw}`, are not
+    /// supported and fail with [`ParseErrorKind::UnsupportedFormat`]. Unmatched
+    /// curly braces fail with [`ParseErrorKind::UnmatchedBrace`].
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// use maplit::hashmap;
+    /// let template = Template::from_std_format("{{{}}} {name} {}").unwrap();
+    /// assert_eq!(r#"{"{"}{0}} {name} {1}"#, template.source());
+    /// assert_eq!("{a} x b", template.render(&hashmap!{
+    ///     "0".into() => "a".into(),
+    ///     "1".into() => "b".into(),
+    ///     "name".into() => "x".into(),
+    /// }));
+    /// ```
+    pub fn from_std_format(format: &str) -> Result<Template, ParseError> {
+        let mut r = String::with_capacity(format.len());
+        let mut position = 0;
+        let mut literal_start = 0;
+        let mut i = 0;
+        while let Some(offset) = format[i..].find(['{', '}']) {
+            let start = i + offset;
+            r.push_str(&escape_literal(&format[literal_start..start]));
+            let rest = &format[start..];
+            if rest.starts_with("{{") {
+                r.push_str(r#"{"{"}"#);
+                i = start + 2;
+            } else if rest.starts_with("}}") {
+                r.push('}');
+                i = start + 2;
+            } else if rest.starts_with('}') {
+                return Err(ParseError::new(ParseErrorKind::UnmatchedBrace, start..start + 1));
+            } else {
+                let Some(len) = rest.find('}') else {
+                    return Err(ParseError::new(ParseErrorKind::UnmatchedBrace, start..start + 1));
+                };
+                let end = start + len + 1;
+                let name = format[start + 1..end - 1].trim();
+                if name.is_empty() {
+                    r.push_str(&format!("{{{position}}}"));
+                    position += 1;
+                } else if name.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '.') && !KEYWORDS.contains(&name) {
+                    r.push_str(&format!("{{{name}}}"));
+                } else {
+                    let kind = ParseErrorKind::UnsupportedFormat(format[start..end].to_owned());
+                    return Err(ParseError::new(kind, start..end));
+                }
+                i = end;
+            }
+            literal_start = i;
+        }
+        r.push_str(&escape_literal(&format[literal_start..]));
+        Template::parse(&r)
+    }
+}
+
+impl Template {
+    /// Converts a template whose literal curly braces are doubled, as in
+    /// `{{` and `}}`, into a template escaping them with the `{"{"}` form,
+    /// the reverse of [`Template::to_double_braces`]. Placeholders and tags
+    /// are kept as written, so a catalog can migrate between the two
+    /// escaping conventions.
+    ///
+    /// A curly brace that is neither doubled nor part of a form fails with
+    /// [`ParseErrorKind::UnmatchedBrace`].
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// let template = Template::from_double_braces("{{{ name | html }}} {if x}{{}}{end}").unwrap();
+    /// assert_eq!(r#"{"{"}{ name | html }} {if x}{"{"}}{end}"#, template.source());
+    /// ```
+    pub fn from_double_braces(source: &str) -> Result<Template, ParseError> {
+        let mut r = String::with_capacity(source.len());
+        let mut literal_start = 0;
+        let mut i = 0;
+        while let Some(offset) = source[i..].find(['{', '}']) {
+            let start = i + offset;
+            r.push_str(&source[literal_start..start]);
+            let rest = &source[start..];
+            if rest.starts_with("{{") {
+                r.push_str(r#"{"{"}"#);
+                i = start + 2;
+            } else if rest.starts_with("}}") {
+                r.push('}');
+                i = start + 2;
+            } else {
+                let end = rest.starts_with('{').then(|| form_end(source, start)).flatten();
+                let Some(end) = end else {
+                    return Err(ParseError::new(ParseErrorKind::UnmatchedBrace, start..start + 1));
+                };
+                r.push_str(&source[start..end]);
+                i = end;
+            }
+            literal_start = i;
+        }
+        r.push_str(&source[literal_start..]);
+        Template::parse(&r)
+    }
+
+    /// Writes the source of the template with its literal curly braces
+    /// doubled, as in `{{` and `}}`, instead of escaped with the `{"{"}`
+    /// form. Escaped forms become literal text, while placeholders, tags
+    /// and `{raw}` blocks are written as in the source.
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// let template = Template::parse(r#"{"{"}{name}} {'"'}{if x}{"}"}{end}"#).unwrap();
+    /// assert_eq!(r#"{{{name}}} "{if x}}}{end}"#, template.to_double_braces());
+    /// ```
+    pub fn to_double_braces(&self) -> String {
+        let mut r = String::with_capacity(self.source.len());
+        let end = self.write_double_braces(&self.segments, 0, &mut r);
+        r.push_str(&self.source[end..]);
+        r
+    }
+
+    /// Writes segments for [`Template::to_double_braces`], preceded by the
+    /// source from `cursor`, and returns the end of the last segment.
+    fn write_double_braces(&self, segments: &[Segment], mut cursor: usize, r: &mut String) -> usize {
+        for segment in segments {
+            let span = segment.span();
+            // the text between segments is made of block tags.
+            r.push_str(&self.source[cursor..span.start]);
+            match segment {
+                Segment::Literal(_) | Segment::Escaped { .. } => {
+                    r.push_str(&segment.text(&self.source).replace('{', "{{").replace('}', "}}"));
+                },
+                Segment::If { .. } | Segment::For { .. } | Segment::Plural { .. } | Segment::Choose { .. } => {
+                    let mut end = span.start + self.source[span.start..].find('}').unwrap() + 1;
+                    r.push_str(&self.source[span.start..end]);
+                    for children in segment.children() {
+                        end = self.write_double_braces(children, end, r);
+                    }
+                    r.push_str(&self.source[end..span.end]);
+                },
+                _ => r.push_str(&self.source[span.clone()]),
+            }
+            cursor = span.end;
+        }
+        cursor
+    }
+}
+
+/// The conversions accepted by [`Template::from_printf`].
+const PRINTF_CONVERSIONS: &str = "sdiufFeEgGxXoc@";
+
+impl Template {
+    /// Converts a printf-style format string, as used by Android and gettext
+    /// catalogs, into a template. Each specifier becomes a placeholder named
+    /// after the matching entry of `names`:
+    ///
+    /// - `%s`, `%d` and the other conversions take the next name in order.
+    /// - `%1$s` takes the name at the given position, starting at 1.
+    /// - `%%` becomes a literal percent sign.
+    ///
+    /// The conversion itself is not applied. Flags, widths and precisions,
+    /// such as `%5.2f`, fail with [`ParseErrorKind::UnsupportedFormat`], and
+    /// a specifier without a name fails with [`ParseErrorKind::UnnamedArgument`].
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// let template = Template::from_printf("%s has %2$d%% of %1$s", &["user", "share"]).unwrap();
+    /// assert_eq!("{user} has {share}% of {user}", template.source());
+    /// assert_eq!("%1$s has %2$s%% of %1$s", template.to_printf(&["user", "share"]).unwrap());
+    /// ```
+    pub fn from_printf(format: &str, names: &[&str]) -> Result<Template, ParseError> {
+        let mut r = String::with_capacity(format.len());
+        let mut next = 0;
+        let mut literal_start = 0;
+        let mut i = 0;
+        while let Some(offset) = format[i..].find('%') {
+            let start = i + offset;
+            r.push_str(&escape_literal(&format[literal_start..start]));
+            let rest = &format[start + 1..];
+            if rest.starts_with('%') {
+                r.push('%');
+                i = start + 2;
+                literal_start = i;
+                continue;
+            }
+            let digits = rest.find(|ch: char| !ch.is_ascii_digit()).unwrap_or(rest.len());
+            let (position, spec) = if digits > 0 && rest[digits..].starts_with('$') {
+                (rest[..digits].parse::<usize>().ok().filter(|n| *n > 0), &rest[digits + 1..])
+            } else {
+                next += 1;
+                (Some(next), rest)
+            };
+            let end = start + 1 + (rest.len() - spec.len()) + spec.chars().next().map_or(0, char::len_utf8);
+            let unsupported = || {
+                let kind = ParseErrorKind::UnsupportedFormat(format[start..end].to_owned());
+                ParseError::new(kind, start..end)
+            };
+            let Some(position) = position else {
+                return Err(unsupported());
+            };
+            if !spec.starts_with(|ch| PRINTF_CONVERSIONS.contains(ch)) {
+                return Err(unsupported());
+            }
+            let Some(name) = names.get(position - 1) else {
+                return Err(ParseError::new(ParseErrorKind::UnnamedArgument(position), start..end));
+            };
+            r.push('{');
+            r.push_str(name);
+            r.push('}');
+            i = end;
+            literal_start = i;
+        }
+        r.push_str(&escape_literal(&format[literal_start..]));
+        Template::parse(&r)
+    }
+
+    /// Converts the template into a printf-style format string, the reverse of
+    /// [`Template::from_printf`]. Each placeholder becomes a `%s` specifier,
+    /// positional as in `%2$s` unless the placeholders use `names` exactly in
+    /// order, and each literal percent sign becomes `%%`.
+    ///
+    /// A placeholder whose name is not in `names`, or that applies filters,
+    /// and a block fail with [`ParseErrorKind::UnsupportedFormat`].
+    pub fn to_printf(&self, names: &[&str]) -> Result<String, ParseError> {
+        let mut positions = vec![];
+        for p in self.placeholders() {
+            let position = names.iter().position(|name| *name == p.name())
+                .filter(|_| p.filters.is_empty())
+                .ok_or_else(|| {
+                    let kind = ParseErrorKind::UnsupportedFormat(self.source[p.span()].to_owned());
+                    ParseError::new(kind, p.span())
+                })?;
+            positions.push(position + 1);
+        }
+        let sequential = positions.iter().enumerate().all(|(i, position)| i + 1 == *position);
+        let mut positions = positions.into_iter();
+        let mut r = String::with_capacity(self.source.len());
+        for segment in &self.segments {
+            match segment {
+                Segment::Placeholder(_) => {
+                    let position = positions.next().unwrap();
+                    if sequential {
+                        r.push_str("%s");
+                    } else {
+                        r.push_str(&format!("%{position}$s"));
+                    }
+                },
+                Segment::If { span, .. } | Segment::For { span, .. } | Segment::Plural { span, .. } | Segment::Choose { span, .. } => {
+                    let kind = ParseErrorKind::UnsupportedFormat(self.source[span.clone()].to_owned());
+                    return Err(ParseError::new(kind, span.clone()));
+                },
+                _ => r.push_str(&segment.text(&self.source).replace('%', "%%")),
+            }
+        }
+        Ok(r)
+    }
+}
+
+impl Template {
+    /// Converts a Mustache template into a template, so that existing
+    /// Mustache templates can be rendered with the same arguments. The
+    /// following subset of Mustache is supported:
+    ///
+    /// - `{{name}}` becomes `{name | html}`, escaping the argument as Mustache does.
+    /// - `{{{name}}}` and `{{&name}}` become `{name}`.
+    /// - `{{#name}}...{{/name}}` becomes `{if name}...{end}`, and `{{^name}}`
+    ///   becomes `{if not name}`. Since arguments are strings, sections do not iterate.
+    /// - `{{! comment }}` is removed.
+    ///
+    /// As in Mustache, a section tag or comment alone on its line removes the
+    /// whole line. Partials, delimiter changes and `{{.}}` fail with
+    /// [`ParseErrorKind::UnsupportedFormat`]. A tag that is not closed fails
+    /// with [`ParseErrorKind::UnmatchedBrace`], and a section that is not
+    /// closed with [`ParseErrorKind::UnclosedBlock`].
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// use maplit::hashmap;
+    /// let template = Template::from_mustache("{{#admin}}<b>{{name}}</b>{{/admin}}{{^admin}}{{{name}}}{{/admin}}").unwrap();
+    /// assert_eq!("{if admin}<b>{name | html}</b>{end}{if not admin}{name}{end}", template.source());
+    /// let arguments = hashmap!{"name".into() => "a&b".into(), "admin".into() => "yes".into()};
+    /// assert_eq!("<b>a&amp;b</b>", template.render(&arguments));
+    /// ```
+    pub fn from_mustache(source: &str) -> Result<Template, ParseError> {
+        let mut r = String::with_capacity(source.len());
+        let mut sections: Vec<(&str, Range<usize>)> = vec![];
+        let mut literal_start = 0;
+        let mut i = 0;
+        while let Some(offset) = source[i..].find("{{") {
+            let start = i + offset;
+            let triple = source[start..].starts_with("{{{");
+            let (content_start, close) = if triple { (start + 3, "}}}") } else { (start + 2, "}}") };
+            let Some(len) = source[content_start..].find(close) else {
+                return Err(ParseError::new(ParseErrorKind::UnmatchedBrace, start..content_start));
+            };
+            let mut end = content_start + len + close.len();
+            let content = source[content_start..content_start + len].trim();
+            let (sigil, name) = match content.chars().next() {
+                Some(sigil @ ('#' | '^' | '/' | '!' | '&')) if !triple => (Some(sigil), content[1..].trim()),
+                _ => (None, content),
+            };
+            let unsupported = || {
+                let kind = ParseErrorKind::UnsupportedFormat(source[start..end].to_owned());
+                ParseError::new(kind, start..end)
+            };
+            if sigil != Some('!') && (name == "." || name.is_empty() || !name.chars().all(is_name_char) || KEYWORDS.contains(&name)) {
+                return Err(unsupported());
+            }
+            // a section tag or comment alone on its line removes the line.
+            let mut literal_end = start;
+            if matches!(sigil, Some('#' | '^' | '/' | '!')) {
+                let line_start = source[..start].rfind('\n').map_or(0, |j| j + 1);
+                let line_end = source[end..].find('\n').map_or(source.len(), |j| end + j + 1);
+                let blank = |s: &str| s.chars().all(|ch| ch == ' ' || ch == '\t' || ch == '\r' || ch == '\n');
+                if line_start >= literal_start && blank(&source[line_start..start]) && blank(&source[end..line_end]) {
+                    literal_end = line_start;
+                    end = line_end;
+                }
+            }
+            r.push_str(&escape_literal(&source[literal_start..literal_end]));
+            match sigil {
+                Some('#') => {
+                    sections.push((name, start..end));
+                    r.push_str(&format!("{{if {name}}}"));
+                },
+                Some('^') => {
+                    sections.push((name, start..end));
+                    r.push_str(&format!("{{if not {name}}}"));
+                },
+                Some('/') => {
+                    if sections.pop().is_none_or(|(open, _)| open != name) {
+                        let kind = ParseErrorKind::UnexpectedTag(source[start..end].trim().to_owned());
+                        return Err(ParseError::new(kind, start..end));
+                    }
+                    r.push_str("{end}");
+                },
+                Some('!') => {},
+                Some('&') => r.push_str(&format!("{{{name}}}")),
+                _ if triple => r.push_str(&format!("{{{name}}}")),
+                _ => r.push_str(&format!("{{{name} | html}}")),
+            }
+            i = end;
+            literal_start = i;
+        }
+        if let Some((_, span)) = sections.pop() {
+            return Err(ParseError::new(ParseErrorKind::UnclosedBlock, span));
+        }
+        r.push_str(&escape_literal(&source[literal_start..]));
+        Template::parse(&r)
+    }
+}
+
+impl Template {
+    /// Converts a template written in a small subset of Jinja syntax into
+    /// a template:
+    ///
+    /// - `{{ name }}` and `{{ name | filter }}` become `{name}` and `{name | filter}`.
+    ///   Only the filters of this crate are available.
+    /// - `{% if name %}`, `{% if not name %}`, `{% else %}` and `{% endif %}`
+    ///   become an `{if}` block.
+    /// - `{% for item in items %}`, `{% else %}` and `{% endfor %}` become a `{for}` block.
+    /// - `{# comment #}` is removed.
+    /// - A `-` at the start or end of a tag, as in `{%- if a -%}`, strips the
+    ///   whitespace before or after the tag.
+    ///
+    /// Other expressions and statements fail with [`ParseErrorKind::UnsupportedFormat`].
+    /// A tag that is not closed fails with [`ParseErrorKind::UnmatchedBrace`], and
+    /// a misplaced or missing `endif` or `endfor` with [`ParseErrorKind::UnexpectedTag`]
+    /// or [`ParseErrorKind::UnclosedBlock`].
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// let template = Template::from_jinja("{% for user in users -%}\n  {{ user | html }}\n{%- endfor %}").unwrap();
+    /// assert_eq!("{for user in users}{user | html}{end}", template.source());
+    /// ```
+    pub fn from_jinja(source: &str) -> Result<Template, ParseError> {
+        let mut r = String::with_capacity(source.len());
+        let mut blocks: Vec<(&str, Range<usize>)> = vec![];
+        let mut literal_start = 0;
+        let mut trim_next = false;
+        let mut i = 0;
+        while let Some(offset) = source[i..].find('{') {
+            let start = i + offset;
+            let close = match source[start + 1..].chars().next() {
+                Some('{') => "}}",
+                Some('%') => "%}",
+                Some('#') => "#}",
+                _ => {
+                    i = start + 1;
+                    continue;
+                },
+            };
+            let Some(len) = source[start + 2..].find(close) else {
+                return Err(ParseError::new(ParseErrorKind::UnmatchedBrace, start..start + 2));
+            };
+            let end = start + 2 + len + 2;
+            let mut content = &source[start + 2..end - 2];
+            let mut literal = &source[literal_start..start];
+            if trim_next {
+                literal = literal.trim_start();
+            }
+            if let Some(rest) = content.strip_prefix('-') {
+                literal = literal.trim_end();
+                content = rest;
+            }
+            trim_next = content.ends_with('-');
+            let content = content.strip_suffix('-').unwrap_or(content).trim();
+            r.push_str(&escape_literal(literal));
+            let unsupported = || {
+                let kind = ParseErrorKind::UnsupportedFormat(source[start..end].to_owned());
+                ParseError::new(kind, start..end)
+            };
+            let is_name = |name: &str| !name.is_empty() && name.chars().all(is_name_char) && !KEYWORDS.contains(&name);
+            let words: Vec<&str> = content.split_whitespace().collect();
+            match close {
+                "#}" => {},
+                "}}" => {
+                    let mut parts = content.split('|').map(str::trim);
+                    let name = parts.next().unwrap();
+                    if !is_name(name) {
+                        return Err(unsupported());
+                    }
+                    r.push('{');
+                    r.push_str(name);
+                    for filter in parts {
+                        if Filter::from_name(filter).is_none() {
+                            return Err(ParseError::new(ParseErrorKind::UnknownFilter(filter.to_owned()), start..end));
+                        }
+                        r.push_str(" | ");
+                        r.push_str(filter);
+                    }
+                    r.push('}');
+                },
+                _ => match words[..] {
+                    ["if", name] if is_name(name) => {
+                        blocks.push(("endif", start..end));
+                        r.push_str(&format!("{{if {name}}}"));
+                    },
+                    ["if", "not", name] if is_name(name) => {
+                        blocks.push(("endif", start..end));
+                        r.push_str(&format!("{{if not {name}}}"));
+                    },
+                    ["for", variable, "in", name] if is_name(variable) && is_name(name) => {
+                        blocks.push(("endfor", start..end));
+                        r.push_str(&format!("{{for {variable} in {name}}}"));
+                    },
+                    ["else"] if !blocks.is_empty() => r.push_str("{else}"),
+                    [tag @ ("endif" | "endfor")] => {
+                        if blocks.pop().is_none_or(|(open, _)| open != tag) {
+                            return Err(ParseError::new(ParseErrorKind::UnexpectedTag(tag.to_owned()), start..end));
+                        }
+                        r.push_str("{end}");
+                    },
+                    _ => return Err(unsupported()),
+                },
+            }
+            i = end;
+            literal_start = i;
+        }
+        if let Some((_, span)) = blocks.pop() {
+            return Err(ParseError::new(ParseErrorKind::UnclosedBlock, span));
+        }
+        let mut literal = &source[literal_start..];
+        if trim_next {
+            literal = literal.trim_start();
+        }
+        r.push_str(&escape_literal(literal));
+        Template::parse(&r)
+    }
+}
+
+/// Renders a printf-style format string against positional arguments, for
+/// compatibility with legacy C-style catalogs. The format is understood as
+/// described in [`Template::from_printf`], with `%s` taking the next
+/// argument and `%1$s` the first one.
+///
+/// A specifier without an argument fails with [`ParseErrorKind::UnnamedArgument`].
+///
+/// ```
+/// assert_eq!("x has 3% of x", late_format::printf("%s has %2$d%% of %1$s", &["x", "3"]).unwrap());
+/// ```
+pub fn printf(format: &str, arguments: &[&str]) -> Result<String, ParseError> {
+    let names: Vec<String> = (1..=arguments.len()).map(|i| i.to_string()).collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let template = Template::from_printf(format, &names)?;
+    let arguments: HashMap<String, String> = names.iter()
+        .zip(arguments)
+        .map(|(name, value)| ((*name).to_owned(), (*value).to_owned()))
+        .collect();
+    Ok(template.render(&arguments))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{printf, ParseErrorKind, Template};
+    use maplit::hashmap;
+
+    #[test]
+    fn std_format() {
+        let template = Template::from_std_format("Hello, {user.name}! You have {0} {} {{new}}").unwrap();
+        let arguments = hashmap!{"user.name".into() => "x".into(), "0".into() => "2".into()};
+        assert_eq!("Hello, x! You have 2 2 {new}", template.render(&arguments));
+        assert!(Template::from_std_format("{raw}").is_err());
+        assert_eq!(&ParseErrorKind::UnmatchedBrace, Template::from_std_format("a } b").unwrap_err().kind());
+        assert_eq!(&ParseErrorKind::UnmatchedBrace, Template::from_std_format("a { b").unwrap_err().kind());
+        let error = Template::from_std_format("{a} {b:>8}").unwrap_err();
+        assert_eq!(&ParseErrorKind::UnsupportedFormat("{b:>8}".into()), error.kind());
+        assert_eq!(4..10, error.span());
+        assert!(Template::from_std_format("{a!r}").is_err());
+    }
+
+    #[test]
+    fn printf_conversion() {
+        let names = ["a", "b", "c"];
+        let template = Template::from_printf("%s: %d %% %c{x}", &names).unwrap();
+        assert_eq!(r#"{a}: {b} % {c}{"{"}x}"#, template.source());
+        assert_eq!("%s: %s %% %s{x}", template.to_printf(&names).unwrap());
+        let template = Template::from_printf("%3$s %1$s %s", &names).unwrap();
+        assert_eq!("{c} {a} {a}", template.source());
+        assert_eq!("%3$s %1$s %1$s", template.to_printf(&names).unwrap());
+        let error = Template::from_printf("%s %s", &["a"]).unwrap_err();
+        assert_eq!((&ParseErrorKind::UnnamedArgument(2), 3..5), (error.kind(), error.span()));
+        let error = Template::from_printf("x %5.2f", &names).unwrap_err();
+        assert_eq!(&ParseErrorKind::UnsupportedFormat("%5".into()), error.kind());
+        assert!(Template::from_printf("%0$s", &names).is_err());
+        assert!(Template::from_printf("%", &names).is_err());
+        let template = Template::parse("{a | dedent} {d}").unwrap();
+        assert_eq!(0..12, template.to_printf(&["a", "d"]).unwrap_err().span());
+        let template = Template::parse("{a} {d}").unwrap();
+        assert_eq!(4..7, template.to_printf(&["a"]).unwrap_err().span());
+    }
+
+    #[test]
+    fn printf_rendering() {
+        assert_eq!("b a {x} b%", printf("%2$s %s {x} %s%%", &["a", "b"]).unwrap());
+        assert_eq!(&ParseErrorKind::UnnamedArgument(3), printf("%s %s %s", &["a", "b"]).unwrap_err().kind());
+    }
+
+    #[test]
+    fn double_braces() {
+        let source = r#"{"{"}x} { a:-{"}"} }{plural n}{one}}{"{"}{ other }x{end}{raw}{{}}{endraw}{'{}'}"#;
+        let template = Template::parse(source).unwrap();
+        let doubled = template.to_double_braces();
+        assert_eq!(r#"{{x}} { a:-{"}"} }{plural n}{one}}}{{{ other }x{end}{raw}{{}}{endraw}{{}}"#, doubled);
+        let migrated = Template::from_double_braces(&doubled).unwrap();
+        assert_eq!(r#"{"{"}x} { a:-{"}"} }{plural n}{one}}{"{"}{ other }x{end}{raw}{{}}{endraw}{"{"}}"#, migrated.source());
+        assert_eq!(template, migrated);
+        let error = Template::from_double_braces("{{a}} }").unwrap_err();
+        assert_eq!((&ParseErrorKind::UnmatchedBrace, 6..7), (error.kind(), error.span()));
+        assert!(Template::from_double_braces("{a").is_err());
+    }
+
+    #[test]
+    fn mustache() {
+        let template = Template::from_mustache("<ul>\n  {{#items}}\n  <li>{{ items }}</li>\n  {{/items}}\n</ul>{{! note }}{x}").unwrap();
+        assert_eq!("<ul>\n  <li>a &lt; b</li>\n</ul>{x}", template.render(&hashmap!{"items".into() => "a < b".into()}));
+        assert_eq!("<ul>\n</ul>{x}", template.render(&hashmap!{}));
+        assert_eq!("{a}", Template::from_mustache("{{&a}}").unwrap().source());
+        assert_eq!(&ParseErrorKind::UnclosedBlock, Template::from_mustache("{{#a}}").unwrap_err().kind());
+        assert_eq!(&ParseErrorKind::UnexpectedTag("{{/b}}".into()), Template::from_mustache("{{#a}}{{/b}}").unwrap_err().kind());
+        assert_eq!(&ParseErrorKind::UnmatchedBrace, Template::from_mustache("{{a").unwrap_err().kind());
+        for source in ["{{> partial}}", "{{=<% %>=}}", "{{.}}", "{{#raw}}{{/raw}}"] {
+            assert!(matches!(Template::from_mustache(source).unwrap_err().kind(), ParseErrorKind::UnsupportedFormat(_)));
+        }
+    }
+
+    #[test]
+    fn jinja() {
+        let template = Template::from_jinja("{# list #}{% if items %}{% for item in items %}[{{ item|dedent }}]{% endfor %}{% else %}none{% endif %} {x}").unwrap();
+        assert_eq!(r#"{if items}{for item in items}[{item | dedent}]{end}{else}none{end} {"{"}x}"#, template.source());
+        assert_eq!("none {x}", template.render(&hashmap!{}));
+        assert_eq!("ab", Template::from_jinja("a  {#- c -#}\n b").unwrap().render(&hashmap!{}));
+        assert_eq!(&ParseErrorKind::UnknownFilter("upper".into()), Template::from_jinja("{{ a | upper }}").unwrap_err().kind());
+        assert_eq!(&ParseErrorKind::UnexpectedTag("endfor".into()), Template::from_jinja("{% if a %}{% endfor %}").unwrap_err().kind());
+        assert_eq!(&ParseErrorKind::UnclosedBlock, Template::from_jinja("{% for a in b %}").unwrap_err().kind());
+        for source in ["{{ a + 1 }}", "{% set a = 1 %}", "{% elif a %}", "{% else %}"] {
+            assert!(matches!(Template::from_jinja(source).unwrap_err().kind(), ParseErrorKind::UnsupportedFormat(_)));
+        }
+    }
+}
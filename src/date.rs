@@ -0,0 +1,265 @@
+use std::fmt;
+
+/// A date of the proleptic Gregorian calendar, with an optional time of
+/// day and no time zone, for the `dateparse` and `date` filters.
+///
+/// Dates are exchanged between filters as ISO 8601 text, such as
+/// `2024-03-05` and `2024-03-05T09:30:00`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Date {
+    year: i32,
+    month: u8,
+    day: u8,
+    /// The hour, minute and second.
+    time: Option<(u8, u8, u8)>,
+}
+
+const MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+
+const WEEKDAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+
+impl Date {
+    /// Parses a date written with a format of `%` directives, where `%Y` is
+    /// the year in four digits, `%m` and `%d` the month and day, `%H`, `%M`
+    /// and `%S` the time, `%b` and `%B` the abbreviated and full English
+    /// month names, and `%%` a percent sign. Other characters match themselves.
+    pub fn parse(s: &str, format: &str) -> Option<Self> {
+        let (mut year, mut month, mut day, mut time) = (None, None, None, None::<(u8, u8, u8)>);
+        let mut rest = s;
+        let mut directives = format.chars();
+        while let Some(c) = directives.next() {
+            if c != '%' {
+                rest = rest.strip_prefix(c)?;
+                continue;
+            }
+            let mut time_part = |i: usize, value: u8| {
+                let t = time.get_or_insert((0, 0, 0));
+                match i {
+                    0 => t.0 = value,
+                    1 => t.1 = value,
+                    _ => t.2 = value,
+                }
+            };
+            match directives.next()? {
+                'Y' => year = Some(digits(&mut rest, 4, 4)? as i32),
+                'm' => month = Some(digits(&mut rest, 1, 2)? as u8),
+                'd' => day = Some(digits(&mut rest, 1, 2)? as u8),
+                'H' => time_part(0, digits(&mut rest, 1, 2)? as u8),
+                'M' => time_part(1, digits(&mut rest, 1, 2)? as u8),
+                'S' => time_part(2, digits(&mut rest, 1, 2)? as u8),
+                d @ ('b' | 'B') => {
+                    let (i, len) = MONTHS.iter().enumerate().find_map(|(i, name)| {
+                        let name = if d == 'b' { &name[..3] } else { name };
+                        let prefix = rest.get(..name.len())?;
+                        prefix.eq_ignore_ascii_case(name).then_some((i, name.len()))
+                    })?;
+                    rest = &rest[len..];
+                    month = Some(i as u8 + 1);
+                },
+                '%' => rest = rest.strip_prefix('%')?,
+                _ => return None,
+            }
+        }
+        let date = Self { year: year?, month: month?, day: day?, time };
+        (rest.is_empty() && date.is_valid()).then_some(date)
+    }
+
+    /// Parses a date in the ISO 8601 form written by [`Date`], also accepting
+    /// a space for the `T`, fractions of seconds and a `Z` suffix.
+    pub fn iso(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let s = s.strip_suffix('Z').unwrap_or(s);
+        let (date, time) = s.split_at_checked(10)?;
+        if time.is_empty() {
+            return Self::parse(date, "%Y-%m-%d");
+        }
+        let time = time.strip_prefix(['T', ' '])?;
+        let time = time.split_once('.').map_or(time, |(time, fraction)| {
+            if fraction.bytes().all(|b| b.is_ascii_digit()) { time } else { "" }
+        });
+        let format = if time.len() == 5 { "%Y-%m-%dT%H:%M" } else { "%Y-%m-%dT%H:%M:%S" };
+        Self::parse(&format!("{date}T{time}"), format)
+    }
+
+    /// Writes the date with a format of the `%` directives of [`Date::parse`],
+    /// and `%y` for the year in two digits, `%a` and `%A` for the abbreviated
+    /// and full English weekday names, and `%j` for the day of the year. A `-`
+    /// after the `%` removes the padding of a number, as in `%-d`.
+    pub fn format(&self, format: &str) -> String {
+        let (hour, minute, second) = self.time.unwrap_or_default();
+        let mut r = String::new();
+        let mut directives = format.chars().peekable();
+        while let Some(c) = directives.next() {
+            if c != '%' {
+                r.push(c);
+                continue;
+            }
+            let padded = directives.next_if_eq(&'-').is_none();
+            let number = |n: i64, width: usize| if padded { format!("{n:0width$}") } else { n.to_string() };
+            match directives.next() {
+                Some('Y') => r.push_str(&number(self.year as i64, 4)),
+                Some('y') => r.push_str(&number(self.year.rem_euclid(100) as i64, 2)),
+                Some('m') => r.push_str(&number(self.month as i64, 2)),
+                Some('d') => r.push_str(&number(self.day as i64, 2)),
+                Some('H') => r.push_str(&number(hour as i64, 2)),
+                Some('M') => r.push_str(&number(minute as i64, 2)),
+                Some('S') => r.push_str(&number(second as i64, 2)),
+                Some('j') => r.push_str(&number(self.day_of_year(), 3)),
+                Some('b') => r.push_str(&MONTHS[self.month as usize - 1][..3]),
+                Some('B') => r.push_str(MONTHS[self.month as usize - 1]),
+                Some('a') => r.push_str(&WEEKDAYS[self.weekday()][..3]),
+                Some('A') => r.push_str(WEEKDAYS[self.weekday()]),
+                Some('%') => r.push('%'),
+                Some(d) => {
+                    r.push('%');
+                    r.push(d);
+                },
+                None => r.push('%'),
+            }
+        }
+        r
+    }
+
+    /// The date and time of a number of seconds since 1970-01-01T00:00:00,
+    /// or `None` if the year does not fit.
+    pub fn from_timestamp(seconds: i64) -> Option<Self> {
+        let (year, month, day) = civil_from_days(seconds.div_euclid(86400))?;
+        let seconds = seconds.rem_euclid(86400);
+        let time = ((seconds / 3600) as u8, (seconds / 60 % 60) as u8, (seconds % 60) as u8);
+        Some(Self { year, month, day, time: Some(time) })
+    }
+
+    pub fn year(&self) -> i32 {
+        self.year
+    }
+
+    /// The number of seconds since 1970-01-01T00:00:00, or `None` for a
+    /// date without a time.
+    pub fn timestamp(&self) -> Option<i64> {
+        let (hour, minute, second) = self.time?;
+        let seconds = i64::from(hour) * 3600 + i64::from(minute) * 60 + i64::from(second);
+        Some(days_from_civil(self.year, self.month, self.day) * 86400 + seconds)
+    }
+
+    fn is_valid(&self) -> bool {
+        let valid_time = self.time.is_none_or(|(h, m, s)| h < 24 && m < 60 && s < 60);
+        (1..=12).contains(&self.month) && self.day >= 1 && self.day <= days_in_month(self.year, self.month) && valid_time
+    }
+
+    /// The day of the week, from 0 for Sunday.
+    fn weekday(&self) -> usize {
+        // 1970-01-01 was a Thursday.
+        (days_from_civil(self.year, self.month, self.day) + 4).rem_euclid(7) as usize
+    }
+
+    fn day_of_year(&self) -> i64 {
+        days_from_civil(self.year, self.month, self.day) - days_from_civil(self.year, 1, 1) + 1
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)?;
+        if let Some((h, m, s)) = self.time {
+            write!(f, "T{h:02}:{m:02}:{s:02}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks that a format has only the directives read by [`Date::parse`], or,
+/// if `parsing` is false, written by [`Date::format`].
+pub(crate) fn is_valid_format(format: &str, parsing: bool) -> bool {
+    let mut directives = format.chars();
+    while let Some(c) = directives.next() {
+        if c != '%' {
+            continue;
+        }
+        let mut d = directives.next();
+        if !parsing && d == Some('-') {
+            d = directives.next();
+        }
+        let known = match d {
+            Some('Y' | 'm' | 'd' | 'H' | 'M' | 'S' | 'b' | 'B' | '%') => true,
+            Some('y' | 'j' | 'a' | 'A') => !parsing,
+            _ => false,
+        };
+        if !known {
+            return false;
+        }
+    }
+    true
+}
+
+/// Reads from `min` to `max` decimal digits.
+fn digits(rest: &mut &str, min: usize, max: usize) -> Option<u32> {
+    let len = rest.bytes().take(max).take_while(u8::is_ascii_digit).count();
+    if len < min {
+        return None;
+    }
+    let n = rest[..len].parse().ok()?;
+    *rest = &rest[len..];
+    Some(n)
+}
+
+pub(crate) fn days_in_month(year: i32, month: u8) -> u8 {
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The days since 1970-01-01, with the algorithm of Howard Hinnant.
+pub(crate) fn days_from_civil(year: i32, month: u8, day: u8) -> i64 {
+    let year = year as i64 - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The year, month and day of a number of days since 1970-01-01, the
+/// inverse of [`days_from_civil`].
+fn civil_from_days(days: i64) -> Option<(i32, u8, u8)> {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let m = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * m + 2) / 5 + 1) as u8;
+    let month = if m < 10 { m + 3 } else { m - 9 } as u8;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    Some((year.try_into().ok()?, month, day))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_valid_format, Date};
+
+    #[test]
+    fn dates() {
+        let date = Date::parse("05/03/2024", "%d/%m/%Y").unwrap();
+        assert_eq!("2024-03-05", date.to_string());
+        assert_eq!("Tuesday, March 5, 2024 (065)", date.format("%A, %B %-d, %Y (%j)"));
+        assert_eq!(Some("2024-02-29T09:30:00".into()), Date::parse("Feb 29 2024 9:30", "%b %d %Y %H:%M").map(|d| d.to_string()));
+        assert_eq!(None, Date::parse("2023-02-29", "%Y-%m-%d"));
+        assert_eq!(None, Date::parse("2024-03-05x", "%Y-%m-%d"));
+        let iso = Date::iso("2024-12-31T23:59:01.250Z").unwrap();
+        assert_eq!("31 Dec 24, 23:59:01 Tue%", iso.format("%d %b %y, %H:%M:%S %a%%"));
+        assert_eq!(Some(date), Date::iso("2024-03-05"));
+        assert!(Date::iso("2024-03-05T25:00").is_none() && Date::iso("2024-3-5").is_none());
+        assert_eq!("2024-03-05T09:30:00", Date::from_timestamp(1709631000).unwrap().to_string());
+        assert_eq!("1969-12-31T23:59:59", Date::from_timestamp(-1).unwrap().to_string());
+        assert!(Date::from_timestamp(i64::MAX).is_none());
+        assert!(is_valid_format("%-d %a", false) && !is_valid_format("%a", true) && !is_valid_format("%q", false));
+    }
+}
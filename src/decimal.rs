@@ -0,0 +1,121 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::{IntoLateValue, Value};
+
+/// An exact decimal number, an integer mantissa scaled by a power of ten,
+/// for amounts of money and other arguments that must not go through
+/// floating point. It converts into its decimal text, which format specs
+/// such as `{price:.2}` and the arithmetic filters read exactly.
+///
+/// ```
+/// use late_format::{arguments, Decimal, FormatOptions, Rounding, Template};
+/// let template = Template::parse("{price:.2} {price | mul:3}").unwrap();
+/// let arguments = arguments!{"price" => Decimal::new(19_995, 3)};
+/// let options = FormatOptions::new().rounding(Rounding::HalfUp);
+/// assert_eq!("20.00 59.985", template.render_arguments(&arguments, &options).unwrap());
+/// assert_eq!(Ok(Decimal::new(-5, 2)), "-0.05".parse());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+/// The error of parsing a [`Decimal`] that is not written as `-12.345`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseDecimalError;
+
+impl Decimal {
+    /// The number `mantissa / 10^scale`, so that `Decimal::new(1999, 2)` is `19.99`.
+    pub fn new(mantissa: i64, scale: u32) -> Self {
+        Self::from_i128_with_scale(mantissa.into(), scale)
+    }
+
+    pub fn from_i128_with_scale(mantissa: i128, scale: u32) -> Self {
+        Self { mantissa, scale }
+    }
+
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    /// The number of fraction digits.
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(value: i64) -> Self {
+        Self::new(value, 0)
+    }
+}
+
+/// Writes the number with all the fraction digits of its scale, as in `19.990`.
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = format!("{:0>width$}", self.mantissa.unsigned_abs(), width = self.scale as usize + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - self.scale as usize);
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let dot = if fraction.is_empty() { "" } else { "." };
+        write!(f, "{sign}{integer}{dot}{fraction}")
+    }
+}
+
+/// Reads a number such as `12`, `-0.05` or `+1.50`, keeping its scale.
+impl FromStr for Decimal {
+    type Err = ParseDecimalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (integer, fraction) = match s.split_once('.') {
+            Some((_, "")) => return Err(ParseDecimalError),
+            Some(parts) => parts,
+            None => (s, ""),
+        };
+        if integer.is_empty() || !integer.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit()) {
+            return Err(ParseDecimalError);
+        }
+        let scale = u32::try_from(fraction.len()).map_err(|_| ParseDecimalError)?;
+        let sign = if negative { "-" } else { "" };
+        let mantissa = format!("{sign}{integer}{fraction}").parse().map_err(|_| ParseDecimalError)?;
+        Ok(Self { mantissa, scale })
+    }
+}
+
+impl fmt::Display for ParseDecimalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid decimal number")
+    }
+}
+
+impl std::error::Error for ParseDecimalError {}
+
+impl IntoLateValue for Decimal {
+    fn into_late_value(self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{arguments, FormatOptions, Rounding, Template};
+    use super::{Decimal, ParseDecimalError};
+
+    #[test]
+    fn decimals() {
+        let cases = [(Decimal::new(1999, 2), "19.99"), (Decimal::new(-5, 3), "-0.005"), (Decimal::new(7, 0), "7"), (Decimal::from_i128_with_scale(i128::MIN, 2), "-1701411834604692317316873037158841057.28")];
+        for (decimal, text) in cases {
+            assert_eq!((text, Ok(decimal)), (decimal.to_string().as_str(), text.parse()));
+        }
+        assert_eq!(Ok(Decimal::new(150, 2)), "+1.50".parse());
+        assert!(["", "-", ".5", "1.", "1e3", "1,000", "x"].iter().all(|s| s.parse::<Decimal>() == Err(ParseDecimalError)));
+        let template = Template::parse("{a | add:0.2} {b:.2} {b:.0} {c | div:3}").unwrap();
+        let arguments = arguments!{"a" => Decimal::new(1, 1), "b" => Decimal::new(1005, 3), "c" => Decimal::new(1000, 2)};
+        let options = FormatOptions::new().rounding(Rounding::HalfUp);
+        assert_eq!("0.3 1.01 1 3.3333333333", template.render_arguments(&arguments, &options).unwrap());
+    }
+}
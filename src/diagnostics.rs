@@ -0,0 +1,67 @@
+use std::fmt::{self, Write};
+use std::ops::Range;
+
+/// Formats a message followed by the line of the source where the span
+/// starts, with the span underlined by carets:
+///
+/// ```plain
+/// error: unknown filter `nope`
+///  --> 2:1
+///   |
+/// 2 | {name | nope}!
+///   | ^^^^^^^^^^^^^
+/// ```
+///
+/// Columns count characters, starting at 1. A span covering several lines
+/// is underlined up to the end of its first line.
+pub(crate) fn snippet(source: &str, span: Range<usize>, message: &dyn fmt::Display) -> String {
+    labeled_snippet(source, span, "error", message)
+}
+
+/// Formats a snippet as [`snippet`] does, with another label than `error`.
+pub(crate) fn labeled_snippet(source: &str, span: Range<usize>, label: &str, message: &dyn fmt::Display) -> String {
+    let start = span.start.min(source.len());
+    let end = span.end.clamp(start, source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+    let line = source[line_start..line_end].trim_end_matches('\r');
+    let line_number = source[..start].matches('\n').count() + 1;
+    let column = source[line_start..start].chars().count();
+    let width = source[start..end.min(line_end)].chars().count().max(1);
+    let gutter = " ".repeat(line_number.to_string().len());
+
+    let mut r = String::new();
+    writeln!(r, "{label}: {message}").unwrap();
+    writeln!(r, "{gutter}--> {line_number}:{}", column + 1).unwrap();
+    writeln!(r, "{gutter} |").unwrap();
+    writeln!(r, "{line_number} | {line}").unwrap();
+    writeln!(r, "{gutter} | {}{}", " ".repeat(column), "^".repeat(width)).unwrap();
+    r
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn snippets() {
+        let source = "line one\nsecond {é | x}\nline three\n";
+        assert_eq!(
+            "error: oops\n --> 2:8\n  |\n2 | second {é | x}\n  |        ^^^^^^^\n",
+            snippet(source, 16..24, &"oops"),
+        );
+        assert_eq!(
+            "error: oops\n --> 1:1\n  |\n1 | line one\n  | ^^^^^^^^\n",
+            snippet(source, 0..source.len(), &"oops"),
+        );
+        assert_eq!(
+            "error: oops\n --> 4:1\n  |\n4 | \n  | ^\n",
+            snippet(source, source.len()..source.len(), &"oops"),
+        );
+        let source = format!("{}x", "\n".repeat(11));
+        assert_eq!(
+            "error: oops\n  --> 12:1\n   |\n12 | x\n   | ^\n",
+            snippet(&source, 11..12, &"oops"),
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::{Arguments, FormatOptions, Template};
+use crate::render::Writer;
+
+/// A template with its arguments, rendered when formatted with
+/// [`Display`](fmt::Display), as returned by [`Template::display`]. This
+/// allows passing a message to `println!`, `write!` or an error type
+/// without allocating the rendered string.
+///
+/// The width, alignment and precision of the format spec apply to the
+/// rendered text as a whole, which is then rendered into a string first.
+/// A render error, as with [`Missing::Error`](crate::Missing::Error), is
+/// reported as [`fmt::Error`].
+///
+/// ```
+/// use late_format::{FormatOptions, LateDisplay, Missing, Template};
+/// use maplit::hashmap;
+/// use std::fmt::Write;
+/// let template = Template::parse("{n} {n | pluralize:file,files}").unwrap();
+/// let arguments = hashmap!{"n".to_owned() => "2".to_owned()};
+/// let mut r = String::new();
+/// write!(r, "{}.", LateDisplay::new(&template, &arguments)).unwrap();
+/// assert_eq!("2 files.", r);
+/// let options = FormatOptions::new().missing(Missing::Error);
+/// assert!(write!(r, "{}", template.display(&hashmap!{"m".to_owned() => "1".to_owned()}).options(&options)).is_err());
+/// ```
+pub struct LateDisplay<'a, A: Arguments + ?Sized = HashMap<String, String>> {
+    template: &'a Template,
+    arguments: &'a A,
+    options: Option<&'a FormatOptions>,
+}
+
+impl<'a, A: Arguments + ?Sized> LateDisplay<'a, A> {
+    pub fn new(template: &'a Template, arguments: &'a A) -> Self {
+        Self { template, arguments, options: None }
+    }
+
+    /// Sets the options to render with, otherwise the options attached to the
+    /// template by [`Template::builder`], or the default ones.
+    pub fn options(mut self, value: &'a FormatOptions) -> Self {
+        self.options = Some(value);
+        self
+    }
+}
+
+impl<A: Arguments + ?Sized> fmt::Display for LateDisplay<'_, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let attached;
+        let options = match self.options {
+            Some(options) => options,
+            None => {
+                attached = self.template.attached_options();
+                &attached
+            },
+        };
+        if f.width().is_some() || f.precision().is_some() {
+            let r = self.template.render_arguments(self.arguments, options).map_err(|_| fmt::Error)?;
+            return f.pad(&r);
+        }
+        let mut writer = Writer::new(f);
+        let rendered = self.template.render_arguments_into(self.arguments, options, &mut writer);
+        writer.result?;
+        rendered.map_err(|_| fmt::Error)
+    }
+}
+
+impl<A: Arguments + ?Sized> fmt::Debug for LateDisplay<'_, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LateDisplay").field("template", &self.template.source()).finish_non_exhaustive()
+    }
+}
+
+/// An error type whose message is a template rendered with its fields, as
+/// implemented with [`Display`](fmt::Display) by the `LateError` derive of
+/// the `late_format_macros` crate. The template source can serve as the key
+/// of a localized message, rendered with the same arguments.
+///
+/// ```
+/// use late_format::{LateError, Template};
+/// use std::collections::HashMap;
+/// struct NotFound { path: String }
+/// impl LateError for NotFound {
+///     fn template(&self) -> &'static Template {
+///         late_format::static_template!("{path} not found")
+///     }
+///     fn arguments(&self) -> HashMap<String, String> {
+///         HashMap::from([("path".to_owned(), self.path.clone())])
+///     }
+/// }
+/// let error = NotFound { path: "a.txt".into() };
+/// let french = Template::parse("{path} introuvable").unwrap();
+/// assert_eq!("a.txt introuvable", french.render(&error.arguments()));
+/// assert_eq!("{path} not found", error.template().source());
+/// ```
+pub trait LateError {
+    /// The template of the message.
+    fn template(&self) -> &'static Template;
+
+    /// The fields used by the template, displayed, by parameter name.
+    fn arguments(&self) -> HashMap<String, String>;
+}
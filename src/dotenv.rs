@@ -0,0 +1,205 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use crate::{Arguments, Value};
+
+/// Arguments loaded from a `.env` file, so that configuration templates can be
+/// expanded from the files the rest of a deployment uses. It is typically
+/// added to [`Layers`](crate::Layers).
+///
+/// Each line is either blank, a `#` comment or a `KEY=value` assignment,
+/// optionally preceded by `export`. Values may be:
+///
+/// - unquoted, ending at a ` #` comment, with surrounding whitespace removed;
+/// - single-quoted, taken verbatim;
+/// - double-quoted, accepting the `\n`, `\t`, `\"` and `\\` backslash sequences
+///   and spanning several lines.
+///
+/// A key defined twice takes the last value.
+///
+/// ```
+/// use late_format::{DotEnv, FormatOptions, Template};
+/// let env = DotEnv::parse("# database\nexport DB_HOST=localhost # local\nDB_NAME='app'\n").unwrap();
+/// let template = Template::parse("postgres://{DB_HOST}/{DB_NAME}").unwrap();
+/// assert_eq!("postgres://localhost/app", template.render_arguments(&env, &FormatOptions::new()).unwrap());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DotEnv {
+    values: HashMap<String, Value>,
+}
+
+/// An error produced when parsing a `.env` file, at a line starting at 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DotEnvError {
+    kind: DotEnvErrorKind,
+    line: usize,
+}
+
+/// The kind of a [`DotEnvError`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DotEnvErrorKind {
+    /// A line is neither blank, a comment nor an assignment.
+    MissingEquals,
+    /// A key contains characters other than ASCII letters, digits, `_` and `.`.
+    InvalidKey(String),
+    /// A quoted value is not closed.
+    UnclosedQuote,
+}
+
+impl DotEnv {
+    pub fn parse(source: &str) -> Result<Self, DotEnvError> {
+        let mut values = HashMap::new();
+        let mut lines = source.lines().enumerate();
+        while let Some((i, line)) = lines.next() {
+            let error = |kind| DotEnvError { kind, line: i + 1 };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line = line.strip_prefix("export ").map_or(line, str::trim_start);
+            let (key, value) = line.split_once('=').ok_or_else(|| error(DotEnvErrorKind::MissingEquals))?;
+            let key = key.trim();
+            if key.is_empty() || !key.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '.') {
+                return Err(error(DotEnvErrorKind::InvalidKey(key.to_owned())));
+            }
+            let value = value.trim_start();
+            let value = if let Some(rest) = value.strip_prefix('\'') {
+                let end = rest.find('\'').ok_or_else(|| error(DotEnvErrorKind::UnclosedQuote))?;
+                rest[..end].to_owned()
+            } else if let Some(rest) = value.strip_prefix('"') {
+                let mut text = rest.to_owned();
+                loop {
+                    if let Some(end) = closing_quote(&text) {
+                        break unescape(&text[..end]);
+                    }
+                    let Some((_, next)) = lines.next() else {
+                        return Err(error(DotEnvErrorKind::UnclosedQuote));
+                    };
+                    text.push('\n');
+                    text.push_str(next);
+                }
+            } else {
+                value.split(" #").next().unwrap().trim_end().to_owned()
+            };
+            values.insert(key.to_owned(), Value::String(value));
+        }
+        Ok(Self { values })
+    }
+
+    /// Reads and parses a `.env` file. A parse error is returned as an error
+    /// of kind [`io::ErrorKind::InvalidData`] wrapping the [`DotEnvError`].
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let source = std::fs::read_to_string(path)?;
+        Self::parse(&source).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        match self.values.get(key) {
+            Some(Value::String(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The assignments of the file, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values.keys().filter_map(|key| Some((key.as_str(), self.get(key)?)))
+    }
+}
+
+impl Arguments for DotEnv {
+    fn get(&self, name: &str) -> Option<Cow<'_, Value>> {
+        self.values.get(name).map(Cow::Borrowed)
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.values.keys().cloned().collect()
+    }
+}
+
+impl DotEnvError {
+    pub fn kind(&self) -> &DotEnvErrorKind {
+        &self.kind
+    }
+
+    /// The line of the error, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for DotEnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at line {}", self.kind, self.line)
+    }
+}
+
+impl fmt::Display for DotEnvErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingEquals => write!(f, "expected `KEY=value`"),
+            Self::InvalidKey(key) => write!(f, "invalid key `{key}`"),
+            Self::UnclosedQuote => write!(f, "unclosed quoted value"),
+        }
+    }
+}
+
+impl std::error::Error for DotEnvError {}
+
+/// Finds the double quote closing a value, skipping backslash sequences.
+fn closing_quote(s: &str) -> Option<usize> {
+    let mut chars = s.char_indices();
+    while let Some((i, ch)) = chars.next() {
+        match ch {
+            '\\' => {
+                chars.next();
+            },
+            '"' => return Some(i),
+            _ => {},
+        }
+    }
+    None
+}
+
+fn unescape(s: &str) -> String {
+    let mut r = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            r.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => r.push('\n'),
+            Some('t') => r.push('\t'),
+            Some(ch @ ('"' | '\\')) => r.push(ch),
+            Some(ch) => {
+                r.push('\\');
+                r.push(ch);
+            },
+            None => r.push('\\'),
+        }
+    }
+    r
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        let env = DotEnv::parse("A = 1\n\n  # c\nB=\"x\\ny \\\"z\\\"\nw\" # c\nC='a # b'\nD=a#b\nA=2\n").unwrap();
+        assert_eq!(Some("2"), env.get("A"));
+        assert_eq!(Some("x\ny \"z\"\nw"), env.get("B"));
+        assert_eq!(Some("a # b"), env.get("C"));
+        assert_eq!(Some("a#b"), env.get("D"));
+        assert_eq!(4, env.iter().count());
+        let error = DotEnv::parse("A=1\nB\n").unwrap_err();
+        assert_eq!((&DotEnvErrorKind::MissingEquals, 2), (error.kind(), error.line()));
+        assert_eq!(&DotEnvErrorKind::UnclosedQuote, DotEnv::parse("A=\"x\ny").unwrap_err().kind());
+        assert_eq!(&DotEnvErrorKind::InvalidKey("A B".into()), DotEnv::parse("A B=1").unwrap_err().kind());
+    }
+}
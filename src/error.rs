@@ -0,0 +1,302 @@
+use std::fmt;
+use std::ops::Range;
+use crate::ArgumentType;
+use crate::diagnostics::snippet;
+
+/// An error produced when parsing a [`Template`](crate::Template).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    span: Range<usize>,
+}
+
+/// The kind of a [`ParseError`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseErrorKind {
+    /// A placeholder uses a filter that does not exist.
+    UnknownFilter(String),
+    /// A filter is given arguments that it does not accept.
+    InvalidFilterArguments(String),
+    /// The template exceeds one of the [`Limits`](crate::Limits).
+    LimitExceeded(Limit),
+    /// A curly brace is not matched, when converting from another syntax.
+    UnmatchedBrace,
+    /// A form has no equivalent in the other syntax, when converting from
+    /// or to another syntax.
+    UnsupportedFormat(String),
+    /// No name or argument is given for the argument at this position,
+    /// starting at 1, in a printf-style format string.
+    UnnamedArgument(usize),
+    /// A block has no matching `{end}`.
+    UnclosedBlock,
+    /// A block tag appears where it is not allowed, such as a second `{else}`.
+    UnexpectedTag(String),
+    /// The format spec of a `{param_name:spec}` placeholder is not valid.
+    InvalidSpec(String),
+    /// A parameter name given to [`Template::append_placeholder`](crate::Template::append_placeholder)
+    /// is not a name that a placeholder can use.
+    InvalidName(String),
+}
+
+/// A limit of [`Limits`](crate::Limits), with its value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Limit {
+    SourceLength(usize),
+    Placeholders(usize),
+    NameLength(usize),
+    Nesting(usize),
+    Width(usize),
+    Precision(usize),
+}
+
+impl ParseError {
+    pub(crate) fn new(kind: ParseErrorKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+
+    /// The name of the filter the error is about, for unknown filters and
+    /// invalid filter arguments.
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// let error = Template::parse("{n | pluralize:a,b,c}").unwrap_err();
+    /// assert_eq!(Some("pluralize"), error.filter());
+    /// assert_eq!(None, Template::parse("{if a}").unwrap_err().filter());
+    /// ```
+    pub fn filter(&self) -> Option<&str> {
+        match &self.kind {
+            ParseErrorKind::UnknownFilter(name) | ParseErrorKind::InvalidFilterArguments(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The byte range of the template source where the error occurs.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Describes the error with a caret-underlined snippet of the source,
+    /// as described in [`Error::snippet`].
+    pub fn snippet(&self, source: &str) -> String {
+        snippet(source, self.span.clone(), &self.kind)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}..{}", self.kind, self.span.start, self.span.end)
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownFilter(name) => write!(f, "unknown filter `{name}`"),
+            Self::InvalidFilterArguments(name) => write!(f, "invalid arguments for filter `{name}`"),
+            Self::LimitExceeded(Limit::SourceLength(n)) => write!(f, "template is longer than {n} bytes"),
+            Self::LimitExceeded(Limit::Placeholders(n)) => write!(f, "template has more than {n} placeholders"),
+            Self::LimitExceeded(Limit::NameLength(n)) => write!(f, "parameter name is longer than {n} bytes"),
+            Self::LimitExceeded(Limit::Nesting(n)) => write!(f, "blocks are nested more than {n} deep"),
+            Self::LimitExceeded(Limit::Width(n)) => write!(f, "format spec is wider than {n}"),
+            Self::LimitExceeded(Limit::Precision(n)) => write!(f, "format spec has a precision over {n}"),
+            Self::UnmatchedBrace => write!(f, "unmatched curly brace"),
+            Self::UnsupportedFormat(form) => write!(f, "unsupported form `{form}`"),
+            Self::UnnamedArgument(position) => write!(f, "no name for argument {position}"),
+            Self::UnclosedBlock => write!(f, "block is not closed by `{{end}}`"),
+            Self::UnexpectedTag(tag) => write!(f, "unexpected `{{{tag}}}`"),
+            Self::InvalidSpec(spec) => write!(f, "invalid format spec `{spec}`"),
+            Self::InvalidName(name) => write!(f, "invalid parameter name `{name}`"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// An error produced when rendering a [`Template`](crate::Template).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenderError {
+    kind: RenderErrorKind,
+    span: Range<usize>,
+}
+
+/// The kind of a [`RenderError`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RenderErrorKind {
+    /// Rendering exceeded the [`Budget`](crate::Budget).
+    BudgetExceeded,
+    /// A parameter is not present in the arguments.
+    MissingArgument(String),
+    /// The argument of a parameter is not of the type annotating its
+    /// placeholder, as in `{count:int}`.
+    WrongType(String, ArgumentType),
+    /// The argument of a parameter is not a number, but its placeholder
+    /// formats it as one, under [`Numbers::Strict`](crate::Numbers::Strict).
+    NotANumber(String),
+    /// The argument of a parameter contains a path separator or a `..`
+    /// component, which a [`PathTemplate`](crate::PathTemplate) does not
+    /// allow.
+    UnsafePath(String),
+}
+
+impl RenderError {
+    pub(crate) fn new(kind: RenderErrorKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+
+    pub fn kind(&self) -> &RenderErrorKind {
+        &self.kind
+    }
+
+    /// The name of the parameter the error is about, for errors about arguments.
+    pub fn parameter(&self) -> Option<&str> {
+        match &self.kind {
+            RenderErrorKind::MissingArgument(name)
+            | RenderErrorKind::WrongType(name, _)
+            | RenderErrorKind::NotANumber(name)
+            | RenderErrorKind::UnsafePath(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The byte range of the template source where the error occurs.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Describes the error with a caret-underlined snippet of the source,
+    /// as described in [`Error::snippet`].
+    pub fn snippet(&self, source: &str) -> String {
+        snippet(source, self.span.clone(), &self.kind)
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}..{}", self.kind, self.span.start, self.span.end)
+    }
+}
+
+impl fmt::Display for RenderErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BudgetExceeded => write!(f, "render budget exceeded"),
+            Self::MissingArgument(name) => write!(f, "missing argument `{name}`"),
+            Self::WrongType(name, ty) => write!(f, "argument `{name}` is not a `{ty}`"),
+            Self::NotANumber(name) => write!(f, "argument `{name}` is not a number"),
+            Self::UnsafePath(name) => write!(f, "argument `{name}` is not a safe path component"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+/// A [`ParseError`] or [`RenderError`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    Parse(ParseError),
+    Render(RenderError),
+}
+
+impl Error {
+    /// The byte range of the template source where the error occurs.
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Self::Parse(e) => e.span(),
+            Self::Render(e) => e.span(),
+        }
+    }
+
+    /// Describes the error with a caret-underlined snippet of the template
+    /// source, which is the source after the [`FormatOptions`](crate::FormatOptions)
+    /// preprocessing, as written by the `Display` implementation of
+    /// [`Template`](crate::Template).
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// let source = "Hello,\n{name | nope}!";
+    /// let error = Template::parse(source).unwrap_err();
+    /// assert_eq!(
+    ///     "error: unknown filter `nope`\n --> 2:1\n  |\n2 | {name | nope}!\n  | ^^^^^^^^^^^^^\n",
+    ///     error.snippet(source),
+    /// );
+    /// ```
+    pub fn snippet(&self, source: &str) -> String {
+        match self {
+            Self::Parse(e) => e.snippet(source),
+            Self::Render(e) => e.snippet(source),
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Self::Parse(error)
+    }
+}
+
+impl From<RenderError> for Error {
+    fn from(error: RenderError) -> Self {
+        Self::Render(error)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(e) => e.fmt(f),
+            Self::Render(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Parse(e) => Some(e),
+            Self::Render(e) => Some(e),
+        }
+    }
+}
+
+/// Every problem found in a template, as returned by
+/// [`Template::validate`](crate::Template::validate). It is never empty.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorReport {
+    pub(crate) errors: Vec<Error>,
+}
+
+impl ErrorReport {
+    /// The errors, in source order of each kind: parse errors come first.
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    /// Describes every error with a snippet of the source, as
+    /// described in [`Error::snippet`].
+    pub fn snippets(&self, source: &str) -> String {
+        let snippets: Vec<_> = self.errors.iter().map(|e| e.snippet(source)).collect();
+        snippets.join("\n")
+    }
+}
+
+/// Writes one error per line.
+impl fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, error) in self.errors.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            error.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ErrorReport {}
@@ -0,0 +1,1073 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::sync::Arc;
+use crate::{FormatOptions, ParseErrorKind, PluralCategory};
+use crate::case;
+use crate::date::{self, Date};
+use crate::number::Decimal;
+use crate::spec::{Kind, Spec};
+use crate::spellout;
+use crate::zone::Zone;
+
+/// A filter applied to an argument through the `{param_name | filter}` and
+/// `{param_name | filter:"argument",...}` forms.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum Filter {
+    Dedent,
+    Html,
+    /// Escapes for XML element content, or for attribute values if `true`.
+    Xml(bool),
+    SpellOut,
+    Roman,
+    Percent,
+    Trim,
+    TrimStart,
+    TrimEnd,
+    StripNewlines,
+    TitleCase,
+    SentenceCase,
+    Duration,
+    /// The length of the argument in the unit, or the number of items of a list.
+    Len(Unit),
+    /// The suffixes for a count of one and for other counts.
+    Pluralize { singular: String, plural: String },
+    /// The separator of the items of a list, and the separator of its last two items.
+    Join { separator: String, last: Option<String> },
+    /// A list in the language of the locale, as a disjunction if `true`.
+    List(bool),
+    /// The delimiter of the fields of a CSV row.
+    Csv(char),
+    /// Base64 encoding of the UTF-8 bytes, with the URL-safe alphabet and
+    /// without padding if `true`.
+    #[cfg(feature = "encoding")]
+    Base64(bool),
+    #[cfg(feature = "encoding")]
+    Hex,
+    /// A digest in hexadecimal, truncated to a number of digits.
+    #[cfg(feature = "digest")]
+    Digest(Digest, Option<usize>),
+    /// The range of items or characters kept, with negative bounds counted from the end.
+    Slice { start: Option<i64>, end: Option<i64> },
+    /// An arithmetic operation with a number, as in `add:1`.
+    Arithmetic(Operator, Decimal),
+    /// The format of dates read by `dateparse`.
+    DateParse(String),
+    /// The format of dates written by `date`.
+    Date(String),
+    /// The time zone to which `tz` converts dates and times in UTC.
+    TimeZone(Zone),
+    /// The text replacing a missing or empty value.
+    Default(String),
+    /// A substring and its replacement.
+    Replace { from: String, to: String },
+    /// A regular expression and its replacement, which may refer to the
+    /// groups of the match as in `$1`.
+    #[cfg(feature = "regex")]
+    ReplaceRegex(Regex, String),
+    /// A filter registered through [`FormatOptions::filter`], and its arguments.
+    Custom(Custom, Vec<String>),
+}
+
+/// A digest algorithm of the `sha256` and `md5` filters.
+#[cfg(feature = "digest")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum Digest {
+    Sha256,
+    Md5,
+}
+
+/// A unit of length of the `len` filter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum Unit {
+    Bytes,
+    Chars,
+    Graphemes,
+}
+
+impl Unit {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Bytes => "bytes",
+            Self::Chars => "chars",
+            Self::Graphemes => "graphemes",
+        }
+    }
+}
+
+/// A compiled regular expression, compared by its pattern.
+#[cfg(feature = "regex")]
+#[derive(Clone, Debug)]
+pub(crate) struct Regex(regex::Regex);
+
+#[cfg(feature = "regex")]
+impl PartialEq for Regex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+#[cfg(feature = "regex")]
+impl Eq for Regex {}
+
+#[cfg(feature = "regex")]
+impl std::hash::Hash for Regex {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.as_str().hash(state);
+    }
+}
+
+#[cfg(feature = "regex")]
+impl PartialOrd for Regex {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(feature = "regex")]
+impl Ord for Regex {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.as_str().cmp(other.0.as_str())
+    }
+}
+
+/// A filter registered through [`FormatOptions::filter`], compared by its name.
+#[derive(Clone)]
+pub(crate) struct Custom(String, CustomFilter);
+
+type CustomFilter = Arc<dyn Fn(&str, &[String]) -> String + Send + Sync>;
+
+impl fmt::Debug for Custom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Custom").field(&self.0).finish()
+    }
+}
+
+impl PartialEq for Custom {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for Custom {}
+
+impl std::hash::Hash for Custom {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl PartialOrd for Custom {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Custom {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+/// The filters registered through [`FormatOptions::filter`], by name.
+#[derive(Clone, Default)]
+pub(crate) struct CustomFilters(HashMap<String, CustomFilter>);
+
+impl CustomFilters {
+    pub fn insert(&mut self, name: String, filter: CustomFilter) {
+        self.0.insert(name, filter);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Custom> {
+        let (name, filter) = self.0.get_key_value(name)?;
+        Some(Custom(name.clone(), filter.clone()))
+    }
+}
+
+impl fmt::Debug for CustomFilters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<_> = self.0.keys().collect();
+        names.sort();
+        f.debug_set().entries(names).finish()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// The fraction digits of the quotients of the `div` filter.
+const DIVISION_PRECISION: usize = 10;
+
+/// The result of a filter applied to the items of a list.
+pub(crate) enum Applied<'v> {
+    Items(Vec<Cow<'v, str>>),
+    Text(String),
+}
+
+impl Filter {
+    /// Resolves a filter without arguments by name, returning `None` if the
+    /// filter is unknown or requires arguments.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::new(name, vec![]).ok()
+    }
+
+    /// Resolves a filter by name, with the arguments following its colon.
+    pub fn new(name: &str, arguments: Vec<String>) -> Result<Self, ParseErrorKind> {
+        let invalid = || Err(ParseErrorKind::InvalidFilterArguments(name.to_owned()));
+        let filter = match name {
+            "dedent" => Self::Dedent,
+            "html" => Self::Html,
+            "xml" => Self::Xml(false),
+            "xml_attr" => Self::Xml(true),
+            "spellout" => Self::SpellOut,
+            "roman" => Self::Roman,
+            "percent" => Self::Percent,
+            "trim" => Self::Trim,
+            "trim_start" => Self::TrimStart,
+            "trim_end" => Self::TrimEnd,
+            "strip_newlines" => Self::StripNewlines,
+            "title_case" => Self::TitleCase,
+            "sentence_case" => Self::SentenceCase,
+            "duration" => Self::Duration,
+            "len" => {
+                let unit = match arguments.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+                    [] | ["chars"] => Unit::Chars,
+                    ["bytes"] => Unit::Bytes,
+                    ["graphemes"] => Unit::Graphemes,
+                    _ => return invalid(),
+                };
+                return Ok(Self::Len(unit));
+            },
+            "pluralize" => {
+                let mut arguments = arguments.into_iter();
+                let (singular, plural) = match (arguments.next(), arguments.next(), arguments.next()) {
+                    (None, _, _) => (String::new(), "s".to_owned()),
+                    (Some(plural), None, _) => (String::new(), plural),
+                    (Some(singular), Some(plural), None) => (singular, plural),
+                    _ => return invalid(),
+                };
+                return Ok(Self::Pluralize { singular, plural });
+            },
+            "join" => {
+                let mut arguments = arguments.into_iter();
+                let (separator, last) = match (arguments.next(), arguments.next(), arguments.next()) {
+                    (separator, last, None) => (separator.unwrap_or_else(|| ", ".to_owned()), last),
+                    _ => return invalid(),
+                };
+                return Ok(Self::Join { separator, last });
+            },
+            "list" => {
+                return match arguments.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+                    [] | ["and"] => Ok(Self::List(false)),
+                    ["or"] => Ok(Self::List(true)),
+                    _ => invalid(),
+                };
+            },
+            "csv" => {
+                let delimiter = match &arguments[..] {
+                    [] => ',',
+                    [delimiter] => {
+                        let mut chars = delimiter.chars();
+                        match (chars.next(), chars.next()) {
+                            (Some(ch), None) if !matches!(ch, '"' | '\r' | '\n') => ch,
+                            _ => return invalid(),
+                        }
+                    },
+                    _ => return invalid(),
+                };
+                return Ok(Self::Csv(delimiter));
+            },
+            #[cfg(feature = "encoding")]
+            "base64" => {
+                return match arguments.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+                    [] => Ok(Self::Base64(false)),
+                    ["url"] => Ok(Self::Base64(true)),
+                    _ => invalid(),
+                };
+            },
+            #[cfg(feature = "encoding")]
+            "hex" => Self::Hex,
+            #[cfg(feature = "digest")]
+            "sha256" | "md5" => {
+                let digest = if name == "sha256" { Digest::Sha256 } else { Digest::Md5 };
+                return match &arguments[..] {
+                    [] => Ok(Self::Digest(digest, None)),
+                    [len] => match len.trim().parse::<usize>() {
+                        Ok(len) if len > 0 => Ok(Self::Digest(digest, Some(len))),
+                        _ => invalid(),
+                    },
+                    _ => invalid(),
+                };
+            },
+            "slice" => {
+                // `slice:1:3` is a single argument, and `slice:1,3` two.
+                let bound = |s: &str| if s.is_empty() { Ok(None) } else { s.trim().parse::<i64>().map(Some) };
+                let (start, end) = match &arguments[..] {
+                    [range] => match range.split_once(':') {
+                        Some((start, end)) => (bound(start), bound(end)),
+                        None => (Ok(None), bound(range)),
+                    },
+                    [start, end] => (bound(start), bound(end)),
+                    _ => return invalid(),
+                };
+                let (Ok(start), Ok(end)) = (start, end) else {
+                    return invalid();
+                };
+                return Ok(Self::Slice { start, end });
+            },
+            "add" | "sub" | "mul" | "div" => {
+                let operator = match name {
+                    "add" => Operator::Add,
+                    "sub" => Operator::Sub,
+                    "mul" => Operator::Mul,
+                    _ => Operator::Div,
+                };
+                let [operand] = &arguments[..] else {
+                    return invalid();
+                };
+                let Some(operand) = Decimal::parse(operand) else {
+                    return invalid();
+                };
+                return Ok(Self::Arithmetic(operator, operand));
+            },
+            "dateparse" | "date" => {
+                let [format] = &arguments[..] else {
+                    return invalid();
+                };
+                if !date::is_valid_format(format, name == "dateparse") {
+                    return invalid();
+                }
+                return Ok(if name == "date" { Self::Date(format.clone()) } else { Self::DateParse(format.clone()) });
+            },
+            "tz" => {
+                let [name] = &arguments[..] else {
+                    return invalid();
+                };
+                let Some(zone) = Zone::new(name) else {
+                    return invalid();
+                };
+                return Ok(Self::TimeZone(zone));
+            },
+            "default" => {
+                let [text] = &arguments[..] else {
+                    return invalid();
+                };
+                return Ok(Self::Default(text.clone()));
+            },
+            "replace" => {
+                return match &arguments[..] {
+                    [from, to] if !from.is_empty() => Ok(Self::Replace { from: from.clone(), to: to.clone() }),
+                    #[cfg(feature = "regex")]
+                    [expression] => {
+                        let Some((pattern, to)) = regex_replacement(expression) else {
+                            return invalid();
+                        };
+                        match regex::Regex::new(&pattern) {
+                            Ok(regex) => Ok(Self::ReplaceRegex(Regex(regex), to)),
+                            Err(_) => invalid(),
+                        }
+                    },
+                    _ => invalid(),
+                };
+            },
+            _ => return Err(ParseErrorKind::UnknownFilter(name.to_owned())),
+        };
+        if !arguments.is_empty() {
+            return invalid();
+        }
+        Ok(filter)
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Dedent => "dedent",
+            Self::Html => "html",
+            Self::Xml(false) => "xml",
+            Self::Xml(true) => "xml_attr",
+            Self::SpellOut => "spellout",
+            Self::Roman => "roman",
+            Self::Percent => "percent",
+            Self::Trim => "trim",
+            Self::TrimStart => "trim_start",
+            Self::TrimEnd => "trim_end",
+            Self::StripNewlines => "strip_newlines",
+            Self::TitleCase => "title_case",
+            Self::SentenceCase => "sentence_case",
+            Self::Duration => "duration",
+            Self::Len(_) => "len",
+            Self::Pluralize { .. } => "pluralize",
+            Self::Join { .. } => "join",
+            Self::List(_) => "list",
+            Self::Csv(_) => "csv",
+            #[cfg(feature = "encoding")]
+            Self::Base64(_) => "base64",
+            #[cfg(feature = "encoding")]
+            Self::Hex => "hex",
+            #[cfg(feature = "digest")]
+            Self::Digest(Digest::Sha256, _) => "sha256",
+            #[cfg(feature = "digest")]
+            Self::Digest(Digest::Md5, _) => "md5",
+            Self::Slice { .. } => "slice",
+            Self::Arithmetic(Operator::Add, _) => "add",
+            Self::Arithmetic(Operator::Sub, _) => "sub",
+            Self::Arithmetic(Operator::Mul, _) => "mul",
+            Self::Arithmetic(Operator::Div, _) => "div",
+            Self::DateParse(_) => "dateparse",
+            Self::Date(_) => "date",
+            Self::TimeZone(_) => "tz",
+            Self::Default(_) => "default",
+            Self::Replace { .. } => "replace",
+            #[cfg(feature = "regex")]
+            Self::ReplaceRegex(..) => "replace",
+            Self::Custom(custom, _) => &custom.0,
+        }
+    }
+
+    /// The arguments of the filter, as written after its colon.
+    pub(crate) fn arguments(&self) -> Vec<String> {
+        match self {
+            Self::Pluralize { singular, plural } if singular.is_empty() && plural == "s" => vec![],
+            Self::Pluralize { singular, plural } if singular.is_empty() => vec![plural.clone()],
+            Self::Pluralize { singular, plural } => vec![singular.clone(), plural.clone()],
+            Self::Join { separator, last: None } if separator == ", " => vec![],
+            Self::Join { separator, last } => std::iter::once(separator).chain(last).cloned().collect(),
+            Self::Slice { start, end } => {
+                let bound = |b: &Option<i64>| b.map_or(String::new(), |b| b.to_string());
+                vec![format!("{}:{}", bound(start), bound(end))]
+            },
+            Self::List(false) => vec![],
+            Self::List(true) => vec!["or".to_owned()],
+            Self::Csv(',') => vec![],
+            Self::Csv(delimiter) => vec![delimiter.to_string()],
+            #[cfg(feature = "encoding")]
+            Self::Base64(true) => vec!["url".to_owned()],
+            #[cfg(feature = "digest")]
+            Self::Digest(_, Some(len)) => vec![len.to_string()],
+            Self::Arithmetic(_, operand) => vec![operand.to_string()],
+            Self::DateParse(format) | Self::Date(format) => vec![format.clone()],
+            Self::TimeZone(zone) => vec![zone.name().to_owned()],
+            Self::Default(text) => vec![text.clone()],
+            Self::Len(Unit::Chars) => vec![],
+            Self::Len(unit) => vec![unit.name().to_owned()],
+            Self::Replace { from, to } => vec![from.clone(), to.clone()],
+            #[cfg(feature = "regex")]
+            Self::ReplaceRegex(regex, to) => vec![format!("/{}/{}/", regex.0.as_str().replace('/', "\\/"), to.replace('/', "\\/"))],
+            Self::Custom(_, arguments) => arguments.clone(),
+            _ => vec![],
+        }
+    }
+
+    /// Whether the filter replaces parts of the argument, so that its output
+    /// may be much longer than the argument.
+    pub fn is_replacement(&self) -> bool {
+        match self {
+            Self::Replace { .. } => true,
+            #[cfg(feature = "regex")]
+            Self::ReplaceRegex(..) => true,
+            _ => false,
+        }
+    }
+
+    /// The length of the output of a replacement, or a bound of it, computed
+    /// without building it.
+    pub fn replaced_len(&self, value: &str) -> usize {
+        match self {
+            Self::Replace { from, to } => {
+                let count = value.matches(from.as_str()).count();
+                (value.len() - count * from.len()).saturating_add(count.saturating_mul(to.len()))
+            },
+            #[cfg(feature = "regex")]
+            Self::ReplaceRegex(regex, to) => {
+                // each group of the replacement expands to at most the match.
+                let groups = to.matches('$').count();
+                regex.0.find_iter(value).fold(value.len(), |len, m| len.saturating_add(groups.saturating_mul(m.len()).saturating_add(to.len())))
+            },
+            _ => value.len(),
+        }
+    }
+
+    /// Whether the filter reads its value as a number.
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Self::SpellOut | Self::Roman | Self::Percent | Self::Duration | Self::Arithmetic(..))
+    }
+
+    pub fn apply(&self, value: &str, options: &FormatOptions) -> String {
+        match self {
+            Self::Dedent => dedent(value),
+            Self::Html => escape_html(value),
+            Self::Xml(attribute) => escape_xml(value, *attribute),
+            Self::SpellOut => spell_out(value, options),
+            Self::Roman => roman(value),
+            Self::Percent => Spec { kind: Kind::Percent, ..Spec::default() }.apply(value, options),
+            Self::Trim => value.trim().to_owned(),
+            Self::TrimStart => value.trim_start().to_owned(),
+            Self::TrimEnd => value.trim_end().to_owned(),
+            Self::StripNewlines => value.replace(['\r', '\n'], ""),
+            Self::TitleCase => case::capitalize(value, options.get_locale(), false),
+            Self::SentenceCase => case::capitalize(value, options.get_locale(), true),
+            Self::Duration => duration(value),
+            Self::Len(Unit::Bytes) => value.len().to_string(),
+            Self::Len(Unit::Chars) => value.chars().count().to_string(),
+            Self::Len(Unit::Graphemes) => graphemes(value).to_string(),
+            Self::Pluralize { singular, plural } => {
+                let one = PluralCategory::select("en", value) == PluralCategory::One;
+                if one { singular } else { plural }.clone()
+            },
+            // a value that is not a list is a list of one item.
+            Self::Join { .. } | Self::List(_) => value.to_owned(),
+            Self::Csv(delimiter) => csv_field(value, *delimiter),
+            #[cfg(feature = "encoding")]
+            Self::Base64(url) => base64(value.as_bytes(), *url),
+            #[cfg(feature = "encoding")]
+            Self::Hex => value.bytes().map(|b| format!("{b:02x}")).collect(),
+            #[cfg(feature = "digest")]
+            Self::Digest(digest, len) => {
+                let bytes = match digest {
+                    Digest::Sha256 => crate::digest::sha256(value.as_bytes()).to_vec(),
+                    Digest::Md5 => crate::digest::md5(value.as_bytes()).to_vec(),
+                };
+                let mut r: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+                r.truncate(len.unwrap_or(r.len()));
+                r
+            },
+            Self::Slice { start, end } => {
+                let chars: Vec<char> = value.chars().collect();
+                chars[slice(chars.len(), *start, *end)].iter().collect()
+            },
+            Self::Arithmetic(operator, operand) => {
+                let Some(n) = Decimal::parse(value) else {
+                    return value.to_owned();
+                };
+                let result = match operator {
+                    Operator::Add => Some(n.add(operand)),
+                    Operator::Sub => Some(n.sub(operand)),
+                    Operator::Mul => Some(n.mul(operand)),
+                    Operator::Div => n.div(operand, DIVISION_PRECISION, options.rounding),
+                };
+                // a division by zero keeps the value as is.
+                result.map_or_else(|| value.to_owned(), |n| n.to_string())
+            },
+            // values that are not dates are kept as is.
+            Self::DateParse(format) => Date::parse(value.trim(), format).map_or_else(|| value.to_owned(), |d| d.to_string()),
+            Self::Date(format) => Date::iso(value).map_or_else(|| value.to_owned(), |d| d.format(format)),
+            Self::TimeZone(zone) => Date::iso(value).and_then(|d| zone.local(&d)).map_or_else(|| value.to_owned(), |d| d.to_string()),
+            Self::Default(text) if value.is_empty() => text.clone(),
+            Self::Default(_) => value.to_owned(),
+            Self::Replace { from, to } => value.replace(from.as_str(), to),
+            #[cfg(feature = "regex")]
+            Self::ReplaceRegex(regex, to) => regex.0.replace_all(value, to.as_str()).into_owned(),
+            Self::Custom(custom, arguments) => (custom.1)(value, arguments),
+        }
+    }
+
+    /// Applies the filter to the items of a list argument. The filters other
+    /// than `join`, `list`, `csv`, `slice`, `len` and `default` receive the items
+    /// separated by `, `.
+    pub fn apply_items<'v>(&self, items: Vec<Cow<'v, str>>, options: &FormatOptions) -> Applied<'v> {
+        match self {
+            Self::Join { separator, last } => {
+                let mut r = String::new();
+                for (i, item) in items.iter().enumerate() {
+                    if i + 1 == items.len() && i != 0 {
+                        r.push_str(last.as_ref().unwrap_or(separator));
+                    } else if i != 0 {
+                        r.push_str(separator);
+                    }
+                    r.push_str(item);
+                }
+                Applied::Text(r)
+            },
+            Self::List(or) => {
+                let (separator, two, last) = list_separators(options.get_locale(), *or);
+                let mut r = String::new();
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        r.push_str(match items.len() - i {
+                            1 if items.len() == 2 => two,
+                            1 => last,
+                            _ => separator,
+                        });
+                    }
+                    r.push_str(item);
+                }
+                Applied::Text(r)
+            },
+            Self::Slice { start, end } => {
+                let range = slice(items.len(), *start, *end);
+                Applied::Items(items.into_iter().skip(range.start).take(range.len()).collect())
+            },
+            Self::Len(_) => Applied::Text(items.len().to_string()),
+            Self::Csv(delimiter) => {
+                let fields: Vec<String> = items.iter().map(|item| csv_field(item, *delimiter)).collect();
+                Applied::Text(fields.join(&delimiter.to_string()))
+            },
+            Self::Default(text) if items.is_empty() => Applied::Text(text.clone()),
+            Self::Default(_) => Applied::Items(items),
+            _ => Applied::Text(self.apply(&items.join(", "), options)),
+        }
+    }
+}
+
+/// Splits the `/pattern/replacement/` argument of the `replace` filter,
+/// where `\/` is a slash.
+#[cfg(feature = "regex")]
+fn regex_replacement(expression: &str) -> Option<(String, String)> {
+    let mut parts = vec![String::new()];
+    let mut chars = expression.strip_prefix('/')?.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'/') => parts.last_mut()?.push(chars.next()?),
+            '/' => parts.push(String::new()),
+            c => parts.last_mut()?.push(c),
+        }
+    }
+    match <[String; 3]>::try_from(parts) {
+        Ok([pattern, to, end]) if !pattern.is_empty() && end.is_empty() => Some((pattern, to)),
+        _ => None,
+    }
+}
+
+/// The range of a slice of a sequence of length `len`, with negative
+/// bounds counted from the end, as in Python.
+fn slice(len: usize, start: Option<i64>, end: Option<i64>) -> std::ops::Range<usize> {
+    let bound = |b: i64| if b < 0 { len.saturating_sub(b.unsigned_abs() as usize) } else { (b as usize).min(len) };
+    let start = start.map_or(0, bound);
+    let end = end.map_or(len, bound);
+    start..end.max(start)
+}
+
+/// Writes the filter as in a template, such as `pluralize:"y","ies"`.
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())?;
+        for (i, argument) in self.arguments().into_iter().enumerate() {
+            let separator = if i == 0 { ':' } else { ',' };
+            write!(f, "{separator}{}", crate::minify::quote(&argument))?;
+        }
+        Ok(())
+    }
+}
+
+/// Spells out an integer with the speller of the options, keeping other
+/// values as is.
+fn spell_out(value: &str, options: &FormatOptions) -> String {
+    let Ok(number) = value.trim().parse::<i64>() else {
+        return value.to_owned();
+    };
+    options.speller.as_ref()
+        .and_then(|speller| speller.0.spell_out(options.get_locale(), number))
+        .unwrap_or_else(|| spellout::english(number))
+}
+
+/// Writes a number of seconds as days, hours, minutes and seconds, as in
+/// `1h 2m 5.5s`, keeping other values as is.
+fn duration(value: &str) -> String {
+    let Some(n) = Decimal::parse(value) else {
+        return value.to_owned();
+    };
+    let fixed = n.fixed(0);
+    let (integer, fraction) = fixed.split_once('.').unwrap_or((&fixed, ""));
+    let Ok(seconds) = integer.parse::<u64>() else {
+        return value.to_owned();
+    };
+    let fraction = fraction.trim_end_matches('0');
+    let mut r = String::new();
+    if n.negative && !n.is_zero() {
+        r.push('-');
+    }
+    let units = [(seconds / 86400, "d"), (seconds / 3600 % 24, "h"), (seconds / 60 % 60, "m")];
+    for (count, unit) in units.into_iter().filter(|(count, _)| *count != 0) {
+        write!(r, "{count}{unit} ").unwrap();
+    }
+    if seconds % 60 != 0 || !fraction.is_empty() || r.trim_start_matches('-').is_empty() {
+        write!(r, "{}", seconds % 60).unwrap();
+        if !fraction.is_empty() {
+            write!(r, ".{fraction}").unwrap();
+        }
+        r.push_str("s ");
+    }
+    r.pop();
+    r
+}
+
+/// Counts the user-perceived characters of a text, approximating the
+/// extended grapheme clusters of Unicode: combining marks, variation
+/// selectors, emoji modifiers and the characters joined by a zero-width
+/// joiner belong to the preceding character, as do the second regional
+/// indicator of a flag and the line feed of a `\r\n`.
+fn graphemes(text: &str) -> usize {
+    let mut count = 0;
+    let mut previous: Option<char> = None;
+    // whether the previous character is the first regional indicator of a flag.
+    let mut flag = false;
+    for ch in text.chars() {
+        let extends = matches!(ch as u32,
+            0x0300..=0x036f | 0x0483..=0x0489 | 0x0591..=0x05bd | 0x0610..=0x061a | 0x064b..=0x065f
+            | 0x0900..=0x0903 | 0x093a..=0x094f | 0x1ab0..=0x1aff | 0x1dc0..=0x1dff | 0x200c..=0x200d
+            | 0x20d0..=0x20ff | 0xfe00..=0xfe0f | 0xfe20..=0xfe2f | 0x1f3fb..=0x1f3ff | 0xe0020..=0xe007f | 0xe0100..=0xe01ef
+        );
+        let regional = ('\u{1f1e6}'..='\u{1f1ff}').contains(&ch);
+        let joined = previous == Some('\u{200d}') || (previous == Some('\r') && ch == '\n');
+        if previous.is_none() || !(extends || joined || (regional && flag)) {
+            count += 1;
+        }
+        flag = regional && !flag;
+        previous = Some(ch);
+    }
+    count
+}
+
+/// Writes an integer from 1 to 3999 in Roman numerals, keeping other values as is.
+fn roman(value: &str) -> String {
+    const NUMERALS: [(u16, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let Some(mut n) = value.trim().parse::<u16>().ok().filter(|n| (1..=3999).contains(n)) else {
+        return value.to_owned();
+    };
+    let mut r = String::new();
+    for (amount, numeral) in NUMERALS {
+        while n >= amount {
+            r.push_str(numeral);
+            n -= amount;
+        }
+    }
+    r
+}
+
+/// Escapes the characters that are significant in HTML text and attributes.
+fn escape_html(s: &str) -> String {
+    let mut r = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '&' => r.push_str("&amp;"),
+            '<' => r.push_str("&lt;"),
+            '>' => r.push_str("&gt;"),
+            '"' => r.push_str("&quot;"),
+            '\'' => r.push_str("&#39;"),
+            _ => r.push(ch),
+        }
+    }
+    r
+}
+
+/// Escapes the characters that are significant in XML element content
+/// or, if `attribute` is true, in attribute values quoted with `"` or `'`,
+/// where tabs and line breaks are also escaped so that they are not
+/// normalized into spaces. Control characters that XML 1.0 does not allow
+/// are removed.
+fn escape_xml(s: &str, attribute: bool) -> String {
+    let mut r = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '&' => r.push_str("&amp;"),
+            '<' => r.push_str("&lt;"),
+            '>' => r.push_str("&gt;"),
+            '"' if attribute => r.push_str("&quot;"),
+            '\'' if attribute => r.push_str("&apos;"),
+            '\t' if attribute => r.push_str("&#9;"),
+            '\n' if attribute => r.push_str("&#10;"),
+            '\r' => r.push_str("&#13;"),
+            '\t' | '\n' => r.push(ch),
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => {},
+            _ => r.push(ch),
+        }
+    }
+    r
+}
+
+/// The separators of the items of a list in the language of a locale: between
+/// items, between the items of a list of two, and before the last item of a
+/// longer list. Languages without patterns are written as in English.
+fn list_separators(locale: &str, or: bool) -> (&'static str, &'static str, &'static str) {
+    let locale = locale.to_ascii_lowercase();
+    let word = |and, or_word| if or { or_word } else { and };
+    let (two, last) = match locale.split(['-', '_']).next().unwrap_or_default() {
+        "ja" => return if or { ("、", "または", "、または") } else { ("、", "、", "、") },
+        "zh" => return if or { ("、", "或", "或") } else { ("、", "和", "和") },
+        "fr" => word((" et ", " et "), (" ou ", " ou ")),
+        "de" => word((" und ", " und "), (" oder ", " oder ")),
+        "es" => word((" y ", " y "), (" o ", " o ")),
+        "pt" => word((" e ", " e "), (" ou ", " ou ")),
+        "it" => word((" e ", " e "), (" o ", " o ")),
+        "nl" => word((" en ", " en "), (" of ", " of ")),
+        "sv" => word((" och ", " och "), (" eller ", " eller ")),
+        "da" | "nb" | "no" | "nn" => word((" og ", " og "), (" eller ", " eller ")),
+        "pl" => word((" i ", " i "), (" lub ", " lub ")),
+        "ru" => word((" и ", " и "), (" или ", " или ")),
+        _ if locale == "en-gb" => word((" and ", " and "), (" or ", " or ")),
+        _ => word((" and ", ", and "), (" or ", ", or ")),
+    };
+    (", ", two, last)
+}
+
+/// Quotes a CSV field as described by RFC 4180 if it contains the delimiter,
+/// a double quote or a line break, doubling its double quotes.
+fn csv_field(s: &str, delimiter: char) -> String {
+    if !s.contains([delimiter, '"', '\r', '\n']) {
+        return s.to_owned();
+    }
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+/// Encodes bytes in base64, with the standard alphabet and padding, or
+/// with the URL-safe alphabet of RFC 4648 and no padding.
+#[cfg(feature = "encoding")]
+fn base64(bytes: &[u8], url: bool) -> String {
+    let alphabet: &[u8; 64] = if url {
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_"
+    } else {
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/"
+    };
+    let mut r = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            r.push(alphabet[(n >> (18 - 6 * i) & 63) as usize] as char);
+        }
+        if !url {
+            r.push_str(&"=="[chunk.len() - 1..]);
+        }
+    }
+    r
+}
+
+/// Strips the common leading indentation from every line of a string.
+///
+/// Line endings are normalized to `\n`, a leading line break is removed,
+/// and whitespace-only lines become empty. This allows writing templates
+/// as indented raw strings:
+///
+/// ```
+/// assert_eq!("a\n  b\n", late_format::dedent("\n    a\n      b\n    "));
+/// ```
+pub fn dedent(s: &str) -> String {
+    let s = s.replace("\r\n", "\n");
+    let s = s.strip_prefix('\n').unwrap_or(&s);
+    // the indentation common to every line, so that a tab and a space never match.
+    let indent = s.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| &line[..line.len() - line.trim_start_matches([' ', '\t']).len()])
+        .reduce(|common, indent| &common[..common.bytes().zip(indent.bytes()).take_while(|(a, b)| a == b).count()])
+        .unwrap_or("");
+    let mut r = String::with_capacity(s.len());
+    for (i, line) in s.split('\n').enumerate() {
+        if i != 0 {
+            r.push('\n');
+        }
+        if !line.trim().is_empty() {
+            r.push_str(&line[indent.len()..]);
+        }
+    }
+    r
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FormatOptions, LateFormat, ParseErrorKind, Template, Value};
+    use crate::template::Segment;
+    use maplit::hashmap;
+
+    #[test]
+    fn numbers() {
+        let options = FormatOptions::new();
+        let render = |n: &str| "{n | roman}".late_format_with(hashmap!{"n".into() => n.into()}, &options);
+        assert_eq!(["MCMXCIV", "IV", "MMMCMXCIX", "0", "4000", "x"], [render("1994"), render("4"), render("3999"), render("0"), render("4000"), render("x")]);
+        let arguments = hashmap!{"n".into() => "-3".into()};
+        assert_eq!("minus three", "{n | spellout}".late_format_with(arguments, &options));
+    }
+
+    #[test]
+    fn xml() {
+        let arguments = hashmap!{"s".into() => "<a href=\"x\">Tom & 'Jerry'</a>\r\n\t\u{1}".into()};
+        let source = "<p title=\"{s | xml_attr}\">{s | xml}</p>";
+        assert_eq!(
+            "<p title=\"&lt;a href=&quot;x&quot;&gt;Tom &amp; &apos;Jerry&apos;&lt;/a&gt;&#13;&#10;&#9;\">&lt;a href=\"x\"&gt;Tom &amp; 'Jerry'&lt;/a&gt;&#13;\n\t</p>",
+            source.late_format(arguments),
+        );
+    }
+
+    #[test]
+    fn csv() {
+        let arguments = hashmap!{"a".into() => Value::from("x"), "b".into() => Value::from("say \"hi\", twice"), "row".into() => Value::from(vec!["1;2", "a\nb", "c"])};
+        let template = Template::parse("{a | csv},{b | csv}\n{row | csv:\";\"}\n{row | csv}").unwrap();
+        assert_eq!("x,\"say \"\"hi\"\", twice\"\n\"1;2\";\"a\nb\";c\n1;2,\"a\nb\",c", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+        assert_eq!(ParseErrorKind::InvalidFilterArguments("csv".into()), *Template::parse("{a | csv:'ab'}").unwrap_err().kind());
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn encoding() {
+        let render = |source: &str, s: &str| source.late_format(hashmap!{"s".into() => s.into()});
+        assert_eq!(["", "Zg==", "Zm8=", "Zm9v", "Zm9vYg=="], ["", "f", "fo", "foo", "foob"].map(|s| render("{s | base64}", s)));
+        assert_eq!("PDw_Pz4-w6k", render("{s | base64:url}", "<<??>>é"));
+        assert_eq!("3c3f3ec3a9", render("{s | hex}", "<?>é"));
+        assert!(Template::parse("{s | base64:x}").is_err() && Template::parse("{s | hex:upper}").is_err());
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn digests() {
+        let arguments = hashmap!{"s".into() => "abc".into()};
+        assert_eq!("ba7816bf 900150983cd24fb0d6963f7d28e17f72", "{s | sha256:8} {s | md5}".late_format(arguments));
+        assert!(Template::parse("{s | md5:0}").is_err() && Template::parse("{s | sha256:x}").unwrap_err().kind() == &ParseErrorKind::InvalidFilterArguments("sha256".into()));
+    }
+
+    #[test]
+    fn list() {
+        let arguments = hashmap!{"names".into() => Value::from(vec!["A", "B", "C"]), "two".into() => Value::from(vec!["A", "B"]), "one".into() => Value::from("A")};
+        let template = Template::parse("{names | list}; {two | list}; {one | list}; {names | list:or}").unwrap();
+        let render = |locale: &str| template.render_arguments(&arguments, &FormatOptions::new().locale(locale)).unwrap();
+        assert_eq!("A, B, and C; A and B; A; A, B, or C", render("en"));
+        assert_eq!("A, B et C; A et B; A; A, B ou C", render("fr-CA"));
+        assert_eq!("A、B和C; A和B; A; A、B或C", render("zh"));
+        assert!(Template::parse("{names | list:nor}").is_err());
+    }
+
+    #[test]
+    fn case() {
+        let arguments = hashmap!{"s".into() => "iSTANBUL AND izmir. see you".into()};
+        let template = Template::parse("{s | title_case}|{s | sentence_case}").unwrap();
+        assert_eq!("Istanbul And Izmir. See You|Istanbul and izmir. See you", template.render(&arguments));
+        let options = FormatOptions::new().locale("tr-TR");
+        assert_eq!("İstanbul And İzmir. See You|İstanbul and izmir. See you", template.render_with(&arguments, &options).unwrap());
+    }
+
+    #[test]
+    fn pluralize() {
+        let source = "{n} item{n|pluralize} {n} cherr{n | pluralize:\"y\",'ies'} {n} box{n|pluralize:es}";
+        assert_eq!("1 item 1 cherry 1 box", source.late_format(hashmap!{"n".into() => "1".into()}));
+        assert_eq!("2 items 2 cherries 2 boxes", source.late_format(hashmap!{"n".into() => "2".into()}));
+        assert_eq!("0 items", "{n} item{n|pluralize}".late_format(hashmap!{"n".into() => "0".into()}));
+        let error = Template::parse("{n | pluralize:a,b,c}").unwrap_err();
+        assert_eq!(&ParseErrorKind::InvalidFilterArguments("pluralize".into()), error.kind());
+        assert!(Template::parse("{n | html:a}").is_err());
+        assert_eq!("{n | pluralize:}", "{n | pluralize:}".late_format(hashmap!{"n".into() => "2".into()}));
+        let template = Template::parse("{n|pluralize:\"y\", \"ies\"}{n|pluralize:'s'}").unwrap();
+        assert_eq!(template, Template::parse("{n | pluralize:y,ies}{n | pluralize}").unwrap());
+        assert!(template.dump_ast().contains("filters=[pluralize:\"y\",\"ies\"]"));
+    }
+
+    #[test]
+    fn slice() {
+        let arguments = hashmap!{"l".into() => Value::from(vec!["a", "b", "c", "d"]), "s".into() => Value::from("héllo")};
+        let template = Template::parse("{l | slice:\":2\"} {l|slice:1:-1|join:\"+\"} {l|slice:-1,4} {l | slice:3:1} {s|slice:1:3} {s | slice:-2:} {l|slice:2|html}").unwrap();
+        assert_eq!("a, b b+c d  él lo a, b", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+        assert!(Template::parse("{l | slice:x}").is_err() && Template::parse("{l | slice:1,2,3}").is_err());
+        assert!(template.dump_ast().contains("filters=[slice:\"1:-1\", join:\"+\"]"));
+    }
+
+    #[test]
+    fn arithmetic() {
+        let arguments = hashmap!{"cents".into() => "1999".into(), "i".into() => "0".into(), "x".into() => "n/a".into()};
+        let source = "{cents | div:100} {i | add:1} {i|sub:1.5} {cents | mul:-0.5} {cents | div:0} {x | add:1} {i | add:1 | spellout}";
+        assert_eq!("19.99 1 -1.5 -999.5 1999 n/a one", source.late_format(arguments));
+        let template = Template::parse("{i|sub:1.5}").unwrap();
+        assert!(Template::parse("{i | add}").is_err() && Template::parse("{i | add:one}").is_err() && Template::parse("{i | div:1,2}").is_err());
+        assert!(template.dump_ast().contains("filters=[sub:\"1.5\"]"));
+    }
+
+    #[test]
+    fn dates() {
+        let arguments = hashmap!{"d".into() => "05/03/2024".into(), "iso".into() => "2024-03-05T09:30:00Z".into(), "x".into() => "soon".into()};
+        let source = "{d | dateparse:\"%d/%m/%Y\"} {d|dateparse:'%d/%m/%Y'|date:'%B %-d, %Y'} {iso | date:\"%H:%M\"} {x | dateparse:\"%Y\" | date:\"%Y\"}";
+        assert_eq!("2024-03-05 March 5, 2024 09:30 soon", source.late_format(arguments.clone()));
+        assert!(Template::parse("{d | dateparse:\"%A\"}").is_err() && Template::parse("{d | date}").is_err());
+        let source = "{iso | tz:'-03:00' | date:'%H:%M'} {iso | tz:\"UTC\"} {d | tz:'+01'} {x | tz:'+01'}";
+        assert_eq!("06:30 2024-03-05T09:30:00 05/03/2024 soon", source.late_format(arguments));
+        assert!(Template::parse("{d | tz:'Nowhere/Nope'}").is_err() && Template::parse("{d | tz}").is_err());
+        assert!(Template::parse("{d | tz:'+01'}").unwrap().dump_ast().contains("filters=[tz:\"+01\"]"));
+    }
+
+    #[test]
+    fn len() {
+        let arguments = hashmap!{"s".into() => "e\u{301}t\u{e9} 🇫🇷👍🏽👩\u{200d}💻\r\n".into()};
+        assert_eq!("36 14 8 14", "{s | len:bytes} {s | len:chars} {s | len:graphemes} {s | len}".late_format(arguments));
+        let template = Template::parse("{list | len} {list | slice:1: | len}").unwrap();
+        let arguments = hashmap!{"list".into() => Value::from(vec!["a", "bc", "d"])};
+        assert_eq!("3 2", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+        assert!(Template::parse("{s | len:words}").is_err());
+    }
+
+    #[test]
+    fn durations() {
+        let arguments = hashmap!{"a".into() => "3725.50".into(), "b".into() => "-86400".into(), "c".into() => "0".into(), "d".into() => "0.25".into(), "e".into() => "later".into()};
+        assert_eq!("1h 2m 5.5s|-1d|0s|0.25s|later", "{a | duration}|{b | duration}|{c | duration}|{d | duration}|{e | duration}".late_format(arguments));
+    }
+
+    #[test]
+    fn replace() {
+        let arguments = hashmap!{"path".into() => "/usr/local/bin".into(), "card".into() => "4111 1111 1111 1234".into()};
+        assert_eq!("usr/local/bin", "{path | replace:\"/usr\",usr}".late_format(arguments.clone()));
+        assert!(Template::parse("{path | replace:a}").is_err() && Template::parse("{path | replace:\"\",b}").is_err());
+        #[cfg(feature = "regex")]
+        {
+            let source = "{card | replace:'/\\d{4} /**** /'} {path | replace:/^\\/usr\\/(\\w+)/$1:/}";
+            assert_eq!("**** **** **** 1234 local:/bin", source.late_format(arguments));
+            assert!(Template::parse("{card | replace:'/(/x/'}").is_err() && Template::parse("{card | replace:'/a/b'}").is_err());
+            let template = Template::parse("{path | replace:'/a\\/b/c/'}").unwrap();
+            assert!(template.dump_ast().contains("filters=[replace:\"/a\\\\/b/c/\"]"));
+        }
+    }
+
+    #[test]
+    fn trim() {
+        let arguments = hashmap!{"s".into() => " \ta\r\nb\n ".into()};
+        let source = "[{s | trim}] [{s|trim_start}] [{s|trim_end}] [{s | strip_newlines | trim}]";
+        assert_eq!("[a\r\nb] [a\r\nb\n ] [ \ta\r\nb] [ab]", source.late_format(arguments));
+    }
+
+    #[test]
+    fn default() {
+        let arguments = hashmap!{"blank".into() => Value::from("  "), "none".into() => Value::from(Vec::<Value>::new()), "name".into() => Value::from("<b>")};
+        let source = "{blank | default:x} {blank | trim | default:'Anonymous' | html} {missing|default:\"?\"} {none | default:'-'} {name | default:x | html}";
+        let options = FormatOptions::new().missing(crate::Missing::Error);
+        assert_eq!("   Anonymous ? - &lt;b&gt;", Template::parse(source).unwrap().render_arguments(&arguments, &options).unwrap());
+        assert!(Template::parse("{name | default}").is_err());
+    }
+
+    #[test]
+    fn join() {
+        let arguments = hashmap!{"names".into() => Value::from(vec!["a", "b", "c"]), "one".into() => Value::from(vec!["a"]), "s".into() => Value::from("x")};
+        let template = Template::parse(r#"{names | join} {names|join:" / "} {names | join:", ", " and " | html} {one|join:"-"," and "} {s | join:"-"}"#).unwrap();
+        assert_eq!("a, b, c a / b / c a, b and c a x", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+        let template = Template::parse("{names | html | join:\"-\"} {empty | join}").unwrap();
+        assert_eq!("a, b, c None", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+        assert!(Template::parse("{names | join:a,b,c}").is_err());
+    }
+
+    #[test]
+    fn custom() {
+        let options = FormatOptions::new()
+            .filter("shout", |value, arguments| format!("{}{}", value.to_uppercase(), arguments.join("")))
+            .filter("trim", |_, _| "custom".to_owned());
+        let arguments = hashmap!{"s".into() => Value::from(" hi "), "names".into() => Value::from(vec!["a", "b"])};
+        let template = Template::parse_with("{s | trim | shout:'!','?'} {names | shout} {if s | shout == 1}x{end}", &options).unwrap();
+        assert_eq!("HI!? A, B ", template.render_arguments(&arguments, &options).unwrap());
+        assert_eq!(["trim", "shout"], template.placeholders().next().unwrap().filters().collect::<Vec<_>>().as_slice());
+        assert_eq!(template, Template::parse_with(template.source(), &options).unwrap());
+        assert_eq!("HI!?", "{s|trim|shout:'!',?}".late_format_with(hashmap!{"s".into() => "hi".into()}, &options));
+        assert_eq!(&ParseErrorKind::UnknownFilter("shout".into()), Template::parse("{s | shout}").unwrap_err().kind());
+        let minified = template.minified();
+        assert_eq!(template.render_arguments(&arguments, &options), minified.render_arguments(&arguments, &options));
+        let options = options.profile(crate::Profile::Untrusted);
+        assert_eq!(&ParseErrorKind::UnknownFilter("shout".into()), Template::parse_with("{s | shout}", &options).unwrap_err().kind());
+        assert_eq!("{s | shout}", "{s | shout}".late_format_with(hashmap!{"s".into() => "hi".into()}, &options));
+    }
+
+    #[test]
+    fn display() {
+        let source = "{a | replace:\"\\u{0}\\t\",'\\\\\"' | pluralize:\"y\",\"ies\" | html}";
+        let Segment::Placeholder(p) = &Template::parse(source).unwrap().segments[0] else { unreachable!() };
+        let written: Vec<_> = p.filters.iter().map(ToString::to_string).collect();
+        assert_eq!(["replace:\"\0\t\",\"\\\\\\\"\"", "pluralize:\"y\",\"ies\"", "html"], written.as_slice());
+        let reparsed = Template::parse(&format!("{{a | {}}}", written.join(" | "))).unwrap();
+        assert_eq!(Template::parse(source).unwrap(), reparsed);
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// The name of the layer of the global defaults, as reported by
+/// [`Resolution::layer`](crate::Resolution::layer).
+pub(crate) const LAYER: &str = "global defaults";
+
+static DEFAULTS: RwLock<Option<Arc<HashMap<String, String>>>> = RwLock::new(None);
+
+/// Sets the arguments used by every template of the process for the
+/// parameters that have no argument otherwise, such as the application name
+/// and version, replacing the previous global defaults. An empty map removes
+/// them.
+///
+/// The global defaults are the last layer looked up, after the resolvers,
+/// [`Lookup`](crate::Lookup) matches and aliases of the options, and are
+/// reported as the `global defaults` layer.
+///
+/// ```
+/// use late_format::{set_global_defaults, LateFormat};
+/// use maplit::hashmap;
+/// set_global_defaults(hashmap!{"app".into() => "demo".into(), "user".into() => "nobody".into()});
+/// assert_eq!("x@demo", "{user}@{app}".late_format(hashmap!{"user".into() => "x".into()}));
+/// set_global_defaults(hashmap!{});
+/// assert_eq!("x@None", "{user}@{app}".late_format(hashmap!{"user".into() => "x".into()}));
+/// ```
+pub fn set_global_defaults(arguments: HashMap<String, String>) {
+    let defaults = (!arguments.is_empty()).then(|| Arc::new(arguments));
+    *DEFAULTS.write().unwrap_or_else(|e| e.into_inner()) = defaults;
+}
+
+/// The global defaults, if any.
+pub(crate) fn get() -> Option<Arc<HashMap<String, String>>> {
+    DEFAULTS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FormatOptions, Layers, Missing, Template};
+    use super::set_global_defaults;
+    use maplit::hashmap;
+
+    #[test]
+    fn global_defaults() {
+        set_global_defaults(hashmap!{"global_test_a".into() => "1".into(), "global_test_b".into() => "2".into()});
+        let template = Template::parse("{global_test_a}{global_test_b}").unwrap();
+        let options = FormatOptions::new().missing(Missing::Error);
+        assert_eq!("x2", template.render_with(&hashmap!{"global_test_a".into() => "x".into()}, &options).unwrap());
+        let arguments = Layers::new().layer("arguments", hashmap!{"global_test_a".to_owned() => "y".to_owned()});
+        let trace = template.render_traced(&arguments, &options).unwrap();
+        let layers: Vec<_> = trace.placeholders().iter().map(|p| p.layer()).collect();
+        assert_eq!(vec![Some("arguments"), Some("global defaults")], layers);
+        assert_eq!("12", template.bind(&hashmap!{}).render());
+        set_global_defaults(hashmap!{});
+        assert!(template.render_with(&hashmap!{}, &options).is_err());
+    }
+}
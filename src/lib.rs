@@ -1,5 +1,83 @@
 use std::collections::HashMap;
-use lazy_regex::*;
+
+mod audit;
+mod builder;
+mod bundle;
+mod cache;
+mod case;
+mod choose;
+mod convert;
+mod date;
+#[cfg(feature = "decimal")]
+mod decimal;
+mod diagnostics;
+#[cfg(feature = "digest")]
+mod digest;
+mod display;
+#[cfg(feature = "dotenv")]
+mod dotenv;
+mod error;
+mod filters;
+#[cfg(feature = "global-defaults")]
+mod globals;
+mod lint;
+#[cfg(feature = "csv")]
+mod merge;
+mod minify;
+mod number;
+mod options;
+mod pack;
+mod parser;
+mod partial;
+mod path;
+mod plural;
+mod rename;
+mod render;
+mod resolver;
+mod set;
+mod source_map;
+mod spec;
+mod spellout;
+mod stream;
+mod tagged;
+mod template;
+#[cfg(feature = "testutil")]
+pub mod testutil;
+mod value;
+mod zone;
+
+pub use audit::{Resolution, Trace, TracedPlaceholder};
+pub use builder::TemplateBuilder;
+pub use bundle::{negotiate_locale, BundleError, MessageBundle, MessageKey};
+pub use cache::RenderCache;
+pub use convert::printf;
+#[cfg(feature = "decimal")]
+pub use decimal::{Decimal, ParseDecimalError};
+pub use display::{LateDisplay, LateError};
+#[cfg(feature = "dotenv")]
+pub use dotenv::{DotEnv, DotEnvError, DotEnvErrorKind};
+pub use error::{Error, ErrorReport, Limit, ParseError, ParseErrorKind, RenderError, RenderErrorKind};
+pub use filters::dedent;
+#[cfg(feature = "global-defaults")]
+pub use globals::set_global_defaults;
+pub use lint::{lint, lint_against, LintKind, LintWarning};
+#[cfg(feature = "csv")]
+pub use merge::{MergeCsv, MergeError};
+pub use options::{Budget, FormatOptions, Limits, Lookup, Masking, Missing, Numbers, Profile, Rounding};
+pub use pack::{PackError, PackedTemplateSet};
+pub use partial::PartialRender;
+pub use path::PathTemplate;
+pub use plural::PluralCategory;
+pub use rename::rename_parameter;
+pub use resolver::{Env, Resolver};
+pub use set::{CheckReport, Mismatch, MismatchKind, ParameterUsage, ParameterUse, SharedTemplateSet, TemplateSet};
+pub use source_map::{OutputSpan, SourceMap};
+pub use spec::ArgumentType;
+pub use spellout::SpellOut;
+pub use stream::{substitute_stream, substitute_stream_with};
+pub use tagged::{TaggedPart, TaggedRender, TaggedValue};
+pub use template::{escape_literal, BoundTemplate, Placeholder, Template};
+pub use value::{Args, Arguments, Document, IntoLateValue, Layers, Value};
 
 /// The `LateFormat` trait allows substituting string parameters
 /// of arbitrary name that is computed at runtime.
@@ -10,20 +88,162 @@ use lazy_regex::*;
 /// 
 /// ```plain
 /// {param_name}     # parameter to replace
+/// {param_name | filter}
+/// {param_name:spec}
+/// {param_name:-default}
 /// {"escaped"}      # escaped sequence
+/// {'escaped'}      # escaped sequence
+/// {raw}...{endraw} # verbatim content
+/// {# comment}
+/// {if param_name}...{else}...{end}
+/// {for item in param_name}...{else}...{end}
+/// {for key, item in param_name}...{else}...{end}
+/// {plural param_name}{one}...{few}...{other}...{end}
+/// {choose}{variant 60%}...{variant 40%}...{end}
 /// ```
 ///
 /// Syntax description:
 ///
 /// - Whitespace is allowed around the parameter name or escaped form, such as
-/// `{ "foo" }` versus `{"foo"}`.
+///   `{ "foo" }` versus `{"foo"}`.
 /// - `{param_name}` expands to either an argument given in the map (whose key string is `param_name`) or
-/// the string `None` if not present. The parameter name may contain any of the following characters:
-/// ```plain
-/// A-Z a-z 0-9 . - _ $
-/// ```
+///   the string `None` if not present (see [`Missing`] for alternatives). The parameter name may contain any of the following characters:
+///   ```plain
+///   A-Z a-z 0-9 . - _ $
+///   ```
+/// - `{param_name.key}` and `{param_name.0}` navigate the maps and lists of a [`Value`]
+///   argument, or of a loop variable, as described in [`Value::get_path`], when no argument
+///   has the whole name. `{param_name[0]}` is equivalent to `{param_name.0}`.
 /// - `{"escaped"}` expands to the string `escaped`. It is often
-/// used for escaping the curly braces.
+///   used for escaping the curly braces. The escaped form accepts the following
+///   backslash sequences, and any other backslash sequence is kept as is:
+///   ```plain
+///   \"  \'  \\  \n  \t  \u{XXXX}
+///   ```
+/// - `{'escaped'}` is equivalent to `{"escaped"}`, which is convenient
+///   for templates embedded in JSON strings.
+/// - `{param_name | filter}` transforms the argument with the given filter.
+///   Filters may be chained as in `{param_name | a | b}`, and some take arguments
+///   after a colon, separated by commas, as in `{param_name | filter:"a",'b',c}`.
+///   An argument is quoted as an escaped form, or written as is up to whitespace,
+///   `,`, `|` or `}`. A parameter using an unknown filter is kept as is.
+///   The available filters are:
+///   - `dedent`: strips the common leading indentation; see [`dedent`].
+///   - `html`: escapes the characters `& < > " '` as HTML character references.
+///   - `xml` and `xml_attr`: escape the argument for XML element content, and for
+///     attribute values quoted with `"` or `'`.
+///   - `list` and `list:or`: join the items of a list argument as a conjunction or
+///     disjunction in the language of [`FormatOptions::locale`], as in `a, b, and c`.
+///   - `csv` and `csv:";"`: quote the argument as a CSV field if it contains the
+///     delimiter, a double quote or a line break, and join the fields of a list.
+///   - `base64`, `base64:url` and `hex`: with the `encoding` feature, encode the
+///     UTF-8 bytes of the argument.
+///   - `sha256` and `md5`: with the `digest` feature, write the hexadecimal digest
+///     of the UTF-8 bytes of the argument, or its first digits, as in `sha256:8`.
+///   - `spellout`: spells out an integer in words, as in `forty-two`, in English
+///     or with the [`SpellOut`] speller of the options. Other values are kept as is.
+///   - `roman`: writes an integer from 1 to 3999 in Roman numerals, as in `XLII`.
+///     Other values are kept as is.
+///   - `percent`: formats a number as the `%` spec.
+///   - `trim`, `trim_start` and `trim_end`: remove the whitespace around the argument,
+///     at its start, or at its end.
+///   - `strip_newlines`: removes the line breaks of the argument.
+///   - `title_case` and `sentence_case`: capitalize the first letter of each word, or
+///     of each sentence, and lowercase the other letters, with the case mappings of
+///     Unicode and of the language of [`FormatOptions::locale`].
+///   - `len`: writes the number of characters of the argument, or, with `len:bytes` and
+///     `len:graphemes`, of bytes or user-perceived characters, and the number of items of
+///     a list argument.
+///   - `duration`: writes a number of seconds in days, hours, minutes and seconds,
+///     as in `1h 2m 5.5s`. Other values are kept as is.
+///   - `default:"text"`: replaces a missing or empty value with the text, where it appears
+///     in the chain, as in `{name | trim | default:"Anonymous" | html}`.
+///   - `pluralize`: writes an English plural suffix, `s` unless the argument is 1, as in
+///     `item{count | pluralize}`. `pluralize:"es"` sets the plural suffix, and
+///     `pluralize:"y","ies"` both the singular and plural suffixes.
+///   - `join`: joins the items of a list argument with `, `, or with the separator
+///     of `join:" / "`. `join:", "," and "` separates the last two items with ` and `.
+///   - `slice:start:end`: keeps the items of a list argument, or the characters of
+///     other arguments, from `start` to before `end`. Either bound may be omitted,
+///     as in `slice::3`, and negative bounds count from the end.
+///   - `add:n`, `sub:n`, `mul:n` and `div:n`: adds, subtracts, multiplies or divides a
+///     number argument by a number, exactly. Quotients are rounded to 10 fraction digits
+///     with the rounding mode of the options. Other values, and divisions by zero, are kept as is.
+///   - `dateparse:"%d/%m/%Y"`: reads a date with `%Y %m %d %H %M %S %b %B` directives,
+///     and writes it in ISO 8601, as in `2024-03-05` or `2024-03-05T09:30:00`.
+///   - `date:"%B %-d, %Y"`: writes an ISO 8601 date with the directives of `dateparse`,
+///     `%y %j %a %A`, and `-` to remove the padding of a number. Names are in English.
+///     Both filters keep other values as is.
+///   - `tz:"Europe/Berlin"`: converts an ISO 8601 date and time in UTC into the local
+///     date and time of a time zone, to be written by `date`. The zone is `UTC`, a fixed
+///     offset such as `+05:30`, or a zone of the IANA database of the system, read from
+///     `$TZDIR` or `/usr/share/zoneinfo` when the template is parsed. Dates without a
+///     time, and other values, are kept as is.
+///   - `replace:"from","to"`: replaces every occurrence of a substring. With the `regex`
+///     feature, `replace:"/pattern/replacement/"` replaces the matches of a regular
+///     expression, with `$1` for the groups of a match in the replacement and `\/` for a slash.
+///
+///   The filters registered by [`FormatOptions::filter`] are also available.
+///
+///   The filters of a placeholder without a spec receive the items of a list
+///   argument, and the other filters than `join` and `slice` the items separated
+///   by `, `. Otherwise, the argument is a list of one item.
+/// - `{param_name:spec}` formats a number argument with a format spec, before
+///   any filter. Arguments that are not numbers, as read by [`Numbers`], are only
+///   padded. A spec is written
+///   `[[fill]align][+][#][0][width][.precision][type]`:
+///   - `<`, `^` and `>` align the argument to the left, center or right of the
+///     width, after a fill character other than `{` and `}`, as in `*^20`. Numbers
+///     are aligned to the right by default, and other arguments to the left.
+///   - `+` writes a sign on positive numbers too, as in `+1.5`.
+///   - `#` writes the `0b`, `0o` and `0x` prefixes of the radix types.
+///   - `0` pads the number with zeros after the sign and prefix, as in `-0042`
+///     for `05`, rather than with spaces before them.
+///   - `5` pads the argument to a width of 5 characters.
+///   - `.2` rounds to 2 fraction digits, as selected by [`FormatOptions::rounding`],
+///     half to even by default. For scientific notation, this rounds the mantissa.
+///   - `{width}` and `.{precision}` take the width and precision from the integer
+///     arguments of other parameters, as in `{value:>{width}.{prec}}`. Without such
+///     an argument, the spec has no width or precision, or fails with [`Missing::Error`].
+///   - No type writes the number in fixed notation, as in `1500`.
+///   - `%` multiplies by 100 and adds the percent sign of [`FormatOptions::locale`],
+///     as in `12.34%`.
+///   - `e` and `E` write the number in scientific notation, as in `1.5e3` and `1.5E3`.
+///   - `b`, `o`, `x` and `X` write an integer in binary, octal or hexadecimal, as in
+///     `2a` and `2A`. Other numbers are kept as is.
+/// - `{param_name:-default}` expands to the default when the argument is missing or
+///   empty. The default runs up to the closing curly brace and may contain placeholders,
+///   including other defaults, as in `{nickname:-{first_name:-anonymous}}`, and escaped
+///   forms. Nested defaults count toward [`Limits::max_nesting`]. `:-` followed by `<`, `^`
+///   or `>` starts a spec instead, as in `{title:-^30}`.
+/// - `{raw}...{endraw}` expands to the content between the tags verbatim, including
+///   any curly braces. This is convenient for content such as CSS or LaTeX. Like the
+///   tag of an unclosed block, a `{raw}` without `{endraw}` is kept as literal text.
+/// - `{# comment}` expands to nothing. It runs up to the first closing curly brace.
+///   A comment written `{#key: value}` is metadata, read by [`Template::metadata`].
+/// - `{if param_name}...{end}` expands to its content when the argument is present
+///   and not empty, and `{if not param_name}` when it is missing or empty. An `{else}`
+///   tag may separate the content used otherwise. Blocks may be nested. Outside of
+///   a block, `{else}` and `{end}` are ordinary parameters.
+/// - `{if param_name | filter > 40}` applies filters to the argument of the
+///   condition, and compares it with a number by `==`, `!=`, `<`, `<=`, `>` or `>=`.
+///   A comparison does not hold for an argument that is not a number.
+/// - `{for item in param_name}...{end}` expands to its content once per item of a list
+///   argument, given as a [`Value`], with `{item}` resolving to the item. Other arguments
+///   are iterated as a list of one item. The content after an `{else}` tag is used when
+///   there is no item.
+/// - `{for key, item in param_name}...{end}` iterates the entries of a map argument
+///   in key order, with `{key}` resolving to the key, or the items of a list with
+///   `{key}` resolving to their position from 0.
+/// - `{plural param_name}...{end}` expands to the content following the tag of the
+///   CLDR plural category of a number argument, among `{zero}`, `{one}`, `{two}`, `{few}`,
+///   `{many}` and `{other}`, under the rules of [`FormatOptions::locale`]. `{other}` is used
+///   when the category has no tag, and the content before the first tag is always used.
+///   See [`PluralCategory`].
+/// - `{choose}...{end}` expands to the content following one of its `{variant}` tags,
+///   selected at random in proportion to their weights: `{variant 3}` or `{variant 60%}`
+///   weighs 3 or 60, and `{variant}` 1. A weight of 0 disables a variant. The random
+///   numbers come from [`FormatOptions::seed`] or [`FormatOptions::random`] when set.
 ///
 /// # Example
 /// 
@@ -35,29 +255,44 @@ use lazy_regex::*;
 /// 
 /// // if a string contains curly braces, they must be escaped.
 /// let escaped: String = r#"{"{"}"#.into();
+/// assert_eq!("{", escaped.late_format(hashmap!{}));
+///
+/// // quotes and control characters are written with backslash sequences.
+/// let escaped: String = r#"{"say \"hi\"\n"}"#.into();
+/// assert_eq!("say \"hi\"\n", escaped.late_format(hashmap!{}));
 /// ```
 ///
+/// Options such as stripping the indentation of the template itself
+/// are given through [`LateFormat::late_format_with`]. A string that is
+/// formatted many times may be parsed once as a [`Template`] instead.
+///
 pub trait LateFormat {
     fn late_format(&self, arguments: HashMap<String, String>) -> String;
+
+    /// Formats with options. Implementations that only provide
+    /// [`LateFormat::late_format`] ignore the options.
+    fn late_format_with(&self, arguments: HashMap<String, String>, options: &FormatOptions) -> String {
+        let _ = options;
+        self.late_format(arguments)
+    }
 }
 
 impl LateFormat for &str {
     fn late_format(&self, arguments: HashMap<String, String>) -> String {
-        regex_replace_all!(
-            r#"(?x)
-            \{\s*(
-                ([a-zA-Z_0-9\-\.\$]+)   | # parameter
-                ("([^\u{22}])*")          # escaped
-            )\s*\}
-            "#,
-            self,
-            |_, s: &str, _, _, _| {
-                if s.starts_with('"') {
-                    return s[1..s.len() - 1].to_owned().clone();
-                }
-                arguments.get(s).map_or("None".to_owned(), |v| v.clone())
-            }
-        ).into_owned()
+        self.late_format_with(arguments, &FormatOptions::default())
+    }
+
+    fn late_format_with(&self, arguments: HashMap<String, String>, options: &FormatOptions) -> String {
+        let template = Template::parse_lenient(self, options);
+        // the source of a literal template is its output, moved rather than copied.
+        if template.is_literal() {
+            return template.source;
+        }
+        let mut r = String::new();
+        if let Err(error) = template.render_into(&arguments, options, &mut r) {
+            r.push_str(&template.source[error.span().start..]);
+        }
+        r
     }
 }
 
@@ -65,6 +300,10 @@ impl LateFormat for String {
     fn late_format(&self, arguments: HashMap<String, String>) -> String {
         self.as_str().late_format(arguments)
     }
+
+    fn late_format_with(&self, arguments: HashMap<String, String>, options: &FormatOptions) -> String {
+        self.as_str().late_format_with(arguments, options)
+    }
 }
 
 #[cfg(test)]
@@ -83,4 +322,56 @@ mod test {
         let user_string: String = "some user string: {id}".into();
         assert_eq!("some user string: None", user_string.late_format(hashmap!{}));
     }
+
+    #[test]
+    fn default_late_format_with() {
+        // implementations written before options existed still compile.
+        struct Fixed;
+        impl LateFormat for Fixed {
+            fn late_format(&self, _: HashMap<String, String>) -> String {
+                "fixed".into()
+            }
+        }
+        assert_eq!("fixed", Fixed.late_format_with(hashmap!{}, &FormatOptions::new().dedent(true)));
+    }
+
+    #[test]
+    fn escape_sequences() {
+        let user_string: String = r#"{"\"quoted\""}"#.into();
+        assert_eq!(r#""quoted""#, user_string.late_format(hashmap!{}));
+        let user_string: String = r#"{"a\\b\nc\td"}"#.into();
+        assert_eq!("a\\b\nc\td", user_string.late_format(hashmap!{}));
+        let user_string: String = r#"{"\u{41}\u{1F600}"}"#.into();
+        assert_eq!("A\u{1F600}", user_string.late_format(hashmap!{}));
+        let user_string: String = r#"{"\q\u{zz}"}"#.into();
+        assert_eq!(r#"\q\u{zz}"#, user_string.late_format(hashmap!{}));
+    }
+
+    #[test]
+    fn dedenting() {
+        let user_string = "\r\n    {id}:\r\n\t\r\n      {value | dedent}\r\n    ";
+        let arguments = hashmap!{"id".into() => "x".into(), "value".into() => "  a\n   b".into()};
+        assert_eq!("x:\n\n  a\n b\n", user_string.late_format_with(arguments.clone(), &FormatOptions::new().dedent(true)));
+        assert_eq!("\n    x:\n\t\n      a\n b\n    ", user_string.late_format_with(arguments.clone(), &FormatOptions::new().normalize_line_endings(true)));
+        assert_eq!("{value | unknown}", "{value | unknown}".late_format(arguments));
+        let arguments = hashmap!{"value".into() => "\t  a\n\t\tb\n    c\n\t  d".into(), "tabs".into() => "\t\ta\n\t\t\tb".into(), "mixed".into() => "\t  a\n\t b".into()};
+        assert_eq!("\t  a\n\t\tb\n    c\n\t  d|a\n\tb| a\nb", "{value | dedent}|{tabs | dedent}|{mixed | dedent}".late_format(arguments));
+    }
+
+    #[test]
+    fn raw_blocks() {
+        let user_string = r#"\section{ {title} }{ raw }\emph{x} {"y"}{ endraw } {id}{raw}{}{endraw}"#;
+        let arguments = hashmap!{"title".into() => "T".into(), "id".into() => "x".into()};
+        assert_eq!(r#"\section{ T }\emph{x} {"y"} x{}"#, user_string.late_format(arguments.clone()));
+        assert_eq!("{raw} x", "{raw} {id}".late_format(arguments));
+        assert_eq!(&crate::ParseErrorKind::UnclosedBlock, Template::parse("{ raw }").unwrap_err().kind());
+    }
+
+    #[test]
+    fn single_quoted_escape() {
+        let user_string: String = "{'{'}id{'}'}".into();
+        assert_eq!("{id}", user_string.late_format(hashmap!{}));
+        let user_string: String = r#"{ 'it\'s "{id}"' }"#.into();
+        assert_eq!(r#"it's "{id}""#, user_string.late_format(hashmap!{"id".into() => "x".into()}));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,237 @@
+use std::fmt;
+use std::ops::Range;
+use crate::{ParseErrorKind, Template};
+use crate::diagnostics::labeled_snippet;
+use crate::template::{walk, Condition, Segment};
+
+/// A suspicious construct found by [`lint`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LintWarning {
+    kind: LintKind,
+    span: Range<usize>,
+}
+
+/// The kind of a [`LintWarning`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LintKind {
+    /// The template does not parse.
+    Invalid(ParseErrorKind),
+    /// A placeholder has no parameter name, as in `{}` or `{ | html}`,
+    /// and is kept as literal text.
+    EmptyName,
+    /// A curly brace is kept as literal text without an escaped form.
+    LoneBrace,
+    /// An escaped form contains no curly brace and no backslash sequence,
+    /// so the text could be written as is.
+    NeedlessEscape,
+    /// A placeholder repeats the placeholder before it, with only
+    /// whitespace between them.
+    DuplicatePlaceholder(String),
+    /// A parameter is not one of the known arguments, but is close to one.
+    PossibleTypo { name: String, suggestion: String },
+}
+
+impl LintWarning {
+    pub fn kind(&self) -> &LintKind {
+        &self.kind
+    }
+
+    /// The byte range of the template source where the construct occurs.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Describes the warning with a caret-underlined snippet of the source,
+    /// as [`Error::snippet`](crate::Error::snippet) describes errors.
+    pub fn snippet(&self, source: &str) -> String {
+        labeled_snippet(source, self.span.clone(), "warning", &self.kind)
+    }
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}..{}", self.kind, self.span.start, self.span.end)
+    }
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(kind) => write!(f, "{kind}"),
+            Self::EmptyName => write!(f, "placeholder has no name"),
+            Self::LoneBrace => write!(f, "curly brace is not escaped"),
+            Self::NeedlessEscape => write!(f, "escaped form escapes nothing"),
+            Self::DuplicatePlaceholder(name) => write!(f, "`{{{name}}}` repeats the previous placeholder"),
+            Self::PossibleTypo { name, suggestion } => write!(f, "unknown parameter `{name}`, did you mean `{suggestion}`?"),
+        }
+    }
+}
+
+/// Finds suspicious constructs in a template source, such as curly braces
+/// that do not form a placeholder, for checking translation files in CI.
+/// The warnings are in source order.
+///
+/// ```
+/// use late_format::{lint, LintKind};
+/// let warnings = lint("Hi {name}{name}, {} {\"x\"} }");
+/// let kinds: Vec<_> = warnings.iter().map(|w| w.kind().clone()).collect();
+/// assert_eq!(vec![
+///     LintKind::DuplicatePlaceholder("name".into()),
+///     LintKind::EmptyName,
+///     LintKind::NeedlessEscape,
+///     LintKind::LoneBrace,
+/// ], kinds);
+/// ```
+pub fn lint(source: &str) -> Vec<LintWarning> {
+    lint_against(source, std::iter::empty::<&str>())
+}
+
+/// Finds suspicious constructs as [`lint`] does, and the parameters that
+/// are not among the names of the supplied arguments but are within a small
+/// edit distance of one of them.
+///
+/// ```
+/// use late_format::{lint_against, LintKind};
+/// let warnings = lint_against("{usr_name} {count}", ["user_name", "count"]);
+/// assert_eq!(
+///     &LintKind::PossibleTypo { name: "usr_name".into(), suggestion: "user_name".into() },
+///     warnings[0].kind(),
+/// );
+/// ```
+pub fn lint_against<S: AsRef<str>>(source: &str, arguments: impl IntoIterator<Item = S>) -> Vec<LintWarning> {
+    let template = match Template::parse(source) {
+        Ok(template) => template,
+        Err(error) => return vec![LintWarning { kind: LintKind::Invalid(error.kind().clone()), span: error.span() }],
+    };
+    let mut warnings = vec![];
+    segments(&template.segments, source, &mut warnings);
+    let arguments: Vec<S> = arguments.into_iter().collect();
+    // the parameters used by placeholders and blocks, with their spans.
+    let mut parameters = vec![];
+    walk(&template.segments, &mut |segment| match segment {
+        Segment::Placeholder(p) => parameters.push((p.name(), p.is_loop_variable(), p.span())),
+        Segment::If { condition: Condition { reference: r, .. }, span, .. }
+        | Segment::For { list: r, span, .. }
+        | Segment::Plural { count: r, span, .. } => parameters.push((&r.name, r.local, span.clone())),
+        _ => {},
+    });
+    let mut reported = vec![];
+    for (name, local, span) in parameters {
+        if local || name.contains(':') || reported.contains(&name) || arguments.iter().any(|a| a.as_ref() == name) {
+            continue;
+        }
+        let max = if name.chars().count() <= 4 { 1 } else { 2 };
+        let closest = arguments.iter().map(|a| (distance(name, a.as_ref()), a.as_ref())).filter(|(d, _)| *d <= max).min();
+        if let Some((_, suggestion)) = closest {
+            reported.push(name);
+            let kind = LintKind::PossibleTypo { name: name.to_owned(), suggestion: suggestion.to_owned() };
+            warnings.push(LintWarning { kind, span });
+        }
+    }
+    warnings.sort_by_key(|w| w.span.start);
+    warnings
+}
+
+/// Checks a sequence of segments and the segments nested in them.
+fn segments(segments: &[Segment], source: &str, warnings: &mut Vec<LintWarning>) {
+    // the last placeholder, unless a segment other than whitespace follows it.
+    let mut previous: Option<&str> = None;
+    for segment in segments {
+        match segment {
+            Segment::Literal(span) => {
+                braces(&source[span.clone()], span.start, warnings);
+                if !source[span.clone()].trim().is_empty() {
+                    previous = None;
+                }
+            },
+            Segment::Escaped { span, text } => {
+                let needless = !text.contains(['{', '}']) && !source[span.clone()].contains('\\') && !text.trim().is_empty();
+                if needless {
+                    warnings.push(LintWarning { kind: LintKind::NeedlessEscape, span: span.clone() });
+                }
+                previous = None;
+            },
+            Segment::Placeholder(p) => {
+                if previous == Some(p.name()) {
+                    warnings.push(LintWarning { kind: LintKind::DuplicatePlaceholder(p.name.clone()), span: p.span() });
+                }
+                previous = Some(p.name());
+            },
+            _ => previous = None,
+        }
+        for children in segment.children() {
+            self::segments(children, source, warnings);
+        }
+    }
+}
+
+/// Reports the curly braces of a literal starting at `offset`.
+fn braces(literal: &str, offset: usize, warnings: &mut Vec<LintWarning>) {
+    let mut i = 0;
+    while let Some(j) = literal[i..].find(['{', '}']) {
+        let start = i + j;
+        i = start + 1;
+        if literal[start..].starts_with('{') {
+            let rest = literal[i..].trim_start();
+            if rest.starts_with(['}', '|', ':']) {
+                let end = literal[i..].find('}').map_or(literal.len(), |k| i + k + 1);
+                warnings.push(LintWarning { kind: LintKind::EmptyName, span: offset + start..offset + end });
+                i = end;
+                continue;
+            }
+        }
+        warnings.push(LintWarning { kind: LintKind::LoneBrace, span: offset + start..offset + start + 1 });
+    }
+}
+
+/// The edit distance between two strings, in characters, counting
+/// insertions, deletions, substitutions and swaps of adjacent characters.
+fn distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // the distances between the prefixes of `a` and of `b`.
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::{distance, lint, lint_against, LintKind};
+
+    #[test]
+    fn warnings() {
+        let source = "{if a}{b}\n {b}{end}{ }{ :x} }{'{'}{\"\\n\"}{\" \"}";
+        let warnings: Vec<_> = lint(source).into_iter().map(|w| (w.kind().clone(), w.span())).collect();
+        assert_eq!(vec![
+            (LintKind::DuplicatePlaceholder("b".into()), 11..14),
+            (LintKind::EmptyName, 19..22),
+            (LintKind::EmptyName, 22..27),
+            (LintKind::LoneBrace, 28..29),
+        ], warnings);
+        assert_eq!(vec![(LintKind::Invalid(crate::ParseErrorKind::UnclosedBlock), 0..6)], lint("{if a}").into_iter().map(|w| (w.kind().clone(), w.span())).collect::<Vec<_>>());
+        let warnings = lint_against("{nmae} {nmae} {for item in lst}{itme}{end} {id}", ["name", "list", "item", "ids"]);
+        assert_eq!([
+            "unknown parameter `nmae`, did you mean `name`? at 0..6", "`{nmae}` repeats the previous placeholder at 7..13",
+            "unknown parameter `lst`, did you mean `list`? at 14..42",
+            "unknown parameter `itme`, did you mean `item`? at 31..37", "unknown parameter `id`, did you mean `ids`? at 43..47",
+        ],
+            warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>()[..]);
+        assert_eq!("warning: curly brace is not escaped\n --> 1:2\n  |\n1 | a}\n  |  ^\n", lint("a}")[0].snippet("a}"));
+        assert_eq!((3, 0, 1, 1), (distance("kitten", "sitting"), distance("", ""), distance("é", "e"), distance("ab", "ba")));
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead};
+use crate::{FormatOptions, RenderError, Template};
+
+impl Template {
+    /// Renders the template once per record of CSV data, as in a mail merge.
+    /// The header row gives the parameter names, and each following record
+    /// the arguments. Records are read one at a time, so the data is never
+    /// loaded in memory as a whole.
+    ///
+    /// Fields are separated by commas and may be quoted as described in
+    /// RFC 4180, with `""` standing for a quote and line breaks allowed
+    /// inside quotes.
+    ///
+    /// ```
+    /// use late_format::{FormatOptions, Template};
+    /// let template = Template::parse("Dear {name}, you owe {amount}.").unwrap();
+    /// let csv = "name,amount\nAda,\"1,000\"\nAlan,5\n";
+    /// let letters: Vec<String> = template.merge_csv(csv.as_bytes(), &FormatOptions::new()).map(Result::unwrap).collect();
+    /// assert_eq!(["Dear Ada, you owe 1,000.", "Dear Alan, you owe 5."], &letters[..]);
+    /// ```
+    pub fn merge_csv<'a, R: BufRead>(&'a self, reader: R, options: &'a FormatOptions) -> MergeCsv<'a, R> {
+        MergeCsv { template: self, options, reader, header: None, line: 0, failed: false }
+    }
+}
+
+/// An iterator over the renders of a template against CSV records, as
+/// returned by [`Template::merge_csv`].
+#[derive(Debug)]
+pub struct MergeCsv<'a, R> {
+    template: &'a Template,
+    options: &'a FormatOptions,
+    reader: R,
+    header: Option<Vec<String>>,
+    /// The number of lines read.
+    line: usize,
+    /// Whether an error ended the iteration.
+    failed: bool,
+}
+
+/// An error produced by [`Template::merge_csv`]. Lines start at 1.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MergeError {
+    Io(io::Error),
+    /// A quoted field starting at this line is not closed.
+    UnclosedQuote(usize),
+    /// A record has a different number of fields than the header row.
+    FieldCount { line: usize, expected: usize, found: usize },
+    /// Rendering the record at this line failed.
+    Render { line: usize, error: RenderError },
+}
+
+impl<R: BufRead> MergeCsv<'_, R> {
+    /// Reads the fields of the next record, or `None` at the end of the data.
+    fn record(&mut self) -> Result<Option<Vec<String>>, MergeError> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).map_err(MergeError::Io)? == 0 {
+            return Ok(None);
+        }
+        self.line += 1;
+        let start = self.line;
+        let mut fields = vec![];
+        let mut field = String::new();
+        let mut quoted = false;
+        loop {
+            let mut chars = line.chars().peekable();
+            while let Some(ch) = chars.next() {
+                match ch {
+                    '"' if quoted && chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    },
+                    '"' if quoted => quoted = false,
+                    '"' if field.is_empty() => quoted = true,
+                    ',' if !quoted => fields.push(std::mem::take(&mut field)),
+                    '\r' | '\n' if !quoted => {},
+                    _ => field.push(ch),
+                }
+            }
+            if !quoted {
+                break;
+            }
+            line.clear();
+            if self.reader.read_line(&mut line).map_err(MergeError::Io)? == 0 {
+                return Err(MergeError::UnclosedQuote(start));
+            }
+            self.line += 1;
+        }
+        fields.push(field);
+        Ok(Some(fields))
+    }
+
+    fn render(&mut self) -> Result<Option<String>, MergeError> {
+        if self.header.is_none() {
+            match self.record()? {
+                Some(header) => self.header = Some(header),
+                None => return Ok(None),
+            }
+        }
+        let Some(fields) = self.record()? else {
+            return Ok(None);
+        };
+        let header = self.header.as_ref().unwrap();
+        if fields.len() != header.len() {
+            return Err(MergeError::FieldCount { line: self.line, expected: header.len(), found: fields.len() });
+        }
+        let arguments: HashMap<String, String> = header.iter().cloned().zip(fields).collect();
+        self.template.render_with(&arguments, self.options)
+            .map(Some)
+            .map_err(|error| MergeError::Render { line: self.line, error })
+    }
+}
+
+impl<R: BufRead> Iterator for MergeCsv<'_, R> {
+    type Item = Result<String, MergeError>;
+
+    /// Renders the next record. The iteration ends after an error.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let r = self.render().transpose();
+        self.failed = matches!(r, Some(Err(_)));
+        r
+    }
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::UnclosedQuote(line) => write!(f, "unclosed quoted field at line {line}"),
+            Self::FieldCount { line, expected, found } => write!(f, "expected {expected} fields, found {found} at line {line}"),
+            Self::Render { line, error } => write!(f, "{error} in the record at line {line}"),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Render { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FormatOptions, Missing, Template};
+    use super::MergeError;
+
+    #[test]
+    fn merging() {
+        let template = Template::parse("{a}|{b}").unwrap();
+        let options = FormatOptions::new();
+        let csv = "a,b\r\n\"x \"\"1\"\"\",\"multi\nline\"\r\n,\n";
+        let r: Vec<_> = template.merge_csv(csv.as_bytes(), &options).map(Result::unwrap).collect();
+        assert_eq!(["x \"1\"|multi\nline", "|"], &r[..]);
+        assert_eq!(0, template.merge_csv("".as_bytes(), &options).count());
+        let mut merge = template.merge_csv("a,b\n1\n2,3\n".as_bytes(), &options);
+        assert!(matches!(merge.next(), Some(Err(MergeError::FieldCount { line: 2, expected: 2, found: 1 }))));
+        assert!(merge.next().is_none());
+        let mut merge = template.merge_csv("a,b\n\"1,2\n".as_bytes(), &options);
+        assert!(matches!(merge.next(), Some(Err(MergeError::UnclosedQuote(2)))));
+        let options = FormatOptions::new().missing(Missing::Error);
+        let mut merge = template.merge_csv("a\n1\n".as_bytes(), &options);
+        assert!(matches!(merge.next(), Some(Err(MergeError::Render { line: 2, .. }))));
+    }
+}
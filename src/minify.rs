@@ -0,0 +1,194 @@
+use std::fmt::Write;
+use crate::{escape_literal, FormatOptions, Template};
+use crate::filters::Filter;
+use crate::template::Segment;
+
+impl Template {
+    /// Returns an equivalent template in a canonical compact form, to shrink
+    /// embedded string tables and to compare templates by their source:
+    ///
+    /// - Each run of whitespace in literal text becomes a single space, or
+    ///   a single line break if it contains one.
+    /// - The whitespace inside placeholders and tags is removed, as in
+    ///   `{  name | trim }` becoming `{name|trim}`.
+    /// - Escaped forms and filter arguments are quoted with `"`, and curly
+    ///   braces of literal text are escaped.
+    /// - `{else}` tags followed by nothing are removed.
+    ///
+    /// The text of escaped forms and of `{raw}` blocks is kept as is.
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// let template = Template::parse("Hello,   {  name | trim }!\n\n  {'  '}{ if  not n }none{ else }{end}").unwrap();
+    /// assert_eq!("Hello, {name|trim}!\n{\"  \"}{if not n}none{end}", template.minified().source());
+    /// ```
+    pub fn minified(&self) -> Template {
+        let mut source = String::with_capacity(self.source.len());
+        let segments = self.minify(&self.segments, &mut source);
+        Template::new(source, segments).with_options_of(self)
+    }
+
+    /// Writes the compact source of segments to `r`, returning the segments
+    /// with their spans in `r`.
+    fn minify(&self, segments: &[Segment], r: &mut String) -> Vec<Segment> {
+        let mut minified = vec![];
+        for segment in segments {
+            let start = r.len();
+            let segment = match segment {
+                Segment::Literal(span) => {
+                    let text = collapse_whitespace(&self.source[span.clone()]);
+                    if text.contains('{') {
+                        let escaped = escape_literal(&text);
+                        let tail = Self::parse_lenient(&escaped, &FormatOptions::default());
+                        minified.extend(tail.segments.iter().map(|s| s.shifted(start)));
+                        r.push_str(&escaped);
+                        continue;
+                    }
+                    r.push_str(&text);
+                    Segment::Literal(start..r.len())
+                },
+                // comments may hold metadata, and are kept as written.
+                Segment::Comment(span) => {
+                    r.push_str(&self.source[span.clone()]);
+                    Segment::Comment(start..r.len())
+                },
+                Segment::Raw { span, content } => {
+                    r.push_str(&self.source[span.clone()]);
+                    let offset = content.start - span.start;
+                    Segment::Raw { span: start..r.len(), content: start + offset..start + offset + content.len() }
+                },
+                Segment::Escaped { text, .. } => {
+                    write!(r, "{{{}}}", quote(text)).unwrap();
+                    Segment::Escaped { span: start..r.len(), text: text.clone() }
+                },
+                Segment::Placeholder(p) => {
+                    write!(r, "{{{}", p.name).unwrap();
+                    let default = p.default.as_ref().map(|default| {
+                        r.push_str(":-");
+                        self.minify(default, r)
+                    });
+                    if let Some(spec) = &p.spec {
+                        write!(r, ":{spec}").unwrap();
+                    }
+                    write_filters(&p.filters, r);
+                    r.push('}');
+                    let mut p = p.clone();
+                    (p.default, p.span) = (default, start..r.len());
+                    Segment::Placeholder(p)
+                },
+                Segment::If { condition, then, otherwise, .. } => {
+                    let not = if condition.negated { "not " } else { "" };
+                    write!(r, "{{if {not}{}", condition.reference.name).unwrap();
+                    write_filters(&condition.filters, r);
+                    if let Some(comparison) = &condition.comparison {
+                        write!(r, "{}{}", comparison.operator(), comparison.operand).unwrap();
+                    }
+                    r.push('}');
+                    let (then, otherwise) = self.minify_branches(then, otherwise, r);
+                    Segment::If { span: start..r.len(), condition: condition.clone(), then, otherwise }
+                },
+                Segment::For { key, variable, list, body, otherwise, .. } => {
+                    let prefix = key.as_ref().map_or(String::new(), |key| format!("{key}, "));
+                    write!(r, "{{for {prefix}{variable} in {}}}", list.name).unwrap();
+                    let (body, otherwise) = self.minify_branches(body, otherwise, r);
+                    Segment::For { span: start..r.len(), key: key.clone(), variable: variable.clone(), list: list.clone(), body, otherwise }
+                },
+                Segment::Plural { count, then, cases, .. } => {
+                    write!(r, "{{plural {}}}", count.name).unwrap();
+                    let then = self.minify(then, r);
+                    let cases = cases.iter().map(|(category, segments)| {
+                        write!(r, "{{{}}}", category.name()).unwrap();
+                        (*category, self.minify(segments, r))
+                    }).collect();
+                    r.push_str("{end}");
+                    Segment::Plural { span: start..r.len(), count: count.clone(), then, cases }
+                },
+                Segment::Choose { then, variants, .. } => {
+                    r.push_str("{choose}");
+                    let then = self.minify(then, r);
+                    let variants = variants.iter().map(|(weight, segments)| {
+                        match weight {
+                            1 => r.push_str("{variant}"),
+                            _ => write!(r, "{{variant {weight}}}").unwrap(),
+                        }
+                        (*weight, self.minify(segments, r))
+                    }).collect();
+                    r.push_str("{end}");
+                    Segment::Choose { span: start..r.len(), then, variants }
+                },
+            };
+            minified.push(segment);
+        }
+        minified
+    }
+
+    /// Writes the segments of a block before and after its `{else}` tag,
+    /// followed by the `{end}` tag.
+    fn minify_branches(&self, then: &[Segment], otherwise: &[Segment], r: &mut String) -> (Vec<Segment>, Vec<Segment>) {
+        let then = self.minify(then, r);
+        if !otherwise.is_empty() {
+            r.push_str("{else}");
+        }
+        let otherwise = self.minify(otherwise, r);
+        r.push_str("{end}");
+        (then, otherwise)
+    }
+}
+
+/// Writes the `|filter:"argument"` chain of filters.
+fn write_filters(filters: &[Filter], r: &mut String) {
+    for filter in filters {
+        write!(r, "|{}", filter.name()).unwrap();
+        for (i, argument) in filter.arguments().iter().enumerate() {
+            let separator = if i == 0 { ':' } else { ',' };
+            write!(r, "{separator}{}", quote(argument)).unwrap();
+        }
+    }
+}
+
+/// Replaces each run of whitespace by a space, or by a line break if the
+/// run contains one.
+fn collapse_whitespace(text: &str) -> String {
+    let mut r = String::with_capacity(text.len());
+    let mut run: Option<char> = None;
+    for ch in text.chars() {
+        if ch.is_whitespace() {
+            run = Some(if ch == '\n' || run == Some('\n') { '\n' } else { ' ' });
+            continue;
+        }
+        r.extend(run.take());
+        r.push(ch);
+    }
+    r.extend(run);
+    r
+}
+
+/// Quotes a text as the escaped form `"text"`.
+pub(crate) fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', r"\\").replace('"', r#"\""#))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Template;
+    use maplit::hashmap;
+
+    #[test]
+    fn minification() {
+        let source = " {for  k , v in map }{ k }={ v:>4 | replace:\"a\", 'b\"' }{ else }-{ end }\t{ plural  n }{ one }{n} item{ other }{n:-none}  \n  items{end} {x {\"}\"}{raw}{ a  b }{endraw}";
+        let template = Template::parse(source).unwrap();
+        let minified = template.minified();
+        assert_eq!(
+            r#" {for k, v in map}{k}={v:>4|replace:"a","b\""}{else}-{end} {plural n}{one}{n} item{other}{n:-none}
+items{end} {"{"}x {"}"}{raw}{ a  b }{endraw}"#,
+            minified.source(),
+        );
+        assert_eq!(minified, minified.minified());
+        assert_eq!(minified.source(), minified.minified().source());
+        let arguments = hashmap!{"n".into() => "1".into(), "x".into() => "y".into()};
+        assert_eq!(" - 1 item {x }{ a  b }", minified.render(&arguments));
+        assert_eq!(template.parameters(), minified.parameters());
+        let spans: Vec<_> = minified.placeholders().map(|p| &minified.source()[p.span()]).collect();
+        assert_eq!(["{k}", r#"{v:>4|replace:"a","b\""}"#, "{n}", "{n:-none}"], &spans[..]);
+    }
+}
@@ -0,0 +1,379 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+use crate::Rounding;
+
+/// A decimal number parsed from the text of an argument, kept as its
+/// decimal digits so that scaling and rounding are exact.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct Decimal {
+    pub negative: bool,
+    /// The significant digits, from 0 to 9, without leading zeros.
+    digits: Vec<u8>,
+    /// The power of ten of the last digit.
+    exponent: i32,
+}
+
+impl Decimal {
+    /// Parses a number such as `-12`, `0.5`, `.5` or `1.5e3`, ignoring
+    /// surrounding whitespace. The digits of the integer part may be grouped
+    /// by three with `,` or `_`, as in `1,234.5`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (mantissa, exponent) = match s.split_once(['e', 'E']) {
+            Some((m, e)) => (m, e.parse::<i32>().ok().filter(|e| e.abs() <= 1000)?),
+            None => (s, 0),
+        };
+        let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let integer = ungrouped(integer)?;
+        let integer = integer.as_ref();
+        if integer.is_empty() && fraction.is_empty() {
+            return None;
+        }
+        if !integer.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let digits: Vec<u8> = integer.bytes().chain(fraction.bytes()).map(|b| b - b'0').skip_while(|d| *d == 0).collect();
+        let exponent = exponent - fraction.len() as i32;
+        Some(Self { negative, digits, exponent })
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.iter().all(|d| *d == 0)
+    }
+
+    /// The magnitude of the number if it is an integer, and fits.
+    pub fn integer(&self) -> Option<u128> {
+        let fraction = (-self.exponent).max(0) as usize;
+        let len = self.digits.len().saturating_sub(fraction);
+        if self.digits[len..].iter().any(|d| *d != 0) {
+            return None;
+        }
+        let mut r: u128 = 0;
+        for d in &self.digits[..len] {
+            r = r.checked_mul(10)?.checked_add(*d as u128)?;
+        }
+        for _ in 0..self.exponent.max(0) {
+            r = r.checked_mul(10)?;
+        }
+        Some(r)
+    }
+
+    /// Multiplies the number by a power of ten.
+    pub fn shift(&mut self, n: i32) {
+        self.exponent += n;
+    }
+
+    /// Rounds the number to `precision` fraction digits. A precision beyond
+    /// the range of exponents keeps every digit.
+    pub fn round(&mut self, precision: usize, mode: Rounding) {
+        let keep = -i32::try_from(precision).unwrap_or(i32::MAX);
+        if self.exponent >= keep {
+            return;
+        }
+        let cut = (keep - self.exponent) as usize;
+        let len = self.digits.len();
+        let (kept, dropped) = self.digits.split_at(len.saturating_sub(cut));
+        // the first dropped digit is an implicit zero when every digit is dropped.
+        let first = if cut > len { 0 } else { dropped[0] };
+        let inexact = dropped.iter().any(|d| *d != 0);
+        let tie = first == 5 && dropped[1..].iter().all(|d| *d == 0);
+        let up = match mode {
+            Rounding::HalfEven => first > 5 || (first == 5 && (!tie || kept.last().is_some_and(|d| d % 2 == 1))),
+            Rounding::HalfUp => first >= 5,
+            Rounding::HalfDown => first > 5 || (first == 5 && !tie),
+            Rounding::Up => inexact,
+            Rounding::Down => false,
+            Rounding::Ceiling => inexact && !self.negative,
+            Rounding::Floor => inexact && self.negative,
+        };
+        let mut digits = kept.to_vec();
+        self.exponent = keep;
+        if up {
+            let mut i = digits.len();
+            loop {
+                if i == 0 {
+                    digits.insert(0, 1);
+                    break;
+                }
+                i -= 1;
+                if digits[i] == 9 {
+                    digits[i] = 0;
+                } else {
+                    digits[i] += 1;
+                    break;
+                }
+            }
+        }
+        self.digits = digits;
+    }
+
+    /// Adds two numbers exactly, keeping the fraction digits of the one with more.
+    pub fn add(&self, other: &Self) -> Self {
+        let exponent = self.exponent.min(other.exponent);
+        let scaled = |n: &Self| {
+            let mut digits = n.digits.clone();
+            digits.extend(std::iter::repeat_n(0, (n.exponent - exponent) as usize));
+            digits
+        };
+        let (a, b) = (scaled(self), scaled(other));
+        let (negative, digits) = if self.negative == other.negative {
+            (self.negative, add_digits(&a, &b))
+        } else if compare_digits(&a, &b) == Ordering::Less {
+            (other.negative, sub_digits(&b, &a))
+        } else {
+            (self.negative, sub_digits(&a, &b))
+        };
+        Self::new(negative, digits, exponent)
+    }
+
+    /// Compares the values of two numbers.
+    pub fn compare(&self, other: &Self) -> std::cmp::Ordering {
+        let difference = self.sub(other);
+        match (difference.is_zero(), difference.negative) {
+            (true, _) => std::cmp::Ordering::Equal,
+            (false, true) => std::cmp::Ordering::Less,
+            (false, false) => std::cmp::Ordering::Greater,
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&Self { negative: !other.negative, ..other.clone() })
+    }
+
+    /// Multiplies two numbers exactly.
+    pub fn mul(&self, other: &Self) -> Self {
+        let mut digits = vec![0u32; self.digits.len() + other.digits.len()];
+        for (i, a) in self.digits.iter().enumerate() {
+            for (j, b) in other.digits.iter().enumerate() {
+                digits[i + j + 1] += (a * b) as u32;
+            }
+        }
+        for i in (1..digits.len()).rev() {
+            digits[i - 1] += digits[i] / 10;
+            digits[i] %= 10;
+        }
+        let digits = digits.into_iter().map(|d| d as u8).collect();
+        Self::new(self.negative != other.negative, digits, self.exponent + other.exponent)
+    }
+
+    /// Divides two numbers, rounding the quotient to `precision` fraction
+    /// digits and removing its trailing zeros, or returns `None` when
+    /// dividing by zero.
+    pub fn div(&self, other: &Self, precision: usize, mode: Rounding) -> Option<Self> {
+        if other.is_zero() {
+            return None;
+        }
+        // the quotient is computed with one more digit, and a last digit
+        // telling whether the rest is zero, so that rounding it is exact.
+        let exponent = -i32::try_from(precision).unwrap_or(i32::MAX) - 1;
+        let mut numerator = self.digits.clone();
+        let mut denominator = other.digits.clone();
+        let shift = self.exponent - other.exponent - exponent;
+        if shift >= 0 {
+            numerator.extend(std::iter::repeat_n(0, shift as usize));
+        } else {
+            denominator.extend(std::iter::repeat_n(0, shift.unsigned_abs() as usize));
+        }
+        let mut quotient = vec![];
+        let mut rest: Vec<u8> = vec![];
+        for d in numerator {
+            rest.push(d);
+            let mut q = 0;
+            while compare_digits(&rest, &denominator) != Ordering::Less {
+                rest = sub_digits(&rest, &denominator);
+                q += 1;
+            }
+            quotient.push(q);
+        }
+        quotient.push(u8::from(rest.iter().any(|d| *d != 0)));
+        let mut n = Self::new(self.negative != other.negative, quotient, exponent - 1);
+        n.round(precision, mode);
+        while n.exponent < 0 && n.digits.last() == Some(&0) {
+            n.digits.pop();
+            n.exponent += 1;
+        }
+        Some(Self::new(n.negative, n.digits, n.exponent))
+    }
+
+    /// A number from its digits, which may have leading zeros. Zero is never negative.
+    fn new(negative: bool, digits: Vec<u8>, exponent: i32) -> Self {
+        let digits: Vec<u8> = digits.into_iter().skip_while(|d| *d == 0).collect();
+        Self { negative: negative && !digits.is_empty(), digits, exponent }
+    }
+
+    /// Writes the magnitude of the number in fixed notation, with at least
+    /// `precision` fraction digits.
+    pub fn fixed(&self, precision: usize) -> String {
+        let fraction_len = (-self.exponent).max(0) as usize;
+        let mut digits: String = self.digits.iter().map(|d| char::from(b'0' + d)).collect();
+        digits.extend(std::iter::repeat_n('0', self.exponent.max(0) as usize));
+        if digits.len() <= fraction_len {
+            digits.insert_str(0, &"0".repeat(fraction_len - digits.len() + 1));
+        }
+        let (integer, fraction) = digits.split_at(digits.len() - fraction_len);
+        let mut r = integer.to_owned();
+        if fraction_len.max(precision) > 0 {
+            r.push('.');
+            r.push_str(fraction);
+            r.extend(std::iter::repeat_n('0', precision.saturating_sub(fraction_len)));
+        }
+        r
+    }
+
+    /// Writes the magnitude of the number in scientific notation, as in
+    /// `1.5e3`, with `precision` fraction digits or as many as needed.
+    pub fn scientific(&self, precision: Option<usize>, mode: Rounding, upper: bool) -> String {
+        let e = if upper { 'E' } else { 'e' };
+        if self.is_zero() {
+            return format!("{}{e}0", Self::default().fixed(precision.unwrap_or(0)));
+        }
+        let mut n = self.clone();
+        let mut exponent = n.exponent + n.digits.len() as i32 - 1;
+        n.shift(-exponent);
+        match precision {
+            Some(precision) => {
+                n.round(precision, mode);
+                // rounding may carry into a second integer digit, as in 9.99 to 10.0.
+                if n.digits.len() as i32 + n.exponent > 1 {
+                    n.shift(-1);
+                    exponent += 1;
+                    n.round(precision, mode);
+                }
+            },
+            None => {
+                while n.digits.last() == Some(&0) {
+                    n.digits.pop();
+                    n.exponent += 1;
+                }
+            },
+        }
+        format!("{}{e}{exponent}", n.fixed(precision.unwrap_or(0)))
+    }
+}
+
+impl fmt::Display for Decimal {
+    /// Writes the number in fixed notation, with its fraction digits.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", if self.negative && !self.is_zero() { "-" } else { "" }, self.fixed(0))
+    }
+}
+
+/// Compares two integers written as digits, which may have leading zeros.
+fn compare_digits(a: &[u8], b: &[u8]) -> Ordering {
+    let len = |d: &[u8]| d.iter().position(|d| *d != 0).map_or(0, |i| d.len() - i);
+    let (len_a, len_b) = (len(a), len(b));
+    len_a.cmp(&len_b).then_with(|| a[a.len() - len_a..].cmp(&b[b.len() - len_b..]))
+}
+
+fn add_digits(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let digit = |d: &[u8], i: usize| if i < d.len() { d[d.len() - 1 - i] } else { 0 };
+    let mut r = vec![];
+    let mut carry = 0;
+    for i in 0..=a.len().max(b.len()) {
+        let sum = digit(a, i) + digit(b, i) + carry;
+        r.push(sum % 10);
+        carry = sum / 10;
+    }
+    r.reverse();
+    r
+}
+
+/// Subtracts `b` from `a`, which is not less than `b`.
+fn sub_digits(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut r = a.to_vec();
+    let mut borrow = 0;
+    for i in 0..r.len() {
+        let j = r.len() - 1 - i;
+        let d = if i < b.len() { b[b.len() - 1 - i] } else { 0 } + borrow;
+        borrow = u8::from(r[j] < d);
+        r[j] = r[j] + 10 * borrow - d;
+    }
+    r
+}
+
+/// The digits of an integer part, without the `,` or `_` separators of its
+/// groups of three digits, or `None` if they are not grouped by three.
+fn ungrouped(integer: &str) -> Option<Cow<'_, str>> {
+    let Some(separator) = integer.chars().find(|ch| matches!(ch, ',' | '_')) else {
+        return Some(Cow::Borrowed(integer));
+    };
+    let mut groups = integer.split(separator);
+    let first = groups.next()?;
+    let grouped = (1..=3).contains(&first.len()) && groups.clone().all(|group| group.len() == 3);
+    grouped.then(|| Cow::Owned(integer.replace(separator, "")))
+}
+
+impl Default for Decimal {
+    /// Zero.
+    fn default() -> Self {
+        Self { negative: false, digits: vec![], exponent: 0 }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Rounding;
+    use super::Decimal;
+
+    #[test]
+    fn decimals() {
+        let fixed = |s: &str, shift: i32, precision: Option<usize>| {
+            let mut n = Decimal::parse(s).unwrap();
+            n.shift(shift);
+            if let Some(p) = precision {
+                n.round(p, Rounding::HalfEven);
+            }
+            n.fixed(precision.unwrap_or(0))
+        };
+        assert_eq!("12.34", fixed("0.1234", 2, None));
+        assert_eq!("50", fixed(".5", 2, None));
+        assert_eq!("0.05", fixed("5e-2", 0, None));
+        assert_eq!("1500", fixed("1.5e3", 0, None));
+        assert_eq!("0.12", fixed("0.125", 0, Some(2)));
+        assert_eq!("0.14", fixed("0.135", 0, Some(2)));
+        assert_eq!("0.13", fixed("0.1251", 0, Some(2)));
+        assert_eq!("10.0", fixed("9.96", 0, Some(1)));
+        assert_eq!("0", fixed("0.4", 0, Some(0)));
+        assert_eq!("0.000", fixed("0.0004", 0, Some(3)));
+        assert_eq!("2.500", fixed("2.5", 0, Some(3)));
+        assert_eq!("0", fixed("0", 0, None));
+        let mut n = Decimal::parse("0.125").unwrap();
+        n.round(1 << 31, Rounding::HalfEven);
+        n.round(usize::MAX, Rounding::HalfEven);
+        assert_eq!("0.125", n.to_string());
+        let scientific = |s: &str, precision| Decimal::parse(s).unwrap().scientific(precision, Rounding::HalfEven, false);
+        assert_eq!(["1.2e3", "1.23e-4", "1.00e1", "0e0", "5.000e0", "1.234e-1"], [
+            scientific("1200", None),
+            scientific("0.000123", None),
+            scientific("9.996", Some(2)),
+            scientific("0", None),
+            scientific("5", Some(3)),
+            scientific("0.12345", Some(3)),
+        ]);
+        let round = |s: &str, mode| {
+            let mut n = Decimal::parse(s).unwrap();
+            n.round(1, mode);
+            format!("{}{}", if n.negative { "-" } else { "" }, n.fixed(1))
+        };
+        let modes = [Rounding::HalfEven, Rounding::HalfUp, Rounding::HalfDown, Rounding::Up, Rounding::Down, Rounding::Ceiling, Rounding::Floor];
+        assert_eq!(["0.2", "0.3", "0.2", "0.3", "0.2", "0.3", "0.2"], modes.map(|mode| round("0.25", mode)));
+        assert_eq!(["-0.2", "-0.3", "-0.2", "-0.3", "-0.2", "-0.2", "-0.3"], modes.map(|mode| round("-0.25", mode)));
+        assert_eq!(["0.0", "0.0", "0.0", "0.1", "0.0", "0.1", "0.0"], modes.map(|mode| round("0.001", mode)));
+        let n = |s: &str| Decimal::parse(s).unwrap();
+        assert_eq!(["2.50", "-0.5", "0", "-3.00", "1.5"], [
+            n("1.50").add(&n("1")), n("0.5").sub(&n("1")), n("-2").add(&n("2")), n("1.50").mul(&n("-2")), n("3e-1").mul(&n("5")),
+        ].map(|n| n.to_string()));
+        let div = |a, b| n(a).div(&n(b), 10, Rounding::HalfEven).map(|n| n.to_string());
+        assert_eq!([Some("12.34"), Some("0.3333333333"), Some("-0.6666666667"), Some("250"), None].map(|n| n.map(String::from)), [
+            div("1234", "100"), div("1", "3"), div("-2", "3"), div("5", "0.02"), div("1", "0.0"),
+        ]);
+        assert!(Decimal::parse("1.2.3").is_none() && Decimal::parse("").is_none() && Decimal::parse("-").is_none());
+        assert_eq!(["-1234567.5", "1000"], [n(" -1,234,567.5 "), n("1_000")].map(|n| n.to_string()));
+        assert!(["1,23", "1234,567", "1,000_000", ",100"].iter().all(|s| Decimal::parse(s).is_none()));
+    }
+}
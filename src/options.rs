@@ -0,0 +1,664 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::{Resolution, Resolver, SpellOut};
+use crate::audit::Observer;
+use crate::choose::{entropy, Random};
+use crate::filters::{dedent, Custom, CustomFilters};
+use crate::resolver::{Coercions, Resolvers};
+use crate::spellout::Speller;
+
+/// Options for [`LateFormat::late_format_with`](crate::LateFormat::late_format_with)
+/// and [`Template::parse_with`](crate::Template::parse_with).
+///
+/// # Example
+///
+/// ```
+/// use late_format::{LateFormat, FormatOptions};
+/// use maplit::hashmap;
+/// let user_string = r#"
+///     Hello, {name}!
+///       Bye.
+/// "#;
+/// let options = FormatOptions::new().dedent(true);
+/// assert_eq!("Hello, x!\n  Bye.\n", user_string.late_format_with(hashmap!{"name".into() => "x".into()}, &options));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FormatOptions {
+    pub(crate) dedent: bool,
+    pub(crate) normalize_line_endings: bool,
+    pub(crate) limits: Limits,
+    pub(crate) budget: Budget,
+    pub(crate) profile: Profile,
+    pub(crate) missing: Missing,
+    pub(crate) lookup: Lookup,
+    pub(crate) aliases: HashMap<String, String>,
+    pub(crate) resolvers: Resolvers,
+    pub(crate) coercions: Coercions,
+    pub(crate) filters: CustomFilters,
+    pub(crate) sensitive: HashSet<String>,
+    pub(crate) masking: Masking,
+    pub(crate) observer: Option<Observer>,
+    pub(crate) locale: Option<String>,
+    pub(crate) speller: Option<Speller>,
+    pub(crate) rounding: Rounding,
+    pub(crate) numbers: Numbers,
+    pub(crate) random: Option<Random>,
+}
+
+impl FormatOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Strips the common leading indentation of the template before
+    /// substitution, as described in [`dedent`](crate::dedent). Implies
+    /// [`FormatOptions::normalize_line_endings`].
+    pub fn dedent(mut self, value: bool) -> Self {
+        self.dedent = value;
+        self
+    }
+
+    /// Converts `\r\n` line endings of the template into `\n`.
+    pub fn normalize_line_endings(mut self, value: bool) -> Self {
+        self.normalize_line_endings = value;
+        self
+    }
+
+    /// Restricts the size of the template, for templates supplied by end users.
+    pub fn limits(mut self, value: Limits) -> Self {
+        self.limits = value;
+        self
+    }
+
+    /// Restricts the work done when rendering, for templates supplied by end users.
+    pub fn budget(mut self, value: Budget) -> Self {
+        self.budget = value;
+        self
+    }
+
+    /// Selects how parameters missing from the arguments are rendered.
+    pub fn missing(mut self, value: Missing) -> Self {
+        self.missing = value;
+        self
+    }
+
+    /// Selects how parameter names are matched against argument names.
+    pub fn lookup(mut self, value: Lookup) -> Self {
+        self.lookup = value;
+        self
+    }
+
+    /// Registers an alias, so that a parameter named `alias` resolves to the
+    /// argument named `target` when there is no argument named `alias`. This
+    /// keeps templates working after a parameter is renamed in code.
+    /// Aliases are not followed transitively.
+    ///
+    /// ```
+    /// use late_format::{FormatOptions, LateFormat};
+    /// use maplit::hashmap;
+    /// let options = FormatOptions::new().alias("username", "user.name");
+    /// let arguments = hashmap!{"user.name".into() => "x".into()};
+    /// assert_eq!("x x", "{username} {user.name}".late_format_with(arguments, &options));
+    /// ```
+    pub fn alias(mut self, alias: impl Into<String>, target: impl Into<String>) -> Self {
+        self.aliases.insert(alias.into(), target.into());
+        self
+    }
+
+    /// Registers a resolver for the placeholders of a namespace, such as
+    /// `{env:HOME}` for the `env` namespace, so that templates can use values
+    /// from outside the arguments with clear provenance. The placeholder
+    /// `{namespace:key}` is a parameter named `namespace:key`, resolved by
+    /// the resolver rather than by the arguments.
+    ///
+    /// Namespaces are recognized when the template is parsed with these options,
+    /// and are disabled by [`Profile::Untrusted`].
+    pub fn resolver(mut self, namespace: impl Into<String>, resolver: impl Resolver + 'static) -> Self {
+        self.resolvers.insert(namespace.into(), Arc::new(resolver));
+        self
+    }
+
+    /// Registers a coercion of the argument of a parameter, which transforms
+    /// it before the filters of the placeholders and the conditions of the
+    /// blocks see it, so that rules such as lowercasing `{email}` or masking
+    /// `{card}` apply to every template rendered with the options. The
+    /// coercion receives each string of a list or map argument, and the
+    /// coercions of the same parameter run in order of registration.
+    ///
+    /// ```
+    /// use late_format::{FormatOptions, LateFormat};
+    /// use maplit::hashmap;
+    /// let options = FormatOptions::new()
+    ///     .coerce("email", |email| email.trim().to_lowercase())
+    ///     .coerce("card", |card| format!("•••• {}", &card[card.len().saturating_sub(4)..]));
+    /// let arguments = hashmap!{"email".into() => " Ana@Example.COM".into(), "card".into() => "4111111111111111".into()};
+    /// assert_eq!("ana@example.com, •••• 1111", "{email}, {card}".late_format_with(arguments, &options));
+    /// ```
+    pub fn coerce(mut self, name: impl Into<String>, coercion: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.coercions.insert(name.into(), Arc::new(coercion));
+        self
+    }
+
+    /// Registers a filter, which receives the argument and the arguments
+    /// following its colon, so that applications can extend the filters
+    /// of their templates, as in `{title | slug}` or `{price | money:"EUR"}`.
+    ///
+    /// Filters are recognized when the template is parsed with these options,
+    /// and are disabled by [`Profile::Untrusted`]. The built-in filters of the
+    /// same name take precedence.
+    ///
+    /// ```
+    /// use late_format::{FormatOptions, LateFormat};
+    /// use maplit::hashmap;
+    /// let options = FormatOptions::new()
+    ///     .filter("slug", |value, _| value.to_lowercase().replace(' ', "-"))
+    ///     .filter("wrap", |value, arguments| format!("{}{value}{}", arguments[0], arguments[1]));
+    /// let arguments = hashmap!{"title".into() => "Hello World".into()};
+    /// assert_eq!("[hello-world]", "{title | slug | wrap:'[',']'}".late_format_with(arguments, &options));
+    /// ```
+    pub fn filter(mut self, name: impl Into<String>, filter: impl Fn(&str, &[String]) -> String + Send + Sync + 'static) -> Self {
+        self.filters.insert(name.into(), Arc::new(filter));
+        self
+    }
+
+    /// Sets the locale of the arguments, such as `ru` or `pt-BR`, which
+    /// selects the plural rules of `{plural}` blocks. The default is `en`.
+    pub fn locale(mut self, value: impl Into<String>) -> Self {
+        self.locale = Some(value.into());
+        self
+    }
+
+    /// Sets the speller of the `spellout` filter for languages other than English.
+    pub fn spell_out(mut self, speller: impl SpellOut + 'static) -> Self {
+        self.speller = Some(Speller(Arc::new(speller)));
+        self
+    }
+
+    /// Selects how format specs such as `{price:.2}` round numbers.
+    ///
+    /// ```
+    /// use late_format::{FormatOptions, LateFormat, Rounding};
+    /// use maplit::hashmap;
+    /// let arguments = hashmap!{"price".into() => "2.345".into()};
+    /// assert_eq!("2.34", "{price:.2}".late_format(arguments.clone()));
+    /// let options = FormatOptions::new().rounding(Rounding::HalfUp);
+    /// assert_eq!("2.35", "{price:.2}".late_format_with(arguments, &options));
+    /// ```
+    pub fn rounding(mut self, value: Rounding) -> Self {
+        self.rounding = value;
+        self
+    }
+
+    /// Selects what a numeric format spec or filter does with an argument
+    /// that is not a number.
+    ///
+    /// ```
+    /// use late_format::{FormatOptions, Numbers, RenderErrorKind, Template};
+    /// use maplit::hashmap;
+    /// let template = Template::parse("{price:.2}").unwrap();
+    /// let options = FormatOptions::new().numbers(Numbers::Strict);
+    /// assert_eq!("1234.50", template.render_with(&hashmap!{"price".into() => "1,234.5".into()}, &options).unwrap());
+    /// let error = template.render_with(&hashmap!{"price".into() => "n/a".into()}, &options).unwrap_err();
+    /// assert_eq!(&RenderErrorKind::NotANumber("price".into()), error.kind());
+    /// ```
+    pub fn numbers(mut self, value: Numbers) -> Self {
+        self.numbers = value;
+        self
+    }
+
+    pub(crate) fn get_locale(&self) -> &str {
+        self.locale.as_deref().unwrap_or("en")
+    }
+
+    /// Marks a parameter as sensitive, so that it is masked as selected by
+    /// [`FormatOptions::masking`]. A name ending with `:`, such as `secret:`,
+    /// marks every parameter of a namespace. The keys and indices of a
+    /// sensitive parameter, as in `{creds.password}` and `{tokens[1]}`, and the
+    /// variables of a `{for}` loop over a sensitive list are masked too.
+    pub fn sensitive(mut self, name: impl Into<String>) -> Self {
+        self.sensitive.insert(name.into());
+        self
+    }
+
+    /// Selects how sensitive parameters are rendered. Options used for logs
+    /// and debugging renders can mask them while the options of the real
+    /// output render them plainly.
+    ///
+    /// ```
+    /// use late_format::{FormatOptions, LateFormat, Masking};
+    /// use maplit::hashmap;
+    /// let options = FormatOptions::new().sensitive("password");
+    /// let arguments = hashmap!{"user".into() => "x".into(), "password".into() => "hunter2".into()};
+    /// assert_eq!("x:hunter2", "{user}:{password}".late_format_with(arguments.clone(), &options));
+    /// let options = options.masking(Masking::Dots);
+    /// assert_eq!("x:•••", "{user}:{password}".late_format_with(arguments, &options));
+    /// ```
+    pub fn masking(mut self, value: Masking) -> Self {
+        self.masking = value;
+        self
+    }
+
+    /// Whether a parameter is marked by [`FormatOptions::sensitive`], or is
+    /// a key or index of one, as in `creds.password` or `tokens.1`.
+    pub(crate) fn is_sensitive(&self, name: &str) -> bool {
+        if self.sensitive.contains(name) {
+            return true;
+        }
+        let parent = name.match_indices('.').any(|(i, _)| self.sensitive.contains(&name[..i]));
+        parent || name.split_once(':').is_some_and(|(namespace, _)| self.sensitive.contains(&name[..namespace.len() + 1]))
+    }
+
+    /// Sets a callback invoked with the [`Resolution`] of each parameter of
+    /// a template when it is rendered, so that services can audit and meter
+    /// which parameters are used. It is not invoked for loop variables.
+    pub fn observer(mut self, observer: impl Fn(&Resolution<'_>) + Send + Sync + 'static) -> Self {
+        self.observer = Some(Observer(Arc::new(observer)));
+        self
+    }
+
+    /// Sets the source of the random numbers selecting the variants of the
+    /// `{choose}` blocks, such as a generator of the application. By default,
+    /// the numbers are random and differ between renders.
+    pub fn random(mut self, random: impl Fn() -> u64 + Send + Sync + 'static) -> Self {
+        self.random = Some(Random(Arc::new(random)));
+        self
+    }
+
+    /// Selects the variants of the `{choose}` blocks by the random numbers of
+    /// a seed, so that a sequence of renders with the options, and with the
+    /// options cloned from them, is reproducible in tests.
+    ///
+    /// ```
+    /// use late_format::{FormatOptions, Template};
+    /// use maplit::hashmap;
+    /// let template = Template::parse("{choose}{variant 60%}Hi{variant 40%}Hello{end}, {name}!").unwrap();
+    /// let arguments = hashmap!{"name".into() => "x".into()};
+    /// let render = |options: &FormatOptions| (0..8).map(|_| template.render_with(&arguments, options).unwrap()).collect::<Vec<_>>();
+    /// assert_eq!(render(&FormatOptions::new().seed(7)), render(&FormatOptions::new().seed(7)));
+    /// assert!(render(&FormatOptions::new().seed(7)).iter().all(|r| r == "Hi, x!" || r == "Hello, x!"));
+    /// ```
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.random = Some(Random::seeded(seed));
+        self
+    }
+
+    /// The next random number for a `{choose}` block.
+    pub(crate) fn next_random(&self) -> u64 {
+        self.random.as_ref().map_or_else(entropy, |random| (random.0)())
+    }
+
+    /// The resolver of a namespace, if enabled.
+    pub(crate) fn namespace(&self, namespace: &str) -> Option<&dyn Resolver> {
+        if self.profile == Profile::Untrusted {
+            return None;
+        }
+        self.resolvers.get(namespace)
+    }
+
+    /// The filter registered under a name, if enabled.
+    pub(crate) fn custom_filter(&self, name: &str) -> Option<Custom> {
+        if self.profile == Profile::Untrusted {
+            return None;
+        }
+        self.filters.get(name)
+    }
+
+    /// Selects the capabilities available to the template, and applies the
+    /// limits and budget of the profile. Options set afterwards override them.
+    pub fn profile(mut self, value: Profile) -> Self {
+        self.profile = value;
+        self.limits = value.limits();
+        self.budget = value.budget();
+        self
+    }
+
+    pub(crate) fn preprocess<'a>(&self, source: &'a str) -> Cow<'a, str> {
+        if self.dedent {
+            dedent(source).into()
+        } else if self.normalize_line_endings {
+            source.replace("\r\n", "\n").into()
+        } else {
+            source.into()
+        }
+    }
+}
+
+/// How parameter names are matched against argument names, for catalogs
+/// whose names are spelled inconsistently. An argument whose name matches
+/// exactly is always preferred; otherwise, the argument names are compared
+/// after normalization. If several argument names normalize alike, the
+/// first in lexicographic order is used.
+///
+/// Unicode normalization forms are not applied, so names should be
+/// written in a consistent form, such as NFC.
+///
+/// # Example
+///
+/// ```
+/// use late_format::{FormatOptions, LateFormat, Lookup};
+/// use maplit::hashmap;
+/// let options = FormatOptions::new().lookup(Lookup::normalized());
+/// let arguments = hashmap!{"user_id".into() => "x".into()};
+/// assert_eq!("x x x", "{UserId} {user_id} {user-id}".late_format_with(arguments, &options));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Lookup {
+    case_insensitive: bool,
+    fold_separators: bool,
+}
+
+impl Lookup {
+    /// Exact matching only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Case-insensitive matching, ignoring `-` and `_`.
+    pub fn normalized() -> Self {
+        Self::new().case_insensitive(true).fold_separators(true)
+    }
+
+    /// Compares names in lowercase, using Unicode case mapping.
+    pub fn case_insensitive(mut self, value: bool) -> Self {
+        self.case_insensitive = value;
+        self
+    }
+
+    /// Ignores `-` and `_`, so that `user_id`, `user-id` and `userid` match.
+    pub fn fold_separators(mut self, value: bool) -> Self {
+        self.fold_separators = value;
+        self
+    }
+
+    pub(crate) fn is_exact(&self) -> bool {
+        !self.case_insensitive && !self.fold_separators
+    }
+
+    pub(crate) fn normalize<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        let mut r = Cow::Borrowed(name);
+        if self.fold_separators && name.contains(['-', '_']) {
+            r = name.replace(['-', '_'], "").into();
+        }
+        if self.case_insensitive && r.chars().any(|ch| ch.is_uppercase()) {
+            r = r.to_lowercase().into();
+        }
+        r
+    }
+}
+
+/// How a parameter missing from the arguments is rendered.
+///
+/// # Example
+///
+/// ```
+/// use late_format::{FormatOptions, LateFormat, Missing};
+/// use maplit::hashmap;
+/// let options = FormatOptions::new().missing(Missing::Marker);
+/// assert_eq!("Hi <missing:user_id>", "Hi {user_id}".late_format_with(hashmap!{}, &options));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Missing {
+    /// Renders the string `None`.
+    #[default]
+    None,
+    /// Renders a stable marker naming the parameter, such as `<missing:user_id>`,
+    /// which keeps snapshot tests of partially populated templates readable.
+    Marker,
+    /// Fails with [`RenderErrorKind::MissingArgument`](crate::RenderErrorKind::MissingArgument).
+    /// [`LateFormat`](crate::LateFormat) instead keeps the remaining text as is.
+    Error,
+}
+
+/// How the parameters marked by [`FormatOptions::sensitive`] are rendered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Masking {
+    /// Renders the arguments as is.
+    #[default]
+    Plain,
+    /// Renders `•••` in place of the arguments.
+    Dots,
+    /// Renders `#` followed by a 64-bit FNV-1a hash of the argument in
+    /// hexadecimal, which tells whether two renders used the same value.
+    /// The hash is not cryptographic, and short values can be recovered from it.
+    Hash,
+}
+
+impl Masking {
+    /// The text standing for a sensitive argument.
+    pub(crate) fn mask(&self, value: &str) -> String {
+        match self {
+            Self::Plain => value.to_owned(),
+            Self::Dots => "•••".to_owned(),
+            Self::Hash => format!("#{:016x}", fnv1a(value)),
+        }
+    }
+}
+
+/// The 64-bit FNV-1a hash of a text, which does not depend on the platform
+/// or on the version of Rust.
+pub(crate) fn fnv1a(text: &str) -> u64 {
+    fnv1a_bytes(text.bytes())
+}
+
+/// The hash of [`fnv1a`] of a sequence of bytes.
+pub(crate) fn fnv1a_bytes(bytes: impl Iterator<Item = u8>) -> u64 {
+    bytes.fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+/// How numbers are rounded to the precision of a format spec. Numbers are
+/// rounded from their decimal text, so `2.675` is a tie between `2.67` and
+/// `2.68`, unlike the binary floating-point value of the same text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Rounding {
+    /// To the nearest, ties to the even digit, as in `0.125` to `0.12`.
+    #[default]
+    HalfEven,
+    /// To the nearest, ties away from zero, as in `0.125` to `0.13`.
+    HalfUp,
+    /// To the nearest, ties toward zero, as in `0.125` to `0.12`.
+    HalfDown,
+    /// Away from zero.
+    Up,
+    /// Toward zero, truncating.
+    Down,
+    /// Toward positive infinity.
+    Ceiling,
+    /// Toward negative infinity.
+    Floor,
+}
+
+/// What a numeric format spec, such as `{n:.2}` or `{n:x}`, or a numeric
+/// filter, such as `add` or `roman`, does with an argument that is not a
+/// number. Arguments are numbers if they are written as `-12`, `0.5`, `1.5e3`
+/// or `1,234.5`, with optional surrounding whitespace.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Numbers {
+    /// Keeps the argument as is, only padding it to the width of the spec.
+    #[default]
+    Lenient,
+    /// Fails with [`RenderErrorKind::NotANumber`](crate::RenderErrorKind::NotANumber).
+    /// [`LateFormat`](crate::LateFormat) instead keeps the remaining text as is.
+    Strict,
+}
+
+/// A set of capabilities available to templates.
+///
+/// # Example
+///
+/// ```
+/// use late_format::{FormatOptions, Profile, Template};
+/// let options = FormatOptions::new().profile(Profile::Untrusted);
+/// assert!(Template::parse_with(&"{a}".repeat(2000), &options).is_err());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Profile {
+    /// Every capability is available, with no limits.
+    #[default]
+    Trusted,
+    /// For templates authored by end users: only plain substitution, escaped
+    /// forms, raw blocks, `{if}` and `{for}` blocks and built-in filters are available, under
+    /// [`Limits::untrusted`] and [`Budget::untrusted`]. Capabilities that
+    /// reach outside of the given arguments, or that run application code,
+    /// such as [`FormatOptions::resolver`] and [`FormatOptions::filter`], are disabled.
+    Untrusted,
+}
+
+impl Profile {
+    /// The default limits of the profile.
+    pub fn limits(&self) -> Limits {
+        match self {
+            Self::Trusted => Limits::new(),
+            Self::Untrusted => Limits::untrusted(),
+        }
+    }
+
+    /// The default budget of the profile.
+    pub fn budget(&self) -> Budget {
+        match self {
+            Self::Trusted => Budget::new(),
+            Self::Untrusted => Budget::untrusted(),
+        }
+    }
+}
+
+/// Limits enforced when parsing a template, so that templates supplied by
+/// end users cannot exhaust resources. Exceeding a limit is
+/// a [`ParseError`](crate::ParseError); [`LateFormat`](crate::LateFormat) instead
+/// keeps the offending text as is.
+///
+/// No limit is set by default.
+///
+/// # Example
+///
+/// ```
+/// use late_format::{FormatOptions, Limits, Template};
+/// let options = FormatOptions::new().limits(Limits::new().max_placeholders(2));
+/// assert!(Template::parse_with("{a}{b}", &options).is_ok());
+/// assert!(Template::parse_with("{a}{b}{c}", &options).is_err());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub(crate) max_source_len: Option<usize>,
+    pub(crate) max_placeholders: Option<usize>,
+    pub(crate) max_name_len: Option<usize>,
+    pub(crate) max_nesting: Option<usize>,
+    pub(crate) max_width: Option<usize>,
+    pub(crate) max_precision: Option<usize>,
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits suitable for untrusted templates: 64 KiB of source,
+    /// 1024 placeholders, 128 bytes per parameter name, blocks
+    /// nested 32 deep, and widths up to 256 and precisions up to 32
+    /// in format specs.
+    pub fn untrusted() -> Self {
+        Self::new()
+            .max_source_len(64 * 1024)
+            .max_placeholders(1024)
+            .max_name_len(128)
+            .max_nesting(32)
+            .max_width(256)
+            .max_precision(32)
+    }
+
+    /// The maximum length of the template source in bytes.
+    pub fn max_source_len(mut self, value: usize) -> Self {
+        self.max_source_len = Some(value);
+        self
+    }
+
+    /// The maximum number of placeholders in the template.
+    pub fn max_placeholders(mut self, value: usize) -> Self {
+        self.max_placeholders = Some(value);
+        self
+    }
+
+    /// The maximum length of a parameter name in bytes.
+    pub fn max_name_len(mut self, value: usize) -> Self {
+        self.max_name_len = Some(value);
+        self
+    }
+
+    /// The maximum depth of nested blocks and `{param_name:-default}` placeholders.
+    pub fn max_nesting(mut self, value: usize) -> Self {
+        self.max_nesting = Some(value);
+        self
+    }
+
+    /// The maximum width of a format spec. A `{name}` width over the
+    /// limit is reduced to it when rendering.
+    pub fn max_width(mut self, value: usize) -> Self {
+        self.max_width = Some(value);
+        self
+    }
+
+    /// The maximum precision of a format spec. A `{name}` precision over
+    /// the limit is reduced to it when rendering.
+    pub fn max_precision(mut self, value: usize) -> Self {
+        self.max_precision = Some(value);
+        self
+    }
+}
+
+/// Limits enforced when rendering a template, so that pathological templates
+/// cannot hold a thread for long. Exceeding the budget is
+/// a [`RenderError`](crate::RenderError); [`LateFormat`](crate::LateFormat) instead
+/// keeps the remaining text as is.
+///
+/// Each substitution of a placeholder and each application of a filter counts
+/// as one step, and so does each 256 characters of the width and precision
+/// of a format spec and of the output of a filter, which bounds the memory
+/// a render takes. No limit is set by default.
+///
+/// # Example
+///
+/// ```
+/// use late_format::{Budget, FormatOptions, RenderErrorKind, Template};
+/// use maplit::hashmap;
+/// let options = FormatOptions::new().budget(Budget::new().max_steps(2));
+/// let template = Template::parse("{a}{b}{c}").unwrap();
+/// let error = template.render_with(&hashmap!{}, &options).unwrap_err();
+/// assert_eq!(&RenderErrorKind::BudgetExceeded, error.kind());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub(crate) max_steps: Option<usize>,
+    pub(crate) max_duration: Option<Duration>,
+}
+
+impl Budget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A budget suitable for untrusted templates: 100 000 steps
+    /// and 100 milliseconds.
+    pub fn untrusted() -> Self {
+        Self::new()
+            .max_steps(100_000)
+            .max_duration(Duration::from_millis(100))
+    }
+
+    /// The maximum number of substitution steps.
+    pub fn max_steps(mut self, value: usize) -> Self {
+        self.max_steps = Some(value);
+        self
+    }
+
+    /// The maximum wall-clock duration of a render.
+    pub fn max_duration(mut self, value: Duration) -> Self {
+        self.max_duration = Some(value);
+        self
+    }
+}
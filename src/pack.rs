@@ -0,0 +1,278 @@
+use std::fmt;
+use crate::{FormatOptions, ParseError, Template, TemplateSet};
+use crate::options::fnv1a_bytes;
+
+/// The first bytes of a pack.
+const MAGIC: &[u8; 8] = b"LATEPACK";
+
+/// The version of the format written by [`TemplateSet::pack`].
+const VERSION: u32 = 1;
+
+/// The length of the header: the magic bytes, the version, the number of
+/// templates and the checksum.
+const HEADER: usize = 24;
+
+/// The length of an entry of the index: the offset of the key in the data,
+/// and the lengths of the key and of the source following it.
+const ENTRY: usize = 16;
+
+/// The templates of a pack written by [`TemplateSet::pack`], read without
+/// parsing them, so that a large catalog, such as a memory-mapped file,
+/// only parses the templates it renders.
+///
+/// A pack is a header, with a version and a checksum of the rest, an index
+/// of the keys in order, and the keys and sources of the templates. The
+/// checksum is the 64-bit FNV-1a hash of the bytes after the header, which
+/// detects corruption and truncation, but not deliberate tampering.
+/// Options attached to the templates are not packed, and the templates are
+/// parsed again with the options given to [`PackedTemplateSet::get_with`],
+/// which must recognize the same namespaces.
+///
+/// ```
+/// use late_format::{PackedTemplateSet, TemplateSet};
+/// let mut set = TemplateSet::new();
+/// set.add("greeting", "Hello, {name}!").unwrap();
+/// set.add("farewell", "Bye, {name}.").unwrap();
+/// let bytes = set.pack();
+/// let packed = PackedTemplateSet::open(&bytes).unwrap();
+/// assert_eq!(vec!["farewell", "greeting"], packed.keys().collect::<Vec<_>>());
+/// assert_eq!("Hello, {name}!", packed.get("greeting").unwrap().unwrap().source());
+/// assert_eq!(2, TemplateSet::unpack(&bytes).unwrap().len());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct PackedTemplateSet<'a> {
+    index: &'a [u8],
+    data: &'a [u8],
+}
+
+/// An error produced by [`PackedTemplateSet::open`] and
+/// [`TemplateSet::unpack`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PackError {
+    /// The bytes do not start with the header of a pack.
+    NotAPack,
+    /// The pack is of a later version of the format.
+    UnsupportedVersion(u32),
+    /// The checksum does not match, the index does not fit the data, or its
+    /// keys are not in order.
+    Corrupted,
+    /// A template of the pack, of this key, does not parse.
+    Parse(String, ParseError),
+}
+
+impl TemplateSet {
+    /// Writes the templates in the binary format of [`PackedTemplateSet`],
+    /// for applications to ship catalogs and load them without parsing
+    /// every template.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a key or source is longer than 4 GiB.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut index = Vec::with_capacity(self.len() * ENTRY);
+        let mut data = vec![];
+        for (key, template) in self.iter() {
+            let length = |s: &str| u32::try_from(s.len()).expect("template too long to pack");
+            index.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            index.extend_from_slice(&length(key).to_le_bytes());
+            index.extend_from_slice(&length(template.source()).to_le_bytes());
+            data.extend_from_slice(key.as_bytes());
+            data.extend_from_slice(template.source().as_bytes());
+        }
+        let count = u32::try_from(self.len()).expect("too many templates to pack");
+        let checksum = fnv1a_bytes(index.iter().chain(&data).copied());
+        let mut r = Vec::with_capacity(HEADER + index.len() + data.len());
+        r.extend_from_slice(MAGIC);
+        r.extend_from_slice(&VERSION.to_le_bytes());
+        r.extend_from_slice(&count.to_le_bytes());
+        r.extend_from_slice(&checksum.to_le_bytes());
+        r.extend_from_slice(&index);
+        r.extend_from_slice(&data);
+        r
+    }
+
+    /// Reads a pack written by [`TemplateSet::pack`], parsing every template.
+    pub fn unpack(bytes: &[u8]) -> Result<Self, PackError> {
+        Self::unpack_with(bytes, &FormatOptions::default())
+    }
+
+    /// Reads a pack, parsing every template with the options.
+    pub fn unpack_with(bytes: &[u8], options: &FormatOptions) -> Result<Self, PackError> {
+        let packed = PackedTemplateSet::open(bytes)?;
+        (0..packed.len()).map(|i| {
+            let (key, source) = packed.entry(i);
+            let template = Template::parse_with(source, options).map_err(|e| PackError::Parse(key.to_owned(), e))?;
+            Ok((key, template))
+        }).collect()
+    }
+}
+
+impl<'a> PackedTemplateSet<'a> {
+    /// Reads the header and index of a pack, checking its version and
+    /// checksum, that its keys and sources are valid UTF-8, and that its
+    /// keys are in strictly increasing order.
+    pub fn open(bytes: &'a [u8]) -> Result<Self, PackError> {
+        let header = bytes.get(..HEADER).filter(|header| header.starts_with(MAGIC)).ok_or(PackError::NotAPack)?;
+        let word = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+        let version = word(8);
+        if version > VERSION {
+            return Err(PackError::UnsupportedVersion(version));
+        }
+        let count = word(12) as usize;
+        let checksum = u64::from_le_bytes(header[16..24].try_into().unwrap());
+        let rest = &bytes[HEADER..];
+        if fnv1a_bytes(rest.iter().copied()) != checksum {
+            return Err(PackError::Corrupted);
+        }
+        let index = count.checked_mul(ENTRY).and_then(|len| rest.get(..len)).ok_or(PackError::Corrupted)?;
+        let packed = Self { index, data: &rest[index.len()..] };
+        let mut previous = None;
+        for i in 0..count {
+            let (offset, key, source) = packed.range(i);
+            let text = key.checked_add(source).and_then(|len| offset.checked_add(len)).and_then(|end| packed.data.get(offset..end));
+            let Some((Ok(key), Ok(_))) = text.map(|text| (std::str::from_utf8(&text[..key]), std::str::from_utf8(&text[key..]))) else {
+                return Err(PackError::Corrupted);
+            };
+            // the index is searched by key.
+            if previous.is_some_and(|previous| previous >= key) {
+                return Err(PackError::Corrupted);
+            }
+            previous = Some(key);
+        }
+        Ok(packed)
+    }
+
+    /// The number of templates.
+    pub fn len(&self) -> usize {
+        self.index.len() / ENTRY
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// The keys, in order.
+    pub fn keys(&self) -> impl Iterator<Item = &'a str> + '_ {
+        (0..self.len()).map(|i| self.entry(i).0)
+    }
+
+    /// The source of the template of a key, without parsing it.
+    pub fn source(&self, key: &str) -> Option<&'a str> {
+        let i = self.find(key)?;
+        Some(self.entry(i).1)
+    }
+
+    /// Parses the template of a key.
+    pub fn get(&self, key: &str) -> Option<Result<Template, ParseError>> {
+        self.get_with(key, &FormatOptions::default())
+    }
+
+    /// Parses the template of a key with the options.
+    pub fn get_with(&self, key: &str, options: &FormatOptions) -> Option<Result<Template, ParseError>> {
+        self.source(key).map(|source| Template::parse_with(source, options))
+    }
+
+    /// The offset in the data and the lengths of the key and source of an
+    /// entry of the index.
+    fn range(&self, i: usize) -> (usize, usize, usize) {
+        let entry = &self.index[i * ENTRY..(i + 1) * ENTRY];
+        let offset = u64::from_le_bytes(entry[..8].try_into().unwrap());
+        let length = |j: usize| u32::from_le_bytes(entry[j..j + 4].try_into().unwrap()) as usize;
+        (usize::try_from(offset).unwrap_or(usize::MAX), length(8), length(12))
+    }
+
+    /// The key and source of an entry, which [`PackedTemplateSet::open`]
+    /// checked.
+    fn entry(&self, i: usize) -> (&'a str, &'a str) {
+        let (offset, key, source) = self.range(i);
+        let data: &'a [u8] = self.data;
+        let text = &data[offset..offset + key + source];
+        // SAFETY: `open` checked that the key and the source of every entry
+        // are valid UTF-8, and the index and data of a pack do not change.
+        unsafe { (std::str::from_utf8_unchecked(&text[..key]), std::str::from_utf8_unchecked(&text[key..])) }
+    }
+
+    /// The position of a key in the index, which is in order of key.
+    fn find(&self, key: &str) -> Option<usize> {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let middle = (low + high) / 2;
+            match self.entry(middle).0.cmp(key) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => return Some(middle),
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAPack => write!(f, "not a template pack"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported template pack version {version}"),
+            Self::Corrupted => write!(f, "corrupted template pack"),
+            Self::Parse(key, error) => write!(f, "`{key}`: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for PackError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Parse(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FormatOptions, ParseErrorKind, TemplateSet, Value};
+    use crate::options::fnv1a_bytes;
+    use super::{PackError, PackedTemplateSet};
+
+    #[test]
+    fn packing() {
+        let options = FormatOptions::new().dedent(true).resolver("env", |_: &str| Some(Value::from("x")));
+        let mut set = TemplateSet::new();
+        set.add_with("b", "\n    {env:HOME} {# ok}é\n", &options).unwrap();
+        set.add("a", "").unwrap();
+        set.add("c", "{plural n}{one}file{other}files{end}").unwrap();
+        let bytes = set.pack();
+        assert_eq!(b"LATEPACK\x01\x00\x00\x00\x03\x00\x00\x00", &bytes[..16]);
+        let packed = PackedTemplateSet::open(&bytes).unwrap();
+        assert_eq!((3, Some("{env:HOME} {# ok}é\n"), None), (packed.len(), packed.source("b"), packed.source("d")));
+        assert_eq!(&ParseErrorKind::InvalidSpec("HOME".into()), packed.get("b").unwrap().unwrap_err().kind());
+        let unpacked = TemplateSet::unpack_with(&bytes, &options).unwrap();
+        assert!(set.iter().zip(unpacked.iter()).all(|((k, t), (l, u))| k == l && t == u && t.fingerprint() == u.fingerprint()));
+        assert!(matches!(TemplateSet::unpack(&bytes), Err(PackError::Parse(key, _)) if key == "b"));
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert_eq!(Some(PackError::Corrupted), PackedTemplateSet::open(&corrupted).err());
+        assert_eq!(Some(PackError::Corrupted), PackedTemplateSet::open(&bytes[..bytes.len() - 1]).err());
+        assert_eq!(Some(PackError::NotAPack), PackedTemplateSet::open(b"LATE").err());
+        let mut later = bytes.clone();
+        later[8] = 2;
+        assert_eq!(Some(PackError::UnsupportedVersion(2)), PackedTemplateSet::open(&later).err());
+        assert!(PackedTemplateSet::open(&TemplateSet::new().pack()).unwrap().is_empty());
+        let reseal = |mut bytes: Vec<u8>| {
+            let checksum = fnv1a_bytes(bytes[24..].iter().copied());
+            bytes[16..24].copy_from_slice(&checksum.to_le_bytes());
+            bytes
+        };
+        let mut unordered = bytes.clone();
+        unordered[24..40].copy_from_slice(&bytes[56..72]);
+        unordered[56..72].copy_from_slice(&bytes[24..40]);
+        assert_eq!(Some(PackError::Corrupted), PackedTemplateSet::open(&reseal(unordered)).err());
+        let mut set = TemplateSet::new();
+        set.add("a", "x").unwrap();
+        set.add("b", "y").unwrap();
+        let mut duplicated = set.pack();
+        assert_eq!(b"axby", &duplicated[56..]);
+        duplicated[58] = b'a';
+        assert_eq!(Some(PackError::Corrupted), PackedTemplateSet::open(&reseal(duplicated)).err());
+    }
+}
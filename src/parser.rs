@@ -0,0 +1,836 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::ops::Range;
+use crate::{FormatOptions, Limit, Limits, ParseError, ParseErrorKind, PluralCategory};
+use crate::filters::Filter;
+use crate::spec::Spec;
+use crate::number::Decimal;
+use crate::template::{Comparison, Condition, Placeholder, Reference, Segment};
+
+/// The words that have a meaning of their own inside curly braces, and
+/// should not be used as parameter names. `else` and `end` are only keywords
+/// inside blocks, the plural categories inside `{plural}` blocks and
+/// `variant` inside `{choose}` blocks, so `{else}`, `{end}` and `{one}`
+/// remain placeholders elsewhere.
+pub(crate) const KEYWORDS: &[&str] = &["raw", "endraw", "if", "not", "for", "in", "else", "end", "plural", "choose", "variant"];
+
+/// The maximum depth of nested `{param_name:-default}` placeholders, even
+/// without [`Limits::max_nesting`], so that parsing cannot overflow the stack.
+/// The placeholders around a deeper one are kept as literal text.
+const MAX_DEFAULT_DEPTH: usize = 64;
+
+/// Parses the segments of a template source.
+///
+/// A curly brace that does not start a valid form is kept as literal text.
+/// A placeholder using an unknown filter or exceeding the limits, and the
+/// tags of a block that is not closed, are kept as literal text too, and
+/// reported in the returned errors.
+pub(crate) fn parse(source: &str, options: &FormatOptions) -> (Vec<Segment>, Vec<ParseError>) {
+    let limits = &options.limits;
+    let mut parser = Parser::new(source, options);
+    if let Some(max) = limits.max_source_len.filter(|max| source.len() > *max) {
+        parser.error(ParseErrorKind::LimitExceeded(Limit::SourceLength(max)), 0..source.len());
+        return (vec![Segment::Literal(0..source.len())], parser.errors);
+    }
+    let mut segments = vec![];
+    let mut last = 0;
+    let mut i = 0;
+    while let Some(offset) = source[i..].find('{') {
+        let start = i + offset;
+        let Some((form, end)) = parser.form(start) else {
+            i = start + 1;
+            continue;
+        };
+        parser.push(&mut segments, Segment::Literal(last..start));
+        i = end;
+        last = end;
+        match form {
+            Form::Segment(segment) => parser.push(&mut segments, segment),
+            Form::Open(kind) => {
+                if let Some(max) = limits.max_nesting.filter(|max| parser.blocks.len() >= *max) {
+                    parser.error(ParseErrorKind::LimitExceeded(Limit::Nesting(max)), start..end);
+                    parser.push(&mut segments, Segment::Literal(start..end));
+                } else {
+                    parser.blocks.push(Block { tag: start..end, kind, then: vec![], otherwise: None, cases: vec![], variants: vec![] });
+                }
+            },
+            Form::Case(category) => {
+                let block = parser.blocks.last_mut().unwrap();
+                if block.cases.iter().any(|(_, c, _)| *c == category) {
+                    parser.error(ParseErrorKind::UnexpectedTag(category.name().into()), start..end);
+                    parser.push(&mut segments, Segment::Literal(start..end));
+                } else {
+                    block.cases.push((start..end, category, vec![]));
+                }
+            },
+            Form::Variant(weight) => parser.blocks.last_mut().unwrap().variants.push((start..end, weight, vec![])),
+            Form::Else => {
+                let block = parser.blocks.last_mut().unwrap();
+                if block.otherwise.is_some() || matches!(block.kind, BlockKind::Plural(_) | BlockKind::Choose) {
+                    parser.error(ParseErrorKind::UnexpectedTag("else".into()), start..end);
+                    parser.push(&mut segments, Segment::Literal(start..end));
+                } else {
+                    block.otherwise = Some((start..end, vec![]));
+                }
+            },
+            Form::End => {
+                let block = parser.blocks.pop().unwrap();
+                let span = block.tag.start..end;
+                let otherwise = block.otherwise.map_or(vec![], |(_, segments)| segments);
+                let segment = match block.kind {
+                    BlockKind::If(condition) => Segment::If { span, condition, then: block.then, otherwise },
+                    BlockKind::For { key, variable, list } => Segment::For { span, key, variable, list, body: block.then, otherwise },
+                    BlockKind::Plural(count) => {
+                        let cases = block.cases.into_iter().map(|(_, c, segments)| (c, segments)).collect();
+                        Segment::Plural { span, count, then: block.then, cases }
+                    },
+                    BlockKind::Choose => {
+                        let variants = block.variants.into_iter().map(|(_, weight, segments)| (weight, segments)).collect();
+                        Segment::Choose { span, then: block.then, variants }
+                    },
+                };
+                parser.push(&mut segments, segment);
+            },
+        }
+    }
+    parser.push(&mut segments, Segment::Literal(last..source.len()));
+    // the tags of unclosed blocks become literal text.
+    while let Some(block) = parser.blocks.pop() {
+        parser.error(ParseErrorKind::UnclosedBlock, block.tag.clone());
+        parser.push(&mut segments, Segment::Literal(block.tag));
+        for segment in block.then {
+            parser.push(&mut segments, segment);
+        }
+        if let Some((tag, otherwise)) = block.otherwise {
+            parser.push(&mut segments, Segment::Literal(tag));
+            for segment in otherwise {
+                parser.push(&mut segments, segment);
+            }
+        }
+        for (tag, _, case) in block.cases {
+            parser.push(&mut segments, Segment::Literal(tag));
+            for segment in case {
+                parser.push(&mut segments, segment);
+            }
+        }
+        for (tag, _, variant) in block.variants {
+            parser.push(&mut segments, Segment::Literal(tag));
+            for segment in variant {
+                parser.push(&mut segments, segment);
+            }
+        }
+    }
+    parser.errors.sort_by_key(|e| e.span().start);
+    (segments, parser.errors)
+}
+
+/// The end of the form starting at the curly brace at `start`, parsed with
+/// the default options, or `None` if the curly brace does not start a form.
+pub(crate) fn form_end(source: &str, start: usize) -> Option<usize> {
+    let options = FormatOptions::default();
+    let mut parser = Parser::new(source, &options);
+    parser.form(start).map(|(_, end)| end)
+}
+
+/// The span of the name of a filter and its arguments, before the filter is resolved.
+type FilterCall = (Range<usize>, Vec<String>);
+
+/// A form parsed by [`Parser::form`].
+enum Form {
+    Segment(Segment),
+    Open(BlockKind),
+    Case(PluralCategory),
+    /// A `{variant}` tag, with its weight.
+    Variant(u32),
+    Else,
+    End,
+}
+
+enum BlockKind {
+    If(Condition),
+    For { key: Option<String>, variable: String, list: Reference },
+    Plural(Reference),
+    Choose,
+}
+
+/// A block whose `{end}` tag is not parsed yet.
+struct Block {
+    tag: Range<usize>,
+    kind: BlockKind,
+    /// The segments of the block, before any `{else}` tag.
+    then: Vec<Segment>,
+    /// The `{else}` tag and the segments that follow it.
+    otherwise: Option<(Range<usize>, Vec<Segment>)>,
+    /// The category tags of a `{plural}` block and the segments that follow them.
+    cases: Vec<(Range<usize>, PluralCategory, Vec<Segment>)>,
+    /// The variant tags of a `{choose}` block, with their weights, and the
+    /// segments that follow them.
+    variants: Vec<(Range<usize>, u32, Vec<Segment>)>,
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    options: &'a FormatOptions,
+    limits: &'a Limits,
+    placeholders: usize,
+    errors: Vec<ParseError>,
+    /// The blocks being parsed, innermost last.
+    blocks: Vec<Block>,
+    /// The number of enclosing `{param_name:-default}` placeholders.
+    depth: usize,
+    /// Why the last `{param_name:-default}` placeholder did not parse.
+    failure: Option<Failure>,
+    /// The start of the defaults that run to the end of the source, which
+    /// do not need parsing again at another depth.
+    unclosed: HashSet<usize>,
+    /// The last search for a `}`, and the first one at or after it.
+    brace: Option<(usize, Option<usize>)>,
+    /// The last search for an `{endraw}`, and the span of the first one at
+    /// or after it.
+    endraw: Option<(usize, Option<Range<usize>>)>,
+}
+
+/// Why a `{param_name:-default}` placeholder did not parse. Either way, the
+/// placeholders around it do not parse either: an unclosed default leaves
+/// the defaults around it unclosed, and stopping there keeps parsing linear.
+#[derive(Clone, Copy)]
+enum Failure {
+    Unclosed,
+    TooDeep,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str, options: &'a FormatOptions) -> Self {
+        Self {
+            source, options, limits: &options.limits, placeholders: 0, errors: vec![], blocks: vec![], depth: 0, failure: None,
+            unclosed: HashSet::new(), brace: None, endraw: None,
+        }
+    }
+
+    fn error(&mut self, kind: ParseErrorKind, span: Range<usize>) {
+        self.errors.push(ParseError::new(kind, span));
+    }
+
+    /// Appends a segment to the innermost block or to `segments`.
+    fn push(&mut self, segments: &mut Vec<Segment>, segment: Segment) {
+        let segments = match self.blocks.last_mut() {
+            Some(Block { cases, .. }) if !cases.is_empty() => &mut cases.last_mut().unwrap().2,
+            Some(Block { variants, .. }) if !variants.is_empty() => &mut variants.last_mut().unwrap().2,
+            Some(Block { otherwise: Some((_, otherwise)), .. }) => otherwise,
+            Some(block) => &mut block.then,
+            None => segments,
+        };
+        append(segments, segment);
+    }
+
+    /// Parses the form starting at the curly brace at `start`, returning it
+    /// with the position after it.
+    fn form(&mut self, start: usize) -> Option<(Form, usize)> {
+        let i = self.skip_whitespace(start + 1);
+        if let Some(segment) = self.raw(start, i) {
+            let end = segment.span().end;
+            return Some((Form::Segment(segment), end));
+        }
+        // like the tag of an unclosed block, `{raw}` without `{endraw}` is literal text.
+        if let Some(end) = self.keyword(i, "raw").and_then(|j| self.close(j)) {
+            self.error(ParseErrorKind::UnclosedBlock, start..end);
+            return Some((Form::Segment(Segment::Literal(start..end)), end));
+        }
+        if let Some(r) = self.tag(i) {
+            return Some(r);
+        }
+        if self.source[i..].starts_with('#') {
+            let end = self.next_brace(i)? + 1;
+            return Some((Form::Segment(Segment::Comment(start..end)), end));
+        }
+        let segment = match self.source[i..].chars().next() {
+            Some(quote @ ('"' | '\'')) => self.escaped(start, i, quote),
+            Some(ch) if is_name_char(ch) => self.placeholder(start, i),
+            _ => None,
+        }?;
+        let end = segment.span().end;
+        Some((Form::Segment(segment), end))
+    }
+
+    /// Parses `{if name}`, `{if not name}`, `{if name | filter > 40}`, `{for variable in name}`,
+    /// `{for key, variable in name}` and
+    /// `{plural name}`, `{choose}`, `{else}` and `{end}` inside a block, the
+    /// plural categories inside a `{plural}` block, and `{variant}`,
+    /// `{variant 3}` and `{variant 60%}` inside a `{choose}` block.
+    fn tag(&mut self, i: usize) -> Option<(Form, usize)> {
+        if let Some(block) = self.blocks.last() {
+            for (keyword, form) in [("else", Form::Else), ("end", Form::End)] {
+                if let Some(end) = self.keyword(i, keyword).and_then(|j| self.close(j)) {
+                    return Some((form, end));
+                }
+            }
+            if matches!(block.kind, BlockKind::Plural(_)) {
+                for category in PluralCategory::ALL {
+                    if let Some(end) = self.keyword(i, category.name()).and_then(|j| self.close(j)) {
+                        return Some((Form::Case(category), end));
+                    }
+                }
+            }
+            if matches!(block.kind, BlockKind::Choose) {
+                if let Some(end) = self.keyword(i, "variant").and_then(|j| self.close(j)) {
+                    return Some((Form::Variant(1), end));
+                }
+                if let Some(j) = self.word(i, "variant") {
+                    let digits = self.take_while(j, |ch| ch.is_ascii_digit());
+                    let weight = self.source[j..digits].parse().ok()?;
+                    let end = self.close(self.keyword(digits, "%").unwrap_or(digits))?;
+                    return Some((Form::Variant(weight), end));
+                }
+            }
+        }
+        if let Some(end) = self.keyword(i, "choose").and_then(|j| self.close(j)) {
+            return Some((Form::Open(BlockKind::Choose), end));
+        }
+        if let Some(j) = self.word(i, "plural") {
+            let (name, j) = self.name(j)?;
+            let end = self.close(j)?;
+            return Some((Form::Open(BlockKind::Plural(name)), end));
+        }
+        if let Some(j) = self.word(i, "if") {
+            let (negated, j) = self.word(j, "not").map_or((false, j), |k| (true, k));
+            let (name, j) = self.name(j)?;
+            let (chain, j) = self.filter_chain(j)?;
+            let (comparison, j) = match self.comparison(j) {
+                Some((comparison, j)) => (Some(comparison), j),
+                None => (None, j),
+            };
+            let end = self.close(j)?;
+            let filters = self.filters(chain, i - 1..end)?;
+            return Some((Form::Open(BlockKind::If(Condition { reference: name, negated, filters, comparison })), end));
+        }
+        let j = self.word(i, "for")?;
+        let (mut variable, j) = self.name(j)?;
+        let mut j = self.skip_whitespace(j);
+        let mut key = None;
+        if self.source[j..].starts_with(',') {
+            let (value, k) = self.name(self.skip_whitespace(j + 1))?;
+            key = Some(std::mem::replace(&mut variable, value).name);
+            j = self.skip_whitespace(k);
+        }
+        let (list, j) = self.name(self.word(j, "in")?)?;
+        let end = self.close(j)?;
+        Some((Form::Open(BlockKind::For { key, variable: variable.name, list }), end))
+    }
+
+    /// Matches a keyword followed by whitespace at `i`, returning the position
+    /// after the whitespace.
+    fn word(&self, i: usize, keyword: &str) -> Option<usize> {
+        let j = self.keyword(i, keyword)?;
+        let k = self.skip_whitespace(j);
+        (k > j).then_some(k)
+    }
+
+    /// Matches a parameter name at `i`, returning it with the position after it.
+    fn name(&self, i: usize) -> Option<(Reference, usize)> {
+        let end = self.name_end(i);
+        if end == i {
+            return None;
+        }
+        Some((Reference { name: dotted(&self.source[i..end]), index: 0, local: false, path: None }, end))
+    }
+
+    fn name_end(&self, i: usize) -> usize {
+        name_end(self.source, i)
+    }
+
+    /// Parses `{raw}...{endraw}`, remembering the search of `{endraw}` as
+    /// [`Parser::next_brace`] does.
+    fn raw(&mut self, start: usize, i: usize) -> Option<Segment> {
+        let content = self.close(self.keyword(i, "raw")?)?;
+        let endraw = match self.endraw.clone() {
+            Some((from, found)) if from <= content && found.as_ref().is_none_or(|span| span.start >= content) => found,
+            _ => {
+                let found = self.find_endraw(content);
+                self.endraw = Some((content, found.clone()));
+                found
+            },
+        }?;
+        Some(Segment::Raw { span: start..endraw.end, content: content..endraw.start })
+    }
+
+    /// The span of the first `{endraw}` at or after `i`.
+    fn find_endraw(&self, mut i: usize) -> Option<Range<usize>> {
+        loop {
+            i += self.source[i..].find('{')?;
+            if let Some(end) = self.keyword(self.skip_whitespace(i + 1), "endraw").and_then(|k| self.close(k)) {
+                return Some(i..end);
+            }
+            i += 1;
+        }
+    }
+
+    /// The position of the first `}` at or after `i`, remembering the search,
+    /// so that unclosed comments do not each scan the rest of the source.
+    fn next_brace(&mut self, i: usize) -> Option<usize> {
+        match self.brace {
+            Some((from, found)) if from <= i && found.is_none_or(|j| j >= i) => found,
+            _ => {
+                let found = self.source[i..].find('}').map(|j| i + j);
+                self.brace = Some((i, found));
+                found
+            },
+        }
+    }
+
+    /// Parses `{"escaped"}` and `{'escaped'}`.
+    fn escaped(&self, start: usize, i: usize, quote: char) -> Option<Segment> {
+        let len = quoted_len(&self.source[i + 1..], quote)?;
+        let end = self.close(i + 1 + len + 1)?;
+        let text = unescape(&self.source[i + 1..i + 1 + len]);
+        Some(Segment::Escaped { span: start..end, text })
+    }
+
+    /// Parses `{param_name:spec | filter}` and `{namespace:key:spec | filter}`.
+    fn placeholder(&mut self, start: usize, i: usize) -> Option<Segment> {
+        let mut name_end = self.name_end(i);
+        if self.source[name_end..].starts_with(':') && self.options.namespace(&self.source[i..name_end]).is_some() {
+            let key_end = self.take_while(name_end + 1, is_name_char);
+            if key_end == name_end + 1 {
+                return None;
+            }
+            name_end = key_end;
+        }
+        // `-` followed by an alignment is the fill character of a spec, as in `{title:-^30}`.
+        if self.source[name_end..].strip_prefix(":-").is_some_and(|r| !r.starts_with(['<', '^', '>'])) {
+            return self.defaulted(start, i, name_end);
+        }
+        let mut spec = None;
+        let mut j = name_end;
+        if self.source[j..].starts_with(':') {
+            // the fill character of an alignment may be whitespace or `|`.
+            let mut chars = self.source[j + 1..].chars();
+            let fill = match (chars.next(), chars.next()) {
+                (Some(fill), Some('<' | '^' | '>')) if fill != '}' => fill.len_utf8() + 1,
+                _ => 0,
+            };
+            let mut spec_end = j + 1 + fill;
+            loop {
+                spec_end = self.take_while(spec_end, |ch| !ch.is_whitespace() && ch != '|' && ch != '}' && ch != '{');
+                // the `{name}` of a dynamic width or precision.
+                let name = self.source[spec_end..].starts_with('{').then(|| self.take_while(spec_end + 1, is_name_char));
+                match name.and_then(|k| self.keyword(k, "}")) {
+                    Some(k) => spec_end = k,
+                    None => break,
+                }
+            }
+            spec = Some(&self.source[j + 1..spec_end]);
+            j = spec_end;
+        }
+        let (filter_names, j) = self.filter_chain(j)?;
+        let end = self.close(j)?;
+        let literal = Some(Segment::Literal(start..end));
+        let spec = match spec.map(|s| (s, Spec::parse(s))) {
+            Some((s, None)) => {
+                self.error(ParseErrorKind::InvalidSpec(s.to_owned()), start..end);
+                return literal;
+            },
+            Some((_, spec)) => spec,
+            None => None,
+        };
+        if let Some(limit) = spec.as_ref().and_then(|spec| spec.exceeded(self.limits)) {
+            self.error(ParseErrorKind::LimitExceeded(limit), start..end);
+            return literal;
+        }
+        let Some(filters) = self.filters(filter_names, start..end) else {
+            return literal;
+        };
+        if !self.count_placeholder(start..end, i..name_end) {
+            return literal;
+        }
+        let name = dotted(&self.source[i..name_end]);
+        let counts = spec.iter().flat_map(Spec::parameters).map(|name| Reference { name: name.to_owned(), index: 0, local: false, path: None }).collect();
+        Some(Segment::Placeholder(Placeholder { name, index: 0, local: false, path: None, spec, counts, filters, default: None, span: start..end }))
+    }
+
+    /// Parses the `| filter:"argument"` chain at `j`, after optional
+    /// whitespace, returning the span of the name and the arguments of each
+    /// filter, and the position after the chain and the whitespace after it.
+    fn filter_chain(&self, j: usize) -> Option<(Vec<FilterCall>, usize)> {
+        let mut chain = vec![];
+        let mut j = self.skip_whitespace(j);
+        while self.source[j..].starts_with('|') {
+            let k = self.skip_whitespace(j + 1);
+            let filter_end = self.take_while(k, |ch| ch.is_ascii_alphanumeric() || ch == '_');
+            if k == filter_end {
+                return None;
+            }
+            let (arguments, arguments_end) = match self.source[filter_end..].starts_with(':') {
+                true => self.filter_arguments(filter_end + 1)?,
+                false => (vec![], filter_end),
+            };
+            chain.push((k..filter_end, arguments));
+            j = self.skip_whitespace(arguments_end);
+        }
+        Some((chain, j))
+    }
+
+    /// Resolves the filters of a chain, reporting an unknown filter or
+    /// invalid arguments at `span`.
+    fn filters(&mut self, chain: Vec<FilterCall>, span: Range<usize>) -> Option<Vec<Filter>> {
+        let mut filters = vec![];
+        for (name, arguments) in chain {
+            let name = &self.source[name];
+            // the built-in filters take precedence over the registered ones.
+            let filter = match self.options.custom_filter(name) {
+                Some(custom) if matches!(Filter::new(name, vec![]), Err(ParseErrorKind::UnknownFilter(_))) => Ok(Filter::Custom(custom, arguments)),
+                _ => Filter::new(name, arguments),
+            };
+            match filter {
+                Ok(filter) => filters.push(filter),
+                Err(kind) => {
+                    self.error(kind, span);
+                    return None;
+                },
+            }
+        }
+        Some(filters)
+    }
+
+    /// Parses the comparison of an `{if}` tag at `i`, as in `> 40`,
+    /// returning it with the position after it.
+    fn comparison(&self, i: usize) -> Option<(Comparison, usize)> {
+        let operators = [("==", Ordering::Equal, false), ("!=", Ordering::Equal, true), ("<=", Ordering::Greater, true), (">=", Ordering::Less, true), ("<", Ordering::Less, false), (">", Ordering::Greater, false)];
+        let (operator, ordering, negated) = operators.into_iter().find(|(operator, ..)| self.source[i..].starts_with(operator))?;
+        let j = self.skip_whitespace(i + operator.len());
+        let end = self.take_while(j, |ch| !ch.is_whitespace() && ch != '}');
+        let operand = Decimal::parse(&self.source[j..end])?;
+        Some((Comparison { ordering, negated, operand }, end))
+    }
+
+    /// Parses the comma-separated arguments of a filter at `i`, each quoted as
+    /// an escaped form or written as is up to whitespace, `,`, `|` or `}`.
+    fn filter_arguments(&self, i: usize) -> Option<(Vec<String>, usize)> {
+        let mut arguments = vec![];
+        let mut j = i;
+        loop {
+            j = self.skip_whitespace(j);
+            let argument = match self.source[j..].chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let len = quoted_len(&self.source[j + 1..], quote)?;
+                    let argument = unescape(&self.source[j + 1..j + 1 + len]);
+                    j += len + 2;
+                    argument
+                },
+                _ => {
+                    let end = self.take_while(j, |ch| !ch.is_whitespace() && !matches!(ch, ',' | '|' | '}' | '{'));
+                    if end == j {
+                        return None;
+                    }
+                    let argument = self.source[j..end].to_owned();
+                    j = end;
+                    argument
+                },
+            };
+            arguments.push(argument);
+            let k = self.skip_whitespace(j);
+            if !self.source[k..].starts_with(',') {
+                return Some((arguments, j));
+            }
+            j = k + 1;
+        }
+    }
+
+    /// Parses `{param_name:-default}`, whose default runs up to the closing
+    /// curly brace and may contain placeholders and escaped forms.
+    fn defaulted(&mut self, start: usize, i: usize, name_end: usize) -> Option<Segment> {
+        if self.unclosed.contains(&start) {
+            self.failure = Some(Failure::Unclosed);
+            return None;
+        }
+        if self.depth >= MAX_DEFAULT_DEPTH {
+            self.failure = Some(Failure::TooDeep);
+            return None;
+        }
+        let nesting = self.limits.max_nesting.filter(|max| self.blocks.len() + self.depth >= *max);
+        let (errors, placeholders) = (self.errors.len(), self.placeholders);
+        self.depth += 1;
+        let parsed = self.default(name_end + 2);
+        self.depth -= 1;
+        let restore = |parser: &mut Self| {
+            // the forms of the default are parsed again as literal text.
+            parser.errors.truncate(errors);
+            parser.placeholders = placeholders;
+        };
+        let Some((default, end)) = parsed else {
+            restore(self);
+            if let Some(Failure::Unclosed) = self.failure {
+                self.unclosed.insert(start);
+            }
+            return None;
+        };
+        if let Some(max) = nesting {
+            restore(self);
+            self.error(ParseErrorKind::LimitExceeded(Limit::Nesting(max)), start..end);
+            return Some(Segment::Literal(start..end));
+        }
+        if !self.count_placeholder(start..end, i..name_end) {
+            return Some(Segment::Literal(start..end));
+        }
+        let name = dotted(&self.source[i..name_end]);
+        let placeholder = Placeholder {
+            name, index: 0, local: false, path: None, spec: None, counts: vec![], filters: vec![], default: Some(default), span: start..end,
+        };
+        Some(Segment::Placeholder(placeholder))
+    }
+
+    /// Parses the default of a placeholder at `i`, returning its segments
+    /// with the position after the closing curly brace.
+    fn default(&mut self, i: usize) -> Option<(Vec<Segment>, usize)> {
+        let mut segments = vec![];
+        let mut last = i;
+        let mut j = i;
+        loop {
+            let Some(k) = self.source[j..].find(['{', '}']).map(|k| j + k) else {
+                self.failure = Some(Failure::Unclosed);
+                return None;
+            };
+            if self.source[k..].starts_with('}') {
+                append(&mut segments, Segment::Literal(last..k));
+                return Some((segments, k + 1));
+            }
+            // block tags are literal text, as they cannot be closed inside the default.
+            self.failure = None;
+            match self.form(k) {
+                Some((Form::Segment(segment), end)) => {
+                    append(&mut segments, Segment::Literal(last..k));
+                    append(&mut segments, segment);
+                    j = end;
+                    last = end;
+                },
+                _ if self.failure.is_some() => return None,
+                _ => j = k + 1,
+            }
+        }
+    }
+
+    /// Checks the limits of a placeholder, counting it if they are not exceeded.
+    fn count_placeholder(&mut self, span: Range<usize>, name: Range<usize>) -> bool {
+        if let Some(max) = self.limits.max_name_len.filter(|max| name.len() > *max) {
+            self.error(ParseErrorKind::LimitExceeded(Limit::NameLength(max)), name);
+            return false;
+        }
+        if let Some(max) = self.limits.max_placeholders.filter(|max| self.placeholders >= *max) {
+            // only the first placeholder over the limit is reported.
+            if self.placeholders == max {
+                self.placeholders += 1;
+                self.error(ParseErrorKind::LimitExceeded(Limit::Placeholders(max)), span);
+            }
+            return false;
+        }
+        self.placeholders += 1;
+        true
+    }
+
+    /// Matches a keyword at `i`, returning the position after it.
+    fn keyword(&self, i: usize, keyword: &str) -> Option<usize> {
+        self.source[i..].starts_with(keyword).then_some(i + keyword.len())
+    }
+
+    /// Matches optional whitespace and a closing curly brace at `i`,
+    /// returning the position after the brace.
+    fn close(&self, i: usize) -> Option<usize> {
+        let i = self.skip_whitespace(i);
+        self.source[i..].starts_with('}').then_some(i + 1)
+    }
+
+    fn skip_whitespace(&self, i: usize) -> usize {
+        self.take_while(i, char::is_whitespace)
+    }
+
+    fn take_while(&self, i: usize, predicate: impl Fn(char) -> bool) -> usize {
+        self.source[i..].find(|ch| !predicate(ch)).map_or(self.source.len(), |j| i + j)
+    }
+}
+
+/// Writes the indices of a parameter name with dots, as in `items.2` for `items[2]`.
+fn dotted(name: &str) -> String {
+    name.replace('[', ".").replace(']', "")
+}
+
+/// Appends a segment, merging adjacent literal text.
+fn append(segments: &mut Vec<Segment>, segment: Segment) {
+    match (segments.last_mut(), &segment) {
+        (_, Segment::Literal(span)) if span.is_empty() => {},
+        (Some(Segment::Literal(last)), Segment::Literal(span)) if last.end == span.start => last.end = span.end,
+        _ => segments.push(segment),
+    }
+}
+
+/// The end of the parameter name at `i`, including the indices written
+/// as in `items[2]`.
+pub(crate) fn name_end(source: &str, i: usize) -> usize {
+    let take_while = |i: usize, predicate: fn(char) -> bool| source[i..].find(|ch| !predicate(ch)).map_or(source.len(), |j| i + j);
+    let mut end = take_while(i, is_name_char);
+    while end > i && source[end..].starts_with('[') {
+        let digits = take_while(end + 1, |ch| ch.is_ascii_digit());
+        if digits == end + 1 || !source[digits..].starts_with(']') {
+            break;
+        }
+        end = take_while(digits + 1, is_name_char);
+    }
+    end
+}
+
+pub(crate) fn is_name_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || matches!(ch, '_' | '-' | '.' | '$')
+}
+
+/// The length of quoted text up to the closing quote, skipping the
+/// backslash sequences.
+fn quoted_len(s: &str, quote: char) -> Option<usize> {
+    let mut chars = s.char_indices();
+    while let Some((j, ch)) = chars.next() {
+        if ch == '\\' {
+            chars.next();
+        } else if ch == quote {
+            return Some(j);
+        }
+    }
+    None
+}
+
+/// Expands the backslash sequences of an escaped form.
+fn unescape(s: &str) -> String {
+    let mut r = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('\\') {
+        r.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        let (ch, len) = match rest.chars().next() {
+            Some('"') => ('"', 1),
+            Some('\'') => ('\'', 1),
+            Some('\\') => ('\\', 1),
+            Some('n') => ('\n', 1),
+            Some('t') => ('\t', 1),
+            Some('u') => unicode_sequence(rest).unwrap_or(('\\', 0)),
+            _ => ('\\', 0),
+        };
+        r.push(ch);
+        rest = &rest[len..];
+    }
+    r.push_str(rest);
+    r
+}
+
+/// Parses `u{XXXX}`, returning the character and the sequence length.
+fn unicode_sequence(s: &str) -> Option<(char, usize)> {
+    let end = s.find('}')?;
+    let code = s.strip_prefix("u{")?.get(..end - 2)?;
+    let ch = char::from_u32(u32::from_str_radix(code, 16).ok()?)?;
+    Some((ch, end + 1))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FormatOptions, LateFormat, Limit, Limits, ParseErrorKind, Template};
+    use maplit::hashmap;
+
+    #[test]
+    fn literal_braces() {
+        let arguments = hashmap!{"a".into() => "x".into()};
+        assert_eq!(r#"{ {x} {"x} {a |} {a b}{"#, r#"{ {{a}} {"x} {a |} {a b}{"#.late_format(arguments.clone()));
+        assert_eq!(r#"{"\"}"#, Template::parse(r#"{"\"}"#).unwrap().render(&arguments));
+        assert_eq!("{raw}{a}{end raw}", "{raw}{raw}{a}{end raw}{endraw}".late_format(arguments));
+    }
+
+    #[test]
+    fn limits() {
+        let options = FormatOptions::new().limits(Limits::new().max_source_len(8).max_name_len(2));
+        let error = Template::parse_with("{a}{bc}{def}", &options).unwrap_err();
+        assert_eq!(&ParseErrorKind::LimitExceeded(Limit::SourceLength(8)), error.kind());
+        assert_eq!("{a}{bc}{def}", "{a}{bc}{def}".late_format_with(hashmap!{}, &options));
+        let options = FormatOptions::new().limits(Limits::new().max_name_len(2).max_placeholders(2));
+        let error = Template::parse_with("{a}{bc}{def}", &options).unwrap_err();
+        assert_eq!(&ParseErrorKind::LimitExceeded(Limit::NameLength(2)), error.kind());
+        assert_eq!(8..11, error.span());
+        assert_eq!("NoneNone{def}{g}", "{a}{bc}{def}{g}".late_format_with(hashmap!{}, &options));
+        let error = Template::parse_with("{a}{bc}{d}", &options).unwrap_err();
+        assert_eq!(&ParseErrorKind::LimitExceeded(Limit::Placeholders(2)), error.kind());
+    }
+
+    #[test]
+    fn blocks() {
+        let arguments = hashmap!{"a".into() => "x".into(), "e".into() => "".into()};
+        let source = "{if a}[{a}{if not e}!{end}]{else}-{end}{ if e }e{ else }{end}";
+        assert_eq!("[x!]", source.late_format(arguments.clone()));
+        assert_eq!("y {else} {end}", "{a} {else} {end}".late_format(hashmap!{"a".into() => "y".into(), "else".into() => "{else}".into(), "end".into() => "{end}".into()}));
+        let template = Template::parse("{if a}x{else}y{end}").unwrap();
+        assert_eq!(["a"], template.parameters());
+        let error = Template::parse("{a}{if a}x{if e}y{end}").unwrap_err();
+        assert_eq!((&ParseErrorKind::UnclosedBlock, 3..9), (error.kind(), error.span()));
+        assert_eq!("x{if a}xy", "{a}{if a}x{if a}y{end}".late_format(arguments.clone()));
+        let error = Template::parse("{if a}{else}{else}{end}").unwrap_err();
+        assert_eq!(&ParseErrorKind::UnexpectedTag("else".into()), error.kind());
+        let template = Template::parse("{for a in b}{a}{for b in a}{b}{end}{end}{for c in d}{else}{a}{end}").unwrap();
+        assert_eq!(["b", "d", "a"], template.parameters());
+        let locals: Vec<_> = template.placeholders().map(|p| (p.index(), p.is_loop_variable())).collect();
+        assert_eq!(vec![(0, true), (1, true), (2, false)], locals);
+        assert!(Template::parse("{for a b}{end}").is_ok());
+        let options = FormatOptions::new().limits(Limits::new().max_nesting(1));
+        let error = Template::parse_with("{if a}{if a}{end}{end}", &options).unwrap_err();
+        assert_eq!((&ParseErrorKind::LimitExceeded(Limit::Nesting(1)), 6..12), (error.kind(), error.span()));
+    }
+
+    #[test]
+    fn choose_blocks() {
+        let template = Template::parse("{choose}<{variant 60%}a{ variant 0 }b{variant}{n}{end}{variant}").unwrap();
+        assert_eq!(["n", "variant"], template.parameters());
+        assert_eq!(template, Template::parse(template.minified().source()).unwrap());
+        let options = FormatOptions::new().random(|| 60);
+        assert_eq!("<7None", template.render_with(&hashmap!{"n".into() => "7".into()}, &options).unwrap());
+        let renders: Vec<_> = (0..100).map(|_| template.render_with(&hashmap!{}, &FormatOptions::new()).unwrap()).collect();
+        assert!(renders.iter().all(|r| r.starts_with("<a") || r.starts_with("<None")));
+        let error = Template::parse("{choose}{variant}a{else}b{end}").unwrap_err();
+        assert_eq!((&ParseErrorKind::UnexpectedTag("else".into()), 18..24), (error.kind(), error.span()));
+        assert_eq!("x{choose}{variant}y", "{a}{choose}{variant}y".late_format(hashmap!{"a".into() => "x".into()}));
+    }
+
+    #[test]
+    fn defaults() {
+        let arguments = hashmap!{"first_name".into() => "Ana".into(), "empty".into() => "".into()};
+        let source = "{nickname:-{first_name}} {empty:-{nickname:-{x:-anonymous}}} {first_name:-x} {a:-{'}'}!}";
+        assert_eq!("Ana anonymous Ana }!", source.late_format(arguments.clone()));
+        assert_eq!("-Ana-", "{first_name:-^5}".late_format(arguments.clone()));
+        let template = Template::parse("{a:-{b | nope}}").unwrap_err();
+        assert_eq!((&ParseErrorKind::UnknownFilter("nope".into()), 4..14), (template.kind(), template.span()));
+        assert_eq!("{a:-None", "{a:-{b}".late_format(arguments.clone()));
+        assert!(Template::parse("{a:-{b}").is_ok());
+        let options = FormatOptions::new().limits(Limits::new().max_nesting(2));
+        let error = Template::parse_with("{if c}{a:-{b:-{c}}}{end}", &options).unwrap_err();
+        assert_eq!((&ParseErrorKind::LimitExceeded(Limit::Nesting(2)), 10..18), (error.kind(), error.span()));
+        let template = Template::parse("{a:-x {b}}").unwrap();
+        assert!(template.check_arguments(&hashmap!{"b".into() => "y".into()}).is_ok());
+        assert_eq!(["a", "b"], template.parameters());
+        assert_eq!(template, Template::parse("{a:-{'x'} {b}}").unwrap());
+        assert_ne!(template, Template::parse("{a:-x}").unwrap());
+        // unclosed defaults are not parsed again at each depth.
+        let started = std::time::Instant::now();
+        for source in ["{a:-".repeat(26), "{a:-".repeat(2_000), "{a:-{b}".repeat(1_000), "{a:-{b:-".repeat(50) + &"}".repeat(99)] {
+            for options in [FormatOptions::default(), FormatOptions::new().limits(Limits::untrusted())] {
+                let _ = Template::parse_with(&source, &options);
+            }
+        }
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+        assert_eq!("{a:-".repeat(64), ("{a:-".repeat(65) + "}").late_format(hashmap!{}));
+        assert_eq!("x", ("{a:-".repeat(64) + "x" + &"}".repeat(64)).late_format(hashmap!{}));
+    }
+
+    #[test]
+    fn unclosed() {
+        // each unclosed `{raw}` and comment does not scan the rest of the source again.
+        let started = std::time::Instant::now();
+        for source in ["{raw}".repeat(20_000), "{ raw }{#".repeat(10_000), "{#".repeat(50_000), "{raw}{endraw}{raw}".repeat(10_000)] {
+            for options in [FormatOptions::default(), FormatOptions::new().limits(Limits::untrusted())] {
+                let _ = Template::parse_with(&source, &options);
+            }
+        }
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+        assert_eq!("x{raw}{raw}y{#", "{raw}x{raw}{raw}y{endraw}{#".late_format(hashmap!{}));
+        assert_eq!(" c} {raw}{x}", "{#a {#b} c} {raw}{raw}{x}{endraw}{# d}".late_format(hashmap!{}));
+    }
+}
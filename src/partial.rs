@@ -0,0 +1,86 @@
+use crate::escape_literal;
+use crate::render::Output;
+
+/// The output of [`Template::render_partial`](crate::Template::render_partial):
+/// a template where the placeholders and blocks that could not be rendered
+/// are kept as written, to be rendered by a later pass.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartialRender {
+    pub(crate) output: String,
+    pub(crate) leftovers: Vec<String>,
+}
+
+impl PartialRender {
+    /// The partially rendered template source.
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    pub fn into_output(self) -> String {
+        self.output
+    }
+
+    /// The parameters left for a later pass, in the order they first appear
+    /// in the output.
+    pub fn leftovers(&self) -> &[String] {
+        &self.leftovers
+    }
+
+    /// Whether every placeholder and block was rendered.
+    pub fn is_complete(&self) -> bool {
+        self.leftovers.is_empty()
+    }
+}
+
+/// Rendered text escaped with [`escape_literal`], so that it is read back
+/// as literal text, except for the forms written verbatim.
+pub(crate) struct Escaping<'o> {
+    pub inner: &'o mut String,
+}
+
+impl Output<'_> for Escaping<'_> {
+    fn push_str(&mut self, s: &str) {
+        match s.contains('{') {
+            true => self.inner.push_str(&escape_literal(s)),
+            false => self.inner.push_str(s),
+        }
+    }
+
+    fn push_verbatim(&mut self, s: &str) {
+        self.inner.push_str(s);
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FormatOptions, Missing, Template};
+    use maplit::hashmap;
+
+    #[test]
+    fn partial() {
+        let source = "{greeting}, {name | html}! {n:>{w}} {if admin}[admin]{end}{for t in tags}#{t} {else}-{end}{plural n}{one}1 item{other}{n} items{end} {x:-none} {y}";
+        let template = Template::parse(source).unwrap();
+        let stage = hashmap!{"greeting".into() => "{Hi}".into(), "n".into() => "2".into(), "y".into() => "y".into()};
+        let partial = template.render_partial(&stage, &FormatOptions::new()).unwrap();
+        assert_eq!(
+            "{\"{\"}Hi}, {name | html}! {n:>{w}} {if admin}[admin]{end}{for t in tags}#{t} {else}-{end}2 items {x:-none} y",
+            partial.output(),
+        );
+        assert_eq!(["name", "w", "admin", "tags", "x"], partial.leftovers());
+        assert!(!partial.is_complete());
+        // the kept forms need all their parameters in the next pass.
+        let last = hashmap!{"name".into() => "<b>".into(), "w".into() => "3".into(), "admin".into() => "".into(), "n".into() => "2".into(), "x".into() => "".into()};
+        let mut all = stage.clone();
+        all.extend(last.clone());
+        let next = Template::parse(partial.output()).unwrap();
+        assert_eq!(template.render(&all), next.render(&last));
+        assert_eq!("{Hi}, &lt;b&gt;!   2 -2 items none y", next.render(&last));
+        let options = FormatOptions::new().missing(Missing::Error);
+        assert_eq!(["tags"], next.render_partial(&last, &options).unwrap().leftovers());
+        assert!(Template::parse("{a}{b}").unwrap().render_partial(&hashmap!{"a".to_owned() => "1".to_owned(), "b".to_owned() => "2".to_owned()}, &options).unwrap().is_complete());
+    }
+}
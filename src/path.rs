@@ -0,0 +1,150 @@
+use std::borrow::Cow;
+use std::path::{is_separator, PathBuf};
+use crate::{Arguments, FormatOptions, ParseError, RenderError, RenderErrorKind, Template};
+use crate::tagged::TaggedValue;
+
+/// A template rendering a file name or path from arguments, such as user
+/// data, which checks that the arguments do not add directories to the path
+/// or leave it through `..`.
+///
+/// An argument containing a path separator, `/` or `\` on any platform, or
+/// a `..` component makes rendering fail with
+/// [`RenderErrorKind::UnsafePath`], unless allowed. With
+/// [`PathTemplate::sanitize`], such arguments are made safe instead.
+///
+/// ```
+/// use late_format::{FormatOptions, PathTemplate};
+/// use maplit::hashmap;
+/// use std::path::PathBuf;
+/// let template = PathTemplate::parse("exports/{user}/{date}.csv").unwrap();
+/// let options = FormatOptions::new();
+/// let path = template.render(&hashmap!{"user".to_owned() => "ana".to_owned(), "date".to_owned() => "2024-03-05".to_owned()}, &options).unwrap();
+/// assert_eq!(PathBuf::from("exports/ana/2024-03-05.csv"), path);
+/// assert!(template.render(&hashmap!{"user".to_owned() => "../etc".to_owned(), "date".to_owned() => "x".to_owned()}, &options).is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct PathTemplate {
+    template: Template,
+    separators: bool,
+    parents: bool,
+    sanitize: bool,
+}
+
+impl PathTemplate {
+    pub fn new(template: Template) -> Self {
+        Self { template, separators: false, parents: false, sanitize: false }
+    }
+
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        Template::parse(source).map(Self::new)
+    }
+
+    /// Allows arguments to contain path separators, so that they name
+    /// subdirectories. `..` components are still rejected unless
+    /// [`PathTemplate::allow_parents`] is also set.
+    pub fn allow_separators(mut self, value: bool) -> Self {
+        self.separators = value;
+        self
+    }
+
+    /// Allows arguments to contain `..` components.
+    pub fn allow_parents(mut self, value: bool) -> Self {
+        self.parents = value;
+        self
+    }
+
+    /// Replaces by `_` the characters of the arguments that are not allowed,
+    /// the `..` components and the characters that are not valid in file
+    /// names on some platforms, `<>:"|?*` and the control characters,
+    /// instead of failing.
+    ///
+    /// ```
+    /// use late_format::{FormatOptions, PathTemplate};
+    /// use maplit::hashmap;
+    /// let template = PathTemplate::parse("{title}.txt").unwrap().sanitize(true);
+    /// let path = template.render(&hashmap!{"title".to_owned() => "a/b: c?".to_owned()}, &FormatOptions::new()).unwrap();
+    /// assert_eq!("a_b_ c_.txt", path.to_str().unwrap());
+    /// ```
+    pub fn sanitize(mut self, value: bool) -> Self {
+        self.sanitize = value;
+        self
+    }
+
+    pub fn template(&self) -> &Template {
+        &self.template
+    }
+
+    /// Renders the path. The text of the template itself is kept as written,
+    /// separators included.
+    pub fn render(&self, arguments: &(impl Arguments + ?Sized), options: &FormatOptions) -> Result<PathBuf, RenderError> {
+        let tagged = self.template.render_tagged(arguments, options)?;
+        if !self.sanitize {
+            if let Some(value) = tagged.values().find(|value| !self.is_safe(value.text())) {
+                return Err(RenderError::new(RenderErrorKind::UnsafePath(value.placeholder().to_owned()), value.source()));
+            }
+        }
+        Ok(PathBuf::from(tagged.join(|value| self.sanitized(value))))
+    }
+
+    fn is_safe(&self, text: &str) -> bool {
+        (self.separators || !text.chars().any(is_path_separator))
+            && (self.parents || !text.split(is_path_separator).any(|component| component == ".."))
+    }
+
+    fn sanitized<'a>(&self, value: &'a TaggedValue) -> Cow<'a, str> {
+        let text = value.text();
+        if !self.sanitize || !text.chars().any(|ch| is_path_separator(ch) || is_reserved(ch)) && self.is_safe(text) {
+            return Cow::Borrowed(text);
+        }
+        let mut r = String::with_capacity(text.len());
+        for (i, component) in text.split(is_path_separator).enumerate() {
+            if i > 0 {
+                r.push(if self.separators { '/' } else { '_' });
+            }
+            if component == ".." && !self.parents {
+                r.push_str("__");
+                continue;
+            }
+            r.extend(component.chars().map(|ch| if is_reserved(ch) { '_' } else { ch }));
+        }
+        Cow::Owned(r)
+    }
+}
+
+/// Whether a character separates the components of a path on some platform.
+fn is_path_separator(ch: char) -> bool {
+    ch == '/' || ch == '\\' || is_separator(ch)
+}
+
+/// Whether a character is not valid in the file names of some platform.
+fn is_reserved(ch: char) -> bool {
+    matches!(ch, '<' | '>' | ':' | '"' | '|' | '?' | '*') || ch.is_control()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FormatOptions, RenderErrorKind};
+    use super::PathTemplate;
+    use maplit::hashmap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn paths() {
+        let template = PathTemplate::parse("out/{dir}/{name | trim}.txt").unwrap();
+        let options = FormatOptions::new();
+        let render = |template: &PathTemplate, dir: &str, name: &str| {
+            template.render(&hashmap!{"dir".to_owned() => dir.to_owned(), "name".to_owned() => name.to_owned()}, &options)
+        };
+        assert_eq!(PathBuf::from("out/a..b/c.txt"), render(&template, "a..b", " c ").unwrap());
+        let error = render(&template, "a", "x\\y").unwrap_err();
+        assert_eq!((&RenderErrorKind::UnsafePath("name".into()), 10..23), (error.kind(), error.span()));
+        assert!(render(&template, "..", "x").is_err());
+        let nested = template.clone().allow_separators(true);
+        assert_eq!(PathBuf::from("out/a/b/x.txt"), render(&nested, "a/b", "x").unwrap());
+        assert!(render(&nested, "a/../..", "x").is_err());
+        assert!(render(&nested.clone().allow_parents(true), "a/../..", "x").is_ok());
+        let sanitized = template.sanitize(true);
+        assert_eq!(PathBuf::from("out/__/a_b_c.txt"), render(&sanitized, "..", "a\\b\u{0}c").unwrap());
+        assert_eq!(PathBuf::from("out/a/__/x.txt"), render(&nested.sanitize(true), "a/..", "x").unwrap());
+    }
+}
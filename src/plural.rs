@@ -0,0 +1,212 @@
+use std::fmt;
+
+/// A CLDR plural category, selecting the branch of a `{plural count}` block.
+///
+/// ```
+/// use late_format::PluralCategory;
+/// assert_eq!(PluralCategory::One, PluralCategory::select("en", "1"));
+/// assert_eq!(PluralCategory::Other, PluralCategory::select("en", "1.0"));
+/// assert_eq!(PluralCategory::Few, PluralCategory::select("ru", "22"));
+/// assert_eq!(PluralCategory::Many, PluralCategory::select("pl", "25"));
+/// assert_eq!(PluralCategory::Two, PluralCategory::select("ar", "2"));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+impl PluralCategory {
+    pub const ALL: [Self; 6] = [Self::Zero, Self::One, Self::Two, Self::Few, Self::Many, Self::Other];
+
+    /// The name of the category, as written in the tags of a plural block.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Zero => "zero",
+            Self::One => "one",
+            Self::Two => "two",
+            Self::Few => "few",
+            Self::Many => "many",
+            Self::Other => "other",
+        }
+    }
+
+    /// Selects the category of a number written in decimal, such as `3` or
+    /// `1.50`, under the cardinal plural rules of a locale such as `ru` or
+    /// `pt-PT`. Trailing zeros of the fraction are significant, as in CLDR.
+    /// Anything else than a number is [`PluralCategory::Other`].
+    ///
+    /// The rules of the most common languages are built in. Other languages
+    /// use the English rules. Compact exponents, as in `1.2c6`, are not supported.
+    pub fn select(locale: &str, number: &str) -> Self {
+        let Some(o) = Operands::parse(number) else {
+            return Self::Other;
+        };
+        let locale = locale.to_ascii_lowercase().replace('_', "-");
+        let language = locale.split('-').next().unwrap_or_default();
+        let (i, v, f, t) = (o.i, o.v, o.f, o.t);
+        // `n` is only equal to an integer when the fraction is zero.
+        let n = |x: u64| t == 0 && i == x;
+        let n_in = |range: std::ops::RangeInclusive<u64>| t == 0 && range.contains(&i);
+        let n_mod = |m: u64| (t == 0).then_some(i % m);
+        let millions = v == 0 && i != 0 && i % 1_000_000 == 0;
+        use PluralCategory::*;
+        match language {
+            "ja" | "zh" | "ko" | "vi" | "th" | "id" | "ms" | "lo" | "my" | "km" => Other,
+            "fr" if i <= 1 => One,
+            "fr" | "it" | "es" | "ca" | "pt" if millions => Many,
+            "pt" if locale == "pt-pt" => if i == 1 && v == 0 { One } else { Other },
+            "pt" => if i <= 1 { One } else { Other },
+            "fr" => Other,
+            "es" | "el" | "hu" | "tr" | "bg" | "nb" | "no" | "nn" | "ka" | "az" | "kk" => if n(1) { One } else { Other },
+            "da" => if n(1) || (t != 0 && i <= 1) { One } else { Other },
+            "hi" | "bn" | "fa" | "zu" | "am" | "gu" | "kn" | "mr" => if i == 0 || n(1) { One } else { Other },
+            "is" => if (t == 0 && i % 10 == 1 && i % 100 != 11) || (t % 10 == 1 && t % 100 != 11) { One } else { Other },
+            "mk" => if (v == 0 && i % 10 == 1 && i % 100 != 11) || (f % 10 == 1 && f % 100 != 11) { One } else { Other },
+            "ru" | "uk" | "be" => match (v, i % 10, i % 100) {
+                (0, 1, m) if m != 11 => One,
+                (0, 2..=4, m) if !(12..=14).contains(&m) => Few,
+                (0, _, _) => Many,
+                _ => Other,
+            },
+            "pl" => match (v, i % 10, i % 100) {
+                _ if v == 0 && i == 1 => One,
+                (0, 2..=4, m) if !(12..=14).contains(&m) => Few,
+                (0, _, _) => Many,
+                _ => Other,
+            },
+            "cs" | "sk" => match (v, i) {
+                (0, 1) => One,
+                (0, 2..=4) => Few,
+                (0, _) => Other,
+                _ => Many,
+            },
+            "hr" | "sr" | "bs" => {
+                if (v == 0 && i % 10 == 1 && i % 100 != 11) || (f % 10 == 1 && f % 100 != 11) {
+                    One
+                } else if (v == 0 && (2..=4).contains(&(i % 10)) && !(12..=14).contains(&(i % 100)))
+                    || ((2..=4).contains(&(f % 10)) && !(12..=14).contains(&(f % 100))) {
+                    Few
+                } else {
+                    Other
+                }
+            },
+            "sl" => match (v, i % 100) {
+                (0, 1) => One,
+                (0, 2) => Two,
+                (0, 3..=4) | (1.., _) => Few,
+                _ => Other,
+            },
+            "ro" => {
+                if i == 1 && v == 0 {
+                    One
+                } else if v != 0 || n(0) || n_mod(100).is_some_and(|m| (2..=19).contains(&m)) {
+                    Few
+                } else {
+                    Other
+                }
+            },
+            "lt" => match n_mod(10).zip(n_mod(100)) {
+                Some((1, m)) if !(11..=19).contains(&m) => One,
+                Some((2..=9, m)) if !(11..=19).contains(&m) => Few,
+                _ if f != 0 => Many,
+                _ => Other,
+            },
+            "lv" => {
+                if n_mod(10) == Some(0) || n_mod(100).is_some_and(|m| (11..=19).contains(&m)) || (v == 2 && (11..=19).contains(&(f % 100))) {
+                    Zero
+                } else if (n_mod(10) == Some(1) && n_mod(100) != Some(11)) || (f % 10 == 1 && (v != 2 || f % 100 != 11)) {
+                    One
+                } else {
+                    Other
+                }
+            },
+            "he" => match (i, v) {
+                (1, 0) => One,
+                (0, 1..) => One,
+                (2, 0) => Two,
+                _ => Other,
+            },
+            "ar" => match n_mod(100) {
+                _ if n(0) => Zero,
+                _ if n(1) => One,
+                _ if n(2) => Two,
+                Some(3..=10) => Few,
+                Some(11..=99) => Many,
+                _ => Other,
+            },
+            "ga" => match () {
+                _ if n(1) => One,
+                _ if n(2) => Two,
+                _ if n_in(3..=6) => Few,
+                _ if n_in(7..=10) => Many,
+                _ => Other,
+            },
+            "cy" => match () {
+                _ if n(0) => Zero,
+                _ if n(1) => One,
+                _ if n(2) => Two,
+                _ if n(3) => Few,
+                _ if n(6) => Many,
+                _ => Other,
+            },
+            _ => if i == 1 && v == 0 { One } else { Other },
+        }
+    }
+}
+
+impl fmt::Display for PluralCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The operands of a number in the CLDR plural rules: the integer digits
+/// `i`, the number of fraction digits `v`, and the fraction digits `f`,
+/// and `t` without trailing zeros.
+struct Operands {
+    i: u64,
+    v: usize,
+    f: u64,
+    t: u64,
+}
+
+impl Operands {
+    fn parse(number: &str) -> Option<Self> {
+        let number = number.trim();
+        let number = number.strip_prefix(['-', '+']).unwrap_or(number);
+        let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+        let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if integer.is_empty() || !digits(integer) || !digits(fraction) || (number.contains('.') && fraction.is_empty()) {
+            return None;
+        }
+        // only the last digits matter to the rules, which avoids overflows.
+        let value = |s: &str| s[s.len().saturating_sub(18)..].parse::<u64>().unwrap_or(0);
+        let trimmed = fraction.trim_end_matches('0');
+        Some(Self { i: value(integer), v: fraction.len(), f: value(fraction), t: value(trimmed) })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PluralCategory::{self, *};
+
+    #[test]
+    fn rules() {
+        let select = |locale, numbers: &[&str]| numbers.iter().map(|n| PluralCategory::select(locale, n)).collect::<Vec<_>>();
+        assert_eq!(vec![One, Other, Other, Other, Other], select("en-US", &["1", "0", "2", "1.5", "x"]));
+        assert_eq!(vec![One, Few, Many, Many, Other], select("ru", &["21", "3", "11", "5", "1.5"]));
+        assert_eq!(vec![One, Few, Many, Many, Other], select("pl", &["1", "22", "21", "12", "0.5"]));
+        assert_eq!(vec![Zero, One, Two, Few, Many, Other], select("ar", &["0", "1", "2", "103", "11", "100"]));
+        assert_eq!(vec![One, One, Many, Other], select("fr", &["0", "1.5", "1000000", "2"]));
+        assert_eq!(vec![One, Other], select("pt_PT", &["1", "0"]));
+        assert_eq!(vec![One, Few, Many, Other], select("cs", &["1", "4", "1.5", "5"]));
+        assert_eq!(vec![Other, Other], select("ja", &["1", "2"]));
+        assert_eq!(One, PluralCategory::select("en", "-1"));
+        assert_eq!(Other, PluralCategory::select("en", "1."));
+    }
+}
@@ -0,0 +1,179 @@
+use std::ops::Range;
+use crate::{ParseError, Template};
+use crate::parser::{is_name_char, name_end};
+use crate::spec::Count;
+use crate::template::{walk, Condition, Segment};
+
+impl Template {
+    /// Returns the template with the parameter `old` renamed to `new` in its
+    /// placeholders, block tags and dynamic widths and precisions. The rest of
+    /// the source, including literal text, escapes and whitespace inside curly
+    /// braces, is preserved byte for byte, so that tooling can refactor
+    /// catalogs safely.
+    ///
+    /// Loop variables named `old` are not renamed. The new name is written
+    /// as is: it should be a valid parameter name that is not the variable
+    /// of an enclosing `{for}` block.
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// let template = Template::parse(r#"{ user | html }{"{user}"}{if user}{user:-none}{for user in users}{user}{end}{end}"#).unwrap();
+    /// assert_eq!(
+    ///     r#"{ name | html }{"{user}"}{if name}{name:-none}{for user in users}{user}{end}{end}"#,
+    ///     template.rename_parameter("user", "name").source(),
+    /// );
+    /// ```
+    pub fn rename_parameter(&self, old: &str, new: &str) -> Template {
+        let edits = self.name_spans(old);
+        let mut source = String::with_capacity(self.source.len());
+        let mut last = 0;
+        for span in &edits {
+            source.push_str(&self.source[last..span.start]);
+            source.push_str(new);
+            last = span.end;
+        }
+        source.push_str(&self.source[last..]);
+        // a position moves by the change of length of the names before it.
+        let position = |i: usize| {
+            let before = edits.iter().take_while(|span| span.end <= i);
+            before.fold(i, |i, span| i + new.len() - span.len())
+        };
+        let mut segments: Vec<_> = self.segments.iter().map(|s| s.mapped(&position)).collect();
+        rename(&mut segments, old, new);
+        Template::new(source, segments).with_options_of(self)
+    }
+
+    /// The spans of the source where the parameter `name` is written, in
+    /// source order.
+    fn name_spans(&self, name: &str) -> Vec<Range<usize>> {
+        let mut spans = vec![];
+        walk(&self.segments, &mut |segment| match segment {
+            Segment::Placeholder(p) => {
+                let start = p.span.start + 1;
+                let start = start + self.source[start..].len() - self.source[start..].trim_start().len();
+                let mut end = name_end(&self.source, start);
+                if p.name.contains(':') {
+                    end = self.source[end + 1..].find(|ch| !is_name_char(ch)).map_or(self.source.len(), |j| end + 1 + j);
+                }
+                if !p.local && p.name == name {
+                    spans.push(start..end);
+                }
+                // the counts of the spec follow the name, before any filter.
+                let count = format!("{{{name}}}");
+                for _ in p.counts.iter().filter(|r| !r.local && r.name == name) {
+                    let Some(i) = self.source[end..p.span.end].find(&count) else {
+                        break;
+                    };
+                    spans.push(end + i + 1..end + i + 1 + name.len());
+                    end += i + count.len();
+                }
+            },
+            Segment::If { span, condition: Condition { reference: r, negated, .. }, .. } if !r.local && r.name == name => {
+                // the name follows the `if` and `not` keywords, and may be followed by filters.
+                let mut start = span.start + 1;
+                for keyword in ["if"].into_iter().chain(negated.then_some("not")) {
+                    start += self.source[start..].find(keyword).unwrap() + keyword.len();
+                    start += self.source[start..].len() - self.source[start..].trim_start().len();
+                }
+                spans.push(start..name_end(&self.source, start));
+            },
+            Segment::For { list: r, span, .. }
+            | Segment::Plural { count: r, span, .. } if !r.local && r.name == name => {
+                // the name is the last word of the opening tag.
+                let tag = &self.source[span.start..span.start + self.source[span.start..].find('}').unwrap()];
+                let tag = tag.trim_end();
+                let start = tag.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+                spans.push(span.start + start..span.start + tag.len());
+            },
+            _ => {},
+        });
+        spans
+    }
+}
+
+/// Renames the parameter `old` of segments and of their nested segments.
+fn rename(segments: &mut [Segment], old: &str, new: &str) {
+    let rename_reference = |name: &mut String, local: bool| {
+        if !local && name == old {
+            *name = new.to_owned();
+        }
+    };
+    for segment in segments {
+        match segment {
+            Segment::Placeholder(p) => {
+                rename_reference(&mut p.name, p.local);
+                for r in &mut p.counts {
+                    rename_reference(&mut r.name, r.local);
+                }
+                if let Some(spec) = &mut p.spec {
+                    for count in [&mut spec.width, &mut spec.precision].into_iter().flatten() {
+                        if matches!(count, Count::Parameter(name) if name == old) {
+                            *count = Count::Parameter(new.to_owned());
+                        }
+                    }
+                }
+                if let Some(default) = &mut p.default {
+                    rename(default, old, new);
+                }
+            },
+            Segment::If { condition, then, otherwise, .. } => {
+                rename_reference(&mut condition.reference.name, condition.reference.local);
+                rename(then, old, new);
+                rename(otherwise, old, new);
+            },
+            Segment::For { list, body, otherwise, .. } => {
+                rename_reference(&mut list.name, list.local);
+                rename(body, old, new);
+                rename(otherwise, old, new);
+            },
+            Segment::Plural { count, then, cases, .. } => {
+                rename_reference(&mut count.name, count.local);
+                rename(then, old, new);
+                for (_, segments) in cases {
+                    rename(segments, old, new);
+                }
+            },
+            Segment::Choose { then, variants, .. } => {
+                rename(then, old, new);
+                for (_, segments) in variants {
+                    rename(segments, old, new);
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Renames a parameter of a template source, preserving the rest of the
+/// source exactly, as described in [`Template::rename_parameter`].
+///
+/// ```
+/// let source = late_format::rename_parameter("Hi {usr}, {usr | html}! {'{usr}'}", "usr", "user").unwrap();
+/// assert_eq!("Hi {user}, {user | html}! {'{usr}'}", source);
+/// ```
+pub fn rename_parameter(source: &str, old: &str, new: &str) -> Result<String, ParseError> {
+    Ok(Template::parse(source)?.rename_parameter(old, new).source)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Template;
+    use maplit::hashmap;
+
+    #[test]
+    fn renaming() {
+        let source = "{ items[0] }{n:>{w}.{w}} {plural  w }{one}{w}{other}{ w:-{ w }}{end}{for k, v in w}{v}{end}{x}";
+        let template = Template::parse(source).unwrap();
+        let renamed = template.rename_parameter("w", "width").rename_parameter("items.0", "first");
+        assert_eq!(
+            "{ first }{n:>{width}.{width}} {plural  width }{one}{width}{other}{ width:-{ width }}{end}{for k, v in width}{v}{end}{x}",
+            renamed.source(),
+        );
+        assert_eq!(["first", "n", "width", "x"], renamed.parameters());
+        let spans: Vec<_> = renamed.placeholders().map(|p| &renamed.source()[p.span()]).collect();
+        assert_eq!(["{ first }", "{n:>{width}.{width}}", "{width}", "{ width:-{ width }}", "{ width }", "{v}", "{x}"], &spans[..]);
+        let renamed = Template::parse("{n:>{w}.{w}}").unwrap().rename_parameter("w", "width");
+        assert_eq!("1.000", renamed.render(&hashmap!{"n".into() => "1".into(), "width".into() => "3".into()}));
+        assert_eq!(template, template.rename_parameter("nope", "x"));
+    }
+}
@@ -0,0 +1,591 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::time::Instant;
+use crate::{FormatOptions, Masking, Missing, Numbers, PluralCategory, RenderError, RenderErrorKind, TracedPlaceholder};
+use crate::choose;
+use crate::filters::{Applied, Filter};
+use crate::number::Decimal;
+use crate::source_map::OutputSpan;
+use crate::template::{Placeholder, Reference, Segment, Template};
+use crate::value::{Value, ValueRef};
+
+/// A destination of rendered text, which may borrow the literals of the
+/// template, of lifetime `'t`, and the arguments.
+pub(crate) trait Output<'t> {
+    fn push_str(&mut self, s: &str);
+
+    fn push_literal(&mut self, s: &'t str) {
+        self.push_str(s);
+    }
+
+    /// Writes the source of a form kept as is by [`Template::render_partial`].
+    fn push_verbatim(&mut self, s: &str) {
+        self.push_str(s);
+    }
+
+    /// Writes the argument of a parameter, unchanged.
+    fn push_argument(&mut self, index: usize, s: &str) {
+        let _ = index;
+        self.push_str(s);
+    }
+
+    fn len(&self) -> usize;
+}
+
+impl Output<'_> for String {
+    fn push_str(&mut self, s: &str) {
+        String::push_str(self, s);
+    }
+
+    fn len(&self) -> usize {
+        String::len(self)
+    }
+}
+
+/// Rendered text written to a [`fmt::Write`], such as a formatter,
+/// remembering the first error.
+pub(crate) struct Writer<'w> {
+    inner: &'w mut dyn fmt::Write,
+    len: usize,
+    pub result: fmt::Result,
+}
+
+impl<'w> Writer<'w> {
+    pub fn new(inner: &'w mut dyn fmt::Write) -> Self {
+        Self { inner, len: 0, result: Ok(()) }
+    }
+}
+
+impl Output<'_> for Writer<'_> {
+    fn push_str(&mut self, s: &str) {
+        self.len += s.len();
+        if self.result.is_ok() {
+            self.result = self.inner.write_str(s);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// Rendered text kept as the parts it is made of, for
+/// [`Template::render_segments`].
+#[derive(Default)]
+pub(crate) struct Parts<'t> {
+    pub parts: Vec<Part<'t>>,
+    len: usize,
+}
+
+pub(crate) enum Part<'t> {
+    Literal(&'t str),
+    /// The argument of the parameter of this index.
+    Argument(usize),
+    Text(String),
+}
+
+impl<'t> Output<'t> for Parts<'t> {
+    fn push_str(&mut self, s: &str) {
+        self.len += s.len();
+        match self.parts.last_mut() {
+            Some(Part::Text(text)) => text.push_str(s),
+            _ => self.parts.push(Part::Text(s.to_owned())),
+        }
+    }
+
+    fn push_literal(&mut self, s: &'t str) {
+        self.len += s.len();
+        self.parts.push(Part::Literal(s));
+    }
+
+    fn push_argument(&mut self, index: usize, s: &str) {
+        self.len += s.len();
+        self.parts.push(Part::Argument(index));
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// Renders the segments of a template, keeping track of the budget.
+pub(crate) struct Renderer<'a, 'o> {
+    template: &'a Template,
+    options: &'o FormatOptions,
+    steps: usize,
+    started: Instant,
+    /// The spans of the rendered segments, if recorded.
+    pub spans: Option<Vec<OutputSpan>>,
+    /// The rendered placeholders, if recorded.
+    pub trace: Option<Vec<TracedPlaceholder>>,
+    /// The parameters of the forms kept as is, when rendering partially.
+    pub leftovers: Option<Vec<String>>,
+}
+
+impl<'a, 'o> Renderer<'a, 'o> {
+    pub fn new(template: &'a Template, options: &'o FormatOptions) -> Self {
+        Self { template, options, steps: 0, started: Instant::now(), spans: None, trace: None, leftovers: None }
+    }
+
+    /// Records the spans of the rendered segments in [`Renderer::spans`].
+    pub fn record_spans(mut self) -> Self {
+        self.spans = Some(vec![]);
+        self
+    }
+
+    /// Records the rendered placeholders in [`Renderer::trace`], without their layers.
+    pub fn record_trace(mut self) -> Self {
+        self.trace = Some(vec![]);
+        self
+    }
+
+    /// Keeps the placeholders and blocks whose parameters are missing as
+    /// they are written, recording the parameters in [`Renderer::leftovers`].
+    pub fn partial(mut self) -> Self {
+        self.leftovers = Some(vec![]);
+        self
+    }
+
+    /// Renders the template into `output`, resolving arguments by parameter index.
+    pub fn render<'v>(&mut self, value: impl Fn(usize) -> Option<ValueRef<'v>>, output: &mut impl Output<'a>) -> Result<(), RenderError> {
+        let mut scope = Scope { value: &value, locals: vec![] };
+        self.segments(&self.template.segments, &mut scope, output)
+    }
+
+    /// Renders a segment of the top level of the template into `output`.
+    pub fn render_segment<'v>(&mut self, segment: &'a Segment, value: impl Fn(usize) -> Option<ValueRef<'v>>, output: &mut impl Output<'a>) -> Result<(), RenderError> {
+        let mut scope = Scope { value: &value, locals: vec![] };
+        self.segment(segment, &mut scope, output)
+    }
+
+    fn segments<'v>(&mut self, segments: &'a [Segment], scope: &mut Scope<'_, 'v>, output: &mut impl Output<'a>) -> Result<(), RenderError> {
+        for segment in segments {
+            let start = output.len();
+            self.segment(segment, scope, output)?;
+            if let (Some(trace), Segment::Placeholder(p)) = (&mut self.trace, segment) {
+                let value = scope.get(p.index, p.local, p.path.as_deref()).map(|v| {
+                    if scope.masked(p, self.options) { self.options.masking.mask(&v.text()) } else { v.text().into_owned() }
+                });
+                trace.push(TracedPlaceholder {
+                    name: p.name.clone(),
+                    parameter: (!p.local).then_some(p.index),
+                    source: p.span(),
+                    output: start..output.len(),
+                    value,
+                    layer: None,
+                    filters: p.filters().map(str::to_owned).collect(),
+                });
+            }
+            // the segments of a block, or of a default, record their own spans.
+            if matches!(segment, Segment::If { .. } | Segment::For { .. } | Segment::Plural { .. } | Segment::Choose { .. }) {
+                continue;
+            }
+            if let Segment::Placeholder(p) = segment {
+                if p.default.is_some() && scope.get(p.index, p.local, p.path.as_deref()).is_none_or(|v| v.is_empty()) {
+                    continue;
+                }
+            }
+            if let Some(spans) = &mut self.spans {
+                let (parameter, missing) = match segment {
+                    Segment::Placeholder(p) => (Some(p.name.clone()), scope.get(p.index, p.local, p.path.as_deref()).is_none()),
+                    _ => (None, false),
+                };
+                spans.push(OutputSpan { output: start..output.len(), source: segment.span(), parameter, missing });
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the source of a segment referring to parameters that are
+    /// missing, when rendering partially, returning whether it did.
+    fn keep<'r>(&mut self, segment: &Segment, references: impl IntoIterator<Item = &'r Reference>, scope: &Scope<'_, '_>, output: &mut impl Output<'a>) -> bool {
+        let Some(leftovers) = &mut self.leftovers else {
+            return false;
+        };
+        let missing: Vec<_> = references.into_iter().filter(|r| !r.local && scope.get(r.index, false, None).is_none()).collect();
+        for r in &missing {
+            if !leftovers.contains(&r.name) {
+                leftovers.push(r.name.clone());
+            }
+        }
+        if !missing.is_empty() {
+            output.push_verbatim(&self.template.source[segment.span()]);
+        }
+        !missing.is_empty()
+    }
+
+    fn segment<'v>(&mut self, segment: &'a Segment, scope: &mut Scope<'_, 'v>, output: &mut impl Output<'a>) -> Result<(), RenderError> {
+        let source: &'a str = &self.template.source;
+        let kept = self.leftovers.is_some() && match segment {
+            Segment::If { condition, .. } => self.keep(segment, [&condition.reference], scope, output),
+            Segment::For { list, .. } => self.keep(segment, [list], scope, output),
+            Segment::Plural { count, .. } => self.keep(segment, [count], scope, output),
+            Segment::Placeholder(p) => {
+                let reference = Reference { name: p.name.clone(), index: p.index, local: p.local, path: None };
+                self.keep(segment, std::iter::once(&reference).chain(&p.counts), scope, output)
+            },
+            _ => false,
+        };
+        if kept {
+            return Ok(());
+        }
+        match segment {
+            Segment::Literal(span) => output.push_literal(&source[span.clone()]),
+            Segment::Raw { content, .. } => output.push_literal(&source[content.clone()]),
+            Segment::Escaped { text, .. } => output.push_literal(text),
+            Segment::Comment(_) => {},
+            Segment::If { condition, then, otherwise, .. } => {
+                self.step(segment)?;
+                let r = &condition.reference;
+                let value = scope.get(r.index, r.local, r.path.as_deref());
+                let holds = match (value, &condition.comparison) {
+                    (None, _) => false,
+                    (Some(v), None) if condition.filters.is_empty() => !v.is_empty(),
+                    (Some(v), comparison) => {
+                        let filtered = self.filter(segment, &r.name, items(v), v.text().into_owned(), &condition.filters)?;
+                        match comparison {
+                            Some(comparison) => Decimal::parse(&filtered).is_some_and(|n| comparison.holds(&n)),
+                            None => !filtered.is_empty(),
+                        }
+                    },
+                };
+                let holds = holds != condition.negated;
+                self.segments(if holds { then } else { otherwise }, scope, output)?;
+            },
+            Segment::For { key, list, body, otherwise, .. } => {
+                self.step(segment)?;
+                // the variables of a loop over a sensitive list are sensitive too.
+                let sensitive = if list.local { scope.locals[list.index].1 } else { self.options.is_sensitive(&list.name) };
+                let list = scope.get(list.index, list.local, list.path.as_deref());
+                let entries: Vec<_> = match key {
+                    Some(_) => list.map_or(vec![], |v| v.entries()).into_iter().map(|(key, item)| (Some(key), item)).collect(),
+                    None => list.map_or(vec![], |v| v.items()).into_iter().map(|item| (None, item)).collect(),
+                };
+                if entries.is_empty() {
+                    self.segments(otherwise, scope, output)?;
+                }
+                for (key, item) in entries {
+                    self.step(segment)?;
+                    let len = scope.locals.len();
+                    scope.locals.extend(key.map(|key| (key, sensitive)));
+                    scope.locals.push((item, sensitive));
+                    let r = self.segments(body, scope, output);
+                    scope.locals.truncate(len);
+                    r?;
+                }
+            },
+            Segment::Plural { count, then, cases, .. } => {
+                self.step(segment)?;
+                let count = scope.get(count.index, count.local, count.path.as_deref()).map(|v| v.text());
+                let category = count.map_or(PluralCategory::Other, |n| PluralCategory::select(self.options.get_locale(), &n));
+                self.segments(then, scope, output)?;
+                let case = cases.iter().find(|(c, _)| *c == category).or_else(|| cases.iter().find(|(c, _)| *c == PluralCategory::Other));
+                if let Some((_, segments)) = case {
+                    self.segments(segments, scope, output)?;
+                }
+            },
+            Segment::Choose { then, variants, .. } => {
+                self.step(segment)?;
+                self.segments(then, scope, output)?;
+                if let Some(i) = choose::pick(variants.iter().map(|(weight, _)| *weight), self.options.next_random()) {
+                    self.segments(&variants[i].1, scope, output)?;
+                }
+            },
+            Segment::Placeholder(p) => {
+                self.step(segment)?;
+                let v = scope.get(p.index, p.local, p.path.as_deref());
+                if let Some(default) = p.default.as_ref().filter(|_| v.is_none_or(|v| v.is_empty())) {
+                    return self.segments(default, scope, output);
+                }
+                // the `default` filter receives a missing argument as an empty one.
+                let Some(v) = v.or_else(|| p.has_default().then_some(ValueRef::Str(""))) else {
+                    match self.options.missing {
+                        Missing::None => output.push_str("None"),
+                        Missing::Marker => {
+                            output.push_str("<missing:");
+                            output.push_str(&p.name);
+                            output.push_str(">");
+                        },
+                        Missing::Error => {
+                            return Err(RenderError::new(RenderErrorKind::MissingArgument(p.name.clone()), p.span()));
+                        },
+                    }
+                    return Ok(());
+                };
+                let value = v;
+                let v = value.text();
+                if scope.masked(p, self.options) {
+                    output.push_str(&self.options.masking.mask(&v));
+                    return Ok(());
+                }
+                if p.spec.is_none() && p.filters.is_empty() {
+                    if p.local {
+                        output.push_str(&v);
+                    } else {
+                        output.push_argument(p.index, &v);
+                    }
+                    return Ok(());
+                }
+                let mut v = v.into_owned();
+                if let Some(spec) = &p.spec {
+                    self.step(segment)?;
+                    if spec.is_numeric() {
+                        self.number(segment, &p.name, &v)?;
+                    }
+                    if p.counts.is_empty() {
+                        self.charge(segment, spec.cost())?;
+                        v = spec.apply(&v, self.options);
+                    } else {
+                        let mut counts = vec![];
+                        for r in &p.counts {
+                            let count = scope.get(r.index, r.local, r.path.as_deref());
+                            if count.is_none() && self.options.missing == Missing::Error {
+                                return Err(RenderError::new(RenderErrorKind::MissingArgument(r.name.clone()), p.span()));
+                            }
+                            counts.push((&r.name, count.and_then(|c| c.text().trim().parse::<usize>().ok())));
+                        }
+                        let spec = spec.resolve(|name| counts.iter().find(|(n, _)| *n == name)?.1, &self.options.limits);
+                        self.charge(segment, spec.cost())?;
+                        v = spec.apply(&v, self.options);
+                    }
+                }
+                // the filters receive the items of a list argument without a spec.
+                let items = if p.spec.is_some() { None } else { items(value) };
+                output.push_str(&self.filter(segment, &p.name, items, v, &p.filters)?);
+            },
+        }
+        Ok(())
+    }
+
+    /// Checks that the text of the argument of `name`, formatted as a number, is one.
+    fn number(&self, segment: &Segment, name: &str, v: &str) -> Result<(), RenderError> {
+        if self.options.numbers == Numbers::Strict && Decimal::parse(v).is_none() {
+            return Err(RenderError::new(RenderErrorKind::NotANumber(name.to_owned()), segment.span()));
+        }
+        Ok(())
+    }
+
+    /// Applies filters to the text `v` of the value of `name`, or to its
+    /// items if it is a list.
+    fn filter(&mut self, segment: &Segment, name: &str, mut items: Option<Vec<Cow<'_, str>>>, mut v: String, filters: &[Filter]) -> Result<String, RenderError> {
+        for filter in filters {
+            let replacement = filter.is_replacement();
+            if let Some(list) = items.take_if(|_| replacement) {
+                v = list.join(", ");
+            }
+            // a replacement is charged for its output before building it.
+            let cost = if replacement { filter.replaced_len(&v) / 256 } else { 0 };
+            self.charge(segment, 1 + cost)?;
+            match items.take() {
+                Some(list) => match filter.apply_items(list, self.options) {
+                    Applied::Items(list) => items = Some(list),
+                    Applied::Text(text) => v = text,
+                },
+                None => {
+                    if filter.is_numeric() {
+                        self.number(segment, name, &v)?;
+                    }
+                    v = filter.apply(&v, self.options);
+                },
+            }
+            if !replacement {
+                let len = items.as_ref().map_or(v.len(), |list| list.iter().map(|item| item.len()).sum());
+                self.charge(segment, len / 256)?;
+            }
+        }
+        if let Some(list) = items {
+            v = list.join(", ");
+        }
+        Ok(v)
+    }
+
+    /// Counts a substitution operation against the budget.
+    fn step(&mut self, segment: &Segment) -> Result<(), RenderError> {
+        self.charge(segment, 1)
+    }
+
+    /// Counts `n` steps against the budget.
+    fn charge(&mut self, segment: &Segment, n: usize) -> Result<(), RenderError> {
+        self.steps = self.steps.saturating_add(n);
+        let budget = &self.options.budget;
+        let exceeded = budget.max_steps.is_some_and(|max| self.steps > max)
+            || budget.max_duration.is_some_and(|max| self.started.elapsed() >= max);
+        if exceeded {
+            return Err(RenderError::new(RenderErrorKind::BudgetExceeded, segment.span()));
+        }
+        Ok(())
+    }
+}
+
+/// The items of a list value, as text.
+fn items<'v>(value: ValueRef<'v>) -> Option<Vec<Cow<'v, str>>> {
+    match value {
+        ValueRef::Value(Value::List(list)) => Some(list.iter().map(|item| ValueRef::Value(item).text()).collect()),
+        _ => None,
+    }
+}
+
+/// The arguments and the loop variables visible while rendering.
+struct Scope<'f, 'v> {
+    value: &'f dyn Fn(usize) -> Option<ValueRef<'v>>,
+    /// The current items of the enclosing `{for}` blocks, outermost first,
+    /// with whether they are sensitive.
+    locals: Vec<(ValueRef<'v>, bool)>,
+}
+
+impl<'v> Scope<'_, 'v> {
+    fn get(&self, index: usize, local: bool, path: Option<&str>) -> Option<ValueRef<'v>> {
+        if local {
+            return match path {
+                Some(path) => self.locals[index].0.get_path(path),
+                None => Some(self.locals[index].0),
+            };
+        }
+        (self.value)(index)
+    }
+
+    /// Whether the output of a placeholder is masked, as its parameter, or
+    /// the list of its loop variable, is sensitive.
+    fn masked(&self, p: &Placeholder, options: &FormatOptions) -> bool {
+        options.masking != Masking::Plain && if p.local { self.locals[p.index].1 } else { options.is_sensitive(&p.name) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use crate::{Budget, FormatOptions, LateFormat, Masking, Missing, ParseErrorKind, RenderErrorKind, Template, Value};
+    use maplit::hashmap;
+
+    #[test]
+    fn budget() {
+        let template = Template::parse("{a} {b | dedent | dedent} {c}").unwrap();
+        let arguments = hashmap!{"a".into() => "x".into(), "b".into() => "y".into()};
+        let options = FormatOptions::new().budget(Budget::new().max_steps(4));
+        assert_eq!("x y {c}", template.source.late_format_with(arguments.clone(), &options));
+        let error = template.render_with(&arguments, &options).unwrap_err();
+        assert_eq!(&RenderErrorKind::BudgetExceeded, error.kind());
+        assert_eq!(26..29, error.span());
+        let options = FormatOptions::new().budget(Budget::new().max_steps(5));
+        assert_eq!("x y None", template.render_with(&arguments, &options).unwrap());
+        let options = FormatOptions::new().budget(Budget::new().max_duration(Duration::ZERO));
+        assert!(template.render_with(&arguments, &options).is_err());
+        // each 256 characters of padding count as a step.
+        let template = Template::parse("{a:600}").unwrap();
+        assert!(template.render_with(&arguments, &FormatOptions::new().budget(Budget::new().max_steps(3))).is_err());
+        assert_eq!(600, template.render_with(&arguments, &FormatOptions::new().budget(Budget::new().max_steps(4))).unwrap().len());
+        // and so do each 256 characters of the output of a filter, charged
+        // before a replacement builds it.
+        let arguments = hashmap!{"a".into() => "a".repeat(60), "b".into() => "a".repeat(600)};
+        let template = Template::parse("{a | replace:a,aaaaaaaaaa}").unwrap();
+        assert!(template.render_with(&arguments, &FormatOptions::new().budget(Budget::new().max_steps(3))).is_err());
+        assert_eq!(600, template.render_with(&arguments, &FormatOptions::new().budget(Budget::new().max_steps(4))).unwrap().len());
+        let template = Template::parse("{b | trim}").unwrap();
+        assert!(template.render_with(&arguments, &FormatOptions::new().budget(Budget::new().max_steps(3))).is_err());
+        assert!(template.render_with(&arguments, &FormatOptions::new().budget(Budget::new().max_steps(4))).is_ok());
+        let template = Template::parse(&format!("{{a{}}}", " | replace:a,aaaaaaaaaa".repeat(9))).unwrap();
+        let error = template.render_with(&arguments, &FormatOptions::new().budget(Budget::untrusted())).unwrap_err();
+        assert_eq!(&RenderErrorKind::BudgetExceeded, error.kind());
+    }
+
+    #[test]
+    fn missing() {
+        let template = Template::parse("{a} {user_id | dedent}").unwrap();
+        let arguments = hashmap!{"a".into() => "x".into()};
+        let options = FormatOptions::new().missing(Missing::Marker);
+        assert_eq!("x <missing:user_id>", template.render_with(&arguments, &options).unwrap());
+        let options = FormatOptions::new().missing(Missing::Error);
+        let error = template.render_with(&arguments, &options).unwrap_err();
+        assert_eq!(&RenderErrorKind::MissingArgument("user_id".into()), error.kind());
+        assert_eq!((Some("user_id"), 4..22), (error.parameter(), error.span()));
+        assert_eq!("x {user_id | dedent}", "{a} {user_id | dedent}".late_format_with(arguments, &options));
+    }
+
+    #[test]
+    fn loops() {
+        let template = Template::parse("{for row in rows}{for cell in row}{cell}{else}-{end};{end}{for x in y}{else}!{end}").unwrap();
+        let arguments = hashmap!{"rows".into() => Value::from(vec![Value::from(vec!["a", "b"]), Value::from(vec![""; 0]), Value::from("c")])};
+        assert_eq!("ab;-;c;!", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+        assert_eq!("x;!", template.render(&hashmap!{"rows".into() => "x".into()}));
+        let options = FormatOptions::new().budget(Budget::new().max_steps(8));
+        assert!(template.render_arguments(&arguments, &options).is_err());
+        let mapped = template.render_mapped(&hashmap!{"rows".into() => "x".into()}, &FormatOptions::new()).unwrap();
+        assert_eq!(Some("cell"), mapped.lookup(0).unwrap().parameter());
+    }
+
+    #[test]
+    fn comparisons() {
+        let source = "{if title | len > 5}long{else}short{end} {if not n >= 10}small{end} {if n|add:1 == 10}ten{end} {if tags | len != 0}tagged{end} {if x < 1}x{end}";
+        let template = Template::parse(source).unwrap();
+        let arguments = hashmap!{"title".into() => Value::from("héllo!"), "n".into() => Value::from("9"), "tags".into() => Value::from(vec!["a"])};
+        assert_eq!("long small ten tagged ", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+        let arguments = hashmap!{"title".into() => Value::from("hé"), "n".into() => Value::from("nan"), "tags".into() => Value::from(vec![""; 0]), "x".into() => Value::from("0.5")};
+        assert_eq!("short small   x", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+        assert_eq!(template.minified(), template);
+        assert_eq!("{if title|len>5}long{else}short{end}", &template.minified().source()[..36]);
+        let renamed = Template::parse("{ if not n|len >= 10 }{end}{if n}{end}").unwrap().rename_parameter("n", "count");
+        assert_eq!("{ if not count|len >= 10 }{end}{if count}{end}", renamed.source());
+        assert!(Template::parse("{if a | nope}{end}").is_err());
+        assert_eq!("{if a > b}", Template::parse("{if a > b}").unwrap().render(&hashmap!{}));
+    }
+
+    #[test]
+    fn entries() {
+        let template = Template::parse("{for name, value in headers}{name}: {value}\n{else}none{end}").unwrap();
+        let headers: Value = [("Host", "x"), ("Accept", "*/*")].into_iter().collect();
+        let arguments = hashmap!{"headers".into() => headers};
+        assert_eq!("Accept: */*\nHost: x\n", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+        assert_eq!("none", template.render(&hashmap!{}));
+        let template = Template::parse("{for i, item in list}{for j , x in item}{i}.{j}={x} {end}{end}").unwrap();
+        let arguments = hashmap!{"list".into() => Value::from(vec![Value::from(vec!["a", "b"]), Value::from("c")])};
+        assert_eq!("0.0=a 0.1=b 1.0=c ", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+        assert_ne!(template, Template::parse("{for i in list}{for j , x in item}{i}.{j}={x} {end}{end}").unwrap());
+    }
+
+    #[test]
+    fn masking() {
+        let options = FormatOptions::new().sensitive("token").sensitive("secret:").resolver("secret", |_: &str| Some(Value::from("s")));
+        let template = Template::parse_with("{token | html} {secret:a} {for t in list}{t}{end} {user}", &options).unwrap();
+        let arguments = hashmap!{"token".into() => Value::from("<t>"), "list".into() => Value::from(vec!["x"]), "user".into() => Value::from("u")};
+        assert_eq!("&lt;t&gt; s x u", template.render_arguments(&arguments, &options).unwrap());
+        let options = options.masking(Masking::Dots);
+        assert_eq!("••• ••• x u", template.render_arguments(&arguments, &options).unwrap());
+        let options = options.masking(Masking::Hash);
+        assert_eq!("#cbf29ce484222325", Masking::Hash.mask(""));
+        assert_eq!(template.render_arguments(&arguments, &options), template.render_arguments(&arguments, &options));
+        assert!(!template.render_arguments(&arguments, &options).unwrap().contains("t&gt;"));
+        // the variables of loops over a sensitive list are masked.
+        let options = FormatOptions::new().sensitive("tokens").masking(Masking::Dots);
+        let template = Template::parse("{for t in tokens}{t},{end}{for row in tokens}{for k, c in row}{k}{c}{end}{end}|{for x in list}{x}{end}").unwrap();
+        let arguments = hashmap!{"tokens".into() => Value::from(vec![Value::from("a"), Value::from(vec!["b"])]), "list".into() => Value::from(vec!["x"])};
+        assert_eq!("•••,•••,••••••••••••|x", template.render_arguments(&arguments, &options).unwrap());
+        let trace = template.render_traced(&arguments, &options).unwrap();
+        assert!(trace.placeholders().iter().all(|p| p.value().is_none_or(|v| v == "•••" || v == "x")));
+        // so are the keys and indices of sensitive parameters.
+        let options = FormatOptions::new().sensitive("creds").sensitive("tokens").masking(Masking::Dots);
+        let template = Template::parse("{creds.password} {tokens.0} {tokens[1]} {credentials} {later}").unwrap();
+        let arguments = hashmap!{"creds".into() => Value::Map([("password".to_owned(), Value::from("hunter2"))].into()), "tokens".into() => Value::from(vec!["a", "b"]), "credentials".into() => Value::from("c")};
+        assert_eq!("••• ••• ••• c None", template.render_arguments(&arguments, &options).unwrap());
+        assert_eq!("••• ••• ••• c {later}", template.render_partial(&arguments, &options).unwrap().output());
+        let trace = template.render_traced(&arguments, &options).unwrap();
+        assert_eq!("••• ••• ••• c None", trace.output());
+        let values: Vec<_> = trace.placeholders().iter().map(|p| p.value()).collect();
+        assert_eq!([Some("•••"), Some("•••"), Some("•••"), Some("c"), None], values.as_slice());
+    }
+
+    #[test]
+    fn plurals() {
+        let template = Template::parse("{plural n}{n} {one}файл{few}файла{many}файлов{other}файла{end}").unwrap();
+        let options = FormatOptions::new().locale("ru");
+        let render = |n: &str| template.render_with(&hashmap!{"n".into() => n.into()}, &options).unwrap();
+        assert_eq!(["1 файл", "3 файла", "11 файлов", "1.5 файла"], [render("1"), render("3"), render("11"), render("1.5")]);
+        let template = Template::parse("{plural n}{one}{n} item{other}{n} items{end}").unwrap();
+        assert_eq!("1 item", template.render(&hashmap!{"n".into() => "1".into()}));
+        assert_eq!("None items", template.render(&hashmap!{}));
+        assert_eq!("", Template::parse("{plural n}{one}x{end}").unwrap().render(&hashmap!{}));
+        let error = Template::parse("{plural n}{one}{one}{else}{end}").unwrap_err();
+        assert_eq!(&ParseErrorKind::UnexpectedTag("one".into()), error.kind());
+        assert_eq!(15..20, error.span());
+        assert_eq!("{one} {few}", "{one} {few}".late_format(hashmap!{"one".into() => "{one}".into(), "few".into() => "{few}".into()}));
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use crate::Value;
+
+/// Resolves the keys of a namespace, for placeholders such as `{env:HOME}`,
+/// registered through [`FormatOptions::resolver`](crate::FormatOptions::resolver).
+///
+/// It is implemented for closures taking the key.
+///
+/// ```
+/// use late_format::{FormatOptions, LateFormat, Value};
+/// use maplit::hashmap;
+/// let options = FormatOptions::new().resolver("upper", |key: &str| Some(Value::from(key.to_uppercase())));
+/// assert_eq!("HELLO x", "{upper:hello} {a}".late_format_with(hashmap!{"a".into() => "x".into()}, &options));
+/// ```
+pub trait Resolver: Send + Sync {
+    /// Resolves a key, returning `None` if it is missing.
+    fn resolve(&self, key: &str) -> Option<Value>;
+}
+
+impl<F: Fn(&str) -> Option<Value> + Send + Sync> Resolver for F {
+    fn resolve(&self, key: &str) -> Option<Value> {
+        self(key)
+    }
+}
+
+/// Resolves keys to the environment variables of the process.
+///
+/// ```
+/// use late_format::{Env, FormatOptions, LateFormat};
+/// use maplit::hashmap;
+/// let options = FormatOptions::new().resolver("env", Env);
+/// assert_eq!(std::env::var("PATH").unwrap(), "{env:PATH}".late_format_with(hashmap!{}, &options));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Env;
+
+impl Resolver for Env {
+    fn resolve(&self, key: &str) -> Option<Value> {
+        std::env::var(key).ok().map(Value::String)
+    }
+}
+
+/// The resolvers of [`FormatOptions`](crate::FormatOptions), by namespace.
+#[derive(Clone, Default)]
+pub(crate) struct Resolvers(HashMap<String, Arc<dyn Resolver>>);
+
+impl Resolvers {
+    pub fn insert(&mut self, namespace: String, resolver: Arc<dyn Resolver>) {
+        self.0.insert(namespace, resolver);
+    }
+
+    pub fn get(&self, namespace: &str) -> Option<&dyn Resolver> {
+        self.0.get(namespace).map(|r| &**r)
+    }
+}
+
+impl fmt::Debug for Resolvers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut namespaces: Vec<_> = self.0.keys().collect();
+        namespaces.sort();
+        f.debug_set().entries(namespaces).finish()
+    }
+}
+
+/// The coercions of [`FormatOptions`](crate::FormatOptions), by parameter
+/// name, in order of registration.
+#[derive(Clone, Default)]
+pub(crate) struct Coercions(HashMap<String, Vec<Coercion>>);
+
+type Coercion = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+impl Coercions {
+    pub fn insert(&mut self, name: String, coercion: Coercion) {
+        self.0.entry(name).or_default().push(coercion);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The coercions of a parameter, composed, if it has any.
+    pub fn get(&self, name: &str) -> Option<impl Fn(&str) -> String + '_> {
+        let coercions = self.0.get(name)?;
+        Some(|text: &str| coercions.iter().fold(text.to_owned(), |text, coercion| coercion(&text)))
+    }
+}
+
+impl fmt::Debug for Coercions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<_> = self.0.keys().collect();
+        names.sort();
+        f.debug_set().entries(names).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Args, FormatOptions, LateFormat, ParseErrorKind, Profile, Template, Value};
+    use maplit::hashmap;
+
+    #[test]
+    fn namespaces() {
+        let secrets = hashmap!{"DB_PASS".to_owned() => "hunter2".to_owned()};
+        let options = FormatOptions::new().resolver("secret", move |key: &str| secrets.get(key).cloned().map(Value::String));
+        let arguments = hashmap!{"secret:DB_PASS".into() => "args".into(), "user".into() => "x".into()};
+        assert_eq!("x:hunter2 {other:DB_PASS}", "{user}:{secret:DB_PASS} {other:DB_PASS}".late_format_with(arguments.clone(), &options));
+        let template = Template::parse_with("{secret:DB_PASS | html}{secret:NOPE}", &options).unwrap();
+        assert_eq!(["secret:DB_PASS", "secret:NOPE"], template.parameters());
+        let report = Template::validate(template.source(), &arguments, &options).unwrap_err();
+        assert_eq!("missing argument `secret:NOPE` at 23..36", report.to_string());
+        let error = Template::parse("{secret:DB_PASS}").unwrap_err();
+        assert_eq!(&ParseErrorKind::InvalidSpec("DB_PASS".into()), error.kind());
+        let options = options.profile(Profile::Untrusted);
+        assert_eq!("{secret:DB_PASS}", "{secret:DB_PASS}".late_format_with(arguments, &options));
+    }
+
+    #[test]
+    fn coercions() {
+        let options = FormatOptions::new()
+            .coerce("tags", |tag| tag.trim().to_owned())
+            .coerce("name", |name| name.trim().to_owned())
+            .coerce("name", |name| name.replace(' ', "_"))
+            .coerce("env:X", |_| "coerced".to_owned())
+            .resolver("env", |_: &str| Some(Value::from("x")));
+        let template = Template::parse_with("{for t in tags}[{t}]{end}{if name}{name | title_case}{else}-{end} {env:X}", &options).unwrap();
+        let arguments = Args::new().set("tags", vec![" a ", "b "]).set("name", "  ana maria ");
+        assert_eq!("[a][b]Ana_Maria coerced", template.render_arguments(&arguments, &options).unwrap());
+        assert_eq!("[a]- coerced", template.render_with(&hashmap!{"tags".into() => " a".into(), "name".into() => "  ".into()}, &options).unwrap());
+        assert_eq!("{\"env:X\", \"name\", \"tags\"}", format!("{:?}", options.coercions));
+    }
+}
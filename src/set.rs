@@ -0,0 +1,435 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, Weak};
+use crate::{FormatOptions, ParseError, Template};
+use crate::template::walk;
+
+/// Templates by key, such as the messages of one locale of an application.
+///
+/// ```
+/// use late_format::TemplateSet;
+/// let mut reference = TemplateSet::new();
+/// reference.add("greeting", "Hello, {name}!").unwrap();
+/// reference.add("inbox", "{count} new messages").unwrap();
+/// let mut french = TemplateSet::new();
+/// french.add("greeting", "Bonjour, {nom} !").unwrap();
+/// french.add("extra", "Salut").unwrap();
+/// let report = french.check_against(&reference).unwrap_err();
+/// assert_eq!("\
+/// `extra`: message is not in the reference
+/// `greeting`: missing `name`, unknown `nom`
+/// `inbox`: missing message", report.to_string());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TemplateSet {
+    templates: BTreeMap<String, Template>,
+}
+
+impl TemplateSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a template, replacing the template of the same key.
+    pub fn insert(&mut self, key: impl Into<String>, template: Template) {
+        self.templates.insert(key.into(), template);
+    }
+
+    /// Parses and adds a template.
+    pub fn add(&mut self, key: impl Into<String>, source: &str) -> Result<(), ParseError> {
+        self.add_with(key, source, &FormatOptions::default())
+    }
+
+    /// Parses and adds a template, applying the source options.
+    pub fn add_with(&mut self, key: impl Into<String>, source: &str, options: &FormatOptions) -> Result<(), ParseError> {
+        self.insert(key, Template::parse_with(source, options)?);
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Template> {
+        self.templates.get(key)
+    }
+
+    /// The keys and templates, in order of key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Template)> {
+        self.templates.iter().map(|(key, template)| (key.as_str(), template))
+    }
+
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+
+    /// Checks that the set has the keys of a reference set, such as the
+    /// messages of the source language, and that each of its templates uses
+    /// exactly the parameters of the reference template of the same key, so
+    /// that localized messages neither drop nor invent arguments. The report
+    /// has one mismatch per key, in order of key.
+    pub fn check_against(&self, reference: &TemplateSet) -> Result<(), CheckReport> {
+        let keys: BTreeSet<&String> = self.templates.keys().chain(reference.templates.keys()).collect();
+        let mismatches: Vec<Mismatch> = keys.into_iter().filter_map(|key| {
+            let kind = match (self.templates.get(key), reference.templates.get(key)) {
+                (Some(template), Some(reference)) => {
+                    let difference = |a: &Template, b: &Template| -> Vec<String> {
+                        a.parameters().iter().filter(|p| !b.parameters().contains(p)).cloned().collect()
+                    };
+                    let (missing, unknown) = (difference(reference, template), difference(template, reference));
+                    if missing.is_empty() && unknown.is_empty() {
+                        return None;
+                    }
+                    MismatchKind::Parameters { missing, unknown }
+                },
+                (None, _) => MismatchKind::MissingMessage,
+                (_, None) => MismatchKind::UnknownMessage,
+            };
+            Some(Mismatch { key: key.clone(), kind })
+        }).collect();
+        if mismatches.is_empty() {
+            return Ok(());
+        }
+        Err(CheckReport { mismatches })
+    }
+}
+
+impl TemplateSet {
+    /// Where each parameter is used in the set, in order of parameter name,
+    /// to find parameters used by few templates, such as dead ones or those
+    /// named inconsistently.
+    ///
+    /// ```
+    /// use late_format::TemplateSet;
+    /// let mut set = TemplateSet::new();
+    /// set.add("inbox", "{user_name}: {count} new {plural count}{one}message{other}messages{end}").unwrap();
+    /// set.add("welcome", "Welcome, {userName}!").unwrap();
+    /// let usage = set.usage();
+    /// let names: Vec<_> = usage.iter().map(|u| (u.parameter(), u.templates())).collect();
+    /// assert_eq!(vec![("count", 1), ("userName", 1), ("user_name", 1)], names);
+    /// assert_eq!([("inbox", 13..20), ("inbox", 25..71)], &usage[0].uses().iter().map(|u| (u.key(), u.span())).collect::<Vec<_>>()[..]);
+    /// ```
+    pub fn usage(&self) -> Vec<ParameterUsage> {
+        let mut usage: BTreeMap<&str, Vec<ParameterUse>> = BTreeMap::new();
+        for (key, template) in &self.templates {
+            for name in template.parameters() {
+                usage.entry(name).or_default();
+            }
+            walk(&template.segments, &mut |segment| {
+                let mut parameters = segment.parameters();
+                parameters.dedup();
+                for index in parameters {
+                    let uses = usage.entry(&template.parameters()[index]).or_default();
+                    uses.push(ParameterUse { key: key.clone(), span: segment.span() });
+                }
+            });
+        }
+        usage.into_iter().map(|(parameter, uses)| ParameterUsage { parameter: parameter.to_owned(), uses }).collect()
+    }
+}
+
+impl<K: Into<String>> FromIterator<(K, Template)> for TemplateSet {
+    fn from_iter<I: IntoIterator<Item = (K, Template)>>(iter: I) -> Self {
+        Self { templates: iter.into_iter().map(|(key, template)| (key.into(), template)).collect() }
+    }
+}
+
+/// The uses of a parameter in a [`TemplateSet`], as reported by
+/// [`TemplateSet::usage`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParameterUsage {
+    parameter: String,
+    uses: Vec<ParameterUse>,
+}
+
+impl ParameterUsage {
+    pub fn parameter(&self) -> &str {
+        &self.parameter
+    }
+
+    /// The placeholders and blocks using the parameter, in order of key,
+    /// then in source order.
+    pub fn uses(&self) -> &[ParameterUse] {
+        &self.uses
+    }
+
+    /// The number of templates using the parameter.
+    pub fn templates(&self) -> usize {
+        let mut keys: Vec<&str> = self.uses.iter().map(|u| u.key()).collect();
+        keys.dedup();
+        keys.len()
+    }
+}
+
+/// A placeholder or block using a parameter, in the template of a key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParameterUse {
+    key: String,
+    span: Range<usize>,
+}
+
+impl ParameterUse {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The byte range of the placeholder or block in the template source.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+/// A template set shared between threads and replaced as a whole, such as
+/// the catalog of a server reloaded while it renders.
+///
+/// Reads are mostly lock-free, rather than lock-free: each thread keeps the
+/// set it last loaded, and loading it again only compares a generation
+/// number, but the first load of each thread after a replacement takes a
+/// read lock, held for cloning an [`Arc`]. A thread also keeps the set it
+/// last loaded alive until its next load, so a replaced set is freed once
+/// every thread that loaded it has loaded again.
+///
+/// ```
+/// use late_format::{SharedTemplateSet, TemplateSet};
+/// let mut set = TemplateSet::new();
+/// set.add("greeting", "Hello, {name}!").unwrap();
+/// let shared = SharedTemplateSet::new(set);
+/// let server = shared.clone();
+/// std::thread::spawn(move || {
+///     let mut set = TemplateSet::new();
+///     set.add("greeting", "Hi, {name}!").unwrap();
+///     server.store(set);
+/// }).join().unwrap();
+/// assert_eq!("Hi, {name}!", shared.load().get("greeting").unwrap().source());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SharedTemplateSet {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    /// The number of replacements, which is also in `current` so that both
+    /// are read together.
+    generation: AtomicUsize,
+    current: RwLock<(usize, Arc<TemplateSet>)>,
+}
+
+/// A set loaded by the current thread.
+struct Loaded {
+    shared: Weak<Shared>,
+    generation: usize,
+    set: Arc<TemplateSet>,
+}
+
+thread_local! {
+    static LOADED: RefCell<Vec<Loaded>> = const { RefCell::new(vec![]) };
+}
+
+impl SharedTemplateSet {
+    pub fn new(set: TemplateSet) -> Self {
+        Self::from(set)
+    }
+
+    /// The current set, taking a read lock only if it was replaced since
+    /// this thread last loaded it.
+    pub fn load(&self) -> Arc<TemplateSet> {
+        let generation = self.shared.generation.load(Ordering::Acquire);
+        LOADED.try_with(|loaded| {
+            let mut loaded = loaded.borrow_mut();
+            let position = loaded.iter().position(|l| Weak::as_ptr(&l.shared) == Arc::as_ptr(&self.shared));
+            if let Some(l) = position.map(|i| &loaded[i]).filter(|l| l.generation == generation) {
+                return l.set.clone();
+            }
+            let (generation, set) = self.read();
+            match position {
+                Some(i) => (loaded[i].generation, loaded[i].set) = (generation, set.clone()),
+                None => {
+                    loaded.retain(|l| l.shared.strong_count() != 0);
+                    loaded.push(Loaded { shared: Arc::downgrade(&self.shared), generation, set: set.clone() });
+                },
+            }
+            set
+        }).unwrap_or_else(|_| self.read().1)
+    }
+
+    fn read(&self) -> (usize, Arc<TemplateSet>) {
+        self.shared.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Replaces the set. Threads loading it afterwards get the new set, and
+    /// those rendering with the previous one finish with it.
+    pub fn store(&self, set: TemplateSet) {
+        let mut current = self.shared.current.write().unwrap_or_else(|e| e.into_inner());
+        *current = (current.0 + 1, Arc::new(set));
+        self.shared.generation.store(current.0, Ordering::Release);
+    }
+}
+
+impl From<TemplateSet> for SharedTemplateSet {
+    fn from(set: TemplateSet) -> Self {
+        let shared = Shared { generation: AtomicUsize::new(0), current: RwLock::new((0, Arc::new(set))) };
+        Self { shared: Arc::new(shared) }
+    }
+}
+
+/// A difference between a template set and its reference, found by
+/// [`TemplateSet::check_against`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    key: String,
+    kind: MismatchKind,
+}
+
+/// The kind of a [`Mismatch`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MismatchKind {
+    /// The reference has a template of the key, and the set has none.
+    MissingMessage,
+    /// The set has a template of a key that the reference has not.
+    UnknownMessage,
+    /// The parameters of the reference template that the template does not
+    /// use, and those it uses that the reference template does not, in
+    /// order of first use.
+    Parameters { missing: Vec<String>, unknown: Vec<String> },
+}
+
+impl Mismatch {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn kind(&self) -> &MismatchKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}`: {}", self.key, self.kind)
+    }
+}
+
+impl fmt::Display for MismatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingMessage => write!(f, "missing message"),
+            Self::UnknownMessage => write!(f, "message is not in the reference"),
+            Self::Parameters { missing, unknown } => {
+                let names = |names: &[String]| names.iter().map(|n| format!("`{n}`")).collect::<Vec<_>>().join(", ");
+                let parts: Vec<String> = [("missing", missing), ("unknown", unknown)].into_iter()
+                    .filter(|(_, names)| !names.is_empty())
+                    .map(|(label, list)| format!("{label} {}", names(list)))
+                    .collect();
+                write!(f, "{}", parts.join(", "))
+            },
+        }
+    }
+}
+
+/// Every mismatch between a template set and its reference, as returned by
+/// [`TemplateSet::check_against`]. It is never empty.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckReport {
+    mismatches: Vec<Mismatch>,
+}
+
+impl CheckReport {
+    /// The mismatches, in order of key.
+    pub fn mismatches(&self) -> &[Mismatch] {
+        &self.mismatches
+    }
+}
+
+/// Writes one mismatch per line.
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, mismatch) in self.mismatches.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            mismatch.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CheckReport {}
+
+#[cfg(test)]
+mod test {
+    use crate::Template;
+    use std::sync::Arc;
+    use super::{MismatchKind, SharedTemplateSet, TemplateSet};
+
+    #[test]
+    fn checking() {
+        let reference: TemplateSet = [
+            ("a", Template::parse("{n} {plural n}{one}file{other}files{end} {for x in xs}{x}{end}").unwrap()),
+            ("b", Template::parse("{user | html}").unwrap()),
+        ].into_iter().collect();
+        let mut set = TemplateSet::new();
+        set.add("a", "{for y in xs}{y}{end} {n:>3}").unwrap();
+        set.add("b", "<b>{user}</b>").unwrap();
+        assert!(set.check_against(&reference).is_ok());
+        set.add("b", "{usr}").unwrap();
+        let report = set.check_against(&reference).unwrap_err();
+        let kind = MismatchKind::Parameters { missing: vec!["user".into()], unknown: vec!["usr".into()] };
+        assert_eq!([("b", &kind)], &report.mismatches().iter().map(|m| (m.key(), m.kind())).collect::<Vec<_>>()[..]);
+        assert!(reference.check_against(&reference).is_ok() && TemplateSet::new().check_against(&TemplateSet::new()).is_ok());
+    }
+
+    #[test]
+    fn usage() {
+        let set: TemplateSet = [
+            ("a", Template::parse("{for x in xs}{x}{end}{n:>{n}}{if not m | len > 2}{x:-{m}}{end}").unwrap()),
+            ("b", Template::parse("{n}{xs | join}").unwrap()),
+        ].into_iter().collect();
+        let usage: Vec<_> = set.usage().iter().map(|u| {
+            let uses: Vec<_> = u.uses().iter().map(|u| (u.key().to_owned(), u.span().start)).collect();
+            (u.parameter().to_owned(), u.templates(), uses)
+        }).collect();
+        let uses = |uses: &[(&str, usize)]| uses.iter().map(|(k, i)| (k.to_string(), *i)).collect::<Vec<_>>();
+        assert_eq!(vec![
+            ("m".to_owned(), 1, uses(&[("a", 29), ("a", 53)])),
+            ("n".to_owned(), 2, uses(&[("a", 21), ("b", 0)])),
+            ("x".to_owned(), 1, uses(&[("a", 49)])),
+            ("xs".to_owned(), 2, uses(&[("a", 0), ("b", 3)])),
+        ], usage);
+        assert!(TemplateSet::new().usage().is_empty());
+    }
+
+    #[test]
+    fn shared() {
+        let set = |source: &str| [("a", Template::parse(source).unwrap())].into_iter().collect::<TemplateSet>();
+        let shared = SharedTemplateSet::new(set("0"));
+        let other = SharedTemplateSet::new(set("x"));
+        let first = shared.load();
+        assert!(Arc::ptr_eq(&first, &shared.load()));
+        let readers: Vec<_> = (0..4).map(|_| {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                let mut last = 0;
+                while last != 100 {
+                    let n: usize = shared.load().get("a").unwrap().source().parse().unwrap();
+                    assert!(n >= last);
+                    last = n;
+                }
+            })
+        }).collect();
+        for i in 1..=100 {
+            shared.store(set(&i.to_string()));
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(("0", "100", "x"), (first.get("a").unwrap().source(), shared.load().get("a").unwrap().source(), other.load().get("a").unwrap().source()));
+        drop(other);
+        SharedTemplateSet::new(set("y")).load();
+        assert_eq!(2, super::LOADED.with(|loaded| loaded.borrow().len()));
+    }
+}
@@ -0,0 +1,138 @@
+use std::ops::Range;
+
+/// A rendered output with the spans of the template source that produced
+/// it, as returned by [`Template::render_mapped`](crate::Template::render_mapped).
+/// This allows tracing a substring of the output back to its placeholder.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceMap {
+    pub(crate) output: String,
+    pub(crate) spans: Vec<OutputSpan>,
+}
+
+/// A range of the output produced by one segment of the template.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputSpan {
+    pub(crate) output: Range<usize>,
+    pub(crate) source: Range<usize>,
+    pub(crate) parameter: Option<String>,
+    pub(crate) missing: bool,
+}
+
+impl SourceMap {
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    pub fn into_output(self) -> String {
+        self.output
+    }
+
+    /// The spans of every segment of the template, in output order. They
+    /// cover the output without gaps; a segment producing no text has an
+    /// empty output range.
+    pub fn spans(&self) -> &[OutputSpan] {
+        &self.spans
+    }
+
+    /// The span producing the output byte at `offset`.
+    pub fn lookup(&self, offset: usize) -> Option<&OutputSpan> {
+        let i = self.spans.partition_point(|s| s.output.end <= offset);
+        self.spans.get(i).filter(|s| s.output.contains(&offset))
+    }
+
+    /// The spans overlapping an output range, such as the range of a bad
+    /// substring found in the output.
+    pub fn lookup_range(&self, range: Range<usize>) -> impl Iterator<Item = &OutputSpan> {
+        self.spans.iter().filter(move |s| {
+            s.output.start < range.end && range.start < s.output.end
+        })
+    }
+
+    /// The output with ANSI color codes highlighting the substituted text,
+    /// for eyeballing which parts of the output come from which arguments in
+    /// a terminal. Each parameter gets its own color, and missing parameters
+    /// are highlighted in bold red.
+    ///
+    /// ```
+    /// use late_format::{FormatOptions, Template};
+    /// use maplit::hashmap;
+    /// let template = Template::parse("{a} and {b}").unwrap();
+    /// let mapped = template.render_mapped(&hashmap!{"a".into() => "x".into()}, &FormatOptions::new()).unwrap();
+    /// assert_eq!("\x1b[36mx\x1b[0m and \x1b[1;31mNone\x1b[0m", mapped.highlight());
+    /// ```
+    pub fn highlight(&self) -> String {
+        const COLORS: [&str; 5] = ["36", "32", "33", "35", "34"];
+        let mut parameters: Vec<&str> = vec![];
+        let mut r = String::with_capacity(self.output.len());
+        for span in &self.spans {
+            let text = &self.output[span.output.clone()];
+            let Some(parameter) = span.parameter.as_deref() else {
+                r.push_str(text);
+                continue;
+            };
+            let color = if span.missing {
+                "1;31"
+            } else {
+                let i = parameters.iter().position(|p| *p == parameter).unwrap_or_else(|| {
+                    parameters.push(parameter);
+                    parameters.len() - 1
+                });
+                COLORS[i % COLORS.len()]
+            };
+            r.push_str(&format!("\x1b[{color}m{text}\x1b[0m"));
+        }
+        r
+    }
+}
+
+impl OutputSpan {
+    /// The byte range of the output.
+    pub fn output(&self) -> Range<usize> {
+        self.output.clone()
+    }
+
+    /// The byte range of the template source.
+    pub fn source(&self) -> Range<usize> {
+        self.source.clone()
+    }
+
+    /// The parameter name, if the output comes from a placeholder.
+    pub fn parameter(&self) -> Option<&str> {
+        self.parameter.as_deref()
+    }
+
+    /// Whether the output comes from a placeholder whose argument is missing.
+    pub fn is_missing(&self) -> bool {
+        self.missing
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FormatOptions, Template};
+    use maplit::hashmap;
+
+    #[test]
+    fn mapping() {
+        let template = Template::parse(r#"{a}{"{"}{b}{empty} {a | dedent}"#).unwrap();
+        let arguments = hashmap!{"a".into() => "xy".into(), "empty".into() => "".into()};
+        let mapped = template.render_mapped(&arguments, &FormatOptions::new()).unwrap();
+        assert_eq!("xy{None xy", mapped.output());
+        let spans: Vec<_> = mapped.spans().iter().map(|s| (s.output(), s.source(), s.parameter())).collect();
+        assert_eq!(vec![
+            (0..2, 0..3, Some("a")),
+            (2..3, 3..8, None),
+            (3..7, 8..11, Some("b")),
+            (7..7, 11..18, Some("empty")),
+            (7..8, 18..19, None),
+            (8..10, 19..31, Some("a")),
+        ], spans);
+        assert_eq!(Some("b"), mapped.lookup(6).unwrap().parameter());
+        assert_eq!(18..19, mapped.lookup(7).unwrap().source());
+        assert!(mapped.lookup(10).is_none());
+        let names: Vec<_> = mapped.lookup_range(1..4).map(|s| s.source()).collect();
+        assert_eq!(vec![0..3, 3..8, 8..11], names);
+        assert!(mapped.lookup(3).unwrap().is_missing());
+        assert_eq!("\x1b[36mxy\x1b[0m{\x1b[1;31mNone\x1b[0m\x1b[32m\x1b[0m \x1b[36mxy\x1b[0m", mapped.highlight());
+    }
+}
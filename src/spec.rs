@@ -0,0 +1,512 @@
+use std::fmt;
+use crate::{FormatOptions, Limit, Limits};
+use crate::date::Date;
+use crate::number::Decimal;
+use crate::parser::is_name_char;
+
+/// The format spec of a `{param_name:spec}` placeholder.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct Spec {
+    /// The padding character, a space by default.
+    pub fill: Option<char>,
+    pub align: Option<Align>,
+    /// Whether positive numbers have a leading `+`.
+    pub plus: bool,
+    /// Whether integers in another radix have a prefix such as `0x`.
+    pub alternate: bool,
+    /// Whether numbers are padded with zeros after the sign and prefix,
+    /// rather than with spaces before them.
+    pub zero: bool,
+    pub width: Option<Count>,
+    pub precision: Option<Count>,
+    pub kind: Kind,
+    /// The type of the arguments, for a spec that is a type annotation.
+    pub ty: Option<ArgumentType>,
+}
+
+/// A width or precision of a spec.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum Count {
+    Fixed(usize),
+    /// `{name}`: the argument of a parameter, resolved when rendering.
+    Parameter(String),
+}
+
+impl Count {
+    fn fixed(count: &Option<Self>) -> Option<usize> {
+        match count {
+            Some(Self::Fixed(n)) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+/// The alignment of an argument within the width of a spec.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    fn from_char(ch: char) -> Option<Self> {
+        match ch {
+            '<' => Some(Self::Left),
+            '^' => Some(Self::Center),
+            '>' => Some(Self::Right),
+            _ => None,
+        }
+    }
+}
+
+/// The presentation of a number selected by the last character of a spec.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum Kind {
+    /// Fixed notation.
+    #[default]
+    Default,
+    /// `%`: the number multiplied by 100, followed by a percent sign.
+    Percent,
+    /// `e` and `E`: scientific notation, with a lowercase or uppercase exponent mark.
+    Exp { upper: bool },
+    /// `b`, `o`, `x` and `X`: an integer in binary, octal or hexadecimal.
+    Radix { radix: u32, upper: bool },
+}
+
+/// The type of the arguments expected by a placeholder annotated with it,
+/// as in `{count:int}`, checked by [`Template::validate`](crate::Template::validate)
+/// and [`Template::check_arguments`](crate::Template::check_arguments).
+///
+/// ```
+/// use late_format::{ArgumentType, Template};
+/// use maplit::hashmap;
+/// let template = Template::parse("{count:int} since {when:date}").unwrap();
+/// assert_eq!(Some(ArgumentType::Date), template.placeholders().nth(1).unwrap().argument_type());
+/// let report = template.check_arguments(&hashmap!{"count".into() => "3".into(), "when".into() => "soon".into()}).unwrap_err();
+/// assert_eq!("argument `when` is not a `date` at 18..29", report.to_string());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum ArgumentType {
+    /// `int`: an integer, with an optional sign.
+    Int,
+    /// `float`: a decimal number, as in `-1.5` and `2e3`.
+    Float,
+    /// `bool`: `true` or `false`.
+    Bool,
+    /// `date`: an ISO 8601 date, with an optional time, as in `2024-03-05`
+    /// and `2024-03-05T09:30:00`.
+    Date,
+    /// `string`: any text.
+    String,
+}
+
+impl ArgumentType {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "int" => Some(Self::Int),
+            "float" => Some(Self::Float),
+            "bool" => Some(Self::Bool),
+            "date" => Some(Self::Date),
+            "string" => Some(Self::String),
+            _ => None,
+        }
+    }
+
+    /// The name of the type in annotations, such as `int`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Int => "int",
+            Self::Float => "float",
+            Self::Bool => "bool",
+            Self::Date => "date",
+            Self::String => "string",
+        }
+    }
+
+    /// Whether an argument is of this type.
+    pub fn accepts(&self, value: &str) -> bool {
+        match self {
+            Self::Int => {
+                let digits = value.strip_prefix(['+', '-']).unwrap_or(value);
+                !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+            },
+            Self::Float => Decimal::parse(value).is_some(),
+            Self::Bool => value == "true" || value == "false",
+            Self::Date => Date::iso(value).is_some(),
+            Self::String => true,
+        }
+    }
+}
+
+impl fmt::Display for ArgumentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Spec {
+    /// Parses the text following the colon of a placeholder, returning
+    /// `None` if it is not a valid spec.
+    pub fn parse(s: &str) -> Option<Self> {
+        if s.is_empty() {
+            return None;
+        }
+        if let Some(ty) = ArgumentType::from_name(s) {
+            return Some(Self { ty: Some(ty), ..Self::default() });
+        }
+        let mut spec = Self::default();
+        let mut rest = s;
+        let mut chars = rest.chars();
+        let (first, second) = (chars.next(), chars.next());
+        if let Some(align) = second.and_then(Align::from_char).filter(|_| !matches!(first, Some('{' | '}'))) {
+            spec.fill = first;
+            spec.align = Some(align);
+            rest = chars.as_str();
+        } else if let Some(align) = first.and_then(Align::from_char) {
+            spec.align = Some(align);
+            rest = &rest[1..];
+        }
+        for (flag, set) in [('+', &mut spec.plus as &mut bool), ('#', &mut spec.alternate), ('0', &mut spec.zero)] {
+            if let Some(r) = rest.strip_prefix(flag) {
+                *set = true;
+                rest = r;
+            }
+        }
+        spec.width = count(&mut rest)?;
+        if let Some(r) = rest.strip_prefix('.') {
+            rest = r;
+            spec.precision = Some(count(&mut rest)??);
+        }
+        spec.kind = match rest {
+            "" => Kind::Default,
+            "%" => Kind::Percent,
+            "e" => Kind::Exp { upper: false },
+            "E" => Kind::Exp { upper: true },
+            "b" => Kind::Radix { radix: 2, upper: false },
+            "o" => Kind::Radix { radix: 8, upper: false },
+            "x" => Kind::Radix { radix: 16, upper: false },
+            "X" => Kind::Radix { radix: 16, upper: true },
+            _ => return None,
+        };
+        let radix = matches!(spec.kind, Kind::Radix { .. });
+        if (radix && spec.precision.is_some()) || (!radix && spec.alternate) {
+            return None;
+        }
+        Some(spec)
+    }
+
+    /// The parameters of the width and precision.
+    pub fn parameters(&self) -> impl Iterator<Item = &str> {
+        [&self.width, &self.precision].into_iter().filter_map(|count| match count {
+            Some(Count::Parameter(name)) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Replaces the parameters of the width and precision by their values,
+    /// reduced to the limits, or removes them if `value` returns `None`.
+    pub fn resolve(&self, value: impl Fn(&str) -> Option<usize>, limits: &Limits) -> Self {
+        let resolve = |count: &Option<Count>, max: Option<usize>| match count {
+            Some(Count::Parameter(name)) => value(name).map(|n| Count::Fixed(max.map_or(n, |max| n.min(max)))),
+            count => count.clone(),
+        };
+        Self { width: resolve(&self.width, limits.max_width), precision: resolve(&self.precision, limits.max_precision), ..self.clone() }
+    }
+
+    /// The limit exceeded by the width or precision, if any.
+    pub fn exceeded(&self, limits: &Limits) -> Option<Limit> {
+        let exceeds = |count: &Option<Count>, max: Option<usize>| max.filter(|max| Count::fixed(count).is_some_and(|n| n > *max));
+        exceeds(&self.width, limits.max_width).map(Limit::Width)
+            .or_else(|| exceeds(&self.precision, limits.max_precision).map(Limit::Precision))
+    }
+
+    /// The steps of the budget that applying the spec costs beyond the
+    /// first, for each 256 characters of its width and precision.
+    pub fn cost(&self) -> usize {
+        (Count::fixed(&self.width).unwrap_or(0) + Count::fixed(&self.precision).unwrap_or(0)) / 256
+    }
+
+    /// Whether the spec formats arguments as numbers, rather than only
+    /// padding them as it does text.
+    pub fn is_numeric(&self) -> bool {
+        self.ty.is_none() && (self.plus || self.zero || self.precision.is_some() || self.kind != Kind::Default)
+    }
+
+    /// Formats an argument. An argument that is not a number is only padded,
+    /// and so is a number that is not an integer for the radix presentations.
+    pub fn apply(&self, value: &str, options: &FormatOptions) -> String {
+        if self.ty.is_some() {
+            return value.to_owned();
+        }
+        match Decimal::parse(value).and_then(|n| self.number(n, options)) {
+            Some(r) => r,
+            None => self.pad("", value, Align::Left),
+        }
+    }
+
+    /// Pads text to the width of the spec, with the fill character or, for
+    /// the `0` flag without an alignment, with zeros after `sign`.
+    fn pad(&self, sign: &str, text: &str, align: Align) -> String {
+        let len = sign.chars().count() + text.chars().count();
+        let padding = Count::fixed(&self.width).unwrap_or(0).saturating_sub(len);
+        if padding == 0 {
+            return format!("{sign}{text}");
+        }
+        if self.zero && self.align.is_none() && !sign.is_empty() {
+            return format!("{sign}{}{text}", "0".repeat(padding));
+        }
+        let fill = self.fill.unwrap_or(if self.zero && self.align.is_none() { '0' } else { ' ' });
+        let (before, after) = match self.align.unwrap_or(align) {
+            Align::Left => (0, padding),
+            Align::Center => (padding / 2, padding - padding / 2),
+            Align::Right => (padding, 0),
+        };
+        let repeat = |n| std::iter::repeat_n(fill, n).collect::<String>();
+        format!("{}{sign}{text}{}", repeat(before), repeat(after))
+    }
+
+    fn number(&self, mut n: Decimal, options: &FormatOptions) -> Option<String> {
+        let (mut prefix, mut suffix) = ("", "");
+        let digits = match self.kind {
+            Kind::Default | Kind::Percent => {
+                if self.kind == Kind::Percent {
+                    n.shift(2);
+                    (prefix, suffix) = percent_sign(options.get_locale());
+                }
+                let precision = Count::fixed(&self.precision);
+                if let Some(precision) = precision {
+                    n.round(precision, options.rounding);
+                }
+                n.fixed(precision.unwrap_or(0))
+            },
+            Kind::Exp { upper } => n.scientific(Count::fixed(&self.precision), options.rounding, upper),
+            Kind::Radix { radix, upper } => {
+                let integer = n.integer()?;
+                if self.alternate {
+                    prefix = match radix {
+                        2 => "0b",
+                        8 => "0o",
+                        _ => "0x",
+                    };
+                }
+                match radix {
+                    2 => format!("{integer:b}"),
+                    8 => format!("{integer:o}"),
+                    _ if upper => format!("{integer:X}"),
+                    _ => format!("{integer:x}"),
+                }
+            },
+        };
+        // zero has no sign, even if it was rounded from a negative number.
+        let sign = match (n.negative && !n.is_zero(), self.plus) {
+            (true, _) => "-",
+            (false, true) => "+",
+            (false, false) => "",
+        };
+        if self.zero && self.align.is_none() {
+            // zeros go after the sign and the prefix.
+            return Some(self.pad(&format!("{sign}{prefix}"), &format!("{digits}{suffix}"), Align::Right));
+        }
+        Some(self.pad(sign, &format!("{prefix}{digits}{suffix}"), Align::Right))
+    }
+}
+
+/// Writes the spec in its shortest form, as parsed by [`Spec::parse`].
+impl fmt::Display for Spec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ty) = self.ty {
+            return write!(f, "{ty}");
+        }
+        if let Some(align) = self.align {
+            if let Some(fill) = self.fill {
+                write!(f, "{fill}")?;
+            }
+            f.write_str(match align {
+                Align::Left => "<",
+                Align::Center => "^",
+                Align::Right => ">",
+            })?;
+        }
+        for (flag, set) in [('+', self.plus), ('#', self.alternate), ('0', self.zero)] {
+            if set {
+                write!(f, "{flag}")?;
+            }
+        }
+        if let Some(width) = &self.width {
+            write!(f, "{width}")?;
+        }
+        if let Some(precision) = &self.precision {
+            write!(f, ".{precision}")?;
+        }
+        f.write_str(match self.kind {
+            Kind::Default => "",
+            Kind::Percent => "%",
+            Kind::Exp { upper: false } => "e",
+            Kind::Exp { upper: true } => "E",
+            Kind::Radix { radix: 2, .. } => "b",
+            Kind::Radix { radix: 8, .. } => "o",
+            Kind::Radix { upper: false, .. } => "x",
+            Kind::Radix { upper: true, .. } => "X",
+        })
+    }
+}
+
+impl fmt::Display for Count {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed(n) => write!(f, "{n}"),
+            Self::Parameter(name) => write!(f, "{{{name}}}"),
+        }
+    }
+}
+
+/// Parses the digits or the `{name}` of a count at the start of `rest`,
+/// advancing it. Returns `Some(None)` if there is no count, and `None` if
+/// it is not valid.
+fn count(rest: &mut &str) -> Option<Option<Count>> {
+    if let Some(r) = rest.strip_prefix('{') {
+        let (name, r) = r.split_once('}')?;
+        if name.is_empty() || !name.chars().all(is_name_char) {
+            return None;
+        }
+        *rest = r;
+        return Some(Some(Count::Parameter(name.to_owned())));
+    }
+    let end = rest.find(|ch: char| !ch.is_ascii_digit()).unwrap_or(rest.len());
+    if end == 0 {
+        return Some(None);
+    }
+    let n = rest[..end].parse().ok()?;
+    *rest = &rest[end..];
+    Some(Some(Count::Fixed(n)))
+}
+
+/// The text before and after a percentage in a locale. Only the percent sign
+/// is localized, not the digits.
+fn percent_sign(locale: &str) -> (&'static str, &'static str) {
+    let locale = locale.to_ascii_lowercase();
+    match locale.split(['-', '_']).next().unwrap_or_default() {
+        "tr" | "eu" => ("%", ""),
+        "fr" => ("", "\u{202f}%"),
+        "de" | "es" | "sv" | "nb" | "no" | "nn" | "fi" | "cs" | "sk" | "da" | "ru" | "bg" => ("", "\u{a0}%"),
+        _ => ("", "%"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FormatOptions, LateFormat, Rounding, Template};
+    use maplit::hashmap;
+
+    #[test]
+    fn percent() {
+        let arguments = hashmap!{"r".into() => "0.1234".into(), "s".into() => "-0.5".into(), "t".into() => "n/a".into()};
+        let source = "{r:%} {r:.1%} {s:.0%} {t:%} {r | percent}";
+        assert_eq!("12.34% 12.3% -50% n/a 12.34%", source.late_format(arguments.clone()));
+        let options = FormatOptions::new().locale("fr-FR");
+        assert_eq!("12.34\u{202f}%", "{r:%}".late_format_with(arguments.clone(), &options));
+        assert!(Template::parse("{r:2s}").is_err());
+        assert!(Template::parse("{r:%%}").is_err());
+        assert_eq!("{r:y}", "{r:y}".late_format(arguments));
+    }
+
+    #[test]
+    fn scientific() {
+        let arguments = hashmap!{"v".into() => "-1234.5".into(), "w".into() => "0.00012".into()};
+        assert_eq!("-1.2345e3 -1.23E3 1.200e-4", "{v:e} {v:.2E} {w:.3e}".late_format(arguments.clone()));
+        let options = FormatOptions::new().rounding(Rounding::Floor);
+        assert_eq!("-1.24E3", "{v:.2E}".late_format_with(arguments, &options));
+    }
+
+    #[test]
+    fn signs() {
+        let arguments = hashmap!{"a".into() => "1.25".into(), "b".into() => "-3".into(), "c".into() => "-0.01".into(), "d".into() => "0".into()};
+        assert_eq!("+1.2 -3.0 0.0 +0 +1.25e0 +125%", "{a:+.1} {b:+.1} {c:.1} {d:+} {a:+e} {a:+%}".late_format(arguments.clone()));
+        let options = FormatOptions::new().rounding(Rounding::Up);
+        assert_eq!("+1.3 -0.1", "{a:+.1} {c:.1}".late_format_with(arguments, &options));
+    }
+
+    #[test]
+    fn padding() {
+        let arguments = hashmap!{"id".into() => "42".into(), "n".into() => "-7".into(), "f".into() => "2.5".into()};
+        let source = "{id:06} {n:06} {n:+04} {id:#06x} {id:X} {id:#b} {n:o} {id:5}| {f:05.2} {f:x} {f:06%}";
+        assert_eq!("000042 -00007 -007 0x002a 2A 0b101010 -7    42| 02.50 2.5 00250%", source.late_format(arguments.clone()));
+        assert!(Template::parse("{id:#}").is_err() && Template::parse("{id:.2x}").is_err());
+    }
+
+    #[test]
+    fn alignment() {
+        let arguments = hashmap!{"s".into() => "ab".into(), "n".into() => "-4".into()};
+        let source = "[{s:*^7}] [{s:6}] [{s:>4}] [{n:<4}] [{n:.>5}] [{s: ^4}] [{n:|^5}] [{s:06}]";
+        assert_eq!("[**ab***] [ab    ] [  ab] [-4  ] [...-4] [ ab ] [|-4||] [ab0000]", source.late_format(arguments.clone()));
+        assert_eq!("[{s:{^4}]", "[{s:{^4}]".late_format(arguments));
+    }
+
+    #[test]
+    fn dynamic() {
+        use crate::{Missing, Value};
+        let arguments = hashmap!{"v".into() => "3.14159".into(), "w".into() => "8".into(), "p".into() => "2".into()};
+        assert_eq!("[    3.14] [3.1416  ]", "[{v:>{w}.{p}}] [{v:<{w}.4}]".late_format(arguments.clone()));
+        assert_eq!("[3.14159]", "[{v:{x}}]".late_format(arguments.clone()));
+        let options = FormatOptions::new().missing(Missing::Error);
+        let template = Template::parse("{v:{x}}").unwrap();
+        assert_eq!(&crate::RenderErrorKind::MissingArgument("x".into()), template.render_with(&arguments, &options).unwrap_err().kind());
+        assert_eq!(["v", "w", "p"], Template::parse("{v:{w}.{p}e}").unwrap().parameters());
+        let template = Template::parse("{for w in widths}[{s:-^{w}}]{end}").unwrap();
+        let arguments = hashmap!{"s".into() => Value::from("x"), "widths".into() => Value::from(vec!["3", "1"])};
+        assert_eq!("[-x-][x]", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+        assert!(Template::parse("{v:{}}").is_err() && Template::parse("{v:.{p}x}").is_err());
+        // a dynamic count that is not closed, or not a name, ends the spec.
+        assert_eq!("{v:{w x}}", "{v:{w x}}".late_format(hashmap!{"v".into() => "1".into()}));
+        let started = std::time::Instant::now();
+        let _ = Template::parse(&"{a:{b}".repeat(20_000));
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+        use crate::{Limit, Limits, ParseErrorKind};
+        let options = FormatOptions::new().limits(Limits::new().max_width(4).max_precision(2));
+        let error = Template::parse_with("[{v:5}]", &options).unwrap_err();
+        assert_eq!((&ParseErrorKind::LimitExceeded(Limit::Width(4)), 1..6), (error.kind(), error.span()));
+        let error = Template::parse_with("{v:.3}", &options).unwrap_err();
+        assert_eq!(&ParseErrorKind::LimitExceeded(Limit::Precision(2)), error.kind());
+        assert_eq!("[3.14] [{v:.300000000}]", "[{v:>{w}.{p}}] [{v:.300000000}]".late_format_with(hashmap!{"v".into() => "3.14159".into(), "w".into() => "8".into(), "p".into() => "9".into()}, &options));
+    }
+
+    #[test]
+    fn types() {
+        use crate::{ArgumentType, Error, RenderErrorKind};
+        let template = Template::parse("{n:int} {x:float}|{ok:bool}|{d:date}|{s:string}|{n:>3}").unwrap();
+        let arguments = hashmap!{"n".into() => "-12".into(), "x".into() => "1.5e3".into(), "ok".into() => "true".into(), "d".into() => "2024-03-05T09:30".into(), "s".into() => "a".into()};
+        assert!(template.check_arguments(&arguments).is_ok());
+        assert_eq!("-12 1.5e3|true|2024-03-05T09:30|a|-12", template.render(&arguments));
+        let arguments = hashmap!{"n".into() => "1.5".into(), "x".into() => "x".into(), "ok".into() => "yes".into(), "d".into() => "2024-02-30".into()};
+        let report = template.check_arguments(&arguments).unwrap_err();
+        let kinds: Vec<_> = report.errors().iter().map(|e| e.to_string()).collect();
+        assert_eq!(vec![
+            "argument `n` is not a `int` at 0..7",
+            "argument `x` is not a `float` at 8..17",
+            "argument `ok` is not a `bool` at 18..27",
+            "argument `d` is not a `date` at 28..36",
+            "missing argument `s` at 37..47",
+        ], kinds);
+        let error = Template::validate("{n:int}", &hashmap!{"n".into() => "x".into()}, &FormatOptions::new()).unwrap_err();
+        let Error::Render(error) = &error.errors()[0] else { panic!() };
+        assert_eq!((Some("n"), &RenderErrorKind::WrongType("n".into(), ArgumentType::Int)), (error.parameter(), error.kind()));
+        assert!(template.minified().source().starts_with("{n:int} "));
+    }
+
+    #[test]
+    fn strict_numbers() {
+        use crate::{Numbers, RenderErrorKind};
+        let options = FormatOptions::new().numbers(Numbers::Strict);
+        let template = Template::parse("{n:+.1} {n:#x} {n | add:1 | roman} {s:>3}").unwrap();
+        let arguments = hashmap!{"n".into() => " 1,000 ".into(), "s".into() => "ab".into()};
+        assert_eq!("+1000.0 0x3e8 MI  ab", template.render_with(&arguments, &options).unwrap());
+        let arguments = hashmap!{"n".into() => "ten".into(), "s".into() => "ab".into()};
+        assert_eq!("ten ten ten  ab", template.render(&arguments));
+        let error = template.render_with(&arguments, &options).unwrap_err();
+        assert_eq!((&RenderErrorKind::NotANumber("n".into()), 0..7), (error.kind(), error.span()));
+        let error = Template::parse("{n | spellout}").unwrap().render_with(&arguments, &options).unwrap_err();
+        assert_eq!("argument `n` is not a number at 0..14", error.to_string());
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// Spells out integers as words, for the `spellout` filter. The English
+/// words are used when the speller returns `None`.
+///
+/// ```
+/// use late_format::{FormatOptions, LateFormat, SpellOut};
+/// use maplit::hashmap;
+/// struct French;
+/// impl SpellOut for French {
+///     fn spell_out(&self, locale: &str, number: i64) -> Option<String> {
+///         let words = ["zéro", "un", "deux", "trois"];
+///         locale.starts_with("fr").then(|| words.get(number as usize).map(|w| w.to_string()))?
+///     }
+/// }
+/// let options = FormatOptions::new().spell_out(French).locale("fr");
+/// let arguments = hashmap!{"n".into() => "2".into(), "m".into() => "42".into()};
+/// assert_eq!("deux forty-two", "{n | spellout} {m | spellout}".late_format_with(arguments, &options));
+/// ```
+pub trait SpellOut: Send + Sync {
+    /// Spells out a number in the language of a locale, or returns `None`
+    /// if the language or the number is not supported.
+    fn spell_out(&self, locale: &str, number: i64) -> Option<String>;
+}
+
+/// The speller of [`FormatOptions`](crate::FormatOptions).
+#[derive(Clone)]
+pub(crate) struct Speller(pub Arc<dyn SpellOut>);
+
+impl fmt::Debug for Speller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Speller")
+    }
+}
+
+const ONES: [&str; 20] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+    "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+];
+
+const TENS: [&str; 10] = ["", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+
+const SCALES: [&str; 7] = ["", "thousand", "million", "billion", "trillion", "quadrillion", "quintillion"];
+
+/// Spells out a number in English words, as in `minus one thousand two hundred forty-two`.
+pub(crate) fn english(number: i64) -> String {
+    let mut n = number.unsigned_abs();
+    if n == 0 {
+        return ONES[0].to_owned();
+    }
+    let mut groups = vec![];
+    let mut scale = 0;
+    while n > 0 {
+        let group = (n % 1000) as usize;
+        if group != 0 {
+            let mut words = hundreds(group);
+            if scale != 0 {
+                words.push(' ');
+                words.push_str(SCALES[scale]);
+            }
+            groups.push(words);
+        }
+        n /= 1000;
+        scale += 1;
+    }
+    if number < 0 {
+        groups.push("minus".to_owned());
+    }
+    groups.reverse();
+    groups.join(" ")
+}
+
+/// Spells out a number from 1 to 999.
+fn hundreds(n: usize) -> String {
+    let mut r = String::new();
+    if n >= 100 {
+        r.push_str(ONES[n / 100]);
+        r.push_str(" hundred");
+    }
+    let rest = n % 100;
+    if rest == 0 {
+        return r;
+    }
+    if !r.is_empty() {
+        r.push(' ');
+    }
+    if rest < 20 {
+        r.push_str(ONES[rest]);
+    } else {
+        r.push_str(TENS[rest / 10]);
+        if !rest.is_multiple_of(10) {
+            r.push('-');
+            r.push_str(ONES[rest % 10]);
+        }
+    }
+    r
+}
+
+#[cfg(test)]
+mod test {
+    use super::english;
+
+    #[test]
+    fn words() {
+        assert_eq!("forty-two", english(42));
+        assert_eq!("zero", english(0));
+        assert_eq!("minus one thousand two hundred thirteen", english(-1213));
+        assert_eq!("one million one", english(1_000_001));
+        assert_eq!("minus nine quintillion two hundred twenty-three quadrillion three hundred seventy-two trillion \
+            thirty-six billion eight hundred fifty-four million seven hundred seventy-five thousand eight hundred eight", english(i64::MIN));
+    }
+}
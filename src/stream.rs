@@ -0,0 +1,161 @@
+use std::io::{self, BufRead, Write};
+use crate::{Arguments, FormatOptions, ParseErrorKind, Template};
+use crate::parser::{form_end, parse};
+use crate::render::Output;
+use crate::template::Segment;
+
+/// The maximum length of a form or block that [`substitute_stream`] keeps
+/// in memory. A curly brace that does not start a form within this length
+/// is written as literal text, and so is the tag of a longer block.
+const MAX_FORM_LEN: usize = 1 << 20;
+
+/// Substitutes the placeholders of a template read from `reader`, writing
+/// the output to `writer` as it goes, so that arbitrarily large inputs are
+/// expanded in constant memory. Only the form being read, or the block, up
+/// to its `{end}` tag, is kept in memory.
+///
+/// As with [`LateFormat::late_format`](crate::LateFormat::late_format),
+/// forms that are not valid are written as literal text.
+///
+/// ```
+/// use late_format::substitute_stream;
+/// use maplit::hashmap;
+/// let input = "Hello, {name}!\n{if name}{raw}{x}{endraw}{end}";
+/// let mut output = vec![];
+/// substitute_stream(input.as_bytes(), &mut output, &hashmap!{"name".to_owned() => "x".to_owned()}).unwrap();
+/// assert_eq!(b"Hello, x!\n{x}", &output[..]);
+/// ```
+pub fn substitute_stream(reader: impl BufRead, writer: impl Write, arguments: &(impl Arguments + ?Sized)) -> io::Result<()> {
+    substitute_stream_with(reader, writer, arguments, &FormatOptions::default())
+}
+
+/// Substitutes the placeholders of a template read from `reader` as
+/// [`substitute_stream`] does, with the given options.
+///
+/// The [`Limits`](crate::Limits) apply to each form or block rather than
+/// to the whole input, and [`FormatOptions::dedent`] and
+/// [`FormatOptions::normalize_line_endings`] are not applied. The input
+/// must be UTF-8, and a render error, as with
+/// [`Missing::Error`](crate::Missing::Error), fails with
+/// [`io::ErrorKind::InvalidData`].
+pub fn substitute_stream_with(
+    mut reader: impl BufRead,
+    mut writer: impl Write,
+    arguments: &(impl Arguments + ?Sized),
+    options: &FormatOptions,
+) -> io::Result<()> {
+    let options = options.clone().dedent(false).normalize_line_endings(false);
+    let mut pending = String::new();
+    // the bytes of a character split between chunks.
+    let mut partial = vec![];
+    let mut eof = false;
+    loop {
+        // writes the complete forms and the literal text of the pending input.
+        while let Some(start) = pending.find('{') {
+            writer.write_all(&pending.as_bytes()[..start])?;
+            pending.drain(..start);
+            let Some(end) = unit_end(&pending, &options, eof) else {
+                break;
+            };
+            let template = Template::parse_lenient(&pending[..end], &options);
+            let mut output = IoWriter { inner: &mut writer, len: 0, result: Ok(()) };
+            let rendered = template.render_arguments_into(arguments, &options, &mut output);
+            output.result?;
+            rendered.map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+            pending.drain(..end);
+        }
+        if !pending.starts_with('{') {
+            writer.write_all(pending.as_bytes())?;
+            pending.clear();
+        }
+        if eof {
+            return writer.flush();
+        }
+        let chunk = reader.fill_buf()?;
+        eof = chunk.is_empty();
+        partial.extend_from_slice(chunk);
+        let len = chunk.len();
+        reader.consume(len);
+        let valid = match std::str::from_utf8(&partial) {
+            Ok(s) => s.len(),
+            Err(error) if error.error_len().is_none() && !eof => error.valid_up_to(),
+            Err(error) => return Err(io::Error::new(io::ErrorKind::InvalidData, error)),
+        };
+        pending.push_str(std::str::from_utf8(&partial[..valid]).unwrap());
+        partial.drain(..valid);
+    }
+}
+
+/// The end of the form or block at the start of `pending`, or `None` if more
+/// input is needed to tell. A curly brace that does not start a form is
+/// returned as a form of its own.
+fn unit_end(pending: &str, options: &FormatOptions, eof: bool) -> Option<usize> {
+    let complete = eof || pending.len() > MAX_FORM_LEN;
+    let Some(end) = form_end(pending, 0) else {
+        return complete.then_some(1);
+    };
+    let (_, errors) = parse(&pending[..end], options);
+    if !errors.iter().any(|e| e.kind() == &ParseErrorKind::UnclosedBlock) {
+        return Some(end);
+    }
+    // the form opens a block, which ends with its `{end}` or `{endraw}` tag.
+    let (segments, _) = parse(pending, options);
+    match segments.first() {
+        Some(
+            Segment::If { span, .. } | Segment::For { span, .. } | Segment::Plural { span, .. } | Segment::Choose { span, .. } | Segment::Raw { span, .. },
+        ) => Some(span.end),
+        _ => complete.then_some(end),
+    }
+}
+
+/// Rendered text written to an [`io::Write`], remembering the first error.
+struct IoWriter<'w> {
+    inner: &'w mut dyn Write,
+    len: usize,
+    result: io::Result<()>,
+}
+
+impl Output<'_> for IoWriter<'_> {
+    fn push_str(&mut self, s: &str) {
+        self.len += s.len();
+        if self.result.is_ok() {
+            self.result = self.inner.write_all(s.as_bytes());
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufReader, Read};
+    use crate::{substitute_stream, substitute_stream_with, FormatOptions, LateFormat, Missing};
+    use maplit::hashmap;
+
+    /// A reader returning its input a few bytes at a time.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(3);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn streaming() {
+        let input = "a {name | html} { {\"{\"} é {for x in list}<{x}>{else}none{end} {b:-{name}} {c:>5}|{ nope | x } {raw}{x}{endraw} {raw} {";
+        let arguments = hashmap!{"name".to_owned() => "<é>".to_owned(), "c".to_owned() => "1".to_owned()};
+        let mut output = vec![];
+        substitute_stream(BufReader::with_capacity(1, Trickle(input.as_bytes())), &mut output, &arguments).unwrap();
+        assert_eq!(input.late_format(arguments.clone()), String::from_utf8(output).unwrap());
+        let options = FormatOptions::new().missing(Missing::Error);
+        let error = substitute_stream_with("{a}".as_bytes(), std::io::sink(), &arguments, &options).unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidData, error.kind());
+        assert!(substitute_stream(&b"\xff"[..], std::io::sink(), &arguments).is_err());
+    }
+}
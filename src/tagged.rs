@@ -0,0 +1,138 @@
+use std::borrow::Cow;
+use std::ops::Range;
+use crate::SourceMap;
+
+/// A rendered output split into the text of the template and the values of
+/// its placeholders, as returned by
+/// [`Template::render_tagged`](crate::Template::render_tagged), so that the
+/// caller encodes the values for each sink the output is written to.
+///
+/// ```
+/// use late_format::{FormatOptions, Template};
+/// use maplit::hashmap;
+/// let template = Template::parse("<b>{user}</b> wrote {n} posts").unwrap();
+/// let tagged = template.render_tagged(&hashmap!{"user".to_owned() => "<x>".to_owned(), "n".to_owned() => "2".to_owned()}, &FormatOptions::new()).unwrap();
+/// let html = tagged.join(|value| value.text().replace('<', "&lt;").replace('>', "&gt;").into());
+/// assert_eq!("<b>&lt;x&gt;</b> wrote 2 posts", html);
+/// assert_eq!("<b><x></b> wrote 2 posts", tagged.to_string());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaggedRender {
+    parts: Vec<TaggedPart>,
+}
+
+/// A part of a [`TaggedRender`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TaggedPart {
+    /// Text of the template, such as literal text and escaped forms.
+    Trusted(String),
+    Value(TaggedValue),
+}
+
+/// The output of a placeholder, which comes from an argument.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaggedValue {
+    text: String,
+    placeholder: String,
+    source: Range<usize>,
+    missing: bool,
+}
+
+impl TaggedRender {
+    /// The parts in output order. Consecutive text of the template is a
+    /// single part.
+    pub fn parts(&self) -> &[TaggedPart] {
+        &self.parts
+    }
+
+    /// The values, in output order.
+    pub fn values(&self) -> impl Iterator<Item = &TaggedValue> {
+        self.parts.iter().filter_map(|part| match part {
+            TaggedPart::Value(value) => Some(value),
+            TaggedPart::Trusted(_) => None,
+        })
+    }
+
+    /// Joins the parts, replacing each value by `encode` of it.
+    pub fn join<'a>(&'a self, mut encode: impl FnMut(&'a TaggedValue) -> Cow<'a, str>) -> String {
+        let mut r = String::new();
+        for part in &self.parts {
+            match part {
+                TaggedPart::Trusted(text) => r.push_str(text),
+                TaggedPart::Value(value) => r.push_str(&encode(value)),
+            }
+        }
+        r
+    }
+}
+
+impl From<SourceMap> for TaggedRender {
+    fn from(mapped: SourceMap) -> Self {
+        let mut parts: Vec<TaggedPart> = vec![];
+        for span in &mapped.spans {
+            let text = &mapped.output[span.output.clone()];
+            match (&span.parameter, parts.last_mut()) {
+                (Some(placeholder), _) => parts.push(TaggedPart::Value(TaggedValue {
+                    text: text.to_owned(),
+                    placeholder: placeholder.clone(),
+                    source: span.source.clone(),
+                    missing: span.missing,
+                })),
+                (None, _) if text.is_empty() => {},
+                (None, Some(TaggedPart::Trusted(trusted))) => trusted.push_str(text),
+                (None, _) => parts.push(TaggedPart::Trusted(text.to_owned())),
+            }
+        }
+        Self { parts }
+    }
+}
+
+/// Writes the parts without encoding the values.
+impl std::fmt::Display for TaggedRender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.join(|value| Cow::Borrowed(value.text())))
+    }
+}
+
+impl TaggedValue {
+    /// The output of the placeholder, after its filters and spec.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The name written in the placeholder: a parameter, or a variable of
+    /// an enclosing `{for}` block, whose value comes from its list.
+    pub fn placeholder(&self) -> &str {
+        &self.placeholder
+    }
+
+    /// The byte range of the placeholder in the template source.
+    pub fn source(&self) -> Range<usize> {
+        self.source.clone()
+    }
+
+    /// Whether the argument is missing, so that the text is the rendering
+    /// of [`Missing`](crate::Missing).
+    pub fn is_missing(&self) -> bool {
+        self.missing
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FormatOptions, Template, Value};
+    use super::TaggedPart;
+    use maplit::hashmap;
+
+    #[test]
+    fn tagging() {
+        let template = Template::parse(r#"{"{"}{for x in xs}[{x | trim}]{end}{a:-(i)}{b}{c:>3}"#).unwrap();
+        let arguments = hashmap!{"xs".to_owned() => Value::from(vec![" <p> ", "q"]), "c".to_owned() => Value::from("&")};
+        let tagged = template.render_tagged(&arguments, &FormatOptions::new()).unwrap();
+        assert_eq!("{[<p>][q](i)None  &", tagged.to_string());
+        let values: Vec<_> = tagged.values().map(|v| (v.text(), v.placeholder(), v.source().start, v.is_missing())).collect();
+        assert_eq!(vec![("<p>", "x", 19, false), ("q", "x", 19, false), ("None", "b", 43, true), ("  &", "c", 46, false)], values);
+        assert_eq!(TaggedPart::Trusted("](i)".to_owned()), tagged.parts()[4]);
+        assert_eq!("{[#][#](i)##", tagged.join(|_| "#".into()));
+    }
+}
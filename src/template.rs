@@ -0,0 +1,1633 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::hash::{Hash, Hasher};
+use std::ops::{Add, Range};
+use std::str::FromStr;
+use std::sync::Arc;
+use crate::{ArgumentType, Error, ErrorReport, FormatOptions, LateDisplay, ParseError, ParseErrorKind, PartialRender, PluralCategory, RenderError, RenderErrorKind, Resolution, Trace};
+use crate::filters::Filter;
+use crate::number::Decimal;
+use crate::options::fnv1a;
+use crate::partial::Escaping;
+use crate::spec::Spec;
+use crate::parser;
+use crate::render::{Output, Part, Parts, Renderer};
+use crate::source_map::SourceMap;
+use crate::tagged::TaggedRender;
+use crate::value::{Arguments, Value, ValueRef};
+
+/// A template parsed ahead of time, which can be rendered many times
+/// without parsing its source again.
+///
+/// The syntax is described in [`LateFormat`](crate::LateFormat).
+///
+/// # Example
+///
+/// ```
+/// use late_format::Template;
+/// use maplit::hashmap;
+/// let greeting = Template::parse("Hello, {name}!").unwrap();
+/// let farewell = Template::parse("Bye, {name}.").unwrap();
+/// let message = Template::join([&greeting, &farewell], " ");
+/// assert_eq!("Hello, x! Bye, x.", message.render(&hashmap!{"name".into() => "x".into()}));
+/// ```
+///
+/// Templates compare equal when they have the same structure, regardless
+/// of how their text is written. For instance, `{ a }{"{"}` equals `{a}{'{'}`.
+#[derive(Clone, Debug)]
+pub struct Template {
+    pub(crate) source: String,
+    pub(crate) segments: Vec<Segment>,
+    /// The distinct parameter names, indexed by [`Placeholder::index`].
+    pub(crate) parameters: Vec<String>,
+    /// The options attached by [`Template::builder`].
+    pub(crate) options: Option<Arc<FormatOptions>>,
+    /// Whether the template is only literal text, as found by [`Template::new`].
+    pub(crate) literal: bool,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum Segment {
+    /// Text copied from the source.
+    Literal(Range<usize>),
+    /// A `{raw}...{endraw}` block, whose content is copied from the source.
+    Raw { span: Range<usize>, content: Range<usize> },
+    /// A `{"escaped"}` form.
+    Escaped { span: Range<usize>, text: String },
+    /// A `{# comment}`, which renders nothing.
+    Comment(Range<usize>),
+    Placeholder(Placeholder),
+    /// An `{if name}...{else}...{end}` block.
+    If { span: Range<usize>, condition: Condition, then: Vec<Segment>, otherwise: Vec<Segment> },
+    /// A `{for variable in list}...{else}...{end}` or `{for key, variable in map}...{end}` block.
+    For { span: Range<usize>, key: Option<String>, variable: String, list: Reference, body: Vec<Segment>, otherwise: Vec<Segment> },
+    /// A `{plural count}...{one}...{other}...{end}` block. `then` holds the
+    /// segments before the first category.
+    Plural { span: Range<usize>, count: Reference, then: Vec<Segment>, cases: Vec<(PluralCategory, Vec<Segment>)> },
+    /// A `{choose}{variant 60%}...{variant 40%}...{end}` block. `then` holds
+    /// the segments before the first variant, and each variant its weight.
+    Choose { span: Range<usize>, then: Vec<Segment>, variants: Vec<(u32, Vec<Segment>)> },
+}
+
+/// A parameter or loop variable name used by a block.
+#[derive(Clone, Debug)]
+pub(crate) struct Reference {
+    pub name: String,
+    /// The index in [`Template::parameters`], or the depth of the
+    /// `{for}` block binding the name if it is a loop variable.
+    pub index: usize,
+    pub local: bool,
+    /// The path navigated from a loop variable, as in `row.0`.
+    pub path: Option<String>,
+}
+
+/// The condition of an `{if name}` or `{if not name}` block, which holds
+/// when the argument is present and not empty, or, with a comparison as in
+/// `{if name | len > 40}`, when the filtered argument is a number satisfying it.
+#[derive(Clone, Debug)]
+pub(crate) struct Condition {
+    pub reference: Reference,
+    pub negated: bool,
+    pub filters: Vec<Filter>,
+    pub comparison: Option<Comparison>,
+}
+
+/// The comparison of a number with an operand, which holds when the
+/// number compares to the operand as `ordering`, or, if `negated`, when it
+/// does not, so `<=` is a negated `Greater`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct Comparison {
+    pub ordering: Ordering,
+    pub negated: bool,
+    pub operand: Decimal,
+}
+
+impl Comparison {
+    pub fn holds(&self, n: &Decimal) -> bool {
+        (n.compare(&self.operand) == self.ordering) != self.negated
+    }
+
+    /// The operator of the comparison, as in `<=`.
+    pub fn operator(&self) -> &'static str {
+        match (self.ordering, self.negated) {
+            (Ordering::Equal, false) => "==",
+            (Ordering::Equal, true) => "!=",
+            (Ordering::Less, false) => "<",
+            (Ordering::Less, true) => ">=",
+            (Ordering::Greater, false) => ">",
+            (Ordering::Greater, true) => "<=",
+        }
+    }
+}
+
+/// A `{param_name}` form of a [`Template`].
+#[derive(Clone, Debug)]
+pub struct Placeholder {
+    pub(crate) name: String,
+    pub(crate) index: usize,
+    /// Whether the name is the variable of an enclosing `{for}` block.
+    pub(crate) local: bool,
+    /// The path navigated from a loop variable, as in `row.0`.
+    pub(crate) path: Option<String>,
+    pub(crate) spec: Option<Spec>,
+    /// The parameters of the dynamic width and precision of the spec.
+    pub(crate) counts: Vec<Reference>,
+    pub(crate) filters: Vec<Filter>,
+    /// The segments of a `{param_name:-default}` placeholder, rendered when
+    /// the argument is missing or empty.
+    pub(crate) default: Option<Vec<Segment>>,
+    pub(crate) span: Range<usize>,
+}
+
+impl Placeholder {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The index of the parameter name in [`Template::parameters`]. For
+    /// a loop variable, this is the depth of the `{for}` block binding it.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Whether the placeholder refers to the variable of an enclosing
+    /// `{for}` block rather than to a parameter.
+    pub fn is_loop_variable(&self) -> bool {
+        self.local
+    }
+
+    /// The names of the filters applied to the argument, in order.
+    pub fn filters(&self) -> impl Iterator<Item = &str> + '_ {
+        self.filters.iter().map(|f| f.name())
+    }
+
+    /// The type annotating the placeholder, as in `{count:int}`.
+    pub fn argument_type(&self) -> Option<ArgumentType> {
+        self.spec.as_ref().and_then(|spec| spec.ty)
+    }
+
+    /// The byte range of the placeholder in the template source.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Whether the placeholder has a default, through the `{param_name:-default}`
+    /// form or the `default` filter, so that its argument may be missing.
+    pub(crate) fn has_default(&self) -> bool {
+        self.default.is_some() || self.filters.iter().any(|f| matches!(f, Filter::Default(_)))
+    }
+}
+
+impl Segment {
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Self::Literal(span) | Self::Comment(span) => span.clone(),
+            Self::Raw { span, .. } => span.clone(),
+            Self::Escaped { span, .. } => span.clone(),
+            Self::Placeholder(p) => p.span.clone(),
+            Self::If { span, .. } | Self::For { span, .. } | Self::Plural { span, .. } | Self::Choose { span, .. } => span.clone(),
+        }
+    }
+
+    /// The text of a segment other than a placeholder or a block.
+    pub fn text<'a>(&'a self, source: &'a str) -> &'a str {
+        match self {
+            Self::Literal(span) => &source[span.clone()],
+            Self::Raw { content, .. } => &source[content.clone()],
+            Self::Escaped { text, .. } => text,
+            Self::Comment(_) | Self::Placeholder(_) | Self::If { .. } | Self::For { .. } | Self::Plural { .. } | Self::Choose { .. } => "",
+        }
+    }
+
+    pub(crate) fn shifted(&self, offset: usize) -> Self {
+        self.mapped(&|i| i + offset)
+    }
+
+    /// The segment with each position of its spans replaced by `position(i)`.
+    pub(crate) fn mapped(&self, position: &impl Fn(usize) -> usize) -> Self {
+        let shift = |r: &Range<usize>| position(r.start)..position(r.end);
+        match self {
+            Self::Literal(span) => Self::Literal(shift(span)),
+            Self::Comment(span) => Self::Comment(shift(span)),
+            Self::Raw { span, content } => Self::Raw { span: shift(span), content: shift(content) },
+            Self::Escaped { span, text } => Self::Escaped { span: shift(span), text: text.clone() },
+            Self::Placeholder(p) => Self::Placeholder(Placeholder {
+                span: shift(&p.span),
+                default: p.default.as_ref().map(|default| default.iter().map(|s| s.mapped(position)).collect()),
+                ..p.clone()
+            }),
+            Self::If { span, condition, then, otherwise } => Self::If {
+                span: shift(span),
+                condition: condition.clone(),
+                then: then.iter().map(|s| s.mapped(position)).collect(),
+                otherwise: otherwise.iter().map(|s| s.mapped(position)).collect(),
+            },
+            Self::For { span, key, variable, list, body, otherwise } => Self::For {
+                span: shift(span),
+                key: key.clone(),
+                variable: variable.clone(),
+                list: list.clone(),
+                body: body.iter().map(|s| s.mapped(position)).collect(),
+                otherwise: otherwise.iter().map(|s| s.mapped(position)).collect(),
+            },
+            Self::Plural { span, count, then, cases } => Self::Plural {
+                span: shift(span),
+                count: count.clone(),
+                then: then.iter().map(|s| s.mapped(position)).collect(),
+                cases: cases.iter().map(|(c, segments)| (*c, segments.iter().map(|s| s.mapped(position)).collect())).collect(),
+            },
+            Self::Choose { span, then, variants } => Self::Choose {
+                span: shift(span),
+                then: then.iter().map(|s| s.mapped(position)).collect(),
+                variants: variants.iter().map(|(w, segments)| (*w, segments.iter().map(|s| s.mapped(position)).collect())).collect(),
+            },
+        }
+    }
+
+    /// The segments nested in a block or in the default of a placeholder.
+    pub(crate) fn children(&self) -> Vec<&[Segment]> {
+        match self {
+            Self::Placeholder(p) => p.default.iter().map(|default| &default[..]).collect(),
+            Self::If { then, otherwise, .. } => vec![then, otherwise],
+            Self::For { body, otherwise, .. } => vec![body, otherwise],
+            Self::Plural { then, cases, .. } => std::iter::once(&then[..]).chain(cases.iter().map(|(_, s)| &s[..])).collect(),
+            Self::Choose { then, variants, .. } => std::iter::once(&then[..]).chain(variants.iter().map(|(_, s)| &s[..])).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The indices of the parameters that the segment itself uses, without
+    /// those of its nested segments and the loop variables.
+    pub(crate) fn parameters(&self) -> Vec<usize> {
+        let mut r = vec![];
+        let references = match self {
+            Self::Placeholder(p) => {
+                if !p.local {
+                    r.push(p.index);
+                }
+                p.counts.iter().collect()
+            },
+            Self::If { condition, .. } => vec![&condition.reference],
+            Self::For { list, .. } => vec![list],
+            Self::Plural { count, .. } => vec![count],
+            _ => vec![],
+        };
+        r.extend(references.into_iter().filter(|r| !r.local).map(|r| r.index));
+        r
+    }
+}
+
+/// Calls `f` on each segment, including the segments nested in blocks,
+/// in source order.
+pub(crate) fn walk<'a>(segments: &'a [Segment], f: &mut impl FnMut(&'a Segment)) {
+    for segment in segments {
+        f(segment);
+        for children in segment.children() {
+            walk(children, f);
+        }
+    }
+}
+
+/// Replaces the literal segments holding curly braces, which are escaped in
+/// `source`, by the escaped forms and literal text between them.
+fn split_escaped(segments: &mut Vec<Segment>, source: &str) {
+    for mut segment in std::mem::take(segments) {
+        match &mut segment {
+            Segment::Literal(span) if source[span.clone()].contains('{') => {
+                let escaped = Template::parse_lenient(&source[span.clone()], &FormatOptions::default());
+                segments.extend(escaped.segments.iter().map(|s| s.shifted(span.start)));
+                continue;
+            },
+            Segment::Placeholder(p) => {
+                if let Some(default) = &mut p.default {
+                    split_escaped(default, source);
+                }
+            },
+            Segment::If { then, otherwise, .. } | Segment::For { body: then, otherwise, .. } => {
+                split_escaped(then, source);
+                split_escaped(otherwise, source);
+            },
+            Segment::Plural { then, cases, .. } => {
+                split_escaped(then, source);
+                for (_, case) in cases {
+                    split_escaped(case, source);
+                }
+            },
+            Segment::Choose { then, variants, .. } => {
+                split_escaped(then, source);
+                for (_, variant) in variants {
+                    split_escaped(variant, source);
+                }
+            },
+            _ => {},
+        }
+        segments.push(segment);
+    }
+}
+
+/// Resolves the names of the segments to the parameters, interning new
+/// parameter names, or to the variables of the enclosing `{for}` blocks.
+fn intern(segments: &mut [Segment], locals: &mut Vec<String>, parameters: &mut Vec<String>) {
+    for segment in segments {
+        match segment {
+            Segment::Placeholder(p) => {
+                (p.index, p.local, p.path) = resolve(&p.name, locals, parameters);
+                for r in &mut p.counts {
+                    (r.index, r.local, r.path) = resolve(&r.name, locals, parameters);
+                }
+                if let Some(default) = &mut p.default {
+                    intern(default, locals, parameters);
+                }
+            },
+            Segment::If { condition, then, otherwise, .. } => {
+                let r = &mut condition.reference;
+                (r.index, r.local, r.path) = resolve(&r.name, locals, parameters);
+                intern(then, locals, parameters);
+                intern(otherwise, locals, parameters);
+            },
+            Segment::For { key, variable, list, body, otherwise, .. } => {
+                (list.index, list.local, list.path) = resolve(&list.name, locals, parameters);
+                let len = locals.len();
+                locals.extend(key.clone());
+                locals.push(variable.clone());
+                intern(body, locals, parameters);
+                locals.truncate(len);
+                intern(otherwise, locals, parameters);
+            },
+            Segment::Plural { count, then, cases, .. } => {
+                (count.index, count.local, count.path) = resolve(&count.name, locals, parameters);
+                intern(then, locals, parameters);
+                for (_, segments) in cases {
+                    intern(segments, locals, parameters);
+                }
+            },
+            Segment::Choose { then, variants, .. } => {
+                intern(then, locals, parameters);
+                for (_, segments) in variants {
+                    intern(segments, locals, parameters);
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Returns the depth of the loop variable or the index of the parameter
+/// of a name, whether it is a loop variable, and the path navigated from
+/// the loop variable, as in `row.0` for the variable `row`.
+fn resolve(name: &str, locals: &[String], parameters: &mut Vec<String>) -> (usize, bool, Option<String>) {
+    if let Some(depth) = locals.iter().rposition(|n| n == name) {
+        return (depth, true, None);
+    }
+    let path = |local: &str| name.strip_prefix(local)?.strip_prefix('.');
+    if let Some((depth, path)) = locals.iter().enumerate().rev().find_map(|(depth, n)| Some((depth, path(n)?))) {
+        return (depth, true, Some(path.to_owned()));
+    }
+    match parameters.iter().position(|n| n == name) {
+        Some(index) => (index, false, None),
+        None => {
+            parameters.push(name.to_owned());
+            (parameters.len() - 1, false, None)
+        },
+    }
+}
+
+impl Template {
+    /// Parses a template, failing if a placeholder uses an unknown filter.
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        Self::parse_with(source, &FormatOptions::default())
+    }
+
+    /// Parses a template, applying the source options such as [`FormatOptions::dedent`].
+    pub fn parse_with(source: &str, options: &FormatOptions) -> Result<Self, ParseError> {
+        let source = options.preprocess(source).into_owned();
+        let (segments, mut errors) = parser::parse(&source, options);
+        if !errors.is_empty() {
+            return Err(errors.swap_remove(0));
+        }
+        Ok(Self::new(source, segments))
+    }
+
+    /// Parses a template, keeping placeholders that use unknown filters or
+    /// exceed the [`Limits`](crate::Limits) as literal text.
+    pub(crate) fn parse_lenient(source: &str, options: &FormatOptions) -> Self {
+        let source = options.preprocess(source).into_owned();
+        let (segments, _) = parser::parse(&source, options);
+        Self::new(source, segments)
+    }
+
+    /// Parses a template and checks it against the given arguments, reporting
+    /// every problem at once rather than stopping at the first one: all parse
+    /// errors, all parameters missing from the arguments and all arguments
+    /// of the wrong [`ArgumentType`].
+    ///
+    /// ```
+    /// use late_format::{FormatOptions, Template};
+    /// use maplit::hashmap;
+    /// let report = Template::validate("{a | nope} {b} {c} {b}", &hashmap!{"c".into() => "x".into()}, &FormatOptions::new()).unwrap_err();
+    /// assert_eq!(2, report.errors().len());
+    /// assert_eq!("unknown filter `nope` at 0..10\nmissing argument `b` at 11..14", report.to_string());
+    /// ```
+    pub fn validate(source: &str, arguments: &HashMap<String, String>, options: &FormatOptions) -> Result<Self, ErrorReport> {
+        let source = options.preprocess(source).into_owned();
+        let (segments, errors) = parser::parse(&source, options);
+        let template = Self::new(source, segments);
+        let mut errors: Vec<Error> = errors.into_iter().map(Error::from).collect();
+        if let Err(report) = template.check_resolved(&template.resolve_map(arguments, options)) {
+            errors.extend(report.errors);
+        }
+        if !errors.is_empty() {
+            return Err(ErrorReport { errors });
+        }
+        Ok(template)
+    }
+
+    /// Reports every parameter of the template missing from the arguments,
+    /// at its first placeholder, and every argument that is not of the
+    /// [`ArgumentType`] annotating a placeholder, as in `{count:int}`.
+    /// Parameters only used by blocks, or by placeholders with a default,
+    /// may be missing.
+    pub fn check_arguments(&self, arguments: &HashMap<String, String>) -> Result<(), ErrorReport> {
+        self.check_resolved(&self.resolve_map(arguments, &self.attached_options()))
+    }
+
+    fn check_resolved<V: AsRef<str>>(&self, values: &[Option<V>]) -> Result<(), ErrorReport> {
+        let mut errors = vec![];
+        let mut reported = vec![false; self.parameters.len()];
+        for p in self.placeholders().filter(|p| !p.local) {
+            let value = values[p.index].as_ref().map(AsRef::as_ref);
+            if let (Some(ty), Some(value)) = (p.argument_type(), value) {
+                if !ty.accepts(value) {
+                    errors.push(RenderError::new(RenderErrorKind::WrongType(p.name.clone(), ty), p.span()).into());
+                }
+            }
+            if reported[p.index] || value.is_some() || p.has_default() {
+                continue;
+            }
+            reported[p.index] = true;
+            errors.push(RenderError::new(RenderErrorKind::MissingArgument(p.name.clone()), p.span()).into());
+        }
+        if !errors.is_empty() {
+            return Err(ErrorReport { errors });
+        }
+        Ok(())
+    }
+
+    /// Resolves the argument of each parameter, through the resolver of its
+    /// [`FormatOptions::resolver`] namespace or `get`, matching names as
+    /// described in [`Lookup`](crate::Lookup) and following the
+    /// [`FormatOptions::alias`]es, and reports each resolution to the
+    /// [`FormatOptions::observer`]. `layer` names the source of an argument.
+    fn resolve<'l, V: Resolved>(
+        &self,
+        options: &FormatOptions,
+        get: impl Fn(&str) -> Option<V>,
+        names: impl FnOnce() -> Vec<String>,
+        layer: impl Fn(&str) -> Option<&'l str>,
+    ) -> Vec<Option<V>> {
+        self.resolve_layered(options, get, names, |name| layer(name), false).0
+    }
+
+    /// Resolves the arguments as [`Template::resolve`] does, also returning
+    /// the source of each argument if `layered`, or if there is an observer.
+    fn resolve_layered<'l, V: Resolved>(
+        &'l self,
+        options: &FormatOptions,
+        get: impl Fn(&str) -> Option<V>,
+        names: impl FnOnce() -> Vec<String>,
+        layer: impl Fn(&str) -> Option<&'l str>,
+        layered: bool,
+    ) -> (Vec<Option<V>>, Vec<Option<&'l str>>) {
+        let mut values = Vec::with_capacity(self.parameters.len());
+        // the namespace or the argument name supplying each value.
+        let mut sources: Vec<Result<&str, Cow<str>>> = Vec::with_capacity(self.parameters.len());
+        for name in &self.parameters {
+            match name.split_once(':').and_then(|(ns, key)| Some((ns, options.namespace(ns)?, key))) {
+                Some((ns, resolver, key)) => {
+                    values.push(resolver.resolve(key).map(V::from_value));
+                    sources.push(Ok(ns));
+                },
+                None => {
+                    values.push(get(name).or_else(|| navigate(&get, name)));
+                    sources.push(Err(Cow::Borrowed(name)));
+                },
+            }
+        }
+        let lookup = &options.lookup;
+        if !(values.iter().all(Option::is_some) || (lookup.is_exact() && options.aliases.is_empty())) {
+            let mut normalized: HashMap<String, String> = HashMap::new();
+            if !lookup.is_exact() {
+                let mut names = names();
+                names.sort();
+                for name in names {
+                    normalized.entry(lookup.normalize(&name).into_owned()).or_insert(name);
+                }
+            }
+            let find = |name: &str| {
+                if let Some(v) = get(name) {
+                    return Some((v, name.to_owned()));
+                }
+                let name = normalized.get(&*lookup.normalize(name))?;
+                Some((get(name)?, name.clone()))
+            };
+            for ((value, source), name) in values.iter_mut().zip(&mut sources).zip(&self.parameters) {
+                if value.is_some() {
+                    continue;
+                }
+                if let Some((v, found)) = find(name).or_else(|| find(options.aliases.get(name)?)) {
+                    *value = Some(v);
+                    *source = Err(Cow::Owned(found));
+                }
+            }
+        }
+        #[cfg(feature = "global-defaults")]
+        if let Some(defaults) = crate::globals::get().filter(|_| values.iter().any(Option::is_none)) {
+            for ((value, source), name) in values.iter_mut().zip(&mut sources).zip(&self.parameters) {
+                if let (None, Some(v)) = (&value, defaults.get(name)) {
+                    *value = Some(V::from_value(Value::String(v.clone())));
+                    *source = Ok(crate::globals::LAYER);
+                }
+            }
+        }
+        if !options.coercions.is_empty() {
+            for (value, name) in values.iter_mut().zip(&self.parameters) {
+                *value = value.take().map(|v| v.coerced(name, options));
+            }
+        }
+        let mut layers = vec![];
+        if layered || options.observer.is_some() {
+            layers = values.iter().zip(&sources).map(|(value, source)| match (value, source) {
+                (None, _) => None,
+                (Some(_), Ok(ns)) => Some(*ns),
+                (Some(_), Err(argument)) => layer(argument),
+            }).collect();
+        }
+        if let Some(observer) = &options.observer {
+            for ((value, layer), name) in values.iter().zip(&layers).zip(&self.parameters) {
+                (observer.0)(&Resolution { name, layer: *layer, len: value.as_ref().map(V::len) });
+            }
+        }
+        (values, layers)
+    }
+
+    pub(crate) fn resolve_map<'v>(&self, arguments: &'v HashMap<String, String>, options: &FormatOptions) -> Vec<Option<Cow<'v, str>>> {
+        self.resolve(
+            options,
+            |name| arguments.get(name).map(|v| Cow::Borrowed(v.as_str())),
+            || arguments.keys().cloned().collect(),
+            |_| None,
+        )
+    }
+
+    /// Creates a template, interning the parameter names of its placeholders
+    /// and blocks.
+    pub(crate) fn new(source: String, mut segments: Vec<Segment>) -> Self {
+        let mut parameters = vec![];
+        intern(&mut segments, &mut vec![], &mut parameters);
+        // the literal segments must also cover the source, so that it is the output.
+        let mut end = 0;
+        let literal = segments.iter().all(|segment| match segment {
+            Segment::Literal(span) if span.start == end => {
+                end = span.end;
+                true
+            },
+            _ => false,
+        }) && end == source.len();
+        Self { source, segments, parameters, options: None, literal }
+    }
+
+    /// The distinct parameter names of the template, in order of first use.
+    pub fn parameters(&self) -> &[String] {
+        &self.parameters
+    }
+
+    /// The index of a parameter name in [`Template::parameters`].
+    pub fn parameter_index(&self, name: &str) -> Option<usize> {
+        self.parameters.iter().position(|n| n == name)
+    }
+
+    /// The source of the template, exactly as it was parsed: whitespace inside
+    /// curly braces, quote styles and escape sequences are preserved. This
+    /// allows tooling that rewrites template files to leave untouched
+    /// templates byte for byte identical.
+    ///
+    /// When [`FormatOptions::dedent`] or [`FormatOptions::normalize_line_endings`]
+    /// is set, this is the source after that preprocessing.
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// let source = r#"{  name |dedent }{'\''}{ raw }{}{endraw}"#;
+    /// assert_eq!(source, Template::parse(source).unwrap().source());
+    /// ```
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// A stable hash of the source of the template, for caches and
+    /// translation memories to detect that a template changed. It is the
+    /// 64-bit FNV-1a hash of [`Template::source`], so it does not depend on
+    /// the platform or the version of this crate, and changes with any edit
+    /// of the source, including its comments.
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// let template = Template::parse("Hello, {name}!").unwrap();
+    /// assert_eq!(0x7f09293967fa2d5d, template.fingerprint());
+    /// assert_ne!(template.fingerprint(), Template::parse("Hello, {name}.").unwrap().fingerprint());
+    /// ```
+    pub fn fingerprint(&self) -> u64 {
+        fnv1a(&self.source)
+    }
+
+    /// The value of the first `{#key: value}` comment with the given key,
+    /// without surrounding whitespace, as in `{#version: 3}`, which lets
+    /// catalogs embed metadata such as versions in their templates.
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// let template = Template::parse("{#version: 3}{# note: reviewed }Hello, {name}!").unwrap();
+    /// assert_eq!((Some("3"), Some("reviewed"), None), (template.metadata("version"), template.metadata("note"), template.metadata("name")));
+    /// assert_eq!("Hello, x!", template.render(&maplit::hashmap!{"name".into() => "x".into()}));
+    /// ```
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        let mut value = None;
+        walk(&self.segments, &mut |segment| {
+            let Segment::Comment(span) = segment else {
+                return;
+            };
+            let comment = self.source[span.start + 1..span.end - 1].trim_start().trim_start_matches('#');
+            if let Some((_, v)) = comment.split_once(':').filter(|(k, _)| k.trim() == key) {
+                value = value.or(Some(v.trim()));
+            }
+        });
+        value
+    }
+
+    /// Whether the template is only literal text, without placeholders,
+    /// blocks, escaped forms or comments, so that it renders as its source
+    /// whatever the arguments and options. Rendering such a template does not
+    /// resolve any argument, and [`Template::render_cow`] borrows its source.
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// assert!(Template::parse("Settings").unwrap().is_literal());
+    /// assert!(!Template::parse("{'{'}").unwrap().is_literal() && !Template::parse("{#}").unwrap().is_literal());
+    /// ```
+    pub fn is_literal(&self) -> bool {
+        self.literal
+    }
+
+    /// The rendered text of a template that [is literal](Template::is_literal),
+    /// borrowed from its source, so that catalogs of mostly placeholder-free
+    /// strings render them without copying.
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// assert_eq!(Some("Settings"), Template::parse("Settings").unwrap().as_literal());
+    /// assert_eq!(None, Template::parse("Hi {name}").unwrap().as_literal());
+    /// ```
+    pub fn as_literal(&self) -> Option<&str> {
+        self.literal.then_some(self.source.as_str())
+    }
+
+    /// The placeholders of the template, including those inside blocks,
+    /// in source order.
+    pub fn placeholders(&self) -> impl Iterator<Item = &Placeholder> {
+        let mut r = vec![];
+        walk(&self.segments, &mut |s| {
+            if let Segment::Placeholder(p) = s {
+                r.push(p);
+            }
+        });
+        r.into_iter()
+    }
+
+    /// Substitutes the parameters of the template. A parameter that is not present
+    /// in the map expands to the string `None`.
+    ///
+    /// The options attached by [`Template::builder`] are used if any. On a
+    /// render error, the remaining source is kept as is, as with
+    /// [`LateFormat`](crate::LateFormat).
+    ///
+    /// The output of a template that [is literal](Template::is_literal) is a
+    /// copy of its source. [`Template::render_cow`] is the path that borrows
+    /// it instead, for catalogs rendering such templates often.
+    pub fn render(&self, arguments: &HashMap<String, String>) -> String {
+        if let Some(literal) = self.as_literal() {
+            return literal.to_owned();
+        }
+        let mut r = String::new();
+        if let Err(error) = self.render_into(arguments, &self.attached_options(), &mut r) {
+            r.push_str(&self.source[error.span().start..]);
+        }
+        r
+    }
+
+    /// Substitutes the parameters of the template as [`Template::render`]
+    /// does, borrowing the source of a template that
+    /// [is literal](Template::is_literal) rather than copying it.
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// use maplit::hashmap;
+    /// use std::borrow::Cow;
+    /// let template = Template::parse("Empty trash?").unwrap();
+    /// assert!(matches!(template.render_cow(&hashmap!{}), Cow::Borrowed("Empty trash?")));
+    /// ```
+    pub fn render_cow(&self, arguments: &HashMap<String, String>) -> Cow<'_, str> {
+        match self.as_literal() {
+            Some(literal) => Cow::Borrowed(literal),
+            None => Cow::Owned(self.render(arguments)),
+        }
+    }
+
+    /// Substitutes the parameters of the template, failing if the
+    /// [`Budget`](crate::Budget) of the options is exceeded.
+    pub fn render_with(&self, arguments: &HashMap<String, String>, options: &FormatOptions) -> Result<String, RenderError> {
+        let mut r = String::new();
+        self.render_into(arguments, options, &mut r)?;
+        Ok(r)
+    }
+
+    /// Renders into `output`. On failure, `output` holds the text rendered
+    /// before the failing span.
+    pub(crate) fn render_into(&self, arguments: &HashMap<String, String>, options: &FormatOptions, output: &mut String) -> Result<(), RenderError> {
+        output.reserve(self.source.len());
+        if let Some(literal) = self.as_literal() {
+            output.push_str(literal);
+            return Ok(());
+        }
+        let values = self.resolve_map(arguments, options);
+        Renderer::new(self, options).render(|index| values[index].as_deref().map(ValueRef::Str), output)
+    }
+
+    /// Substitutes the parameters of the template as [`Template::render`] does,
+    /// returning the parts of the output without joining them. The literals
+    /// are borrowed from the template, and the arguments that are not changed
+    /// by a spec or filter from the map, which allows assembling the output
+    /// into another structure without copying them.
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// use maplit::hashmap;
+    /// use std::borrow::Cow;
+    /// let template = Template::parse("Hello, {name}{if name}!{end} {n:03}").unwrap();
+    /// let arguments = hashmap!{"name".into() => "x".into(), "n".into() => "7".into()};
+    /// let parts = template.render_segments(&arguments);
+    /// assert_eq!(vec!["Hello, ", "x", "!", " ", "007"], parts);
+    /// assert!(parts.iter().take(4).all(|part| matches!(part, Cow::Borrowed(_))));
+    /// ```
+    pub fn render_segments<'a>(&'a self, arguments: &'a HashMap<String, String>) -> Vec<Cow<'a, str>> {
+        let options = self.attached_options();
+        let values = self.resolve_map(arguments, &options);
+        let mut parts = Parts::default();
+        let rendered = Renderer::new(self, &options).render(|index| values[index].as_deref().map(ValueRef::Str), &mut parts);
+        if let Err(error) = rendered {
+            parts.parts.push(Part::Literal(&self.source[error.span().start..]));
+        }
+        parts.parts.into_iter().map(|part| match part {
+            Part::Literal(s) => Cow::Borrowed(s),
+            Part::Argument(index) => values[index].clone().unwrap_or_default(),
+            Part::Text(s) => Cow::Owned(s),
+        }).collect()
+    }
+
+    /// Substitutes the parameters of the template with arguments resolved
+    /// from any [`Arguments`] source, such as a map to [`Value`](crate::Value)s.
+    pub fn render_arguments(&self, arguments: &(impl Arguments + ?Sized), options: &FormatOptions) -> Result<String, RenderError> {
+        let mut r = String::with_capacity(self.source.len());
+        self.render_arguments_into(arguments, options, &mut r)?;
+        Ok(r)
+    }
+
+    /// Renders with arguments from any [`Arguments`] source into `output`.
+    pub(crate) fn render_arguments_into<'a>(
+        &'a self,
+        arguments: &(impl Arguments + ?Sized),
+        options: &FormatOptions,
+        output: &mut impl Output<'a>,
+    ) -> Result<(), RenderError> {
+        if let Some(literal) = self.as_literal() {
+            output.push_literal(literal);
+            return Ok(());
+        }
+        let values = self.resolve(options, |name| arguments.get(name), || arguments.names(), |name| arguments.layer(name));
+        Renderer::new(self, options).render(|index| values[index].as_deref().map(ValueRef::Value), output)
+    }
+
+    /// Wraps the template and its arguments into a [`LateDisplay`], which
+    /// renders them when formatted, without allocating the rendered string.
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// use maplit::hashmap;
+    /// let template = Template::parse("Hello, {name}!").unwrap();
+    /// let arguments = hashmap!{"name".to_owned() => "x".to_owned()};
+    /// assert_eq!("[Hello, x!   ]", format!("[{:<12}]", template.display(&arguments)));
+    /// ```
+    pub fn display<'a, A: Arguments + ?Sized>(&'a self, arguments: &'a A) -> LateDisplay<'a, A> {
+        LateDisplay::new(self, arguments)
+    }
+
+    /// Renders the template with arguments from any [`Arguments`] source,
+    /// recording the argument, source and filters of each substituted placeholder.
+    ///
+    /// ```
+    /// use late_format::{FormatOptions, Layers, Template};
+    /// use maplit::hashmap;
+    /// let layers = Layers::new()
+    ///     .layer("arguments", hashmap!{"user".to_owned() => "x".to_owned()})
+    ///     .layer("defaults", hashmap!{"user".to_owned() => "nobody".to_owned(), "app".to_owned() => "demo".to_owned()});
+    /// let trace = Template::parse("{user | html}@{app}").unwrap().render_traced(&layers, &FormatOptions::new()).unwrap();
+    /// assert_eq!("x@demo", trace.output());
+    /// let app = &trace.placeholders()[1];
+    /// assert_eq!((Some("demo"), Some("defaults"), 2..6), (app.value(), app.layer(), app.output()));
+    /// ```
+    pub fn render_traced(&self, arguments: &(impl Arguments + ?Sized), options: &FormatOptions) -> Result<Trace, RenderError> {
+        let (values, layers) = self.resolve_layered(options, |name| arguments.get(name), || arguments.names(), |name| arguments.layer(name), true);
+        let mut renderer = Renderer::new(self, options).record_trace();
+        let mut output = String::with_capacity(self.source.len());
+        renderer.render(|index| values[index].as_deref().map(ValueRef::Value), &mut output)?;
+        let mut placeholders = renderer.trace.unwrap();
+        for p in &mut placeholders {
+            p.layer = p.parameter.and_then(|index| layers[index]).map(str::to_owned);
+        }
+        Ok(Trace { output, placeholders })
+    }
+
+    /// Renders the template, mapping each range of the output to the range of
+    /// the template source it comes from.
+    ///
+    /// ```
+    /// use late_format::{FormatOptions, Template};
+    /// use maplit::hashmap;
+    /// let template = Template::parse("Hello, {name}!").unwrap();
+    /// let mapped = template.render_mapped(&hashmap!{"name".into() => "x".into()}, &FormatOptions::new()).unwrap();
+    /// assert_eq!("Hello, x!", mapped.output());
+    /// let span = mapped.lookup(7).unwrap();
+    /// assert_eq!((7..8, 7..13, Some("name")), (span.output(), span.source(), span.parameter()));
+    /// ```
+    pub fn render_mapped(&self, arguments: &HashMap<String, String>, options: &FormatOptions) -> Result<SourceMap, RenderError> {
+        let values = self.resolve_map(arguments, options);
+        let mut renderer = Renderer::new(self, options).record_spans();
+        let mut output = String::with_capacity(self.source.len());
+        renderer.render(|index| values[index].as_deref().map(ValueRef::Str), &mut output)?;
+        Ok(SourceMap { output, spans: renderer.spans.unwrap() })
+    }
+
+    /// Renders the text of the template, which is trusted, and returns the
+    /// output of each placeholder as a tagged value, with its origin, for the
+    /// caller to encode for each sink, such as HTML and plain text, as
+    /// described in [`TaggedRender`]. Filters and specs still apply to the
+    /// values, so that escaping filters such as `html` are left to the caller.
+    pub fn render_tagged(&self, arguments: &(impl Arguments + ?Sized), options: &FormatOptions) -> Result<TaggedRender, RenderError> {
+        let values = self.resolve(options, |name| arguments.get(name), || arguments.names(), |name| arguments.layer(name));
+        let mut renderer = Renderer::new(self, options).record_spans();
+        let mut output = String::with_capacity(self.source.len());
+        renderer.render(|index| values[index].as_deref().map(ValueRef::Value), &mut output)?;
+        Ok(SourceMap { output, spans: renderer.spans.unwrap() }.into())
+    }
+
+    /// Renders what can be rendered with the arguments, keeping the
+    /// placeholders and blocks whose parameters are missing as they are
+    /// written, for pipelines where several passes fill in different
+    /// parameters. A placeholder with a default is also kept, for a later
+    /// pass to supply its argument. A kept placeholder or block is rendered
+    /// by the later pass as a whole, which needs all of its parameters.
+    ///
+    /// The output is the source of a template: the curly braces of the
+    /// rendered text are escaped with [`escape_literal`], so that the next
+    /// pass renders them as is. The parameters that were missing are listed by
+    /// [`PartialRender::leftovers`]. It only fails if the
+    /// [`Budget`](crate::Budget) is exceeded.
+    ///
+    /// ```
+    /// use late_format::{FormatOptions, Template};
+    /// use maplit::hashmap;
+    /// let template = Template::parse("{greeting}, {name}!{if vip} ★{end}").unwrap();
+    /// let partial = template.render_partial(&hashmap!{"greeting".to_owned() => "Hello".to_owned()}, &FormatOptions::new()).unwrap();
+    /// assert_eq!(("Hello, {name}!{if vip} ★{end}", &["name".to_owned(), "vip".to_owned()][..]), (partial.output(), partial.leftovers()));
+    /// let next = Template::parse(partial.output()).unwrap();
+    /// assert_eq!("Hello, x!", next.render(&hashmap!{"name".into() => "x".into(), "vip".into() => "".into()}));
+    /// ```
+    pub fn render_partial(&self, arguments: &(impl Arguments + ?Sized), options: &FormatOptions) -> Result<PartialRender, RenderError> {
+        let values = self.resolve(options, |name| arguments.get(name), || arguments.names(), |name| arguments.layer(name));
+        let mut renderer = Renderer::new(self, options).partial();
+        let mut output = String::with_capacity(self.source.len());
+        renderer.render(|index| values[index].as_deref().map(ValueRef::Value), &mut Escaping { inner: &mut output })?;
+        Ok(PartialRender { output, leftovers: renderer.leftovers.unwrap() })
+    }
+
+    /// Resolves the arguments once, for rendering the template repeatedly.
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// use maplit::hashmap;
+    /// let template = Template::parse("{done} of {total}").unwrap();
+    /// let mut bound = template.bind(&hashmap!{"total".into() => "10".into()});
+    /// let done = template.parameter_index("done").unwrap();
+    /// for i in 1..=3 {
+    ///     bound.set_index(done, i.to_string());
+    ///     assert_eq!(format!("{i} of 10"), bound.render());
+    /// }
+    /// ```
+    pub fn bind(&self, arguments: &HashMap<String, String>) -> BoundTemplate<'_> {
+        let values = self.resolve_map(arguments, &self.attached_options()).into_iter().map(|v| v.map(Cow::into_owned)).collect();
+        BoundTemplate { template: self, values }
+    }
+
+    /// Appends another template, shifting the spans of its placeholders
+    /// accordingly.
+    pub fn concat(&self, other: &Template) -> Template {
+        let mut r = self.sealed();
+        let offset = r.source.len();
+        r.source.push_str(&other.source);
+        r.segments.extend(other.segments.iter().map(|s| s.shifted(offset)));
+        Self::new(r.source, r.segments)
+    }
+
+    /// Concatenates templates, placing the separator text between them
+    /// verbatim.
+    pub fn join<'a>(templates: impl IntoIterator<Item = &'a Template>, separator: &str) -> Template {
+        let separator = Self::parse_lenient(&escape_literal(separator), &FormatOptions::default()).sealed();
+        let (mut source, mut segments) = (String::new(), vec![]);
+        let mut push = |template: &Template| {
+            let offset = source.len();
+            source.push_str(&template.source);
+            segments.extend(template.segments.iter().map(|s| s.shifted(offset)));
+        };
+        // as with `concat`, each template but the last is sealed, but only
+        // once rather than again at each join.
+        let mut templates = templates.into_iter().peekable();
+        while let Some(template) = templates.next() {
+            if templates.peek().is_none() {
+                push(template);
+            } else {
+                push(&template.sealed());
+                push(&separator);
+            }
+        }
+        Self::new(source, segments)
+    }
+
+    /// The template followed by a text rendered verbatim, escaped with
+    /// [`escape_literal`], so that building a template from untrusted text
+    /// never reads it as syntax. The attached options are kept.
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// use maplit::hashmap;
+    /// let user_string = "{secret} {if a}";
+    /// let template = Template::default().append_literal(user_string).append_placeholder("name").unwrap();
+    /// assert_eq!("{secret} {if a}x", template.render(&hashmap!{"name".into() => "x".into()}));
+    /// ```
+    pub fn append_literal(&self, text: &str) -> Template {
+        let literal = Self::parse_lenient(&escape_literal(text), &FormatOptions::default());
+        self.concat(&literal).with_options_of(self)
+    }
+
+    /// The template followed by a `{name}` placeholder. The attached options
+    /// are kept. As with [`Template::concat`], the literal text of the
+    /// template is escaped, so that the placeholder cannot close a block or
+    /// a `{raw}` tag kept as literal text.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`ParseErrorKind::InvalidName`](crate::ParseErrorKind::InvalidName),
+    /// spanning the name, if it is not a parameter name, such as `a | html`
+    /// or `a}{b`, so that a name from untrusted text cannot introduce other
+    /// syntax.
+    pub fn append_placeholder(&self, name: &str) -> Result<Template, ParseError> {
+        let placeholder = Self::parse_lenient(&format!("{{{name}}}"), &FormatOptions::default());
+        let valid = name.chars().all(parser::is_name_char) && match &placeholder.segments[..] {
+            [Segment::Placeholder(p)] => placeholder.parameters == [name] && p.filters.is_empty() && p.spec.is_none() && p.default.is_none(),
+            _ => false,
+        };
+        if !valid {
+            return Err(ParseError::new(ParseErrorKind::InvalidName(name.to_owned()), 0..name.len()));
+        }
+        Ok(self.concat(&placeholder).with_options_of(self))
+    }
+
+    /// Describes the parsed segments of the template with their spans,
+    /// one segment per line. This is intended for debugging and snapshot tests.
+    ///
+    /// ```
+    /// use late_format::Template;
+    /// let template = Template::parse(r#"Hi {name | dedent}{"!"}"#).unwrap();
+    /// assert_eq!(
+    ///     "Literal 0..3 \"Hi \"\nPlaceholder 3..18 name=\"name\" filters=[dedent]\nEscaped 18..23 \"!\"\n",
+    ///     template.dump_ast(),
+    /// );
+    /// ```
+    pub fn dump_ast(&self) -> String {
+        let mut r = String::new();
+        self.dump_segments(&self.segments, 0, &mut r);
+        r
+    }
+
+    fn dump_segments(&self, segments: &[Segment], depth: usize, r: &mut String) {
+        for segment in segments {
+            let span = segment.span();
+            r.push_str(&"  ".repeat(depth));
+            match segment {
+                Segment::Literal(_) => writeln!(r, "Literal {span:?} {:?}", &self.source[span.clone()]),
+                Segment::Raw { content, .. } => writeln!(r, "Raw {span:?} {:?}", &self.source[content.clone()]),
+                Segment::Escaped { text, .. } => writeln!(r, "Escaped {span:?} {text:?}"),
+                Segment::Comment(_) => writeln!(r, "Comment {span:?} {:?}", &self.source[span.clone()]),
+                Segment::Placeholder(p) => {
+                    let filters: Vec<_> = p.filters.iter().map(Filter::to_string).collect();
+                    let spec = p.spec.as_ref().map_or(String::new(), |spec| format!(" spec={spec:?}"));
+                    let default = if p.default.is_some() { " default" } else { "" };
+                    writeln!(r, "Placeholder {span:?} name={:?}{spec} filters=[{}]{default}", p.name, filters.join(", "))
+                },
+                Segment::If { condition, .. } => {
+                    let not = if condition.negated { "not " } else { "" };
+                    let filters: Vec<_> = condition.filters.iter().map(Filter::to_string).collect();
+                    let filters = if filters.is_empty() { String::new() } else { format!(" filters=[{}]", filters.join(", ")) };
+                    let comparison = condition.comparison.as_ref().map_or(String::new(), |c| format!(" {} {}", c.operator(), c.operand));
+                    writeln!(r, "If {span:?} {not}name={:?}{filters}{comparison}", condition.reference.name)
+                },
+                Segment::For { key: None, variable, list, .. } => writeln!(r, "For {span:?} {variable:?} in name={:?}", list.name),
+                Segment::For { key: Some(key), variable, list, .. } => writeln!(r, "For {span:?} {key:?}, {variable:?} in name={:?}", list.name),
+                Segment::Plural { count, .. } => writeln!(r, "Plural {span:?} name={:?}", count.name),
+                Segment::Choose { .. } => writeln!(r, "Choose {span:?}"),
+            }.unwrap();
+            if let Segment::Choose { then, variants, .. } = segment {
+                self.dump_segments(then, depth + 1, r);
+                for (weight, segments) in variants {
+                    r.push_str(&"  ".repeat(depth));
+                    writeln!(r, "Variant {weight}").unwrap();
+                    self.dump_segments(segments, depth + 1, r);
+                }
+                continue;
+            }
+            if let Segment::Plural { then, cases, .. } = segment {
+                self.dump_segments(then, depth + 1, r);
+                for (category, segments) in cases {
+                    r.push_str(&"  ".repeat(depth));
+                    writeln!(r, "Case {category}").unwrap();
+                    self.dump_segments(segments, depth + 1, r);
+                }
+                continue;
+            }
+            let children = segment.children();
+            if let [default] = &children[..] {
+                self.dump_segments(default, depth + 1, r);
+            }
+            if let [children, otherwise] = &children[..] {
+                self.dump_segments(children, depth + 1, r);
+                if !otherwise.is_empty() {
+                    r.push_str(&"  ".repeat(depth));
+                    r.push_str("Else\n");
+                    self.dump_segments(otherwise, depth + 1, r);
+                }
+            }
+        }
+    }
+
+    /// Returns the template with the curly braces of its literal text
+    /// escaped, so that appending source text cannot complete the forms it
+    /// kept as literal text, such as an unclosed block or default.
+    fn sealed(&self) -> Template {
+        let mut edits = vec![];
+        walk(&self.segments, &mut |segment| match segment {
+            Segment::Literal(span) if self.source[span.clone()].contains('{') => edits.push(span.clone()),
+            _ => {},
+        });
+        if edits.is_empty() {
+            return self.clone();
+        }
+        edits.sort_by_key(|span| span.start);
+        let mut source = String::with_capacity(self.source.len());
+        let mut last = 0;
+        for span in &edits {
+            source.push_str(&self.source[last..span.start]);
+            source.push_str(&escape_literal(&self.source[span.clone()]));
+            last = span.end;
+        }
+        source.push_str(&self.source[last..]);
+        // a position moves by the escaped forms of the curly braces before it.
+        let position = |i: usize| {
+            let before = edits.iter().take_while(|span| span.end <= i);
+            before.fold(i, |i, span| i + escape_literal(&self.source[span.clone()]).len() - span.len())
+        };
+        let mut segments: Vec<_> = self.segments.iter().map(|s| s.mapped(&position)).collect();
+        split_escaped(&mut segments, &source);
+        Template::new(source, segments).with_options_of(self)
+    }
+}
+
+/// A [`Template`] whose arguments are resolved to parameter indices, as
+/// returned by [`Template::bind`]. Rendering it does not look up argument names.
+#[derive(Clone, Debug)]
+pub struct BoundTemplate<'a> {
+    template: &'a Template,
+    values: Vec<Option<String>>,
+}
+
+impl BoundTemplate<'_> {
+    /// Sets an argument by name, returning `false` if the template does not
+    /// use the parameter.
+    pub fn set(&mut self, name: &str, value: impl Into<String>) -> bool {
+        match self.template.parameter_index(name) {
+            Some(index) => {
+                self.values[index] = Some(value.into());
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Sets an argument by its index in [`Template::parameters`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn set_index(&mut self, index: usize, value: impl Into<String>) {
+        self.values[index] = Some(value.into());
+    }
+
+    /// Removes an argument by its index in [`Template::parameters`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn unset_index(&mut self, index: usize) {
+        self.values[index] = None;
+    }
+
+    /// Substitutes the arguments with the options attached to the template,
+    /// as [`Template::render`] does.
+    pub fn render(&self) -> String {
+        let mut r = String::with_capacity(self.template.source.len());
+        let rendered = Renderer::new(self.template, &self.template.attached_options())
+            .render(|index| self.values[index].as_deref().map(ValueRef::Str), &mut r);
+        if let Err(error) = rendered {
+            r.push_str(&self.template.source[error.span().start..]);
+        }
+        r
+    }
+}
+
+/// Resolves a dotted parameter name, such as `items.0` or `user.name`,
+/// by navigating the argument of its longest prefix.
+fn navigate<V: Resolved>(get: impl Fn(&str) -> Option<V>, name: &str) -> Option<V> {
+    name.rmatch_indices('.').find_map(|(i, _)| get(&name[..i])?.get_path(&name[i + 1..]))
+}
+
+/// A resolved argument, borrowed from the arguments or produced by a resolver.
+trait Resolved: Sized {
+    fn from_value(value: Value) -> Self;
+
+    /// Navigates the argument as described in [`Value::get_path`].
+    fn get_path(self, path: &str) -> Option<Self>;
+
+    /// The length of the text of the argument.
+    fn len(&self) -> usize;
+
+    /// The argument after the [`FormatOptions::coerce`] coercions of its
+    /// parameter.
+    fn coerced(self, name: &str, options: &FormatOptions) -> Self;
+}
+
+impl Resolved for Cow<'_, str> {
+    fn from_value(value: Value) -> Self {
+        Cow::Owned(value.to_string())
+    }
+
+    fn get_path(self, _: &str) -> Option<Self> {
+        None
+    }
+
+    fn len(&self) -> usize {
+        str::len(self)
+    }
+
+    fn coerced(self, name: &str, options: &FormatOptions) -> Self {
+        match options.coercions.get(name) {
+            Some(coerce) => Cow::Owned(coerce(&self)),
+            None => self,
+        }
+    }
+}
+
+impl Resolved for Cow<'_, Value> {
+    fn from_value(value: Value) -> Self {
+        Cow::Owned(value)
+    }
+
+    fn get_path(self, path: &str) -> Option<Self> {
+        match self {
+            Cow::Borrowed(v) => v.get_path(path).map(Cow::Borrowed),
+            Cow::Owned(v) => v.get_path(path).cloned().map(Cow::Owned),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match &**self {
+            Value::String(s) => s.len(),
+            v => v.to_string().len(),
+        }
+    }
+
+    fn coerced(self, name: &str, options: &FormatOptions) -> Self {
+        match options.coercions.get(name) {
+            Some(coerce) => Cow::Owned(self.map_strings(&coerce)),
+            None => self,
+        }
+    }
+}
+
+/// A segment of [`Template::normalized`].
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum NormalizedSegment<'a> {
+    Text(String),
+    Placeholder(&'a str, Option<&'a Spec>, &'a [Filter], Option<Vec<NormalizedSegment<'a>>>),
+    If(&'a str, bool, &'a [Filter], Option<&'a Comparison>, Vec<NormalizedSegment<'a>>, Vec<NormalizedSegment<'a>>),
+    For(Option<&'a str>, &'a str, &'a str, Vec<NormalizedSegment<'a>>, Vec<NormalizedSegment<'a>>),
+    Plural(&'a str, Vec<NormalizedSegment<'a>>, Vec<(PluralCategory, Vec<NormalizedSegment<'a>>)>),
+    Choose(Vec<NormalizedSegment<'a>>, Vec<(u32, Vec<NormalizedSegment<'a>>)>),
+}
+
+impl Template {
+    /// The structure of the template, with adjacent text merged.
+    fn normalized(&self) -> Vec<NormalizedSegment<'_>> {
+        self.normalize(&self.segments)
+    }
+
+    fn normalize<'a>(&'a self, segments: &'a [Segment]) -> Vec<NormalizedSegment<'a>> {
+        let mut r = vec![];
+        for segment in segments {
+            let text = match segment {
+                Segment::Literal(span) => &self.source[span.clone()],
+                Segment::Raw { content, .. } => &self.source[content.clone()],
+                Segment::Escaped { text, .. } => text.as_str(),
+                Segment::Comment(_) => continue,
+                Segment::Placeholder(p) => {
+                    let default = p.default.as_ref().map(|default| self.normalize(default));
+                    r.push(NormalizedSegment::Placeholder(&p.name, p.spec.as_ref(), &p.filters, default));
+                    continue;
+                },
+                Segment::If { condition, then, otherwise, .. } => {
+                    let (then, otherwise) = (self.normalize(then), self.normalize(otherwise));
+                    let (filters, comparison) = (&condition.filters, condition.comparison.as_ref());
+                    r.push(NormalizedSegment::If(&condition.reference.name, condition.negated, filters, comparison, then, otherwise));
+                    continue;
+                },
+                Segment::For { key, variable, list, body, otherwise, .. } => {
+                    let (body, otherwise) = (self.normalize(body), self.normalize(otherwise));
+                    r.push(NormalizedSegment::For(key.as_deref(), variable, &list.name, body, otherwise));
+                    continue;
+                },
+                Segment::Plural { count, then, cases, .. } => {
+                    let cases = cases.iter().map(|(c, segments)| (*c, self.normalize(segments))).collect();
+                    r.push(NormalizedSegment::Plural(&count.name, self.normalize(then), cases));
+                    continue;
+                },
+                Segment::Choose { then, variants, .. } => {
+                    let variants = variants.iter().map(|(w, segments)| (*w, self.normalize(segments))).collect();
+                    r.push(NormalizedSegment::Choose(self.normalize(then), variants));
+                    continue;
+                },
+            };
+            match r.last_mut() {
+                Some(NormalizedSegment::Text(last)) => last.push_str(text),
+                _ => r.push(NormalizedSegment::Text(text.to_owned())),
+            }
+        }
+        r.retain(|s| !matches!(s, NormalizedSegment::Text(t) if t.is_empty()));
+        r
+    }
+}
+
+impl PartialEq for Template {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized() == other.normalized()
+    }
+}
+
+impl Eq for Template {}
+
+impl Hash for Template {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized().hash(state);
+    }
+}
+
+impl PartialOrd for Template {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Template {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.normalized().cmp(&other.normalized())
+    }
+}
+
+/// Writes the source of the template, as returned by [`Template::source`].
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl FromStr for Template {
+    type Err = ParseError;
+
+    fn from_str(source: &str) -> Result<Self, ParseError> {
+        Self::parse(source)
+    }
+}
+
+impl TryFrom<&str> for Template {
+    type Error = ParseError;
+
+    fn try_from(source: &str) -> Result<Self, ParseError> {
+        Self::parse(source)
+    }
+}
+
+impl TryFrom<String> for Template {
+    type Error = ParseError;
+
+    fn try_from(source: String) -> Result<Self, ParseError> {
+        Self::parse(&source)
+    }
+}
+
+/// An empty template.
+impl Default for Template {
+    fn default() -> Self {
+        Self::new(String::new(), vec![])
+    }
+}
+
+impl Add for Template {
+    type Output = Template;
+
+    fn add(self, other: Template) -> Template {
+        self.concat(&other)
+    }
+}
+
+impl Add<&Template> for &Template {
+    type Output = Template;
+
+    fn add(self, other: &Template) -> Template {
+        self.concat(other)
+    }
+}
+
+/// Parses a constant template source once, on first use, and yields the
+/// `&'static Template`, so that rendering a constant message does not parse
+/// it again. Each use of the macro has its own template.
+///
+/// # Panics
+///
+/// Panics on first use if the source is not a valid template, as
+/// [`Template::parse`] reports.
+///
+/// ```
+/// use late_format::static_template;
+/// use maplit::hashmap;
+/// fn greeting() -> &'static late_format::Template {
+///     static_template!("Hello, {name}!")
+/// }
+/// assert!(std::ptr::eq(greeting(), greeting()));
+/// assert_eq!("Hello, x!", greeting().render(&hashmap!{"name".into() => "x".into()}));
+/// ```
+#[macro_export]
+macro_rules! static_template {
+    ($source:expr $(,)?) => {{
+        static TEMPLATE: ::std::sync::OnceLock<$crate::Template> = ::std::sync::OnceLock::new();
+        let template: &'static $crate::Template = TEMPLATE.get_or_init(|| match $crate::Template::parse($source) {
+            Ok(template) => template,
+            Err(error) => panic!("invalid static template: {error}"),
+        });
+        template
+    }};
+}
+
+/// Escapes a text so that it renders verbatim when embedded into a template,
+/// by converting each `{` into the `{"{"}` escaped form. This prevents
+/// programmatically built templates from accidentally introducing placeholders.
+///
+/// ```
+/// use late_format::{escape_literal, LateFormat};
+/// use maplit::hashmap;
+/// let user_string = format!("{} {{name}}", escape_literal("{name} is"));
+/// assert_eq!("{name} is x", user_string.late_format(hashmap!{"name".into() => "x".into()}));
+/// ```
+pub fn escape_literal(text: &str) -> String {
+    text.replace('{', r#"{"{"}"#)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use maplit::hashmap;
+
+    #[test]
+    fn composition() {
+        let a = Template::parse("{a} {").unwrap();
+        let b = Template::parse("b} {b | dedent}").unwrap();
+        let arguments = hashmap!{"a".into() => "x".into(), "b".into() => "y".into()};
+        let ab = &a + &b;
+        assert_eq!("x {b} y", ab.render(&arguments));
+        let spans: Vec<_> = ab.placeholders().map(|p| (p.name().to_owned(), p.span())).collect();
+        assert_eq!(vec![("a".to_owned(), 0..3), ("b".to_owned(), 12..24)], spans);
+        assert_eq!("{b | dedent}", &ab.source[12..24]);
+        assert_eq!("x {, y", Template::join([&a, &Template::parse("{b}").unwrap()], ", ").render(&arguments));
+        assert_eq!("x {{x {", Template::join([&a, &a], "{").render(&arguments));
+        assert_eq!("", Template::join([], ", ").render(&arguments));
+        // the source of a concatenation parses back to it.
+        let sources = [
+            "{raw}x", "{endraw}", "{a} {", "b} {b | dedent}", "{if a}{b}", "{end}", "{else}", "{a:-x", "}", "{# c", "{\"",
+            "{if a}{b:-x{end}", "{plural n}{one}x", "x{raw}{ endraw }y", "{for x in y}{x}", "{a:-{b", "{one}",
+        ];
+        let templates: Vec<_> = sources.iter().map(|s| Template::parse_lenient(s, &FormatOptions::default())).collect();
+        for a in &templates {
+            for b in &templates {
+                let ab = a.concat(b);
+                assert_eq!(ab, Template::parse_lenient(ab.source(), &FormatOptions::default()), "{:?} + {:?}", a.source(), b.source());
+                let joined = Template::join([a, b, a], "{end}");
+                assert_eq!(joined, Template::parse_lenient(joined.source(), &FormatOptions::default()), "{:?}", joined.source());
+                assert_eq!(joined, a.concat(&Template::parse("{'{end}'}").unwrap()).concat(b).concat(&Template::parse("{'{end}'}").unwrap()).concat(a));
+            }
+        }
+        // joining many templates builds the joined source once.
+        let started = std::time::Instant::now();
+        let joined = Template::join(&vec![Template::parse_lenient("{if a}{b}", &FormatOptions::default()); 20_000], ",");
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+        assert_eq!(20_000, joined.placeholders().count());
+        assert_eq!("R{raw}x", Template::parse("{r}").unwrap().append_literal("{raw}x").render(&hashmap!{"r".into() => "R".into()}));
+    }
+
+    #[test]
+    fn interning() {
+        let template = Template::parse("{b}{a}{b | dedent}").unwrap() + Template::parse("{c}{a}").unwrap();
+        assert_eq!(["b", "a", "c"], template.parameters());
+        let indices: Vec<_> = template.placeholders().map(|p| p.index()).collect();
+        assert_eq!(vec![0, 1, 0, 2, 1], indices);
+        let mut bound = template.bind(&hashmap!{"a".into() => "x".into()});
+        assert_eq!("NonexNoneNonex", bound.render());
+        assert!(bound.set("b", " y"));
+        assert!(!bound.set("d", "z"));
+        bound.unset_index(1);
+        assert_eq!(" yNoneyNoneNone", bound.render());
+    }
+
+    #[test]
+    fn validation() {
+        use crate::{Limits, ParseErrorKind};
+        let options = FormatOptions::new().limits(Limits::new().max_placeholders(1));
+        let report = Template::validate("{a | x}{a | y}{b}{c}{d}", &hashmap!{}, &options).unwrap_err();
+        let errors: Vec<_> = report.errors().iter().map(|e| (e.to_string(), e.span())).collect();
+        assert_eq!(vec![
+            ("unknown filter `x` at 0..7".to_owned(), 0..7),
+            ("unknown filter `y` at 7..14".to_owned(), 7..14),
+            ("template has more than 1 placeholders at 17..20".to_owned(), 17..20),
+            ("missing argument `b` at 14..17".to_owned(), 14..17),
+        ], errors);
+        assert!(matches!(&report.errors()[0], Error::Parse(e) if e.kind() == &ParseErrorKind::UnknownFilter("x".into())));
+        let template = Template::validate("{a}{'{'}", &hashmap!{"a".into() => "x".into()}, &options).unwrap();
+        assert_eq!("x{", template.render(&hashmap!{"a".into() => "x".into()}));
+    }
+
+    #[test]
+    fn escaping() {
+        for text in ["", "plain", "{a}", r#"{"{"}"#, "{raw}{a}{endraw}", "{{a}}}", "{ 'x' }"] {
+            assert_eq!(text, Template::parse(&escape_literal(text)).unwrap().render(&hashmap!{"a".into() => "x".into()}));
+        }
+    }
+
+    #[test]
+    fn display() {
+        let source = r#"{ a }{'{'}{raw}{}{endraw} {b|dedent}"#;
+        let template = Template::parse(source).unwrap();
+        assert_eq!(source, template.to_string());
+        assert_eq!(concat!(
+            "Placeholder 0..5 name=\"a\" filters=[]\n",
+            "Escaped 5..10 \"{\"\n",
+            "Raw 10..25 \"{}\"\n",
+            "Literal 25..26 \" \"\n",
+            "Placeholder 26..36 name=\"b\" filters=[dedent]\n",
+        ), template.dump_ast());
+        assert_eq!(concat!(
+            "If 0..21 name=\"a\"\n",
+            "  Placeholder 6..9 name=\"b\" filters=[]\n",
+            "Else\n",
+            "  Literal 15..16 \"c\"\n",
+        ), Template::parse("{if a}{b}{else}c{end}").unwrap().dump_ast());
+    }
+
+    #[test]
+    fn round_trip() {
+        for source in ["", "{", "}{", "{ a\t}\r\n", r#"{ "\u{41}\n" }"#, "{a|dedent}{ b | dedent |dedent }", "{raw}\n{ endraw }", "{a b}{'x", "{if a}{b}{else}{end}"] {
+            let template = Template::parse(source).unwrap();
+            assert_eq!(source, template.source());
+            assert_eq!(source, template.to_string());
+            let spans: Vec<_> = template.segments.iter().map(|s| s.span()).collect();
+            assert_eq!(source.len(), spans.iter().map(|s| s.len()).sum::<usize>());
+        }
+    }
+
+    #[test]
+    fn conversions() {
+        let template: Template = "{a | dedent}".parse().unwrap();
+        assert_eq!("{a | dedent}", template.to_string());
+        assert!(Template::try_from("{a | nope}").is_err());
+        assert!(Template::try_from(String::from("{a}")).is_ok());
+    }
+
+    #[test]
+    fn equality() {
+        use std::collections::HashSet;
+        let a = Template::parse(r#"{ a }{"{"}b"#).unwrap();
+        let b = Template::parse("{a}{'{'}{raw}b{endraw}").unwrap();
+        let c = Template::parse("{a | dedent}{'{'}b").unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a, Template::parse("{a}").unwrap() + Template::parse("{'{b'}").unwrap());
+        assert_eq!(2, HashSet::from([a, b, c]).len());
+        assert_eq!(Template::parse("{ if a }x{raw}y{endraw}{end}").unwrap(), Template::parse("{if a}xy{end}").unwrap());
+        assert_ne!(Template::parse("{if a}x{end}").unwrap(), Template::parse("{if not a}x{end}").unwrap());
+    }
+
+    #[test]
+    fn comments() {
+        let source = "{# title }{#version: 3}{if a}{ #version: 4 }{#id:x:y}{end}a{#}b {#nope";
+        let template = Template::parse(source).unwrap();
+        assert_eq!("ab {#nope", template.render(&hashmap!{"a".into() => "".into()}));
+        assert_eq!((Some("3"), Some("x:y"), None), (template.metadata("version"), template.metadata("id"), template.metadata("title")));
+        assert_eq!(template, Template::parse("{if a}{end}ab {\"{\"}#nope").unwrap());
+        assert_ne!(template.fingerprint(), Template::parse(&source.replace('3', "4")).unwrap().fingerprint());
+        assert_eq!(template.minified().source(), "{# title }{#version: 3}{if a}{ #version: 4 }{#id:x:y}{end}a{#}b {\"{\"}#nope");
+    }
+
+    #[test]
+    fn unknown_filter() {
+        use crate::ParseErrorKind;
+        let error = Template::parse("{a} {b | nope}").unwrap_err();
+        assert_eq!(&ParseErrorKind::UnknownFilter("nope".into()), error.kind());
+        assert_eq!(4..14, error.span());
+    }
+
+    #[test]
+    fn lookup() {
+        use crate::Lookup;
+        let template = Template::parse("{User-Name} {user_name} {ID}").unwrap();
+        let arguments = hashmap!{"user_name".into() => "x".into(), "username".into() => "y".into(), "id".into() => "1".into()};
+        let options = FormatOptions::new().lookup(Lookup::normalized());
+        assert_eq!("x x 1", template.render_with(&arguments, &options).unwrap());
+        let options = FormatOptions::new().lookup(Lookup::new().case_insensitive(true));
+        assert_eq!("None x 1", template.render_with(&arguments, &options).unwrap());
+        assert!(Template::validate("{ID}", &arguments, &options).is_ok());
+        assert!(Template::validate("{ID}", &arguments, &FormatOptions::new()).is_err());
+        let values = hashmap!{"Id".to_owned() => crate::Value::from("2")};
+        assert_eq!("None None 2", template.render_arguments(&crate::Layers::new().layer("a", values), &options).unwrap());
+        let options = options.alias("User-Name", "ID").alias("id", "username");
+        assert_eq!("1 x 1", template.render_with(&arguments, &options).unwrap());
+    }
+
+    #[test]
+    fn literal() {
+        let template = Template::parse("Empty trash?").unwrap();
+        let options = FormatOptions::new().missing(crate::Missing::Error);
+        assert_eq!("Empty trash?", template.render_with(&hashmap!{}, &options).unwrap());
+        assert_eq!("Empty trash?", template.render_arguments(&hashmap!{"a".to_owned() => crate::Value::from("1")}, &options).unwrap());
+        for source in ["{raw}x{endraw}", "a{#}b", "{a}", "{if a}x{end}"] {
+            assert!(!Template::parse(source).unwrap().is_literal(), "{source}");
+        }
+        assert_eq!(Some(""), Template::parse("").unwrap().as_literal());
+        // the output of a literal template is its source, not a copy.
+        assert_eq!(template.source().as_ptr(), template.render_cow(&hashmap!{}).as_ptr());
+        assert!(matches!(Template::parse("{a}").unwrap().render_cow(&hashmap!{"a".into() => "x".into()}), Cow::Owned(s) if s == "x"));
+        assert!((Template::parse("a").unwrap() + Template::parse("b").unwrap()).is_literal());
+        assert!(Template::parse_with("\n    x\n", &FormatOptions::new().dedent(true)).unwrap().as_literal() == Some("x\n"));
+    }
+
+    #[test]
+    fn appending() {
+        let template = Template::builder().missing(crate::Missing::Marker).parse("{a}: ").unwrap()
+            .append_literal("{b} {\"{\"} {end}")
+            .append_placeholder("user.name-2").unwrap()
+            .append_literal("}");
+        assert_eq!("x: {b} {\"{\"} {end}<missing:user.name-2>}", template.render(&hashmap!{"a".into() => "x".into()}));
+        assert_eq!(["a", "user.name-2"], template.parameters());
+        assert!(template.options().is_some());
+        let open = Template::parse_lenient("{raw}x{if a}", &FormatOptions::default());
+        let appended = open.append_placeholder("endraw").unwrap().append_placeholder("end").unwrap();
+        assert_eq!("{raw}x{if a}EF", appended.render(&hashmap!{"endraw".into() => "E".into(), "end".into() => "F".into()}));
+        assert_eq!(appended, Template::parse(appended.source()).unwrap());
+        for name in ["", "a}{b", "a | html", "a:>3", "a:-b", "#x", "a b", "items[0]", "raw"] {
+            let error = Template::default().append_placeholder(name).unwrap_err();
+            assert_eq!((&ParseErrorKind::InvalidName(name.into()), 0..name.len()), (error.kind(), error.span()), "{name}");
+        }
+    }
+}
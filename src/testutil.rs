@@ -0,0 +1,225 @@
+//! Generators of random templates and arguments, and the invariants that
+//! the engine keeps for them, so that crates extending it, such as with
+//! [`Resolver`](crate::Resolver)s, can property-test their extensions.
+//!
+//! ```
+//! use late_format::testutil::{check_generated, check_template, Generator};
+//! use late_format::{FormatOptions, Template};
+//! check_generated(7, 100, &FormatOptions::new()).unwrap();
+//! let mut generator = Generator::new(7);
+//! let template = Template::parse(&generator.template()).unwrap();
+//! let arguments = generator.arguments(&template);
+//! check_template(&template, &arguments, &FormatOptions::new()).unwrap();
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+use crate::{escape_literal, FormatOptions, Missing, RenderCache, Template};
+
+/// The parameter names of generated templates. Those starting with `n` are
+/// given number arguments.
+const NAMES: [&str; 5] = ["a", "b", "c", "n", "n2"];
+
+/// Generates random template sources, texts and arguments from a seed, the
+/// same for the same seed.
+#[derive(Clone, Debug)]
+pub struct Generator {
+    state: u64,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// A number in `0..n`, from the SplitMix64 sequence.
+    fn below(&mut self, n: usize) -> usize {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        ((z ^ (z >> 31)) % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+
+    /// A text of up to 12 characters, including curly braces, quotes,
+    /// whitespace and characters outside of ASCII.
+    pub fn text(&mut self) -> String {
+        const CHARS: [char; 16] = ['a', 'Z', '0', ' ', '\n', '{', '}', '"', '\'', '|', ':', '#', '\\', 'é', 'ß', '名'];
+        (0..self.below(13)).map(|_| CHARS[self.below(CHARS.len())]).collect()
+    }
+
+    /// The source of a valid template, with placeholders, filters, specs,
+    /// escaped forms, comments, raw blocks and conditional and plural blocks.
+    pub fn template(&mut self) -> String {
+        let mut r = String::new();
+        self.segments(&mut r, 2);
+        r
+    }
+
+    fn segments(&mut self, r: &mut String, depth: usize) {
+        for _ in 0..self.below(6) {
+            let name = self.pick(&NAMES);
+            let piece = match self.below(if depth == 0 { 7 } else { 9 }) {
+                0 | 1 => escape_literal(&self.text()),
+                2 => format!("{{{name}}}"),
+                3 => format!("{{{} | {}}}", self.pick(&["a", "b", "c"]), self.pick(&["dedent", "trim", "html", "xml_attr", "title_case", "trim | sentence_case", "len", "csv"])),
+                4 => format!("{{{}:{}}}", self.pick(&["a", "b"]), self.pick(&[">6", "<4", "^7", "*^5"])),
+                5 => format!("{{{}:{}}}", self.pick(&["n", "n2"]), self.pick(&["03", ".2", "+", ">8.1"])),
+                6 => self.pick(&[r#"{"{"}"#, "{'}'}", "{# note}", "{raw}{a} {{b}}{endraw}"]).to_owned(),
+                7 => {
+                    let mut block = format!("{{if {}{name}}}", self.pick(&["", "not "]));
+                    self.segments(&mut block, depth - 1);
+                    if self.below(2) == 0 {
+                        block.push_str("{else}");
+                        self.segments(&mut block, depth - 1);
+                    }
+                    block + "{end}"
+                },
+                _ => {
+                    let mut block = format!("{{plural {}}}{{one}}", self.pick(&["n", "n2"]));
+                    self.segments(&mut block, depth - 1);
+                    block.push_str("{other}");
+                    self.segments(&mut block, depth - 1);
+                    block + "{end}"
+                },
+            };
+            r.push_str(&piece);
+        }
+    }
+
+    /// Arguments for every parameter of a template: numbers for the
+    /// parameters starting with `n`, and texts, which may be empty, for the
+    /// others.
+    pub fn arguments(&mut self, template: &Template) -> HashMap<String, String> {
+        template.parameters().iter().map(|name| {
+            let value = match name.starts_with('n') {
+                true => self.pick(&["0", "1", "2", "21", "3.5", "-4", "1000000"]).to_owned(),
+                false => self.text(),
+            };
+            (name.clone(), value)
+        }).collect()
+    }
+}
+
+/// Checks that a text embedded with [`escape_literal`] or
+/// [`Template::append_literal`] renders verbatim.
+pub fn check_escape_round_trip(text: &str) -> Result<(), Violation> {
+    let violation = |detail: String| Violation { invariant: "escape round-trip", source: text.to_owned(), detail };
+    let options = FormatOptions::new().missing(Missing::Error);
+    let escaped = Template::parse(&escape_literal(text)).map_err(|e| violation(format!("parse error: {e}")))?;
+    for template in [escaped, Template::default().append_literal(text)] {
+        match template.render_with(&HashMap::new(), &options) {
+            Ok(output) if output == text => {},
+            Ok(output) => return Err(violation(format!("renders `{output}`"))),
+            Err(e) => return Err(violation(format!("render error: {e}"))),
+        }
+    }
+    Ok(())
+}
+
+/// Checks that parsing the source of a template, or of its
+/// [minified](Template::minified) form, gives the same template again, and
+/// that the ways of rendering it agree with [`Template::render_with`] for the
+/// arguments and options: [`RenderCache`],
+/// [`Template::render_partial`] followed by a second pass, and
+/// [`Template::render_mapped`], whose spans must cover the output in order
+/// and name parameters of the template.
+pub fn check_template(template: &Template, arguments: &HashMap<String, String>, options: &FormatOptions) -> Result<(), Violation> {
+    let violation = |invariant: &'static str, detail: String| Violation { invariant, source: template.source().to_owned(), detail };
+    let output = template.render_with(arguments, options).map_err(|e| violation("rendering", e.to_string()))?;
+    let source_options = FormatOptions::new().limits(options.limits);
+    for (invariant, template) in [("source round-trip", template.clone()), ("minification", template.minified())] {
+        let reparsed = Template::parse_with(template.source(), &source_options).map_err(|e| violation(invariant, e.to_string()))?;
+        if reparsed != template || reparsed.fingerprint() != template.fingerprint() {
+            return Err(violation(invariant, "parsing the source again gives another template".into()));
+        }
+    }
+    let agrees = |invariant: &'static str, other: Result<String, String>| match other {
+        Ok(other) if other == output => Ok(()),
+        Ok(other) => Err(violation(invariant, format!("renders `{other}` instead of `{output}`"))),
+        Err(e) => Err(violation(invariant, e)),
+    };
+    let mut cache = RenderCache::with_options(template.clone(), options.clone());
+    agrees("render cache", cache.render(arguments).map(str::to_owned).map_err(|e| e.to_string()))?;
+    let partial = template.render_partial(arguments, options).map_err(|e| violation("partial rendering", e.to_string()))?;
+    if partial.is_complete() {
+        let next = Template::parse(partial.output()).map_err(|e| violation("partial rendering", e.to_string()))?;
+        agrees("partial rendering", next.render_with(&HashMap::new(), options).map_err(|e| e.to_string()))?;
+    }
+    let mapped = template.render_mapped(arguments, options).map_err(|e| violation("source map", e.to_string()))?;
+    agrees("source map", Ok(mapped.output().to_owned()))?;
+    let mut end = 0;
+    for span in mapped.spans() {
+        if span.output().start < end || span.output().end > output.len() {
+            return Err(violation("source map", format!("span {:?} is out of order", span.output())));
+        }
+        if span.parameter().is_some_and(|p| !template.parameters().iter().any(|name| name == p)) {
+            return Err(violation("source map", format!("span {:?} names an unknown parameter", span.output())));
+        }
+        end = span.output().end;
+    }
+    Ok(())
+}
+
+/// Checks the invariants for a number of texts, templates and arguments
+/// generated from a seed.
+pub fn check_generated(seed: u64, cases: usize, options: &FormatOptions) -> Result<(), Violation> {
+    let mut generator = Generator::new(seed);
+    for _ in 0..cases {
+        check_escape_round_trip(&generator.text())?;
+        let source = generator.template();
+        let template = Template::parse(&source).map_err(|e| Violation { invariant: "parsing", source, detail: e.to_string() })?;
+        let arguments = generator.arguments(&template);
+        check_template(&template, &arguments, options)?;
+    }
+    Ok(())
+}
+
+/// An invariant that does not hold for a template, as found by the checks
+/// of this module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    invariant: &'static str,
+    source: String,
+    detail: String,
+}
+
+impl Violation {
+    /// The name of the invariant, such as `escape round-trip`.
+    pub fn invariant(&self) -> &str {
+        self.invariant
+    }
+
+    /// The source of the template, or the text, that violates it.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} does not hold for {:?}: {}", self.invariant, self.source, self.detail)
+    }
+}
+
+impl std::error::Error for Violation {}
+
+#[cfg(test)]
+mod test {
+    use crate::{FormatOptions, Template};
+    use super::{check_generated, check_template, Generator};
+
+    #[test]
+    fn generated() {
+        check_generated(0, 500, &FormatOptions::new()).unwrap();
+        check_generated(1, 200, &FormatOptions::new().locale("fr")).unwrap();
+        let mut generator = Generator::new(3);
+        assert_eq!(generator.clone().template(), generator.template());
+        let template = Template::parse("{a}").unwrap();
+        let violation = check_template(&template, &generator.arguments(&template), &FormatOptions::new().budget(crate::Budget::new().max_steps(0))).unwrap_err();
+        assert_eq!(("rendering", "{a}"), (violation.invariant(), violation.source()));
+    }
+}
@@ -0,0 +1,629 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use crate::date::Date;
+
+/// An argument value, for arguments that are not plain strings, such as
+/// the lists iterated by `{for item in items}` blocks.
+///
+/// ```
+/// use late_format::{FormatOptions, Template, Value};
+/// use maplit::hashmap;
+/// let template = Template::parse("{for name in names}<{name}>{end}").unwrap();
+/// let arguments = hashmap!{"names".to_owned() => Value::from(vec!["a", "b"])};
+/// assert_eq!("<a><b>", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Value {
+    String(String),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+impl Value {
+    /// Whether the value is an empty string, list or map, which does not
+    /// satisfy an `{if}` block.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::String(s) => s.is_empty(),
+            Self::List(items) => items.is_empty(),
+            Self::Map(entries) => entries.is_empty(),
+        }
+    }
+
+    /// Navigates nested maps and lists by a dot-separated path, as in
+    /// `database.host` or `servers.0.host`, where a list is navigated by
+    /// the position of an item, from 0.
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        path.split('.').try_fold(self, |value, key| match value {
+            Self::Map(entries) => entries.get(key),
+            Self::List(items) => items.get(key.parse::<usize>().ok()?),
+            _ => None,
+        })
+    }
+
+    /// The value with each of its strings transformed.
+    pub(crate) fn map_strings(&self, f: &impl Fn(&str) -> String) -> Value {
+        match self {
+            Self::String(s) => Self::String(f(s)),
+            Self::List(items) => Self::List(items.iter().map(|item| item.map_strings(f)).collect()),
+            Self::Map(entries) => Self::Map(entries.iter().map(|(key, value)| (key.clone(), value.map_strings(f))).collect()),
+        }
+    }
+}
+
+/// Writes a string as is, the items of a list separated by `, `, and the
+/// entries of a map as `key: value` separated by `, `.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(s) => f.write_str(s),
+            Self::List(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                Ok(())
+            },
+            Self::Map(entries) => {
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{key}: {value}")?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(value: Vec<T>) -> Self {
+        Self::List(value.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<K: Into<String>, V: Into<Value>> FromIterator<(K, V)> for Value {
+    /// Collects entries into a map.
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(entries: I) -> Self {
+        Self::Map(entries.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+}
+
+/// A conversion of a Rust value into a [`Value`] argument. Numbers, `bool`
+/// and `char` are written as with [`Display`](fmt::Display), `None` is an
+/// empty string, and vectors are lists. As `false` is written, it satisfies
+/// an `{if}` block, unlike `None`.
+///
+/// A [`Duration`] is written as a number of seconds, as in `90.5`, for the
+/// arithmetic and `duration` filters, and a [`SystemTime`] as an ISO 8601
+/// date and time in UTC to the second, as in `2024-03-05T09:30:00Z`, for
+/// the `date` filter. A time beyond the range of dates is written as its
+/// seconds since 1970 instead, as a duration is.
+///
+/// The [`arguments!`](crate::arguments) macro builds argument maps with it:
+///
+/// ```
+/// use late_format::{arguments, FormatOptions, Template};
+/// let template = Template::parse("{name} has {count} items{if admin}, as an admin{end} ({tags}) {nickname:-}").unwrap();
+/// let arguments = arguments!{"name" => "Ana", "count" => 3, "admin" => true, "tags" => vec!['a', 'b'], "nickname" => None::<String>};
+/// assert_eq!("Ana has 3 items, as an admin (a, b) ", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+/// ```
+pub trait IntoLateValue {
+    fn into_late_value(self) -> Value;
+}
+
+impl IntoLateValue for Value {
+    fn into_late_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoLateValue for &str {
+    fn into_late_value(self) -> Value {
+        Value::String(self.to_owned())
+    }
+}
+
+impl IntoLateValue for String {
+    fn into_late_value(self) -> Value {
+        Value::String(self)
+    }
+}
+
+macro_rules! into_late_value_by_display {
+    ($($t:ty),*) => {
+        $(impl IntoLateValue for $t {
+            fn into_late_value(self) -> Value {
+                Value::String(self.to_string())
+            }
+        })*
+    };
+}
+
+into_late_value_by_display!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64, bool, char);
+
+impl IntoLateValue for Duration {
+    fn into_late_value(self) -> Value {
+        Value::String(seconds(self))
+    }
+}
+
+/// The number of seconds of a duration, with as many fraction digits as needed.
+fn seconds(duration: Duration) -> String {
+    let fraction = format!("{:09}", duration.subsec_nanos());
+    let fraction = fraction.trim_end_matches('0');
+    let dot = if fraction.is_empty() { "" } else { "." };
+    format!("{}{dot}{fraction}", duration.as_secs())
+}
+
+impl IntoLateValue for SystemTime {
+    fn into_late_value(self) -> Value {
+        // the seconds are rounded down, also before 1970.
+        let since = self.duration_since(SystemTime::UNIX_EPOCH);
+        let timestamp = match &since {
+            Ok(elapsed) => i64::try_from(elapsed.as_secs()).ok(),
+            Err(error) => {
+                let before = error.duration();
+                i64::try_from(before.as_secs()).ok().map(|s| -s - i64::from(before.subsec_nanos() > 0))
+            },
+        };
+        match (timestamp.and_then(Date::from_timestamp), since) {
+            (Some(date), _) => Value::String(format!("{date}Z")),
+            (None, Ok(elapsed)) => Value::String(seconds(elapsed)),
+            (None, Err(error)) => Value::String(format!("-{}", seconds(error.duration()))),
+        }
+    }
+}
+
+impl<T: IntoLateValue> IntoLateValue for Option<T> {
+    fn into_late_value(self) -> Value {
+        self.map_or_else(|| Value::String(String::new()), IntoLateValue::into_late_value)
+    }
+}
+
+impl<T: IntoLateValue> IntoLateValue for Vec<T> {
+    fn into_late_value(self) -> Value {
+        Value::List(self.into_iter().map(IntoLateValue::into_late_value).collect())
+    }
+}
+
+/// Builds a `HashMap<String, Value>` of arguments, converting each value
+/// with [`IntoLateValue`].
+#[macro_export]
+macro_rules! arguments {
+    ($($name:expr => $value:expr),* $(,)?) => {{
+        let mut arguments = ::std::collections::HashMap::<::std::string::String, $crate::Value>::new();
+        $(arguments.insert(::std::convert::Into::into($name), $crate::IntoLateValue::into_late_value($value));)*
+        arguments
+    }};
+}
+
+/// A builder of argument maps, converting each value with [`IntoLateValue`],
+/// as an alternative to map literals and the [`arguments!`](crate::arguments)
+/// macro.
+///
+/// ```
+/// use late_format::{Args, FormatOptions, Template};
+/// use maplit::hashmap;
+/// let defaults = Args::new().set("nick", "anonymous").set("app", "demo");
+/// let arguments = Args::new()
+///     .set("id", 42)
+///     .set_opt("nick", None::<&str>)
+///     .extend(hashmap!{"admin" => true})
+///     .defaults(defaults);
+/// let template = Template::parse("{id} {nick} {app}{if admin} (admin){end}").unwrap();
+/// assert_eq!("42 anonymous demo (admin)", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Args {
+    values: HashMap<String, Value>,
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets an argument, replacing any previous one.
+    pub fn set(mut self, name: impl Into<String>, value: impl IntoLateValue) -> Self {
+        self.values.insert(name.into(), value.into_late_value());
+        self
+    }
+
+    /// Sets an argument if the value is `Some`, and otherwise leaves the
+    /// argument as it is, unlike [`Args::set`] which sets `None` as an
+    /// empty string.
+    pub fn set_opt<T: IntoLateValue>(self, name: impl Into<String>, value: Option<T>) -> Self {
+        match value {
+            Some(value) => self.set(name, value),
+            None => self,
+        }
+    }
+
+    /// Sets the arguments of a map or of other `Args`, replacing the
+    /// previous ones.
+    pub fn extend<K: Into<String>, V: IntoLateValue>(mut self, arguments: impl IntoIterator<Item = (K, V)>) -> Self {
+        self.values.extend(arguments.into_iter().map(|(name, value)| (name.into(), value.into_late_value())));
+        self
+    }
+
+    /// Sets the arguments of a map or of other `Args` that are not set yet,
+    /// as a layer of lower precedence.
+    pub fn defaults<K: Into<String>, V: IntoLateValue>(mut self, arguments: impl IntoIterator<Item = (K, V)>) -> Self {
+        for (name, value) in arguments {
+            self.values.entry(name.into()).or_insert_with(|| value.into_late_value());
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    pub fn into_map(self) -> HashMap<String, Value> {
+        self.values
+    }
+}
+
+impl IntoIterator for Args {
+    type Item = (String, Value);
+    type IntoIter = std::collections::hash_map::IntoIter<String, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.into_iter()
+    }
+}
+
+impl From<Args> for HashMap<String, Value> {
+    fn from(args: Args) -> Self {
+        args.values
+    }
+}
+
+impl Arguments for Args {
+    fn get(&self, name: &str) -> Option<Cow<'_, Value>> {
+        self.values.get(name).map(Cow::Borrowed)
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.values.keys().cloned().collect()
+    }
+}
+
+/// A source of arguments, resolving parameter names to values.
+///
+/// It is implemented for maps from names to strings, to [`Value`]s and to
+/// boxed [`Display`](fmt::Display) trait objects, and may be implemented to resolve arguments from elsewhere.
+pub trait Arguments {
+    /// Resolves the argument of a parameter, returning `None` if it is missing.
+    fn get(&self, name: &str) -> Option<Cow<'_, Value>>;
+
+    /// The names of the arguments, which allows a normalizing
+    /// [`Lookup`](crate::Lookup) to match them. By default, no name is
+    /// listed and only exact matches are found.
+    fn names(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// The name of the source supplying an argument, as reported by
+    /// [`Resolution::layer`](crate::Resolution::layer). `None` by default.
+    fn layer(&self, name: &str) -> Option<&str> {
+        let _ = name;
+        None
+    }
+}
+
+impl Arguments for HashMap<String, String> {
+    fn get(&self, name: &str) -> Option<Cow<'_, Value>> {
+        HashMap::get(self, name).map(|v| Cow::Owned(Value::String(v.clone())))
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.keys().cloned().collect()
+    }
+}
+
+impl Arguments for HashMap<String, Value> {
+    fn get(&self, name: &str) -> Option<Cow<'_, Value>> {
+        HashMap::get(self, name).map(Cow::Borrowed)
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.keys().cloned().collect()
+    }
+}
+
+/// Maps of [`Display`](fmt::Display) trait objects, written when a template
+/// uses them, so that values of several types may be collected once and
+/// rendered by many templates.
+///
+/// ```
+/// use late_format::{FormatOptions, Template};
+/// use std::collections::HashMap;
+/// use std::fmt::Display;
+/// let mut arguments: HashMap<String, Box<dyn Display>> = HashMap::new();
+/// arguments.insert("name".into(), Box::new("x"));
+/// arguments.insert("count".into(), Box::new(3));
+/// let template = Template::parse("{name}: {count}").unwrap();
+/// assert_eq!("x: 3", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+/// ```
+macro_rules! display_arguments {
+    ($($t:ty),*) => {
+        $(impl Arguments for HashMap<String, $t> {
+            fn get(&self, name: &str) -> Option<Cow<'_, Value>> {
+                HashMap::get(self, name).map(|v| Cow::Owned(Value::String(v.to_string())))
+            }
+
+            fn names(&self) -> Vec<String> {
+                self.keys().cloned().collect()
+            }
+        })*
+    };
+}
+
+display_arguments!(
+    Box<dyn fmt::Display>, Box<dyn fmt::Display + Send + Sync>,
+    Rc<dyn fmt::Display>, Arc<dyn fmt::Display>, Arc<dyn fmt::Display + Send + Sync>
+);
+
+impl<T: Arguments + ?Sized> Arguments for &T {
+    fn get(&self, name: &str) -> Option<Cow<'_, Value>> {
+        (**self).get(name)
+    }
+
+    fn names(&self) -> Vec<String> {
+        (**self).names()
+    }
+
+    fn layer(&self, name: &str) -> Option<&str> {
+        (**self).layer(name)
+    }
+}
+
+/// Arguments resolved from several named sources, such as explicit arguments
+/// over configuration files over defaults. The first layer that has an
+/// argument supplies it.
+///
+/// ```
+/// use late_format::{FormatOptions, Layers, Template};
+/// use maplit::hashmap;
+/// let arguments = hashmap!{"user".to_owned() => "x".to_owned()};
+/// let defaults = hashmap!{"user".to_owned() => "nobody".to_owned(), "app".to_owned() => "demo".to_owned()};
+/// let layers = Layers::new().layer("arguments", &arguments).layer("defaults", defaults);
+/// let template = Template::parse("{user}@{app}").unwrap();
+/// assert_eq!("x@demo", template.render_arguments(&layers, &FormatOptions::new()).unwrap());
+/// ```
+#[derive(Default)]
+pub struct Layers<'a> {
+    layers: Vec<(String, Box<dyn Arguments + 'a>)>,
+}
+
+impl<'a> Layers<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a layer with lower precedence than the layers added before.
+    pub fn layer(mut self, name: impl Into<String>, arguments: impl Arguments + 'a) -> Self {
+        self.layers.push((name.into(), Box::new(arguments)));
+        self
+    }
+
+    /// Resolves an argument, returning it with the name of the layer that
+    /// supplies it.
+    pub fn get_with_layer(&self, name: &str) -> Option<(&str, Cow<'_, Value>)> {
+        self.layers.iter().find_map(|(layer, arguments)| Some((layer.as_str(), arguments.get(name)?)))
+    }
+
+    /// The names of the layers, in order of precedence.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.layers.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+impl Arguments for Layers<'_> {
+    fn get(&self, name: &str) -> Option<Cow<'_, Value>> {
+        self.get_with_layer(name).map(|(_, value)| value)
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.layers.iter().flat_map(|(_, arguments)| arguments.names()).collect()
+    }
+
+    fn layer(&self, name: &str) -> Option<&str> {
+        self.get_with_layer(name).map(|(layer, _)| layer)
+    }
+}
+
+impl fmt::Debug for Layers<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Layers").field("names", &self.names().collect::<Vec<_>>()).finish()
+    }
+}
+
+/// Arguments from a nested document, such as a parsed configuration file,
+/// resolving a parameter name such as `database.host` by navigating the maps
+/// of the document with [`Value::get_path`].
+///
+/// ```
+/// use late_format::{Document, FormatOptions, Template, Value};
+/// let database: Value = [("host", "localhost"), ("port", "5432")].into_iter().collect();
+/// let document = Document::new([("database", database)].into_iter().collect());
+/// let template = Template::parse("{database.host}:{database.port}").unwrap();
+/// assert_eq!("localhost:5432", template.render_arguments(&document, &FormatOptions::new()).unwrap());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Document {
+    root: Value,
+}
+
+impl Document {
+    /// Creates a document from its root value, usually a [`Value::Map`].
+    pub fn new(root: Value) -> Self {
+        Self { root }
+    }
+
+    pub fn root(&self) -> &Value {
+        &self.root
+    }
+}
+
+impl Arguments for Document {
+    fn get(&self, name: &str) -> Option<Cow<'_, Value>> {
+        self.root.get_path(name).map(Cow::Borrowed)
+    }
+}
+
+/// A borrowed argument, as resolved when rendering.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum ValueRef<'v> {
+    Str(&'v str),
+    Value(&'v Value),
+    /// The position of an item in a list, as the key of a `{for key, variable in list}` block.
+    Index(usize),
+}
+
+impl<'v> ValueRef<'v> {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Str(s) => s.is_empty(),
+            Self::Value(v) => v.is_empty(),
+            Self::Index(_) => false,
+        }
+    }
+
+    pub fn text(&self) -> Cow<'v, str> {
+        match self {
+            Self::Str(s) => Cow::Borrowed(s),
+            Self::Value(Value::String(s)) => Cow::Borrowed(s),
+            Self::Value(v) => Cow::Owned(v.to_string()),
+            Self::Index(i) => Cow::Owned(i.to_string()),
+        }
+    }
+
+    /// The items iterated by a `{for}` block: the items of a list, or the
+    /// value itself otherwise.
+    pub fn items(&self) -> Vec<ValueRef<'v>> {
+        match self {
+            Self::Value(Value::List(items)) => items.iter().map(ValueRef::Value).collect(),
+            _ => vec![*self],
+        }
+    }
+
+    /// Navigates the value as described in [`Value::get_path`].
+    pub fn get_path(&self, path: &str) -> Option<ValueRef<'v>> {
+        match self {
+            Self::Value(v) => v.get_path(path).map(Self::Value),
+            _ => None,
+        }
+    }
+
+    /// The keys and items iterated by a `{for key, variable in map}` block:
+    /// the entries of a map in key order, or the positions and items of
+    /// [`ValueRef::items`].
+    pub fn entries(&self) -> Vec<(ValueRef<'v>, ValueRef<'v>)> {
+        match self {
+            Self::Value(Value::Map(entries)) => entries.iter().map(|(k, v)| (Self::Str(k), Self::Value(v))).collect(),
+            _ => self.items().into_iter().enumerate().map(|(i, v)| (Self::Index(i), v)).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn paths() {
+        let user: Value = [("name", Value::from("x")), ("roles", Value::from(vec!["a", "b"]))].into_iter().collect();
+        let root: Value = [("user", user)].into_iter().collect();
+        assert_eq!(Some(&Value::from("x")), root.get_path("user.name"));
+        assert_eq!(Some(&Value::from("b")), root.get_path("user.roles.1"));
+        assert_eq!(None, root.get_path("user.roles.2"));
+        assert_eq!(None, root.get_path("user.name.x"));
+        assert_eq!("user: name: x, roles: a, b", root.to_string());
+        let document = Document::new(root);
+        assert_eq!(Some("a, b".into()), document.get("user.roles").map(|v| v.to_string()));
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(Value::from("-3"), (-3i8).into_late_value());
+        assert_eq!(Value::from("0.1"), 0.1f64.into_late_value());
+        assert_eq!(Value::from(vec!["1", ""]), vec![Some(1u64), None].into_late_value());
+        assert_eq!(Value::from("é"), 'é'.into_late_value());
+        let arguments = crate::arguments!{"a".to_owned() => false, "b" => Value::from("x")};
+        assert_eq!((Some(&Value::from("false")), Some(&Value::from("x"))), (arguments.get("a"), arguments.get("b")));
+    }
+
+    #[test]
+    fn args() {
+        let layer = Args::new().set("a", 1).set("b", vec![2, 3]);
+        let args = Args::new().set("a", "x").set_opt("c", Some('c')).set_opt("d", None::<u8>).defaults(layer.clone()).extend(Args::new().set("e", ""));
+        assert_eq!((Some(&Value::from("x")), Some(&Value::from(vec!["2", "3"]))), (args.get("a"), args.get("b")));
+        assert_eq!((Some(&Value::from("c")), None, Some(&Value::from(""))), (args.get("c"), args.get("d"), args.get("e")));
+        assert_eq!(Some(&Value::from("1")), layer.clone().extend(Args::new().set("b", 0)).get("a"));
+        let mut names = Arguments::names(&args);
+        names.sort();
+        assert_eq!(["a", "b", "c", "e"], &names[..]);
+        assert_eq!(4, HashMap::from(args).len());
+    }
+
+    #[test]
+    fn times() {
+        use crate::{FormatOptions, Template};
+        let started = SystemTime::UNIX_EPOCH + Duration::from_secs(1709631000);
+        assert_eq!(Value::from("2024-03-05T09:30:00Z"), started.into_late_value());
+        assert_eq!(Value::from("1969-12-31T23:59:59Z"), (SystemTime::UNIX_EPOCH - Duration::from_millis(1)).into_late_value());
+        assert_eq!(Value::from("90.25"), Duration::from_millis(90250).into_late_value());
+        let far = Duration::from_secs(1 << 57);
+        assert_eq!(Value::from("144115188075855872"), (SystemTime::UNIX_EPOCH + far).into_late_value());
+        assert_eq!(Value::from("-144115188075855872.5"), (SystemTime::UNIX_EPOCH - far - Duration::from_millis(500)).into_late_value());
+        let template = Template::parse("{started | date:\"%-d %b %H:%M\"} took {took | duration} ({took | div:60}m)").unwrap();
+        let arguments = crate::arguments!{"started" => started, "took" => Duration::from_secs(90)};
+        assert_eq!("5 Mar 09:30 took 1m 30s (1.5m)", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+    }
+
+    #[test]
+    fn displays() {
+        use crate::{FormatOptions, Template};
+        let mut arguments: HashMap<String, Arc<dyn fmt::Display + Send + Sync>> = HashMap::new();
+        arguments.insert("pi".into(), Arc::new(3.5));
+        arguments.insert("ip".into(), Arc::new(std::net::Ipv4Addr::LOCALHOST));
+        let template = Template::parse("{pi:.0} {ip} {if ip}up{end}").unwrap();
+        assert_eq!("4 127.0.0.1 up", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+        assert_eq!(2, arguments.names().len());
+    }
+
+    #[test]
+    fn indices() {
+        use crate::{FormatOptions, Template};
+        use maplit::hashmap;
+        let user: Value = [("name", Value::from("x")), ("roles", Value::from(vec!["a", "b"]))].into_iter().collect();
+        let arguments = hashmap!{"items".to_owned() => Value::from(vec!["p", "q", "r"]), "users".to_owned() => Value::from(vec![user])};
+        let template = Template::parse("{items.0} {items[2]} {users[0].roles[1]} {users.0.name} {items.3} {for u in users}{u.roles.0}{u.x}{end}").unwrap();
+        assert_eq!("p r b x None aNone", template.render_arguments(&arguments, &FormatOptions::new()).unwrap());
+        assert_eq!(["items.0", "items.2", "users.0.roles.1", "users.0.name", "items.3", "users"], template.parameters());
+        assert_eq!("{items[x]}", Template::parse("{items[x]}").unwrap().render(&hashmap!{}));
+    }
+}
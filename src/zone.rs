@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use crate::date::{days_from_civil, days_in_month, Date};
+
+/// A time zone of the `tz` filter: `UTC`, a fixed offset such as `+05:30`,
+/// or a zone of the IANA time zone database, such as `Europe/Berlin`, read
+/// from the TZif files of the system, in `$TZDIR` or `/usr/share/zoneinfo`.
+///
+/// Zones are compared by name.
+#[derive(Clone, Debug)]
+pub(crate) struct Zone {
+    name: String,
+    rules: Arc<Rules>,
+}
+
+/// The offsets from UTC of a zone, in seconds east of UTC.
+#[derive(Debug)]
+enum Rules {
+    Fixed(i64),
+    /// The transitions of a TZif file, the index of the offset starting at
+    /// each, and the rule of the times after the last transition.
+    Transitions { times: Vec<i64>, types: Vec<usize>, offsets: Vec<i64>, footer: Option<Posix> },
+}
+
+/// A rule of a POSIX `TZ` string, such as `CET-1CEST,M3.5.0,M10.5.0/3`.
+#[derive(Debug, PartialEq, Eq)]
+struct Posix {
+    standard: i64,
+    /// The daylight saving offset, and when it starts and ends, in the local
+    /// time of the offset in effect before each.
+    daylight: Option<(i64, Change, Change)>,
+}
+
+/// A day of a POSIX `TZ` rule, and the time of the change on that day.
+type Change = (Day, i64);
+
+/// A day of a year in a POSIX `TZ` rule.
+#[derive(Debug, PartialEq, Eq)]
+enum Day {
+    /// `Jn`, from 1 to 365, not counting February 29.
+    Julian(i64),
+    /// `n`, from 0 to 365.
+    Ordinal(i64),
+    /// `Mm.w.d`: the weekday `d`, from 0 for Sunday, of the week `w` of the
+    /// month `m`, where the week 5 is the last.
+    Month(u8, i64, i64),
+}
+
+/// The zones loaded from files, by name.
+static LOADED: RwLock<Option<HashMap<String, Arc<Rules>>>> = RwLock::new(None);
+
+impl Zone {
+    /// Finds a zone by name, or returns `None` if it is not a fixed offset
+    /// and the database of the system does not have it.
+    pub fn new(name: &str) -> Option<Self> {
+        let rules = match name {
+            "UTC" | "Z" => Arc::new(Rules::Fixed(0)),
+            _ if name.starts_with(['+', '-']) => Arc::new(Rules::Fixed(fixed_offset(name)?)),
+            _ => load(name)?,
+        };
+        Some(Self { name: name.to_owned(), rules })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The local date and time of a date and time in UTC, or `None` for a
+    /// date without a time.
+    pub fn local(&self, date: &Date) -> Option<Date> {
+        let timestamp = date.timestamp()?;
+        Date::from_timestamp(timestamp.checked_add(self.rules.offset(timestamp))?)
+    }
+}
+
+impl PartialEq for Zone {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for Zone {}
+
+impl std::hash::Hash for Zone {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+impl PartialOrd for Zone {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Zone {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.name.cmp(&other.name)
+    }
+}
+
+impl Rules {
+    fn offset(&self, timestamp: i64) -> i64 {
+        match self {
+            Self::Fixed(offset) => *offset,
+            Self::Transitions { times, types, offsets, footer } => {
+                match (times.partition_point(|t| *t <= timestamp), footer) {
+                    (i, Some(footer)) if i == times.len() => footer.offset(timestamp),
+                    // the times before the first transition have the first offset.
+                    (0, _) => offsets[0],
+                    (i, _) => offsets[types[i - 1]],
+                }
+            },
+        }
+    }
+}
+
+impl Posix {
+    /// Parses a POSIX `TZ` string, whose offsets are in seconds west of UTC.
+    fn parse(s: &str) -> Option<Self> {
+        let mut rest = s;
+        name(&mut rest)?;
+        let standard = -posix_time(&mut rest)?;
+        if rest.is_empty() {
+            return Some(Self { standard, daylight: None });
+        }
+        name(&mut rest)?;
+        let daylight = if rest.starts_with(',') { standard + 3600 } else { -posix_time(&mut rest)? };
+        let change = |rest: &mut &str| -> Option<Change> {
+            *rest = rest.strip_prefix(',')?;
+            let day = if let Some(r) = rest.strip_prefix('J') {
+                *rest = r;
+                Day::Julian(number(rest)?).valid(1..=365)?
+            } else if let Some(r) = rest.strip_prefix('M') {
+                *rest = r;
+                let month = number(rest)?;
+                *rest = rest.strip_prefix('.')?;
+                let week = number(rest)?;
+                *rest = rest.strip_prefix('.')?;
+                let weekday = number(rest)?;
+                let valid = (1..=12).contains(&month) && (1..=5).contains(&week) && (0..=6).contains(&weekday);
+                valid.then_some(Day::Month(month as u8, week, weekday))?
+            } else {
+                Day::Ordinal(number(rest)?).valid(0..=365)?
+            };
+            let time = match rest.strip_prefix('/') {
+                Some(r) => {
+                    *rest = r;
+                    posix_time(rest)?
+                },
+                None => 7200,
+            };
+            Some((day, time))
+        };
+        let start = change(&mut rest)?;
+        let end = change(&mut rest)?;
+        rest.is_empty().then_some(Self { standard, daylight: Some((daylight, start, end)) })
+    }
+
+    fn offset(&self, timestamp: i64) -> i64 {
+        let Some((daylight, (start_day, start_time), (end_day, end_time))) = &self.daylight else {
+            return self.standard;
+        };
+        let Some(year) = Date::from_timestamp(timestamp + self.standard).map(|date| date.year()) else {
+            return self.standard;
+        };
+        let start = start_day.days(year) * 86400 + start_time - self.standard;
+        let end = end_day.days(year) * 86400 + end_time - daylight;
+        // in the southern hemisphere, daylight saving time spans the end of the year.
+        let in_daylight = match start < end {
+            true => (start..end).contains(&timestamp),
+            false => timestamp >= start || timestamp < end,
+        };
+        if in_daylight { *daylight } else { self.standard }
+    }
+}
+
+impl Day {
+    fn valid(self, range: std::ops::RangeInclusive<i64>) -> Option<Self> {
+        match &self {
+            Self::Julian(n) | Self::Ordinal(n) if !range.contains(n) => None,
+            _ => Some(self),
+        }
+    }
+
+    /// The day in a year, as a number of days since 1970-01-01.
+    fn days(&self, year: i32) -> i64 {
+        let first = days_from_civil(year, 1, 1);
+        match *self {
+            Self::Julian(n) => {
+                let leap = days_in_month(year, 2) == 29;
+                first + n - 1 + i64::from(leap && n >= 60)
+            },
+            Self::Ordinal(n) => first + n,
+            Self::Month(month, week, weekday) => {
+                let start = days_from_civil(year, month, 1);
+                // 1970-01-01 was a Thursday.
+                let mut day = 1 + (weekday - (start + 4)).rem_euclid(7) + (week - 1) * 7;
+                while day > i64::from(days_in_month(year, month)) {
+                    day -= 7;
+                }
+                start + day - 1
+            },
+        }
+    }
+}
+
+/// Parses a fixed offset of the form `+hh`, `+hh:mm` or `+hhmm`, in seconds
+/// east of UTC.
+fn fixed_offset(s: &str) -> Option<i64> {
+    let sign = if s.starts_with('-') { -1 } else { 1 };
+    let rest = s.get(1..)?;
+    let (hours, minutes) = match rest.len() {
+        2 => (rest, "00"),
+        4 => rest.split_at_checked(2)?,
+        5 if rest.as_bytes()[2] == b':' => (&rest[..2], &rest[3..]),
+        _ => return None,
+    };
+    if !(hours.bytes().chain(minutes.bytes())).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes): (i64, i64) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Loads a zone of the database of the system.
+fn load(name: &str) -> Option<Arc<Rules>> {
+    if let Some(rules) = LOADED.read().unwrap_or_else(|e| e.into_inner()).as_ref().and_then(|zones| zones.get(name)) {
+        return Some(rules.clone());
+    }
+    // names are paths relative to the database, which they must not leave.
+    let valid = name.split('/').all(|part| !part.is_empty() && !part.starts_with('.') && part.bytes().all(|b| b.is_ascii_alphanumeric() || b"_+-".contains(&b)));
+    if !valid {
+        return None;
+    }
+    let directory = std::env::var_os("TZDIR").unwrap_or_else(|| "/usr/share/zoneinfo".into());
+    let rules = Arc::new(parse_tzif(&std::fs::read(std::path::Path::new(&directory).join(name)).ok()?)?);
+    LOADED.write().unwrap_or_else(|e| e.into_inner()).get_or_insert_with(HashMap::new).insert(name.to_owned(), rules.clone());
+    Some(rules)
+}
+
+/// Parses a TZif file, as described in RFC 8536, reading the 64-bit data
+/// and the footer of the files of version 2 and later.
+fn parse_tzif(bytes: &[u8]) -> Option<Rules> {
+    let header = |bytes: &[u8]| -> Option<[usize; 6]> {
+        if bytes.get(..4)? != b"TZif" {
+            return None;
+        }
+        let count = |i: usize| Some(u32::from_be_bytes(bytes.get(20 + i * 4..24 + i * 4)?.try_into().ok()?) as usize);
+        Some([count(0)?, count(1)?, count(2)?, count(3)?, count(4)?, count(5)?])
+    };
+    let [ut, std, leap, time, types, chars] = header(bytes)?;
+    let version = *bytes.get(4)?;
+    let v1_len = 44 + time * 5 + types * 6 + chars + leap * 8 + std + ut;
+    let (data, size, counts) = match version {
+        0 => (&bytes[44..], 4, [ut, std, leap, time, types, chars]),
+        _ => {
+            let second = bytes.get(v1_len..)?;
+            (second.get(44..)?, 8, header(second)?)
+        },
+    };
+    let [ut, std, leap, time, types, chars] = counts;
+    if types == 0 {
+        return None;
+    }
+    let times = (0..time).map(|i| {
+        let b = data.get(i * size..(i + 1) * size)?;
+        Some(if size == 4 { i64::from(i32::from_be_bytes(b.try_into().ok()?)) } else { i64::from_be_bytes(b.try_into().ok()?) })
+    }).collect::<Option<Vec<_>>>()?;
+    let indices = data.get(time * size..time * (size + 1))?;
+    let transition_types = indices.iter().map(|i| usize::from(*i)).collect::<Vec<_>>();
+    if transition_types.iter().any(|i| *i >= types) {
+        return None;
+    }
+    let infos = data.get(time * (size + 1)..time * (size + 1) + types * 6)?;
+    let offsets = infos.chunks(6).map(|info| i64::from(i32::from_be_bytes([info[0], info[1], info[2], info[3]]))).collect();
+    let end = time * (size + 1) + types * 6 + chars + leap * (size + 4) + std + ut;
+    let footer = match version {
+        0 => None,
+        _ => {
+            let footer = std::str::from_utf8(data.get(end..)?).ok()?;
+            let footer = footer.strip_prefix('\n')?.trim_end_matches('\n');
+            (!footer.is_empty()).then(|| Posix::parse(footer)).flatten()
+        },
+    };
+    Some(Rules::Transitions { times, types: transition_types, offsets, footer })
+}
+
+/// Reads the name of a POSIX `TZ` string, either letters or a quoted `<...>`.
+fn name(rest: &mut &str) -> Option<()> {
+    let len = match rest.strip_prefix('<') {
+        Some(quoted) => quoted.find('>')? + 2,
+        None => rest.bytes().take_while(u8::is_ascii_alphabetic).count(),
+    };
+    if len < 3 {
+        return None;
+    }
+    *rest = &rest[len..];
+    Some(())
+}
+
+/// Reads a time of a POSIX `TZ` string, `[+-]hh[:mm[:ss]]`, in seconds.
+fn posix_time(rest: &mut &str) -> Option<i64> {
+    let sign = match rest.as_bytes().first() {
+        Some(b'-') => -1,
+        _ => 1,
+    };
+    *rest = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+    let mut seconds = number(rest)? * 3600;
+    for unit in [60, 1] {
+        let Some(r) = rest.strip_prefix(':') else {
+            break;
+        };
+        *rest = r;
+        seconds += number(rest)? * unit;
+    }
+    Some(sign * seconds)
+}
+
+fn number(rest: &mut &str) -> Option<i64> {
+    let len = rest.bytes().take_while(u8::is_ascii_digit).count();
+    let n = rest[..len].parse().ok()?;
+    *rest = &rest[len..];
+    Some(n)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::date::Date;
+    use super::{Day, Posix, Zone};
+
+    #[test]
+    fn zones() {
+        let date = Date::iso("2024-03-31T00:59:59Z").unwrap();
+        let local = |zone: &str, date: &Date| Zone::new(zone).unwrap().local(date).unwrap().to_string();
+        assert_eq!("2024-03-31T06:29:59", local("+05:30", &date));
+        assert_eq!("2024-03-30T22:59:59", local("-0200", &date));
+        assert!(Zone::new("+5").is_none() && Zone::new("../etc/passwd").is_none() && Zone::new("+05:3:0").is_none());
+        let berlin = Posix::parse("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        assert_eq!(Some((7200, (Day::Month(3, 5, 0), 7200), (Day::Month(10, 5, 0), 10800))), berlin.daylight);
+        let offsets = ["2024-03-31T00:59:59", "2024-03-31T01:00:00", "2024-10-27T00:59:59", "2024-10-27T01:00:00"]
+            .map(|date| berlin.offset(Date::iso(date).unwrap().timestamp().unwrap()));
+        assert_eq!([3600, 7200, 7200, 3600], offsets);
+        let sydney = Posix::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(39600, sydney.offset(Date::iso("2024-01-15T00:00:00").unwrap().timestamp().unwrap()));
+        assert_eq!(Some(Posix { standard: -10800, daylight: None }), Posix::parse("<-03>3"));
+        assert!(Posix::parse("CET-1CEST,M13.5.0,M10.5.0").is_none());
+        // the database of the system, if it has the zone.
+        if let Some(zone) = Zone::new("America/New_York") {
+            assert_eq!("2024-03-30T20:59:59", zone.local(&date).unwrap().to_string());
+            assert_eq!("2124-01-01T07:00:00", zone.local(&Date::iso("2124-01-01T12:00:00").unwrap()).unwrap().to_string());
+        }
+    }
+}